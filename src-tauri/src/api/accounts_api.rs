@@ -0,0 +1,191 @@
+use serde::Serialize;
+use thiserror::Error;
+
+use crate::model::password_policy::PasswordReport;
+use crate::model::user::{PasswordError, UserError};
+use crate::service::accounts::acounts_service::AccountsServiceError;
+
+use super::ApiControllerError;
+
+#[derive(Error, Debug, Serialize)]
+pub enum AccountsApiError {
+    #[error("Failed to initialise the api controller because: {0}")]
+    ApiControllerFailure(#[from] ApiControllerError),
+
+    // The service error wraps sqlx and hashing errors which can't be serialized,
+    // the frontend only needs the message
+    #[error("{0}")]
+    AccountsFailure(String),
+
+    #[error("Password rejected: {0}")]
+    PasswordRejected(PasswordReport),
+
+    #[error("A second factor code is required")]
+    SecondFactorRequired,
+}
+
+impl From<AccountsServiceError> for AccountsApiError {
+    fn from(value: AccountsServiceError) -> Self {
+        match value {
+            AccountsServiceError::InvalidUser(UserError::PasswordValidation(
+                PasswordError::Policy(report),
+            )) => AccountsApiError::PasswordRejected(report),
+            AccountsServiceError::SecondFactorRequired => AccountsApiError::SecondFactorRequired,
+            other => AccountsApiError::AccountsFailure(other.to_string()),
+        }
+    }
+}
+
+pub mod signup {
+    use super::AccountsApiError;
+    use crate::api::get_controller;
+    use crate::model::password_policy::PasswordReport;
+
+    use tracing::info;
+
+    #[tauri::command]
+    pub async fn check_password(password: String) -> Result<PasswordReport, AccountsApiError> {
+        let controller = get_controller().await?;
+
+        Ok(controller.accounts_service.check_password(&password))
+    }
+
+    #[tauri::command]
+    pub async fn signup(username: String, password: String) -> Result<(), AccountsApiError> {
+        let controller = get_controller().await?;
+
+        let user = controller
+            .accounts_service
+            .signup(&username, &password)
+            .await?;
+        info!("Signed up: {}", user.username.get_username());
+        Ok(())
+    }
+}
+
+pub mod login {
+    use super::AccountsApiError;
+    use crate::api::get_controller;
+
+    use tracing::info;
+
+    #[tauri::command]
+    pub async fn login(
+        username: String,
+        password: String,
+        code: Option<String>,
+    ) -> Result<String, AccountsApiError> {
+        let controller = get_controller().await?;
+
+        let user = controller
+            .accounts_service
+            .login(&username, &password, code.as_deref())
+            .await?;
+        info!("Logged in: {}", user.username.get_username());
+        Ok(user.username.get_username())
+    }
+}
+
+pub mod two_factor {
+    use super::AccountsApiError;
+    use crate::api::get_controller;
+    use crate::service::accounts::two_factor::TotpEnrolment;
+
+    #[tauri::command]
+    pub async fn begin_totp_enrolment(
+        username: String,
+        password: String,
+    ) -> Result<TotpEnrolment, AccountsApiError> {
+        let controller = get_controller().await?;
+
+        Ok(controller
+            .accounts_service
+            .begin_totp_enrolment(&username, &password)
+            .await?)
+    }
+
+    #[tauri::command]
+    pub async fn confirm_totp_enrolment(
+        username: String,
+        code: String,
+    ) -> Result<Vec<String>, AccountsApiError> {
+        let controller = get_controller().await?;
+
+        Ok(controller
+            .accounts_service
+            .confirm_totp_enrolment(&username, &code)
+            .await?)
+    }
+
+    #[tauri::command]
+    pub async fn regenerate_backup_codes(
+        username: String,
+        password: String,
+        code: String,
+    ) -> Result<Vec<String>, AccountsApiError> {
+        let controller = get_controller().await?;
+
+        Ok(controller
+            .accounts_service
+            .regenerate_backup_codes(&username, &password, &code)
+            .await?)
+    }
+
+    #[tauri::command]
+    pub async fn disable_totp(
+        username: String,
+        password: String,
+        code: String,
+    ) -> Result<(), AccountsApiError> {
+        let controller = get_controller().await?;
+
+        Ok(controller
+            .accounts_service
+            .disable_totp(&username, &password, &code)
+            .await?)
+    }
+}
+
+pub mod personal_data {
+    use super::AccountsApiError;
+    use crate::api::get_controller;
+    use crate::service::accounts::personal_data::AccountDeletion;
+
+    use tracing::info;
+
+    /// Writes the zip archive to `destination`, picked by the user
+    #[tauri::command]
+    pub async fn export_personal_data(
+        username: String,
+        password: String,
+        code: Option<String>,
+        destination: String,
+    ) -> Result<(), AccountsApiError> {
+        let controller = get_controller().await?;
+
+        let export = controller
+            .accounts_service
+            .export_personal_data(&username, &password, code.as_deref())
+            .await?;
+        tokio::fs::write(&destination, export.to_zip()?)
+            .await
+            .map_err(|e| AccountsApiError::AccountsFailure(e.to_string()))?;
+
+        info!("Exported the personal data of {username} to {destination}");
+        Ok(())
+    }
+
+    #[tauri::command]
+    pub async fn delete_account(
+        username: String,
+        password: String,
+        code: Option<String>,
+    ) -> Result<AccountDeletion, AccountsApiError> {
+        let controller = get_controller().await?;
+
+        Ok(controller
+            .accounts_service
+            .delete_account(&username, &password, code.as_deref())
+            .await?)
+    }
+}
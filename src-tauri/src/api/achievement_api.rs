@@ -0,0 +1,38 @@
+use serde::Serialize;
+use thiserror::Error;
+
+use crate::api::{emit_event, get_controller, ApiControllerError};
+use crate::model::achievement::AchievementStatus;
+use crate::service::karma::karma_service::KarmaServiceError;
+use crate::service::notifier::Notifier;
+
+/// Emitted to every window with the achievements a command just unlocked
+pub const ACHIEVEMENTS_UNLOCKED_EVENT: &str = "achievements-unlocked";
+
+/// Forwards what the services announce to the frontend as events, does nothing
+/// until the app handle is set
+#[derive(Debug, Default, Clone, Copy)]
+pub struct EventNotifier;
+
+impl Notifier for EventNotifier {
+    fn achievements_unlocked(&self, unlocked: &[AchievementStatus]) {
+        emit_event(ACHIEVEMENTS_UNLOCKED_EVENT, unlocked.to_vec());
+    }
+}
+
+#[derive(Error, Debug, Serialize)]
+pub enum AchievementApiError {
+    #[error("Failed to initialise the api controller because: {0}")]
+    ApiControllerFailure(#[from] ApiControllerError),
+
+    #[error("Failed to evaluate achievements: {0}")]
+    AchievementsFailed(#[from] KarmaServiceError),
+}
+
+/// Every achievement with its progress and when it was unlocked
+#[tauri::command]
+pub async fn list_achievements() -> Result<Vec<AchievementStatus>, AchievementApiError> {
+    let controller = get_controller().await?;
+
+    Ok(controller.karma_service.achievements().await?)
+}
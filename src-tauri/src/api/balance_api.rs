@@ -0,0 +1,41 @@
+use serde::Serialize;
+use thiserror::Error;
+
+use crate::api::{get_controller, ApiControllerError};
+use crate::model::balance::{BalanceReport, BalanceTargets};
+use crate::service::karma::karma_service::KarmaServiceError;
+
+#[derive(Error, Debug, Serialize)]
+pub enum BalanceApiError {
+    #[error("Failed to initialise the api controller because: {0}")]
+    ApiControllerFailure(#[from] ApiControllerError),
+
+    #[error("Failed to compute the balance: {0}")]
+    BalanceFailed(#[from] KarmaServiceError),
+}
+
+/// This week's score, time per type against the targets and suggestions
+#[tauri::command]
+pub async fn get_balance() -> Result<BalanceReport, BalanceApiError> {
+    let controller = get_controller().await?;
+
+    Ok(controller.karma_service.balance().await?)
+}
+
+#[tauri::command]
+pub async fn get_balance_targets() -> Result<BalanceTargets, BalanceApiError> {
+    let controller = get_controller().await?;
+
+    Ok(controller.karma_service.balance_targets().await?)
+}
+
+/// `targets` as comma separated type=hours per week, e.g. `work=40, sport=5`
+#[tauri::command]
+pub async fn set_balance_targets(targets: String) -> Result<BalanceTargets, BalanceApiError> {
+    let controller = get_controller().await?;
+
+    Ok(controller
+        .karma_service
+        .set_balance_targets(&targets)
+        .await?)
+}
@@ -0,0 +1,137 @@
+use std::time::Duration;
+
+use serde::Serialize;
+use thiserror::Error;
+use tokio::time::MissedTickBehavior;
+use tracing::warn;
+
+use crate::api::{emit_event, get_controller, ApiControllerError};
+use crate::model::focus::{FocusRules, FocusState, FocusSummary, Interruption};
+use crate::model::karma::KarmaType;
+use crate::service::karma::karma_service::KarmaServiceError;
+
+/// How often the focus timer catches up and reports
+pub const FOCUS_TICK_PERIOD: Duration = Duration::from_secs(1);
+
+/// Emitted every tick with the running focus session, with null once it ended
+pub const FOCUS_TICK_EVENT: &str = "focus-tick";
+
+/// Emitted with every phase a focus session enters
+pub const FOCUS_PHASE_EVENT: &str = "focus-phase-changed";
+
+#[derive(Error, Debug, Serialize)]
+pub enum FocusApiError {
+    #[error("Failed to initialise the api controller because: {0}")]
+    ApiControllerFailure(#[from] ApiControllerError),
+
+    #[error("Focus session failed: {0}")]
+    FocusFailed(#[from] KarmaServiceError),
+}
+
+/// Runs the Pomodoro timer in the background, independent of any window, so a
+/// reloaded frontend picks up where the session is
+pub fn spawn_focus_timer() {
+    tokio::spawn(async {
+        let mut interval = tokio::time::interval(FOCUS_TICK_PERIOD);
+        interval.set_missed_tick_behavior(MissedTickBehavior::Skip);
+        let mut running = false;
+        loop {
+            interval.tick().await;
+
+            // The daily job already reports a controller that can't be created
+            let Ok(controller) = get_controller().await else {
+                continue;
+            };
+            match controller.karma_service.advance_focus().await {
+                Ok(Some(tick)) => {
+                    for phase in tick.entered {
+                        emit_event(FOCUS_PHASE_EVENT, phase);
+                    }
+                    emit_event(FOCUS_TICK_EVENT, Some(tick.state));
+                    running = true;
+                }
+                Ok(None) if running => {
+                    emit_event(FOCUS_TICK_EVENT, None::<FocusState>);
+                    running = false;
+                }
+                Ok(None) => {}
+                Err(e) => warn!("Focus timer failed: {e}"),
+            }
+        }
+    });
+}
+
+/// Starts a session of a work or learning activity run as pomodoros
+#[tauri::command]
+pub async fn start_focus(
+    name: String,
+    purpose: Option<KarmaType>,
+) -> Result<FocusState, FocusApiError> {
+    let controller = get_controller().await?;
+
+    Ok(controller.karma_service.start_focus(&name, purpose).await?)
+}
+
+/// The running focus session, null when there is none
+#[tauri::command]
+pub async fn get_focus() -> Result<Option<FocusState>, FocusApiError> {
+    let controller = get_controller().await?;
+
+    Ok(controller
+        .karma_service
+        .advance_focus()
+        .await?
+        .map(|tick| tick.state))
+}
+
+#[tauri::command]
+pub async fn log_interruption(note: String, external: bool) -> Result<Interruption, FocusApiError> {
+    let controller = get_controller().await?;
+
+    Ok(controller
+        .karma_service
+        .log_interruption(&note, external)
+        .await?)
+}
+
+/// Ends the running focus session, `rating` from 1 to 5
+#[tauri::command]
+pub async fn stop_focus(rating: Option<u8>) -> Result<FocusSummary, FocusApiError> {
+    let controller = get_controller().await?;
+
+    Ok(controller.karma_service.stop_focus(rating).await?)
+}
+
+#[tauri::command]
+pub async fn rate_focus(id: i32, rating: u8) -> Result<(), FocusApiError> {
+    let controller = get_controller().await?;
+
+    Ok(controller.karma_service.rate_focus(id, rating).await?)
+}
+
+/// The last `limit` focus sessions, 20 by default
+#[tauri::command]
+pub async fn focus_history(limit: Option<i64>) -> Result<Vec<FocusSummary>, FocusApiError> {
+    let controller = get_controller().await?;
+
+    Ok(controller
+        .karma_service
+        .focus_history(limit.unwrap_or(20))
+        .await?)
+}
+
+#[tauri::command]
+pub async fn get_focus_rules() -> Result<FocusRules, FocusApiError> {
+    let controller = get_controller().await?;
+
+    Ok(controller.karma_service.focus_rules().await?)
+}
+
+/// `rules` as comma separated work=minutes, short=minutes, long=minutes,
+/// every=pomodoros and max=pomodoros, the ones left out stay as they are
+#[tauri::command]
+pub async fn set_focus_rules(rules: String) -> Result<FocusRules, FocusApiError> {
+    let controller = get_controller().await?;
+
+    Ok(controller.karma_service.set_focus_rules(&rules).await?)
+}
@@ -0,0 +1,63 @@
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+use crate::api::{get_controller, ApiControllerError};
+use crate::model::goal::{Goal, GoalComparison, GoalError, GoalMetric, GoalStatus};
+use crate::model::report::{ReportError, ReportPeriod};
+use crate::service::karma::karma_service::KarmaServiceError;
+
+#[derive(Error, Debug, Serialize)]
+pub enum GoalApiError {
+    #[error("Failed to initialise the api controller because: {0}")]
+    ApiControllerFailure(#[from] ApiControllerError),
+
+    #[error("{0}")]
+    InvalidGoal(#[from] GoalError),
+
+    #[error("{0}")]
+    InvalidPeriod(#[from] ReportError),
+
+    #[error("Goal operation failed: {0}")]
+    GoalFailed(#[from] KarmaServiceError),
+}
+
+/// A goal as sent by the frontend or typed on the command line
+#[derive(Debug, Clone, Deserialize)]
+pub struct GoalRequest {
+    /// A type such as learning or the name of a karma point
+    pub subject: String,
+    /// hours, daily_hours or sessions
+    pub metric: String,
+    /// at_least or at_most
+    pub comparison: String,
+    pub target: f64,
+    /// week or month
+    pub period: String,
+}
+
+#[tauri::command]
+pub async fn list_goals() -> Result<Vec<GoalStatus>, GoalApiError> {
+    let controller = get_controller().await?;
+
+    Ok(controller.karma_service.goals().await?)
+}
+
+#[tauri::command]
+pub async fn add_goal(goal: GoalRequest) -> Result<Goal, GoalApiError> {
+    let metric = GoalMetric::try_from(goal.metric.as_str())?;
+    let comparison = GoalComparison::try_from(goal.comparison.as_str())?;
+    let period = ReportPeriod::try_from(goal.period.as_str())?;
+    let controller = get_controller().await?;
+
+    Ok(controller
+        .karma_service
+        .add_goal(&goal.subject, metric, comparison, goal.target, period)
+        .await?)
+}
+
+#[tauri::command]
+pub async fn delete_goal(id: i32) -> Result<(), GoalApiError> {
+    let controller = get_controller().await?;
+
+    Ok(controller.karma_service.delete_goal(id).await?)
+}
@@ -1,5 +1,6 @@
 use thiserror::Error;
 
+use crate::model::karma_query::KarmaQueryError;
 use crate::{service::karma::karma_service::KarmaServiceError, storage::db::DbManagerError};
 use serde::Serialize;
 
@@ -15,6 +16,27 @@ pub enum KarmaApiError {
 
     #[error("Failed to create karma point: {0}")]
     KarmaCreationFailed(#[from] KarmaServiceError),
+
+    #[error("Failed to list karma points: {0}")]
+    KarmaListingFailed(KarmaServiceError),
+
+    #[error("Failed to convert karma state: {0}")]
+    InvalidKarmaState(String),
+
+    #[error("Invalid karma query: {0}")]
+    InvalidQuery(#[from] KarmaQueryError),
+
+    #[error("Failed to search karma points: {0}")]
+    KarmaSearchFailed(KarmaServiceError),
+
+    #[error("Failed to resolve the karma name: {0}")]
+    KarmaResolutionFailed(KarmaServiceError),
+
+    #[error("Failed to start the karma session: {0}")]
+    KarmaStartFailed(String),
+
+    #[error("Failed to track the karma session: {0}")]
+    KarmaTrackingFailed(String),
 }
 
 pub mod create {
@@ -28,18 +50,26 @@ pub mod create {
         type Error = KarmaApiError;
 
         fn try_from(value: &str) -> Result<Self, Self::Error> {
-            // Todo: handle all the other arms
-            match value {
+            match value.to_lowercase().as_str() {
                 "work" => Ok(KarmaType::Work),
+                "social" => Ok(KarmaType::Social),
+                "sport" => Ok(KarmaType::Sport),
+                "learning" => Ok(KarmaType::Learning),
+                "sleeping" => Ok(KarmaType::Sleeping),
                 _ => Err(KarmaApiError::InvalidKarmaType(value.to_string())),
             }
         }
     }
 
+    /// Creates a template without starting it, `default_duration` is in seconds
     #[tauri::command]
-    pub async fn create_karma(name: String, purpose: String) -> Result<(), KarmaApiError> {
+    pub async fn create_karma(
+        name: String,
+        purpose: String,
+        default_duration: Option<i64>,
+    ) -> Result<(), KarmaApiError> {
         let karma_type = KarmaType::try_from(purpose.as_str())?;
-        let karma_point = KarmaPoint::new(karma_type, name);
+        let karma_point = KarmaPoint::new(karma_type, name).with_default_duration(default_duration);
 
         let controller = get_controller().await?;
 
@@ -48,3 +78,238 @@ pub mod create {
         Ok(())
     }
 }
+
+pub mod list {
+    use super::KarmaApiError;
+    use crate::api::get_controller;
+    use crate::model::karma::{KarmaType, State};
+    use crate::model::karma_query::{KarmaCursor, KarmaPage, KarmaQuery, KarmaSort};
+
+    use serde::Deserialize;
+
+    /// The filters as sent by the frontend or typed on the command line
+    #[derive(Debug, Clone, Default, Deserialize)]
+    pub struct KarmaListRequest {
+        pub purpose: Option<String>,
+        pub state: Option<String>,
+        pub from: Option<i64>,
+        pub to: Option<i64>,
+        pub name_prefix: Option<String>,
+        pub sort: Option<String>,
+        pub limit: Option<u32>,
+        pub cursor: Option<String>,
+    }
+
+    impl TryFrom<KarmaListRequest> for KarmaQuery {
+        type Error = KarmaApiError;
+
+        fn try_from(request: KarmaListRequest) -> Result<Self, Self::Error> {
+            let purpose = request
+                .purpose
+                .as_deref()
+                .map(KarmaType::try_from)
+                .transpose()?;
+            let state = request
+                .state
+                .as_deref()
+                .map(|state| {
+                    State::try_from(state.to_lowercase().as_str())
+                        .map_err(|_| KarmaApiError::InvalidKarmaState(state.to_string()))
+                })
+                .transpose()?;
+            let sort = request
+                .sort
+                .as_deref()
+                .map(KarmaSort::try_from)
+                .transpose()?
+                .unwrap_or_default();
+            let after = request
+                .cursor
+                .as_deref()
+                .map(KarmaCursor::decode)
+                .transpose()?;
+
+            Ok(KarmaQuery {
+                purpose,
+                state,
+                from: request.from,
+                to: request.to,
+                name_prefix: request.name_prefix.filter(|prefix| !prefix.is_empty()),
+                sort,
+                limit: request.limit.unwrap_or_default(),
+                after,
+            })
+        }
+    }
+
+    #[tauri::command]
+    pub async fn list_karma(filter: KarmaListRequest) -> Result<KarmaPage, KarmaApiError> {
+        let query = KarmaQuery::try_from(filter)?;
+        let controller = get_controller().await?;
+
+        controller
+            .karma_service
+            .list_karma(&query)
+            .await
+            .map_err(KarmaApiError::KarmaListingFailed)
+    }
+}
+
+pub mod search {
+    use super::KarmaApiError;
+    use crate::api::get_controller;
+    use crate::model::karma_search::{KarmaSearchResult, DEFAULT_SEARCH_LIMIT};
+
+    use tracing::info;
+
+    #[tauri::command]
+    pub async fn search_karma(
+        query: String,
+        limit: Option<u32>,
+    ) -> Result<Vec<KarmaSearchResult>, KarmaApiError> {
+        let controller = get_controller().await?;
+
+        controller
+            .karma_service
+            .search_karma(&query, limit.unwrap_or(DEFAULT_SEARCH_LIMIT))
+            .await
+            .map_err(KarmaApiError::KarmaSearchFailed)
+    }
+
+    #[tauri::command]
+    pub async fn rebuild_search_index() -> Result<(), KarmaApiError> {
+        let controller = get_controller().await?;
+
+        controller
+            .karma_service
+            .rebuild_search_index()
+            .await
+            .map_err(KarmaApiError::KarmaSearchFailed)?;
+        info!("Rebuilt the karma search index");
+        Ok(())
+    }
+}
+
+pub mod resolve {
+    use super::KarmaApiError;
+    use crate::api::get_controller;
+    use crate::model::karma_query::KarmaListItem;
+
+    /// Resolves a name typed by the user. An ambiguous name fails with the
+    /// candidates so the frontend can ask which one was meant.
+    #[tauri::command]
+    pub async fn resolve_karma(name: String) -> Result<KarmaListItem, KarmaApiError> {
+        let controller = get_controller().await?;
+
+        controller
+            .karma_service
+            .resolve_karma(&name)
+            .await
+            .map_err(KarmaApiError::KarmaResolutionFailed)
+    }
+}
+
+pub mod start {
+    use super::KarmaApiError;
+    use crate::api::get_controller;
+    use crate::model::karma::KarmaType;
+    use crate::model::karma_session::StartedSession;
+
+    use tracing::info;
+
+    /// Starts a new session, creating the template when a type is given for an
+    /// unknown name. `planned_duration` is in seconds.
+    #[tauri::command]
+    pub async fn start_karma(
+        name: String,
+        purpose: Option<String>,
+        planned_duration: Option<i64>,
+    ) -> Result<StartedSession, KarmaApiError> {
+        let purpose = purpose
+            .as_deref()
+            .filter(|purpose| !purpose.is_empty())
+            .map(KarmaType::try_from)
+            .transpose()?;
+        let controller = get_controller().await?;
+
+        let started = controller
+            .karma_service
+            .start_karma(&name, purpose, planned_duration)
+            .await
+            .map_err(|e| KarmaApiError::KarmaStartFailed(e.to_string()))?;
+        info!("Started: {:?}", started.session);
+        Ok(started)
+    }
+}
+
+pub mod track {
+    use super::KarmaApiError;
+    use crate::api::get_controller;
+    use crate::model::duration::{DurationTotals, SessionDuration};
+    use crate::model::karma::{KarmaStatus, KarmaType};
+
+    #[tauri::command]
+    pub async fn pause_session(session_id: i32) -> Result<KarmaStatus, KarmaApiError> {
+        let controller = get_controller().await?;
+
+        controller
+            .karma_service
+            .pause_session(session_id)
+            .await
+            .map_err(|e| KarmaApiError::KarmaTrackingFailed(e.to_string()))
+    }
+
+    #[tauri::command]
+    pub async fn resume_session(session_id: i32) -> Result<KarmaStatus, KarmaApiError> {
+        let controller = get_controller().await?;
+
+        controller
+            .karma_service
+            .resume_session(session_id)
+            .await
+            .map_err(|e| KarmaApiError::KarmaTrackingFailed(e.to_string()))
+    }
+
+    /// Ends the session, `closed_with` is the type it turned out to be
+    #[tauri::command]
+    pub async fn close_session(
+        session_id: i32,
+        closed_with: Option<String>,
+    ) -> Result<KarmaStatus, KarmaApiError> {
+        let closed_with = closed_with
+            .as_deref()
+            .filter(|closed_with| !closed_with.is_empty())
+            .map(KarmaType::try_from)
+            .transpose()?;
+        let controller = get_controller().await?;
+
+        controller
+            .karma_service
+            .close_session(session_id, closed_with)
+            .await
+            .map_err(|e| KarmaApiError::KarmaTrackingFailed(e.to_string()))
+    }
+
+    #[tauri::command]
+    pub async fn session_duration(session_id: i32) -> Result<SessionDuration, KarmaApiError> {
+        let controller = get_controller().await?;
+
+        controller
+            .karma_service
+            .session_duration(session_id)
+            .await
+            .map_err(|e| KarmaApiError::KarmaTrackingFailed(e.to_string()))
+    }
+
+    /// Active time per point, type and day between `from` and `to` in unix seconds
+    #[tauri::command]
+    pub async fn duration_totals(from: i64, to: i64) -> Result<DurationTotals, KarmaApiError> {
+        let controller = get_controller().await?;
+
+        controller
+            .karma_service
+            .duration_totals(from, to)
+            .await
+            .map_err(|e| KarmaApiError::KarmaTrackingFailed(e.to_string()))
+    }
+}
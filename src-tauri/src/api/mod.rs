@@ -1,61 +1,219 @@
+pub mod accounts_api;
+pub mod achievement_api;
+pub mod balance_api;
+pub mod focus_api;
+pub mod goal_api;
 pub mod karma_api;
+pub mod plan_api;
+pub mod profiles_api;
+pub mod relapse_api;
+pub mod report_api;
+pub mod reward_api;
+pub mod schedule_api;
+pub mod scoring_api;
+pub mod settings_api;
+pub mod sleep_api;
+pub mod streak_api;
+pub mod suggestion_api;
 
+use crate::api::achievement_api::EventNotifier;
+use crate::model::achievement::AchievementBook;
+use crate::model::password_policy::{PasswordBlocklist, PasswordPolicy};
+use crate::model::profile::{Profile, ProfileError};
+use crate::service::accounts::acounts_service::{AccountsService, AccountsServiceError};
 use crate::service::karma::karma_service::KarmaService;
 use crate::storage::db::{DbManager, DbManagerError};
+use crate::storage::profile_registry::{ProfileRegistry, ProfileRegistryError, PROFILES_FILE};
 
-use once_cell::sync::OnceCell;
+use once_cell::sync::{Lazy, OnceCell as SyncOnceCell};
 use serde::Serialize;
+use std::path::Path;
+use std::sync::Arc;
+use tauri::{AppHandle, Manager};
 use thiserror::Error;
-use tokio::sync::Mutex;
+use tokio::sync::{Mutex, OnceCell, RwLock};
+use tracing::{info, warn};
 
-static API_CONTROLLER: OnceCell<ApiController> = OnceCell::new();
-static API_CONTROLLER_INITIALIZED: OnceCell<Mutex<bool>> = OnceCell::new();
+/// Optional path to a user supplied list of common or breached passwords,
+/// checked on top of the bundled one
+const PASSWORD_BLOCKLIST_ENV: &str = "KARMA_PASSWORD_BLOCKLIST";
 
-pub async fn get_controller() -> Result<&'static ApiController, ApiControllerError> {
-    // this is racy, but that's OK: it's just a fast case
-    if let Some(controller) = API_CONTROLLER.get() {
-        return Ok(controller);
-    }
+/// Optional path to a JSON file of user defined achievements, added to the bundled
+/// ones and replacing those with the same id
+const ACHIEVEMENTS_ENV: &str = "KARMA_ACHIEVEMENTS";
+
+// Commands clone the Arc and keep using it until they finish, so swapping the
+// controller on a profile switch never pulls the services from under them.
+// The old database pools are closed once the last command using them is done.
+static API_CONTROLLER: Lazy<RwLock<Option<Arc<ApiController>>>> = Lazy::new(|| RwLock::new(None));
+static PROFILE_REGISTRY: OnceCell<Mutex<ProfileRegistry>> = OnceCell::const_new();
+
+// Only set when the desktop app runs, the command line has no frontend to tell
+static APP_HANDLE: SyncOnceCell<AppHandle> = SyncOnceCell::new();
+
+pub fn set_app_handle(handle: AppHandle) {
+    let _ = APP_HANDLE.set(handle);
+}
 
-    // it hasn't been initialized yet, so let's grab the lock & try to initialize it
-    let initializing_mutex = API_CONTROLLER_INITIALIZED.get_or_init(|| Mutex::new(false));
+/// Sends `payload` to every window, does nothing until the app handle is set
+pub fn emit_event<S: Serialize + Clone>(event: &str, payload: S) {
+    let Some(handle) = APP_HANDLE.get() else {
+        return;
+    };
+    if let Err(e) = handle.emit_all(event, payload) {
+        warn!("Failed to emit {event}: {e}");
+    }
+}
 
-    // this will wait if another task is currently initializing the client
-    let mut initialized = initializing_mutex.lock().await;
+pub async fn get_controller() -> Result<Arc<ApiController>, ApiControllerError> {
+    // fast case, it was already initialized
+    if let Some(controller) = API_CONTROLLER.read().await.as_ref() {
+        return Ok(controller.clone());
+    }
 
-    // if initialized is true, then someone else initialized it while we waited,
-    // and we can just skip this part.
-    if !*initialized {
-        // no one else has initialized it yet, so
-        let controller = ApiController::new().await?;
+    // this will wait if another task is currently initializing the controller
+    // or switching the profile
+    let mut controller = API_CONTROLLER.write().await;
 
-        API_CONTROLLER.set(controller).expect(
-            "no one else should be initializing this \
-            as we hold API_CONTROLLER_INITIALIZED lock",
-        );
-        *initialized = true;
-        drop(initialized);
+    // someone else initialized it while we waited
+    if let Some(controller) = controller.as_ref() {
+        return Ok(controller.clone());
     }
 
-    Ok(API_CONTROLLER.get().unwrap())
+    let profile = get_profile_registry().await?.lock().await.last_used();
+    let initialized = Arc::new(ApiController::new(profile).await?);
+    *controller = Some(initialized.clone());
+
+    Ok(initialized)
+}
+
+pub async fn get_profile_registry() -> Result<&'static Mutex<ProfileRegistry>, ApiControllerError> {
+    let registry = PROFILE_REGISTRY
+        .get_or_try_init(|| async {
+            ProfileRegistry::load(Path::new(PROFILES_FILE))
+                .await
+                .map(Mutex::new)
+        })
+        .await?;
+
+    Ok(registry)
+}
+
+/// A controller for the given profile without making it the current one, as used by the
+/// command line. Falls back to the current controller when no profile is given.
+pub async fn get_controller_for(
+    profile: Option<&str>,
+) -> Result<Arc<ApiController>, ApiControllerError> {
+    let Some(name) = profile else {
+        return get_controller().await;
+    };
+
+    let profile = get_profile_registry()
+        .await?
+        .lock()
+        .await
+        .get(name)
+        .cloned()
+        .ok_or_else(|| ProfileRegistryError::NotFound(name.to_string()))?;
+
+    Ok(Arc::new(ApiController::new(profile).await?))
+}
+
+/// Opens the databases of another profile and makes every new command use them
+pub async fn switch_profile(name: &str) -> Result<Profile, ApiControllerError> {
+    // Holding the registry lock serializes concurrent switches
+    let mut registry = get_profile_registry().await?.lock().await;
+    let profile = registry
+        .get(name)
+        .cloned()
+        .ok_or_else(|| ProfileRegistryError::NotFound(name.to_string()))?;
+
+    // Connect before taking the write lock, commands keep being served meanwhile
+    let controller = Arc::new(ApiController::new(profile.clone()).await?);
+    registry.set_last_used(&profile.name).await?;
+    *API_CONTROLLER.write().await = Some(controller);
+
+    info!("Switched to profile {}", profile.name);
+    Ok(profile)
 }
 
 #[derive(Debug, Error, Serialize)]
 pub enum ApiControllerError {
     #[error("Failed to initialise and conncet to the database")]
     DatabaseConnectionFailure(#[from] DbManagerError),
+
+    #[error("Failed to initialise the accounts service: {0}")]
+    AccountsServiceFailure(String),
+
+    #[error("Invalid profile: {0}")]
+    InvalidProfile(#[from] ProfileError),
+
+    #[error("Failed to load the profiles: {0}")]
+    ProfileRegistryFailure(String),
+}
+
+impl From<ProfileRegistryError> for ApiControllerError {
+    fn from(value: ProfileRegistryError) -> Self {
+        ApiControllerError::ProfileRegistryFailure(value.to_string())
+    }
+}
+
+impl From<AccountsServiceError> for ApiControllerError {
+    fn from(value: AccountsServiceError) -> Self {
+        ApiControllerError::AccountsServiceFailure(value.to_string())
+    }
 }
 
 #[derive(Debug)]
 pub struct ApiController {
-    karma_service: KarmaService<DbManager>,
+    pub(crate) profile: Profile,
+    pub(crate) karma_service: KarmaService<DbManager>,
+    pub(crate) accounts_service: AccountsService,
 }
 
 impl ApiController {
-    pub async fn new() -> Result<ApiController, ApiControllerError> {
-        let karma_repo = DbManager::new("karma_db.sqlite").await?;
-        let karma_service = KarmaService::new(karma_repo);
+    pub async fn new(profile: Profile) -> Result<ApiController, ApiControllerError> {
+        let karma_repo = DbManager::new(&profile.db_file).await?;
+        let karma_service = KarmaService::new(karma_repo)
+            .with_achievements(achievements())
+            .with_notifier(Arc::new(EventNotifier));
+        let accounts_service = AccountsService::new(&profile.db_file)
+            .await?
+            .with_password_policy(password_policy());
 
-        Ok(ApiController { karma_service })
+        Ok(ApiController {
+            profile,
+            karma_service,
+            accounts_service,
+        })
     }
 }
+
+fn achievements() -> AchievementBook {
+    let mut achievements = AchievementBook::bundled();
+
+    if let Ok(path) = std::env::var(ACHIEVEMENTS_ENV) {
+        let user_achievements = std::fs::read_to_string(&path)
+            .map_err(|e| e.to_string())
+            .and_then(|json| AchievementBook::from_json(&json).map_err(|e| e.to_string()));
+        match user_achievements {
+            Ok(user_achievements) => achievements = achievements.merge(user_achievements),
+            Err(e) => warn!("Failed to load the achievements {path}: {e}"),
+        }
+    }
+
+    achievements
+}
+
+fn password_policy() -> PasswordPolicy {
+    let mut blocklist = PasswordBlocklist::bundled();
+
+    if let Ok(path) = std::env::var(PASSWORD_BLOCKLIST_ENV) {
+        match PasswordBlocklist::from_file(std::path::Path::new(&path)) {
+            Ok(user_blocklist) => blocklist = blocklist.merge(user_blocklist),
+            Err(e) => warn!("Failed to load the password blocklist {path}: {e}"),
+        }
+    }
+
+    PasswordPolicy::default().with_blocklist(blocklist)
+}
@@ -0,0 +1,72 @@
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+use crate::api::{get_controller, ApiControllerError};
+use crate::model::karma::KarmaType;
+use crate::model::plan::{PlanEntry, PlanReview};
+use crate::model::recurrence::{parse_date, parse_time_of_day, RecurrenceError};
+use crate::service::karma::karma_service::KarmaServiceError;
+
+#[derive(Error, Debug, Serialize)]
+pub enum PlanApiError {
+    #[error("Failed to initialise the api controller because: {0}")]
+    ApiControllerFailure(#[from] ApiControllerError),
+
+    #[error("{0}")]
+    InvalidDate(#[from] RecurrenceError),
+
+    #[error("Plan operation failed: {0}")]
+    PlanFailed(#[from] KarmaServiceError),
+}
+
+/// A plan entry as sent by the frontend
+#[derive(Debug, Clone, Deserialize)]
+pub struct PlanEntryRequest {
+    /// YYYY-MM-DD
+    pub day: String,
+    pub name: String,
+    /// The point's type when missing
+    pub purpose: Option<KarmaType>,
+    /// The point's default duration when missing
+    pub minutes: Option<i64>,
+    /// HH:MM
+    pub starts_at: Option<String>,
+}
+
+#[tauri::command]
+pub async fn add_plan_entry(entry: PlanEntryRequest) -> Result<PlanEntry, PlanApiError> {
+    let day = parse_date(&entry.day)?;
+    let starts_at = entry
+        .starts_at
+        .as_deref()
+        .map(parse_time_of_day)
+        .transpose()?;
+    let controller = get_controller().await?;
+
+    Ok(controller
+        .karma_service
+        .add_plan_entry(
+            day,
+            &entry.name,
+            entry.purpose,
+            entry.minutes.map(|minutes| minutes * 60),
+            starts_at,
+        )
+        .await?)
+}
+
+#[tauri::command]
+pub async fn remove_plan_entry(id: i32) -> Result<(), PlanApiError> {
+    let controller = get_controller().await?;
+
+    Ok(controller.karma_service.remove_plan_entry(id).await?)
+}
+
+/// The plan of a day, YYYY-MM-DD, with what was done, skipped or replaced
+#[tauri::command]
+pub async fn review_plan(day: String) -> Result<PlanReview, PlanApiError> {
+    let day = parse_date(&day)?;
+    let controller = get_controller().await?;
+
+    Ok(controller.karma_service.review_plan(day).await?)
+}
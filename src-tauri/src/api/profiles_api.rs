@@ -0,0 +1,61 @@
+use serde::Serialize;
+
+use crate::model::profile::Profile;
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ProfilesView {
+    pub profiles: Vec<Profile>,
+    /// The profile opened at startup when the user doesn't pick another one
+    pub last_used: Profile,
+}
+
+pub mod list {
+    use super::ProfilesView;
+    use crate::api::{get_controller, get_profile_registry, ApiControllerError};
+    use crate::model::profile::Profile;
+
+    #[tauri::command]
+    pub async fn list_profiles() -> Result<ProfilesView, ApiControllerError> {
+        let registry = get_profile_registry().await?.lock().await;
+
+        Ok(ProfilesView {
+            profiles: registry.profiles().to_vec(),
+            last_used: registry.last_used(),
+        })
+    }
+
+    #[tauri::command]
+    pub async fn current_profile() -> Result<Profile, ApiControllerError> {
+        let controller = get_controller().await?;
+
+        Ok(controller.profile.clone())
+    }
+}
+
+pub mod manage {
+    use crate::api::{get_profile_registry, switch_profile as switch, ApiControllerError};
+    use crate::model::profile::Profile;
+
+    use tracing::info;
+
+    /// Registers the profile, its database is created the first time it is opened
+    #[tauri::command]
+    pub async fn create_profile(name: String) -> Result<Profile, ApiControllerError> {
+        let profile = Profile::new(&name)?;
+
+        get_profile_registry()
+            .await?
+            .lock()
+            .await
+            .add(profile.clone())
+            .await?;
+        info!("Created profile {}", profile.name);
+
+        Ok(profile)
+    }
+
+    #[tauri::command]
+    pub async fn switch_profile(name: String) -> Result<Profile, ApiControllerError> {
+        switch(&name).await
+    }
+}
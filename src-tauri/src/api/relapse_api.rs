@@ -0,0 +1,53 @@
+use serde::Serialize;
+use thiserror::Error;
+
+use crate::api::{get_controller, ApiControllerError};
+use crate::model::karma::{KarmaPoint, Polarity};
+use crate::model::karma_session::StartedSession;
+use crate::model::relapse::RelapseReport;
+use crate::model::report::{ReportError, ReportPeriod};
+use crate::service::karma::karma_service::KarmaServiceError;
+
+#[derive(Error, Debug, Serialize)]
+pub enum RelapseApiError {
+    #[error("Failed to initialise the api controller because: {0}")]
+    ApiControllerFailure(#[from] ApiControllerError),
+
+    #[error("{0}")]
+    InvalidPeriod(#[from] ReportError),
+
+    #[error("Relapse tracking failed: {0}")]
+    RelapseFailed(#[from] KarmaServiceError),
+}
+
+/// Every habit to avoid with its relapses, counted per week or month
+#[tauri::command]
+pub async fn list_relapses(period: Option<String>) -> Result<RelapseReport, RelapseApiError> {
+    let period = ReportPeriod::try_from(period.as_deref().unwrap_or("week"))?;
+    let controller = get_controller().await?;
+
+    Ok(controller.karma_service.relapses(period).await?)
+}
+
+#[tauri::command]
+pub async fn log_relapse(name: String) -> Result<StartedSession, RelapseApiError> {
+    let controller = get_controller().await?;
+
+    Ok(controller.karma_service.log_relapse(&name).await?)
+}
+
+/// Marks the karma point as a habit to avoid, or as something to do again
+#[tauri::command]
+pub async fn set_avoided(name: String, avoided: bool) -> Result<KarmaPoint, RelapseApiError> {
+    let polarity = if avoided {
+        Polarity::Negative
+    } else {
+        Polarity::Positive
+    };
+    let controller = get_controller().await?;
+
+    Ok(controller
+        .karma_service
+        .set_polarity(&name, polarity)
+        .await?)
+}
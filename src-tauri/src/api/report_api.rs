@@ -0,0 +1,81 @@
+use chrono::NaiveDate;
+use serde::Serialize;
+use thiserror::Error;
+
+use crate::api::{get_controller, ApiControllerError};
+use crate::model::deviation::DeviationAnalysis;
+use crate::model::recurrence::{parse_date, RecurrenceError};
+use crate::model::report::{KarmaReport, ReportError, ReportFormat, ReportPeriod};
+use crate::service::karma::karma_service::KarmaServiceError;
+
+#[derive(Error, Debug, Serialize)]
+pub enum ReportApiError {
+    #[error("Failed to initialise the api controller because: {0}")]
+    ApiControllerFailure(#[from] ApiControllerError),
+
+    #[error("{0}")]
+    InvalidReport(#[from] ReportError),
+
+    #[error("{0}")]
+    InvalidDate(#[from] RecurrenceError),
+
+    #[error("Failed to build the report: {0}")]
+    ReportFailed(#[from] KarmaServiceError),
+}
+
+/// `period` is week or month, `date` any YYYY-MM-DD day in it, today when missing
+async fn build_report(period: &str, date: Option<&str>) -> Result<KarmaReport, ReportApiError> {
+    let period = ReportPeriod::try_from(period)?;
+    let date: Option<NaiveDate> = date
+        .filter(|date| !date.is_empty())
+        .map(parse_date)
+        .transpose()?;
+    let controller = get_controller().await?;
+
+    Ok(controller.karma_service.report(period, date).await?)
+}
+
+#[tauri::command]
+pub async fn karma_report(
+    period: String,
+    date: Option<String>,
+) -> Result<KarmaReport, ReportApiError> {
+    build_report(&period, date.as_deref()).await
+}
+
+/// The same report as Markdown or plain text, for copying or saving
+#[tauri::command]
+pub async fn render_karma_report(
+    period: String,
+    date: Option<String>,
+    format: String,
+) -> Result<String, ReportApiError> {
+    let format = ReportFormat::try_from(format.as_str())?;
+
+    Ok(build_report(&period, date.as_deref()).await?.render(format))
+}
+
+/// Planned against actual types of the sessions closed between both YYYY-MM-DD days,
+/// with a trend per week or month
+#[tauri::command]
+pub async fn deviation_analysis(
+    from: Option<String>,
+    to: Option<String>,
+    trend_period: Option<String>,
+) -> Result<DeviationAnalysis, ReportApiError> {
+    let parse = |date: Option<String>| {
+        date.filter(|date| !date.is_empty())
+            .as_deref()
+            .map(parse_date)
+            .transpose()
+    };
+    let first_day = parse(from)?;
+    let last_day = parse(to)?;
+    let trend_period = ReportPeriod::try_from(trend_period.as_deref().unwrap_or("week"))?;
+    let controller = get_controller().await?;
+
+    Ok(controller
+        .karma_service
+        .deviation_analysis(first_day, last_day, trend_period)
+        .await?)
+}
@@ -0,0 +1,61 @@
+use serde::Serialize;
+use thiserror::Error;
+
+use crate::api::{get_controller, ApiControllerError};
+use crate::model::reward::{Redemption, Reward, RewardShop};
+use crate::service::karma::karma_service::KarmaServiceError;
+
+#[derive(Error, Debug, Serialize)]
+pub enum RewardApiError {
+    #[error("Failed to initialise the api controller because: {0}")]
+    ApiControllerFailure(#[from] ApiControllerError),
+
+    #[error("Reward shop failed: {0}")]
+    RewardFailed(#[from] KarmaServiceError),
+}
+
+/// The points balance with every reward and whether it can be redeemed today
+#[tauri::command]
+pub async fn reward_shop() -> Result<RewardShop, RewardApiError> {
+    let controller = get_controller().await?;
+
+    Ok(controller.karma_service.reward_shop().await?)
+}
+
+/// `limit` such as "once per week" or "3 per month", unlimited when missing
+#[tauri::command]
+pub async fn add_reward(
+    name: String,
+    cost: f64,
+    limit: Option<String>,
+) -> Result<Reward, RewardApiError> {
+    let limit = limit.filter(|limit| !limit.trim().is_empty());
+    let controller = get_controller().await?;
+
+    Ok(controller
+        .karma_service
+        .add_reward(&name, cost, limit.as_deref())
+        .await?)
+}
+
+#[tauri::command]
+pub async fn delete_reward(name: String) -> Result<(), RewardApiError> {
+    let controller = get_controller().await?;
+
+    Ok(controller.karma_service.delete_reward(&name).await?)
+}
+
+#[tauri::command]
+pub async fn redeem_reward(name: String) -> Result<Redemption, RewardApiError> {
+    let controller = get_controller().await?;
+
+    Ok(controller.karma_service.redeem_reward(&name).await?)
+}
+
+/// Every redemption, the latest first
+#[tauri::command]
+pub async fn list_redemptions() -> Result<Vec<Redemption>, RewardApiError> {
+    let controller = get_controller().await?;
+
+    Ok(controller.karma_service.redemptions().await?)
+}
@@ -0,0 +1,187 @@
+use std::collections::BTreeSet;
+use std::time::Duration;
+
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+use tracing::{info, warn};
+
+use crate::api::{get_controller, ApiControllerError};
+use crate::model::recurrence::{
+    parse_date, parse_time_of_day, Recurrence, RecurrenceError, RecurrenceRule,
+};
+use crate::service::karma::karma_service::KarmaServiceError;
+
+/// How often the background job plans occurrences and looks for missed ones
+pub const DAILY_JOB_PERIOD: Duration = Duration::from_secs(60 * 60);
+
+#[derive(Error, Debug, Serialize)]
+pub enum ScheduleApiError {
+    #[error("Failed to initialise the api controller because: {0}")]
+    ApiControllerFailure(#[from] ApiControllerError),
+
+    #[error("{0}")]
+    InvalidSchedule(#[from] RecurrenceError),
+
+    #[error("Unknown occurrence status or resolution {0}")]
+    InvalidStatus(String),
+
+    #[error("Schedule operation failed: {0}")]
+    ScheduleFailed(#[from] KarmaServiceError),
+}
+
+/// A schedule as sent by the frontend or typed on the command line
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct ScheduleRequest {
+    /// For example `FREQ=WEEKLY;BYDAY=MO,WE,FR`
+    pub rule: String,
+    /// YYYY-MM-DD
+    pub starts_on: String,
+    /// HH:MM
+    pub start_time: Option<String>,
+    /// YYYY-MM-DD dates to leave out
+    #[serde(default)]
+    pub exceptions: Vec<String>,
+}
+
+impl TryFrom<ScheduleRequest> for Recurrence {
+    type Error = RecurrenceError;
+
+    fn try_from(request: ScheduleRequest) -> Result<Self, Self::Error> {
+        Ok(Recurrence {
+            rule: RecurrenceRule::parse(&request.rule)?,
+            starts_on: parse_date(&request.starts_on)?,
+            start_time: request
+                .start_time
+                .as_deref()
+                .map(parse_time_of_day)
+                .transpose()?,
+            exceptions: request
+                .exceptions
+                .iter()
+                .map(|date| parse_date(date))
+                .collect::<Result<BTreeSet<_>, _>>()?,
+        })
+    }
+}
+
+/// Runs the daily job now and then every period, on the profile open at that time
+pub fn spawn_daily_job() {
+    tokio::spawn(async {
+        let mut interval = tokio::time::interval(DAILY_JOB_PERIOD);
+        loop {
+            interval.tick().await;
+
+            let report = match get_controller().await {
+                Ok(controller) => controller.karma_service.run_daily_job().await,
+                Err(e) => {
+                    warn!("Daily job skipped: {e}");
+                    continue;
+                }
+            };
+            match report {
+                Ok(report) => info!("Daily job: {report:?}"),
+                Err(e) => warn!("Daily job failed: {e}"),
+            }
+        }
+    });
+}
+
+pub mod manage {
+    use super::{ScheduleApiError, ScheduleRequest};
+    use crate::api::get_controller;
+    use crate::model::recurrence::{parse_date, Recurrence};
+    use crate::model::schedule::KarmaSchedule;
+
+    use chrono::NaiveDate;
+
+    #[tauri::command]
+    pub async fn set_karma_schedule(
+        name: String,
+        schedule: ScheduleRequest,
+    ) -> Result<KarmaSchedule, ScheduleApiError> {
+        let recurrence = Recurrence::try_from(schedule)?;
+        let controller = get_controller().await?;
+
+        Ok(controller
+            .karma_service
+            .set_schedule(&name, recurrence)
+            .await?)
+    }
+
+    #[tauri::command]
+    pub async fn clear_karma_schedule(name: String) -> Result<(), ScheduleApiError> {
+        let controller = get_controller().await?;
+
+        Ok(controller.karma_service.clear_schedule(&name).await?)
+    }
+
+    /// The dates the schedule falls on between both dates inclusive
+    #[tauri::command]
+    pub async fn expand_karma_schedule(
+        name: String,
+        from: String,
+        to: String,
+    ) -> Result<Vec<NaiveDate>, ScheduleApiError> {
+        let (from, to) = (parse_date(&from)?, parse_date(&to)?);
+        let controller = get_controller().await?;
+
+        Ok(controller
+            .karma_service
+            .expand_schedule(&name, from, to)
+            .await?)
+    }
+}
+
+pub mod occurrences {
+    use super::ScheduleApiError;
+    use crate::api::get_controller;
+    use crate::model::recurrence::parse_date;
+    use crate::model::schedule::{KarmaOccurrence, OccurrenceResolution, OccurrenceStatus};
+
+    #[tauri::command]
+    pub async fn list_occurrences(
+        from: String,
+        to: String,
+        status: Option<String>,
+    ) -> Result<Vec<KarmaOccurrence>, ScheduleApiError> {
+        let (from, to) = (parse_date(&from)?, parse_date(&to)?);
+        let status = status
+            .as_deref()
+            .map(OccurrenceStatus::try_from)
+            .transpose()
+            .map_err(ScheduleApiError::InvalidStatus)?;
+        let controller = get_controller().await?;
+
+        Ok(controller
+            .karma_service
+            .list_occurrences(from, to, status)
+            .await?)
+    }
+
+    /// `resolution` is done or skipped, a done occurrence can point to its session
+    #[tauri::command]
+    pub async fn resolve_occurrence(
+        id: i32,
+        resolution: String,
+        session_id: Option<i32>,
+    ) -> Result<KarmaOccurrence, ScheduleApiError> {
+        let resolution = parse_resolution(&resolution, session_id)?;
+        let controller = get_controller().await?;
+
+        Ok(controller
+            .karma_service
+            .resolve_occurrence(id, resolution)
+            .await?)
+    }
+
+    pub fn parse_resolution(
+        resolution: &str,
+        session_id: Option<i32>,
+    ) -> Result<OccurrenceResolution, ScheduleApiError> {
+        match resolution.to_lowercase().as_str() {
+            "done" => Ok(OccurrenceResolution::Done { session_id }),
+            "skipped" | "skip" => Ok(OccurrenceResolution::Skipped),
+            other => Err(ScheduleApiError::InvalidStatus(other.to_string())),
+        }
+    }
+}
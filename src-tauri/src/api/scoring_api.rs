@@ -0,0 +1,39 @@
+use serde::Serialize;
+use thiserror::Error;
+
+use crate::api::{get_controller, ApiControllerError};
+use crate::model::scoring::{PointsBalance, ScoringRules};
+use crate::service::karma::karma_service::KarmaServiceError;
+
+#[derive(Error, Debug, Serialize)]
+pub enum ScoringApiError {
+    #[error("Failed to initialise the api controller because: {0}")]
+    ApiControllerFailure(#[from] ApiControllerError),
+
+    #[error("Scoring failed: {0}")]
+    ScoringFailed(#[from] KarmaServiceError),
+}
+
+/// The points balance with a ledger of the last `days` days, 30 by default
+#[tauri::command]
+pub async fn get_points_balance(days: Option<i64>) -> Result<PointsBalance, ScoringApiError> {
+    let controller = get_controller().await?;
+
+    Ok(controller.karma_service.points_balance(days).await?)
+}
+
+#[tauri::command]
+pub async fn get_scoring_rules() -> Result<ScoringRules, ScoringApiError> {
+    let controller = get_controller().await?;
+
+    Ok(controller.karma_service.scoring_rules().await?)
+}
+
+/// `rules` as comma separated type=points per hour, deviation=points, relapse=points
+/// and decay=days, the ones left out stay as they are
+#[tauri::command]
+pub async fn set_scoring_rules(rules: String) -> Result<ScoringRules, ScoringApiError> {
+    let controller = get_controller().await?;
+
+    Ok(controller.karma_service.set_scoring_rules(&rules).await?)
+}
@@ -0,0 +1,42 @@
+use serde::Serialize;
+use thiserror::Error;
+
+use crate::api::ApiControllerError;
+use crate::service::karma::karma_service::KarmaServiceError;
+
+#[derive(Error, Debug, Serialize)]
+pub enum SettingsApiError {
+    #[error("Failed to initialise the api controller because: {0}")]
+    ApiControllerFailure(#[from] ApiControllerError),
+
+    #[error("Failed to access the settings: {0}")]
+    SettingsFailed(#[from] KarmaServiceError),
+}
+
+pub mod time {
+    use super::SettingsApiError;
+    use crate::api::get_controller;
+    use crate::model::local_time::TimeSettings;
+
+    #[tauri::command]
+    pub async fn get_time_settings() -> Result<TimeSettings, SettingsApiError> {
+        let controller = get_controller().await?;
+
+        Ok(controller.karma_service.time_settings().await?)
+    }
+
+    /// `timezone` is an IANA name such as Europe/Berlin or an offset such as +02:00,
+    /// `week_start` a week day such as monday. Missing ones are left unchanged.
+    #[tauri::command]
+    pub async fn set_time_settings(
+        timezone: Option<String>,
+        week_start: Option<String>,
+    ) -> Result<TimeSettings, SettingsApiError> {
+        let controller = get_controller().await?;
+
+        Ok(controller
+            .karma_service
+            .set_time_settings(timezone.as_deref(), week_start.as_deref())
+            .await?)
+    }
+}
@@ -0,0 +1,59 @@
+use serde::Serialize;
+use thiserror::Error;
+
+use crate::api::{get_controller, ApiControllerError};
+use crate::model::sleep::{parse_local_datetime, Night, SleepError, SleepReport, SleepRules};
+use crate::service::karma::karma_service::KarmaServiceError;
+
+#[derive(Error, Debug, Serialize)]
+pub enum SleepApiError {
+    #[error("Failed to initialise the api controller because: {0}")]
+    ApiControllerFailure(#[from] ApiControllerError),
+
+    #[error("{0}")]
+    InvalidTime(#[from] SleepError),
+
+    #[error("Sleep tracking failed: {0}")]
+    SleepFailed(#[from] KarmaServiceError),
+}
+
+/// Sleep debt and consistency over the last `nights` nights, the rules' window by default
+#[tauri::command]
+pub async fn sleep_report(nights: Option<u32>) -> Result<SleepReport, SleepApiError> {
+    let controller = get_controller().await?;
+
+    Ok(controller.karma_service.sleep_report(nights).await?)
+}
+
+/// Records a past sleep, `bedtime` and `wake` as local YYYY-MM-DD HH:MM
+#[tauri::command]
+pub async fn log_sleep(
+    name: Option<String>,
+    bedtime: String,
+    wake: String,
+) -> Result<Night, SleepApiError> {
+    let bedtime = parse_local_datetime(&bedtime)?;
+    let wake = parse_local_datetime(&wake)?;
+    let controller = get_controller().await?;
+
+    Ok(controller
+        .karma_service
+        .log_sleep(name.as_deref().unwrap_or("Sleep"), bedtime, wake)
+        .await?)
+}
+
+#[tauri::command]
+pub async fn get_sleep_rules() -> Result<SleepRules, SleepApiError> {
+    let controller = get_controller().await?;
+
+    Ok(controller.karma_service.sleep_rules().await?)
+}
+
+/// `rules` as comma separated target=hours and window=nights, the ones left out
+/// stay as they are
+#[tauri::command]
+pub async fn set_sleep_rules(rules: String) -> Result<SleepRules, SleepApiError> {
+    let controller = get_controller().await?;
+
+    Ok(controller.karma_service.set_sleep_rules(&rules).await?)
+}
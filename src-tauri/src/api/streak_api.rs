@@ -0,0 +1,43 @@
+use serde::Serialize;
+use thiserror::Error;
+
+use crate::api::{get_controller, ApiControllerError};
+use crate::model::recurrence::{parse_date, RecurrenceError};
+use crate::model::streak::Streak;
+use crate::service::karma::karma_service::KarmaServiceError;
+
+#[derive(Error, Debug, Serialize)]
+pub enum StreakApiError {
+    #[error("Failed to initialise the api controller because: {0}")]
+    ApiControllerFailure(#[from] ApiControllerError),
+
+    #[error("{0}")]
+    InvalidDate(#[from] RecurrenceError),
+
+    #[error("Streak operation failed: {0}")]
+    StreakFailed(#[from] KarmaServiceError),
+}
+
+#[tauri::command]
+pub async fn list_streaks() -> Result<Vec<Streak>, StreakApiError> {
+    let controller = get_controller().await?;
+
+    Ok(controller.karma_service.streaks().await?)
+}
+
+/// `day` as YYYY-MM-DD, returns whether it wasn't frozen already
+#[tauri::command]
+pub async fn freeze_streak_day(day: String) -> Result<bool, StreakApiError> {
+    let day = parse_date(&day)?;
+    let controller = get_controller().await?;
+
+    Ok(controller.karma_service.freeze_day(day).await?)
+}
+
+#[tauri::command]
+pub async fn unfreeze_streak_day(day: String) -> Result<bool, StreakApiError> {
+    let day = parse_date(&day)?;
+    let controller = get_controller().await?;
+
+    Ok(controller.karma_service.unfreeze_day(day).await?)
+}
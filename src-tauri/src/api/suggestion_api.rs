@@ -0,0 +1,92 @@
+use serde::Serialize;
+use thiserror::Error;
+
+use crate::api::{get_controller, ApiControllerError};
+use crate::model::plan::PlanEntry;
+use crate::model::recurrence::{parse_date, RecurrenceError};
+use crate::model::suggestion::{BlockedTime, ScheduleProposal, SuggestionRules};
+use crate::service::karma::karma_service::KarmaServiceError;
+
+#[derive(Error, Debug, Serialize)]
+pub enum SuggestionApiError {
+    #[error("Failed to initialise the api controller because: {0}")]
+    ApiControllerFailure(#[from] ApiControllerError),
+
+    #[error("{0}")]
+    InvalidDate(#[from] RecurrenceError),
+
+    #[error("Schedule suggestion failed: {0}")]
+    SuggestionFailed(#[from] KarmaServiceError),
+}
+
+/// Suggestions for the week of `week`, YYYY-MM-DD, the next week by default
+#[tauri::command]
+pub async fn suggest_schedule(
+    week: Option<String>,
+) -> Result<ScheduleProposal, SuggestionApiError> {
+    let week = week.as_deref().map(parse_date).transpose()?;
+    let controller = get_controller().await?;
+
+    Ok(controller.karma_service.suggest_schedule(week).await?)
+}
+
+/// Adds the suggestions for the week of `week` to the day plans
+#[tauri::command]
+pub async fn accept_schedule_suggestions(
+    week: Option<String>,
+) -> Result<Vec<PlanEntry>, SuggestionApiError> {
+    let week = week.as_deref().map(parse_date).transpose()?;
+    let controller = get_controller().await?;
+
+    Ok(controller
+        .karma_service
+        .accept_schedule_suggestions(week)
+        .await?)
+}
+
+#[tauri::command]
+pub async fn get_suggestion_rules() -> Result<SuggestionRules, SuggestionApiError> {
+    let controller = get_controller().await?;
+
+    Ok(controller.karma_service.suggestion_rules().await?)
+}
+
+/// `rules` as comma separated daily=hours, block=minutes, from=HH:MM and to=HH:MM,
+/// the ones left out stay as they are
+#[tauri::command]
+pub async fn set_suggestion_rules(rules: String) -> Result<SuggestionRules, SuggestionApiError> {
+    let controller = get_controller().await?;
+
+    Ok(controller
+        .karma_service
+        .set_suggestion_rules(&rules)
+        .await?)
+}
+
+#[tauri::command]
+pub async fn list_blocked_times() -> Result<Vec<BlockedTime>, SuggestionApiError> {
+    let controller = get_controller().await?;
+
+    Ok(controller.karma_service.blocked_times().await?)
+}
+
+/// `time` like `MO,TU 09:00-17:00`, without days for every day
+#[tauri::command]
+pub async fn add_blocked_time(
+    label: String,
+    time: String,
+) -> Result<BlockedTime, SuggestionApiError> {
+    let controller = get_controller().await?;
+
+    Ok(controller
+        .karma_service
+        .add_blocked_time(&label, &time)
+        .await?)
+}
+
+#[tauri::command]
+pub async fn delete_blocked_time(id: i32) -> Result<(), SuggestionApiError> {
+    let controller = get_controller().await?;
+
+    Ok(controller.karma_service.delete_blocked_time(id).await?)
+}
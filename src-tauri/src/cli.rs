@@ -0,0 +1,1653 @@
+use chrono::NaiveDate;
+use clap::{Parser, Subcommand};
+
+use crate::api::get_controller_for;
+use crate::api::karma_api::list::KarmaListRequest;
+use crate::api::schedule_api::{occurrences::parse_resolution, ScheduleRequest};
+use crate::model::goal::{GoalComparison, GoalMetric};
+use crate::model::karma::{KarmaType, Polarity};
+use crate::model::karma_query::KarmaQuery;
+use crate::model::karma_resolver::KarmaResolveError;
+use crate::model::karma_search::DEFAULT_SEARCH_LIMIT;
+use crate::model::local_time::TimeSettings;
+use crate::model::recurrence::{format_time_of_day, parse_date, parse_time_of_day, Recurrence};
+use crate::model::report::{format_duration, ReportFormat, ReportPeriod};
+use crate::model::schedule::OccurrenceStatus;
+use crate::model::sleep::parse_local_datetime;
+use crate::service::karma::karma_service::KarmaServiceError;
+
+/// Without a subcommand the desktop app is started
+#[derive(Debug, Parser)]
+#[command(name = "karma-manager", version, about = "Keep your karma balanced")]
+pub struct Cli {
+    /// Profile to use instead of the one opened last
+    #[arg(long, global = true)]
+    pub profile: Option<String>,
+
+    #[command(subcommand)]
+    pub command: Option<Command>,
+}
+
+#[derive(Debug, Subcommand)]
+pub enum Command {
+    /// List karma points, newest first
+    List {
+        /// work, social, sport, learning or sleeping
+        #[arg(long = "type")]
+        purpose: Option<String>,
+
+        /// active, paused or closed
+        #[arg(long)]
+        state: Option<String>,
+
+        /// Start of the date range, YYYY-MM-DD or unix seconds
+        #[arg(long, value_parser = parse_time_arg)]
+        from: Option<TimeArg>,
+
+        /// End of the date range, YYYY-MM-DD (the whole day) or unix seconds
+        #[arg(long, value_parser = parse_time_arg)]
+        to: Option<TimeArg>,
+
+        /// Only names starting with this, case insensitive
+        #[arg(long)]
+        prefix: Option<String>,
+
+        /// created or activity
+        #[arg(long)]
+        sort: Option<String>,
+
+        #[arg(long)]
+        limit: Option<u32>,
+
+        /// Cursor printed at the end of the previous page
+        #[arg(long)]
+        cursor: Option<String>,
+    },
+    /// Search karma points by name, best matches first
+    Search {
+        /// Words to look for, each one matches as a prefix
+        query: Vec<String>,
+
+        #[arg(long, default_value_t = DEFAULT_SEARCH_LIMIT)]
+        limit: u32,
+    },
+    /// Rebuild the search index from the stored karma points
+    Reindex,
+    /// Show a karma point, the name may be a prefix or contain typos
+    Show { name: Vec<String> },
+    /// Start a new session of an activity
+    Start {
+        /// A known activity, possibly abbreviated, or a new one when --type is given
+        name: Vec<String>,
+
+        /// work, social, sport, learning or sleeping
+        #[arg(long = "type")]
+        purpose: Option<String>,
+
+        /// Planned duration in minutes
+        #[arg(long)]
+        minutes: Option<i64>,
+    },
+    /// Pause a running session, paused time isn't counted
+    Pause { session: i32 },
+    /// Continue a paused session
+    Resume { session: i32 },
+    /// End a session
+    Stop {
+        session: i32,
+
+        /// What the session turned out to be, when not what was planned
+        #[arg(long = "as")]
+        closed_with: Option<String>,
+    },
+    /// Show the active time per karma point, type and day
+    Totals {
+        /// YYYY-MM-DD or unix seconds, the start of today by default
+        #[arg(long, value_parser = parse_time_arg)]
+        from: Option<TimeArg>,
+
+        /// YYYY-MM-DD (the whole day) or unix seconds, now by default
+        #[arg(long, value_parser = parse_time_arg)]
+        to: Option<TimeArg>,
+    },
+    /// Summarise a week or month next to the one before it
+    Report {
+        /// week or month
+        #[arg(default_value = "week")]
+        period: String,
+
+        /// Any day of the period, YYYY-MM-DD, today by default
+        #[arg(long)]
+        date: Option<String>,
+
+        /// markdown or text
+        #[arg(long, default_value = "text")]
+        format: String,
+
+        /// Write the report to this file instead of printing it
+        #[arg(long)]
+        output: Option<std::path::PathBuf>,
+    },
+    /// Show how often sessions ended as another type than planned
+    Deviations {
+        /// YYYY-MM-DD, 90 days before --to by default
+        #[arg(long)]
+        from: Option<String>,
+
+        /// YYYY-MM-DD, today by default
+        #[arg(long)]
+        to: Option<String>,
+
+        /// Trend per week or month
+        #[arg(long, default_value = "week")]
+        by: String,
+    },
+    /// This week's balance against the target hours per type, with suggestions
+    Balance {
+        /// Replace the targets first, e.g. "work=40, sport=5"
+        #[arg(long)]
+        targets: Option<String>,
+    },
+    /// Make an activity recur, replacing its previous schedule
+    Schedule {
+        name: Vec<String>,
+
+        /// For example FREQ=WEEKLY;BYDAY=MO,WE,FR or FREQ=MONTHLY;BYMONTHDAY=1
+        #[arg(long)]
+        rule: String,
+
+        /// First day of the schedule, YYYY-MM-DD, today by default
+        #[arg(long)]
+        starts: Option<String>,
+
+        /// Time of day the occurrences are due, HH:MM
+        #[arg(long)]
+        at: Option<String>,
+
+        /// Days to leave out, YYYY-MM-DD
+        #[arg(long = "except", value_delimiter = ',')]
+        exceptions: Vec<String>,
+    },
+    /// Stop an activity from recurring
+    Unschedule { name: Vec<String> },
+    /// List planned, done and missed occurrences, a week around today by default
+    Occurrences {
+        /// YYYY-MM-DD
+        #[arg(long)]
+        from: Option<String>,
+
+        /// YYYY-MM-DD
+        #[arg(long)]
+        to: Option<String>,
+
+        /// planned, done, missed or skipped
+        #[arg(long)]
+        status: Option<String>,
+    },
+    /// Settle a missed occurrence as done or skipped
+    ResolveOccurrence {
+        id: i32,
+
+        /// done or skipped
+        resolution: String,
+
+        /// The session that did it, for done
+        #[arg(long)]
+        session: Option<i32>,
+    },
+    /// Plan upcoming occurrences and mark missed ones, meant to run daily
+    Plan,
+    /// Set a goal for a type or an activity, e.g. learning --at-least 5
+    Goal {
+        /// A type such as learning or the name of an activity
+        subject: Vec<String>,
+
+        #[arg(long, conflicts_with = "at_most", required_unless_present = "at_most")]
+        at_least: Option<f64>,
+
+        #[arg(long)]
+        at_most: Option<f64>,
+
+        /// hours, daily_hours or sessions
+        #[arg(long, default_value = "hours")]
+        metric: String,
+
+        /// week or month
+        #[arg(long, default_value = "week")]
+        per: String,
+    },
+    /// List the goals with their progress this period and the previous ones
+    Goals,
+    /// Remove a goal
+    DeleteGoal { id: i32 },
+    /// The points balance with what was earned and lost to decay each day
+    Points {
+        /// How many days the ledger goes back
+        #[arg(long, default_value_t = 7)]
+        days: i64,
+
+        /// Change the scoring first, e.g. "sport=2, deviation=1, relapse=2, decay=30"
+        #[arg(long)]
+        rules: Option<String>,
+    },
+    /// The points balance and the rewards it can be spent on
+    Rewards,
+    /// Offer a reward for a number of points
+    Reward {
+        name: String,
+
+        #[arg(long)]
+        cost: f64,
+
+        /// How often it can be redeemed, e.g. "once per week" or "3 per month"
+        #[arg(long)]
+        limit: Option<String>,
+    },
+    /// Take a reward off the shop, its redemptions stay in the history
+    DeleteReward { name: String },
+    /// Spend points on a reward
+    Redeem { name: String },
+    /// Every redemption, the latest first
+    Redemptions,
+    /// Add an activity to the plan of a day, tomorrow by default
+    PlanDay {
+        name: Vec<String>,
+
+        /// YYYY-MM-DD
+        #[arg(long)]
+        day: Option<String>,
+
+        /// The type it is meant as, the activity's by default
+        #[arg(long = "type")]
+        purpose: Option<String>,
+
+        /// Planned duration in minutes, the activity's default one otherwise
+        #[arg(long)]
+        minutes: Option<i64>,
+
+        /// Time of day to start at, HH:MM
+        #[arg(long)]
+        at: Option<String>,
+    },
+    /// Take an entry off its plan
+    Unplan { entry: i32 },
+    /// What was planned for a day against what was done, today by default
+    Review {
+        /// YYYY-MM-DD
+        #[arg(long)]
+        day: Option<String>,
+    },
+    /// Sessions to plan next week so the goals on types are met, around what is
+    /// already scheduled, planned or blocked
+    Suggest {
+        /// Suggest for the week of this day instead, YYYY-MM-DD
+        #[arg(long)]
+        week: Option<String>,
+
+        /// Add the suggestions to the plans of their days
+        #[arg(long)]
+        accept: bool,
+
+        /// Change the rules first, e.g. "daily=6, block=45, from=09:00, to=21:00"
+        #[arg(long)]
+        rules: Option<String>,
+    },
+    /// Keep time free of suggestions every week
+    Block {
+        label: String,
+
+        /// Days and times, e.g. "MO,TU,WE,TH,FR 09:00-17:00", every day without days
+        time: String,
+    },
+    /// The time kept free of suggestions
+    Blocks,
+    /// Stop keeping time free of suggestions
+    Unblock { id: i32 },
+    /// Start a work or learning session run as pomodoros, without a name show the
+    /// running one
+    Focus {
+        name: Vec<String>,
+
+        /// work or learning, creates the activity when it doesn't exist yet
+        #[arg(long = "type")]
+        purpose: Option<String>,
+
+        /// Change the phase lengths first, e.g. "work=50, short=10, long=30, every=3, max=6"
+        #[arg(long)]
+        rules: Option<String>,
+    },
+    /// Note what just interrupted the running focus session
+    Interrupt {
+        note: Vec<String>,
+
+        /// Someone or something else caused it
+        #[arg(long)]
+        external: bool,
+    },
+    /// End the running focus session and its session
+    Unfocus {
+        /// How well it went, 1 to 5
+        #[arg(long)]
+        rating: Option<u8>,
+    },
+    /// Rate a focus session after it ended, 1 to 5
+    RateFocus { id: i32, rating: u8 },
+    /// The last focus sessions, newest first
+    FocusHistory {
+        #[arg(long, default_value_t = 10)]
+        limit: i64,
+    },
+    /// Sleep per night with the sleep debt and how regular the bedtimes are
+    Sleep {
+        /// Nights to look back over, the window of the rules by default
+        #[arg(long)]
+        nights: Option<u32>,
+
+        /// Change the rules first, e.g. "target=7.5, window=28"
+        #[arg(long)]
+        rules: Option<String>,
+    },
+    /// Record a past sleep, times as local YYYY-MM-DD HH:MM
+    Slept {
+        bedtime: String,
+        wake: String,
+
+        /// The sleeping activity, created when missing
+        #[arg(long, default_value = "Sleep")]
+        name: String,
+    },
+    /// Every achievement, unlocked or how far along it is
+    Achievements,
+    /// Track a karma point as a habit to avoid, its sessions become relapses
+    Avoid {
+        name: String,
+
+        /// Track it as something to do again
+        #[arg(long)]
+        undo: bool,
+    },
+    /// Record a relapse into a habit to avoid
+    Relapse { name: String },
+    /// Time since the last relapse and how often each habit to avoid comes back
+    Relapses {
+        /// week or month
+        #[arg(long, default_value = "week")]
+        per: String,
+    },
+    /// Current and longest streaks per scheduled activity and per type
+    Streaks {
+        /// Freeze a day, YYYY-MM-DD, so it neither extends nor breaks a streak
+        #[arg(long)]
+        freeze: Option<String>,
+
+        /// Undo a freeze, YYYY-MM-DD
+        #[arg(long)]
+        unfreeze: Option<String>,
+    },
+    /// Show or change the timezone and first day of the week days are counted in
+    Settings {
+        /// An IANA name such as Europe/Berlin or an offset such as +02:00
+        #[arg(long)]
+        timezone: Option<String>,
+
+        /// monday, sunday, ...
+        #[arg(long)]
+        week_start: Option<String>,
+    },
+}
+
+/// A point in time typed on the command line, dates are resolved in the
+/// configured timezone once the profile is open
+#[derive(Debug, Clone, Copy)]
+pub enum TimeArg {
+    Unix(i64),
+    Date(NaiveDate),
+}
+
+impl TimeArg {
+    fn start(self, settings: &TimeSettings) -> i64 {
+        match self {
+            TimeArg::Unix(timestamp) => timestamp,
+            TimeArg::Date(date) => settings.day_start(date),
+        }
+    }
+
+    /// A date as the end of the range includes that whole day
+    fn end(self, settings: &TimeSettings) -> i64 {
+        match self {
+            TimeArg::Unix(timestamp) => timestamp,
+            TimeArg::Date(date) => settings.next_day_start(date) - 1,
+        }
+    }
+}
+
+/// Runs a command line subcommand, returning the message to print on failure
+pub async fn run(profile: Option<String>, command: Command) -> Result<(), String> {
+    let controller = get_controller_for(profile.as_deref())
+        .await
+        .map_err(|e| e.to_string())?;
+    let settings = controller
+        .karma_service
+        .time_settings()
+        .await
+        .map_err(|e| e.to_string())?;
+    let now = chrono::Utc::now().timestamp();
+
+    match command {
+        Command::List {
+            purpose,
+            state,
+            from,
+            to,
+            prefix,
+            sort,
+            limit,
+            cursor,
+        } => {
+            let query = KarmaQuery::try_from(KarmaListRequest {
+                purpose,
+                state,
+                from: from.map(|from| from.start(&settings)),
+                to: to.map(|to| to.end(&settings)),
+                name_prefix: prefix,
+                sort,
+                limit,
+                cursor,
+            })
+            .map_err(|e| e.to_string())?;
+
+            let page = controller
+                .karma_service
+                .list_karma(&query)
+                .await
+                .map_err(|e| e.to_string())?;
+
+            println!(
+                "{:>6}  {:<9} {:<7} {:<16}  NAME",
+                "ID", "TYPE", "STATE", "LAST ACTIVITY"
+            );
+            for item in &page.items {
+                let state = item
+                    .state
+                    .as_ref()
+                    .map(|state| state.to_string())
+                    .unwrap_or_else(|| "-".to_string());
+                println!(
+                    "{:>6}  {:<9} {:<7} {:<16}  {}",
+                    item.karma.get_id().unwrap_or_default(),
+                    format!("{:?}", item.karma.get_purpose()),
+                    state,
+                    settings.format(item.last_activity),
+                    item.karma.get_name()
+                );
+            }
+
+            if let Some(cursor) = page.next_cursor {
+                println!("\nMore results: --cursor {cursor}");
+            }
+            Ok(())
+        }
+        Command::Search { query, limit } => {
+            let results = controller
+                .karma_service
+                .search_karma(&query.join(" "), limit)
+                .await
+                .map_err(|e| e.to_string())?;
+
+            for result in &results {
+                let snippet: String = result
+                    .snippet
+                    .iter()
+                    .map(|part| {
+                        if part.highlighted {
+                            format!("[{}]", part.text)
+                        } else {
+                            part.text.clone()
+                        }
+                    })
+                    .collect();
+                println!(
+                    "{:>6}  {:<9} {}",
+                    result.karma.get_id().unwrap_or_default(),
+                    format!("{:?}", result.karma.get_purpose()),
+                    snippet
+                );
+            }
+
+            if results.is_empty() {
+                println!("No karma point matches");
+            }
+            Ok(())
+        }
+        Command::Reindex => {
+            controller
+                .karma_service
+                .rebuild_search_index()
+                .await
+                .map_err(|e| e.to_string())?;
+            println!("Search index rebuilt");
+            Ok(())
+        }
+        Command::Show { name } => {
+            let item = match controller
+                .karma_service
+                .resolve_karma(&name.join(" "))
+                .await
+            {
+                Ok(item) => item,
+                Err(KarmaServiceError::Resolve(KarmaResolveError::Ambiguous {
+                    name,
+                    candidates,
+                })) => {
+                    let mut message = format!("{name} could mean:");
+                    for candidate in candidates {
+                        message.push_str(&format!(
+                            "\n  {:>6}  {}",
+                            candidate.item.karma.get_id().unwrap_or_default(),
+                            candidate.item.karma.get_name()
+                        ));
+                    }
+                    return Err(message);
+                }
+                Err(e) => return Err(e.to_string()),
+            };
+
+            let state = item
+                .state
+                .as_ref()
+                .map(|state| state.to_string())
+                .unwrap_or_else(|| "never started".to_string());
+            println!("{}", item.karma.get_name());
+            println!(
+                "  id:            {}",
+                item.karma.get_id().unwrap_or_default()
+            );
+            println!("  type:          {:?}", item.karma.get_purpose());
+            println!("  state:         {state}");
+            println!(
+                "  created:       {}",
+                settings.format(item.karma.get_created_at())
+            );
+            println!("  last activity: {}", settings.format(item.last_activity));
+            Ok(())
+        }
+        Command::Start {
+            name,
+            purpose,
+            minutes,
+        } => {
+            let purpose = purpose
+                .as_deref()
+                .map(KarmaType::try_from)
+                .transpose()
+                .map_err(|e| e.to_string())?;
+
+            let started = controller
+                .karma_service
+                .start_karma(
+                    &name.join(" "),
+                    purpose,
+                    minutes.map(|minutes| minutes * 60),
+                )
+                .await
+                .map_err(|e| e.to_string())?;
+
+            println!(
+                "Started session {} of {} ({:?}) at {}",
+                started.session.get_id().unwrap_or_default(),
+                started.template.get_name(),
+                started.session.get_purpose(),
+                settings.format(started.session.get_started_at())
+            );
+            Ok(())
+        }
+        Command::Pause { session } => {
+            let status = controller
+                .karma_service
+                .pause_session(session)
+                .await
+                .map_err(|e| e.to_string())?;
+            println!(
+                "Paused session {session} at {}",
+                settings.format(status.timestamp)
+            );
+            Ok(())
+        }
+        Command::Resume { session } => {
+            let status = controller
+                .karma_service
+                .resume_session(session)
+                .await
+                .map_err(|e| e.to_string())?;
+            println!(
+                "Resumed session {session} at {}",
+                settings.format(status.timestamp)
+            );
+            Ok(())
+        }
+        Command::Stop {
+            session,
+            closed_with,
+        } => {
+            let closed_with = closed_with
+                .as_deref()
+                .map(KarmaType::try_from)
+                .transpose()
+                .map_err(|e| e.to_string())?;
+
+            controller
+                .karma_service
+                .close_session(session, closed_with)
+                .await
+                .map_err(|e| e.to_string())?;
+            let duration = controller
+                .karma_service
+                .session_duration(session)
+                .await
+                .map_err(|e| e.to_string())?;
+            println!(
+                "Stopped session {session} after {}",
+                format_duration(duration.active_seconds)
+            );
+            Ok(())
+        }
+        Command::Totals { from, to } => {
+            let from = match from {
+                Some(from) => from.start(&settings),
+                None => settings.day_start(settings.local_date(now)),
+            };
+            // The end is inclusive on the command line
+            let to = to.map(|to| to.end(&settings)).unwrap_or(now) + 1;
+
+            let totals = controller
+                .karma_service
+                .duration_totals(from, to)
+                .await
+                .map_err(|e| e.to_string())?;
+
+            println!("Total {}", format_duration(totals.total_seconds));
+            println!("\nPer point");
+            for point in &totals.per_point {
+                println!("  {:>9}  {}", format_duration(point.seconds), point.name);
+            }
+            println!("\nPer type");
+            for category in &totals.per_category {
+                println!(
+                    "  {:>9}  {:?}",
+                    format_duration(category.seconds),
+                    category.purpose
+                );
+            }
+            println!("\nPer day");
+            for day in &totals.per_day {
+                println!("  {:>9}  {}", format_duration(day.seconds), day.day);
+            }
+            println!("\nPer week");
+            for week in &totals.per_week {
+                println!(
+                    "  {:>9}  from {}",
+                    format_duration(week.seconds),
+                    week.week_start
+                );
+            }
+            Ok(())
+        }
+        Command::Report {
+            period,
+            date,
+            format,
+            output,
+        } => {
+            let period = ReportPeriod::try_from(period.as_str()).map_err(|e| e.to_string())?;
+            let format = ReportFormat::try_from(format.as_str()).map_err(|e| e.to_string())?;
+            let date = date
+                .as_deref()
+                .map(parse_date)
+                .transpose()
+                .map_err(|e| e.to_string())?;
+
+            let report = controller
+                .karma_service
+                .report(period, date)
+                .await
+                .map_err(|e| e.to_string())?
+                .render(format);
+
+            match output {
+                Some(path) => {
+                    std::fs::write(&path, report).map_err(|e| e.to_string())?;
+                    println!("Report written to {}", path.display());
+                }
+                None => print!("{report}"),
+            }
+            Ok(())
+        }
+        Command::Deviations { from, to, by } => {
+            let parse = |date: Option<String>| {
+                date.as_deref()
+                    .map(parse_date)
+                    .transpose()
+                    .map_err(|e| e.to_string())
+            };
+            let trend_period = ReportPeriod::try_from(by.as_str()).map_err(|e| e.to_string())?;
+
+            let analysis = controller
+                .karma_service
+                .deviation_analysis(parse(from)?, parse(to)?, trend_period)
+                .await
+                .map_err(|e| e.to_string())?;
+
+            println!(
+                "{} of {} sessions closed from {} to {} ended as another type",
+                analysis.deviated, analysis.closed, analysis.first_day, analysis.last_day
+            );
+            println!("\nPlanned as");
+            for purpose in &analysis.per_purpose {
+                let became: Vec<String> = purpose
+                    .became
+                    .iter()
+                    .map(|count| format!("{:?} {}", count.actual, count.sessions))
+                    .collect();
+                println!(
+                    "  {:<9} {:>4.0}% of {:>3}  {}",
+                    format!("{:?}", purpose.planned),
+                    purpose.rate * 100.0,
+                    purpose.closed,
+                    became.join(", ")
+                );
+            }
+            println!("\nTrend");
+            for point in &analysis.trend {
+                println!(
+                    "  {}  {:>4.0}% of {:>3}",
+                    point.period_start,
+                    point.rate * 100.0,
+                    point.closed
+                );
+            }
+            if !analysis.drifting.is_empty() {
+                println!("\nDrifting most");
+            }
+            for point in &analysis.drifting {
+                println!(
+                    "  {:<24} {:>4.0}% of {:>3}{}",
+                    point.name,
+                    point.rate * 100.0,
+                    point.closed,
+                    point
+                        .usually_becomes
+                        .as_ref()
+                        .map(|actual| format!(", usually becomes {actual:?}"))
+                        .unwrap_or_default()
+                );
+            }
+            Ok(())
+        }
+        Command::Balance { targets } => {
+            if let Some(targets) = targets {
+                controller
+                    .karma_service
+                    .set_balance_targets(&targets)
+                    .await
+                    .map_err(|e| e.to_string())?;
+            }
+            let balance = controller
+                .karma_service
+                .balance()
+                .await
+                .map_err(|e| e.to_string())?;
+
+            println!(
+                "Week of {}, {:.0}% over, score {}",
+                balance.week_start,
+                balance.elapsed * 100.0,
+                balance
+                    .score
+                    .map(|score| score.to_string())
+                    .unwrap_or_else(|| "-".to_string())
+            );
+            for category in &balance.categories {
+                println!(
+                    "  {:<9} {:>9} of {:>9} due, {:>9} target",
+                    format!("{:?}", category.purpose),
+                    format_duration(category.actual_seconds),
+                    format_duration(category.expected_seconds),
+                    format_duration(category.target_seconds)
+                );
+            }
+            for suggestion in &balance.suggestions {
+                println!("{}", suggestion.message);
+            }
+            Ok(())
+        }
+        Command::Schedule {
+            name,
+            rule,
+            starts,
+            at,
+            exceptions,
+        } => {
+            let recurrence = Recurrence::try_from(ScheduleRequest {
+                rule,
+                starts_on: starts.unwrap_or_else(|| settings.local_date(now).to_string()),
+                start_time: at,
+                exceptions,
+            })
+            .map_err(|e| e.to_string())?;
+
+            let schedule = controller
+                .karma_service
+                .set_schedule(&name.join(" "), recurrence)
+                .await
+                .map_err(|e| e.to_string())?;
+            println!(
+                "Scheduled {} from {}, planned until {}",
+                schedule.recurrence.rule,
+                schedule.recurrence.starts_on,
+                schedule
+                    .planned_until
+                    .map(|date| date.to_string())
+                    .unwrap_or_else(|| "-".to_string())
+            );
+            Ok(())
+        }
+        Command::Unschedule { name } => {
+            controller
+                .karma_service
+                .clear_schedule(&name.join(" "))
+                .await
+                .map_err(|e| e.to_string())?;
+            println!("Schedule removed");
+            Ok(())
+        }
+        Command::Occurrences { from, to, status } => {
+            let today = settings.local_date(now);
+            let from = match from {
+                Some(from) => parse_date(&from).map_err(|e| e.to_string())?,
+                None => today - chrono::Duration::days(7),
+            };
+            let to = match to {
+                Some(to) => parse_date(&to).map_err(|e| e.to_string())?,
+                None => today + chrono::Duration::days(7),
+            };
+            let status = status
+                .as_deref()
+                .map(OccurrenceStatus::try_from)
+                .transpose()
+                .map_err(|status| format!("Unknown occurrence status {status}"))?;
+
+            let occurrences = controller
+                .karma_service
+                .list_occurrences(from, to, status)
+                .await
+                .map_err(|e| e.to_string())?;
+
+            println!("{:>6}  {:<16}  {:<8}  NAME", "ID", "DUE", "STATUS");
+            for occurrence in &occurrences {
+                println!(
+                    "{:>6}  {:<16}  {:<8}  {}",
+                    occurrence.id.unwrap_or_default(),
+                    settings.format(occurrence.due_at),
+                    occurrence.status.as_str(),
+                    occurrence.name
+                );
+            }
+            Ok(())
+        }
+        Command::ResolveOccurrence {
+            id,
+            resolution,
+            session,
+        } => {
+            let resolution = parse_resolution(&resolution, session).map_err(|e| e.to_string())?;
+            let occurrence = controller
+                .karma_service
+                .resolve_occurrence(id, resolution)
+                .await
+                .map_err(|e| e.to_string())?;
+            println!(
+                "{} on {} is now {}",
+                occurrence.name,
+                occurrence.due_on,
+                occurrence.status.as_str()
+            );
+            Ok(())
+        }
+        Command::Settings {
+            timezone,
+            week_start,
+        } => {
+            let settings = if timezone.is_some() || week_start.is_some() {
+                controller
+                    .karma_service
+                    .set_time_settings(timezone.as_deref(), week_start.as_deref())
+                    .await
+                    .map_err(|e| e.to_string())?
+            } else {
+                settings
+            };
+            println!("timezone:   {}", settings.timezone);
+            println!("week start: {}", settings.week_start);
+            Ok(())
+        }
+        Command::Goal {
+            subject,
+            at_least,
+            at_most,
+            metric,
+            per,
+        } => {
+            let (comparison, target) = match (at_least, at_most) {
+                (Some(target), _) => (GoalComparison::AtLeast, target),
+                (None, Some(target)) => (GoalComparison::AtMost, target),
+                (None, None) => return Err("Either --at-least or --at-most is needed".to_string()),
+            };
+            let metric = GoalMetric::try_from(metric.as_str()).map_err(|e| e.to_string())?;
+            let period = ReportPeriod::try_from(per.as_str()).map_err(|e| e.to_string())?;
+
+            let goal = controller
+                .karma_service
+                .add_goal(&subject.join(" "), metric, comparison, target, period)
+                .await
+                .map_err(|e| e.to_string())?;
+            println!("Goal {}: {}", goal.id.unwrap_or_default(), goal.describe());
+            Ok(())
+        }
+        Command::Goals => {
+            let goals = controller
+                .karma_service
+                .goals()
+                .await
+                .map_err(|e| e.to_string())?;
+
+            for status in &goals {
+                println!(
+                    "{:>4}  {}",
+                    status.goal.id.unwrap_or_default(),
+                    status.description
+                );
+                for progress in std::iter::once(&status.current).chain(&status.history) {
+                    println!(
+                        "      {}  {:>7.1}  {:>4.0}%  {:?}",
+                        progress.first_day,
+                        progress.value,
+                        progress.ratio * 100.0,
+                        progress.state
+                    );
+                }
+            }
+            Ok(())
+        }
+        Command::DeleteGoal { id } => {
+            controller
+                .karma_service
+                .delete_goal(id)
+                .await
+                .map_err(|e| e.to_string())?;
+            println!("Goal removed");
+            Ok(())
+        }
+        Command::Points { days, rules } => {
+            if let Some(rules) = rules {
+                let rules = controller
+                    .karma_service
+                    .set_scoring_rules(&rules)
+                    .await
+                    .map_err(|e| e.to_string())?;
+                println!("Scoring: {rules}");
+            }
+            let balance = controller
+                .karma_service
+                .points_balance(Some(days))
+                .await
+                .map_err(|e| e.to_string())?;
+
+            println!("Balance {:.1}", balance.balance);
+            for day in &balance.ledger {
+                println!(
+                    "  {}  {:>+7.1} earned  {:>6.1} spent  {:>6.1} decayed  {:>7.1}",
+                    day.day, day.earned, day.spent, day.decayed, day.balance
+                );
+                for award in &day.awards {
+                    println!(
+                        "      {:>+6.1}  {}: {}",
+                        award.points, award.name, award.reason
+                    );
+                }
+                for redemption in &day.redemptions {
+                    println!("      {:>+6.1}  {}", -redemption.cost, redemption.name);
+                }
+            }
+            Ok(())
+        }
+        Command::Rewards => {
+            let shop = controller
+                .karma_service
+                .reward_shop()
+                .await
+                .map_err(|e| e.to_string())?;
+
+            println!("Balance {:.1}", shop.balance);
+            for status in &shop.rewards {
+                let availability = match (status.available_from, status.affordable) {
+                    (Some(day), _) => format!("available from {day}"),
+                    (None, false) => "not affordable yet".to_string(),
+                    (None, true) => "available".to_string(),
+                };
+                let limit = status
+                    .reward
+                    .limit
+                    .map(|limit| format!(", {limit}"))
+                    .unwrap_or_default();
+                println!(
+                    "  {:>6.1}  {}{limit}, {availability}",
+                    status.reward.cost, status.reward.name
+                );
+            }
+            Ok(())
+        }
+        Command::Reward { name, cost, limit } => {
+            let reward = controller
+                .karma_service
+                .add_reward(&name, cost, limit.as_deref())
+                .await
+                .map_err(|e| e.to_string())?;
+            println!("{} offered for {:.1} points", reward.name, reward.cost);
+            Ok(())
+        }
+        Command::DeleteReward { name } => {
+            controller
+                .karma_service
+                .delete_reward(&name)
+                .await
+                .map_err(|e| e.to_string())?;
+            println!("Reward removed");
+            Ok(())
+        }
+        Command::Redeem { name } => {
+            let redemption = controller
+                .karma_service
+                .redeem_reward(&name)
+                .await
+                .map_err(|e| e.to_string())?;
+            println!(
+                "Redeemed {} for {:.1} points",
+                redemption.name, redemption.cost
+            );
+            Ok(())
+        }
+        Command::Redemptions => {
+            let redemptions = controller
+                .karma_service
+                .redemptions()
+                .await
+                .map_err(|e| e.to_string())?;
+
+            for redemption in &redemptions {
+                println!(
+                    "{}  {:>6.1}  {}",
+                    redemption.redeemed_on, redemption.cost, redemption.name
+                );
+            }
+            Ok(())
+        }
+        Command::PlanDay {
+            name,
+            day,
+            purpose,
+            minutes,
+            at,
+        } => {
+            let day = match day {
+                Some(day) => parse_date(&day).map_err(|e| e.to_string())?,
+                None => settings.local_date(now) + chrono::Duration::days(1),
+            };
+            let purpose = purpose
+                .as_deref()
+                .map(KarmaType::try_from)
+                .transpose()
+                .map_err(|e| e.to_string())?;
+            let starts_at = at
+                .as_deref()
+                .map(parse_time_of_day)
+                .transpose()
+                .map_err(|e| e.to_string())?;
+
+            let entry = controller
+                .karma_service
+                .add_plan_entry(
+                    day,
+                    &name.join(" "),
+                    purpose,
+                    minutes.map(|minutes| minutes * 60),
+                    starts_at,
+                )
+                .await
+                .map_err(|e| e.to_string())?;
+            println!(
+                "Planned {} as {:?} for {} on {}, entry {}",
+                entry.name,
+                entry.purpose,
+                format_duration(entry.duration),
+                entry.day,
+                entry.id.unwrap_or_default()
+            );
+            Ok(())
+        }
+        Command::Unplan { entry } => {
+            controller
+                .karma_service
+                .remove_plan_entry(entry)
+                .await
+                .map_err(|e| e.to_string())?;
+            println!("Removed plan entry {entry}");
+            Ok(())
+        }
+        Command::Review { day } => {
+            let day = match day {
+                Some(day) => parse_date(&day).map_err(|e| e.to_string())?,
+                None => settings.local_date(now),
+            };
+            let review = controller
+                .karma_service
+                .review_plan(day)
+                .await
+                .map_err(|e| e.to_string())?;
+
+            println!(
+                "Plan of {}, {} of {} done",
+                review.day,
+                format_duration(review.done_seconds),
+                format_duration(review.planned_seconds)
+            );
+            for entry in &review.entries {
+                let slot = entry
+                    .entry
+                    .starts_at
+                    .map(|at| format!(" at {}", format_time_of_day(at)))
+                    .unwrap_or_default();
+                let carried = entry
+                    .entry
+                    .carried_from
+                    .map(|day| format!(", carried from {day}"))
+                    .unwrap_or_default();
+                let actual = match (&entry.session, &entry.replaced_by) {
+                    (Some(session), _) => format!(
+                        ", {} as {:?}",
+                        format_duration(session.active_seconds),
+                        session.purpose
+                    ),
+                    (None, Some(session)) => {
+                        format!(" by {} ({:?})", session.name, session.purpose)
+                    }
+                    (None, None) => String::new(),
+                };
+                println!(
+                    "  {:>4}  {} ({:?}) {}{slot}{carried}: {:?}{actual}",
+                    entry.entry.id.unwrap_or_default(),
+                    entry.entry.name,
+                    entry.entry.purpose,
+                    format_duration(entry.entry.duration),
+                    entry.outcome
+                );
+            }
+            if !review.unplanned.is_empty() {
+                println!("Unplanned");
+            }
+            for session in &review.unplanned {
+                println!(
+                    "  {} ({:?}) {}",
+                    session.name,
+                    session.purpose,
+                    format_duration(session.active_seconds)
+                );
+            }
+            Ok(())
+        }
+        Command::Suggest {
+            week,
+            accept,
+            rules,
+        } => {
+            if let Some(rules) = rules {
+                let rules = controller
+                    .karma_service
+                    .set_suggestion_rules(&rules)
+                    .await
+                    .map_err(|e| e.to_string())?;
+                println!("Suggestions: {rules}");
+            }
+            let week = week
+                .as_deref()
+                .map(parse_date)
+                .transpose()
+                .map_err(|e| e.to_string())?;
+            let proposal = controller
+                .karma_service
+                .suggest_schedule(week)
+                .await
+                .map_err(|e| e.to_string())?;
+
+            println!("Week of {}", proposal.week_start);
+            for category in &proposal.categories {
+                let unmet = if category.unmet_seconds > 0 {
+                    format!(", {} short", format_duration(category.unmet_seconds))
+                } else {
+                    String::new()
+                };
+                println!(
+                    "  {:?}: {} of {}, {} planned, {} suggested{unmet}",
+                    category.purpose,
+                    format_duration(category.done_seconds),
+                    format_duration(category.target_seconds),
+                    format_duration(category.committed_seconds),
+                    format_duration(category.suggested_seconds)
+                );
+            }
+            for suggestion in &proposal.suggestions {
+                let activity = suggestion
+                    .activity
+                    .as_ref()
+                    .map(|activity| format!(" {}", activity.name))
+                    .unwrap_or_default();
+                println!(
+                    "  {} {} {:?}{activity} {}",
+                    suggestion.day.format("%a %Y-%m-%d"),
+                    format_time_of_day(suggestion.starts_at),
+                    suggestion.purpose,
+                    format_duration(suggestion.duration)
+                );
+            }
+            if proposal.suggestions.is_empty() {
+                println!("Nothing to suggest");
+            }
+
+            if accept {
+                let entries = controller
+                    .karma_service
+                    .accept_schedule_suggestions(week)
+                    .await
+                    .map_err(|e| e.to_string())?;
+                println!(
+                    "Planned {} of {}",
+                    entries.len(),
+                    proposal.suggestions.len()
+                );
+            }
+            Ok(())
+        }
+        Command::Block { label, time } => {
+            let blocked = controller
+                .karma_service
+                .add_blocked_time(&label, &time)
+                .await
+                .map_err(|e| e.to_string())?;
+            println!(
+                "Blocked {} {} {blocked}",
+                blocked.id.unwrap_or_default(),
+                blocked.label
+            );
+            Ok(())
+        }
+        Command::Blocks => {
+            let blocked_times = controller
+                .karma_service
+                .blocked_times()
+                .await
+                .map_err(|e| e.to_string())?;
+            for blocked in &blocked_times {
+                println!(
+                    "{:>4}  {} {blocked}",
+                    blocked.id.unwrap_or_default(),
+                    blocked.label
+                );
+            }
+            if blocked_times.is_empty() {
+                println!("No blocked time");
+            }
+            Ok(())
+        }
+        Command::Unblock { id } => {
+            controller
+                .karma_service
+                .delete_blocked_time(id)
+                .await
+                .map_err(|e| e.to_string())?;
+            println!("Unblocked {id}");
+            Ok(())
+        }
+        Command::Focus {
+            name,
+            purpose,
+            rules,
+        } => {
+            if let Some(rules) = rules {
+                let rules = controller
+                    .karma_service
+                    .set_focus_rules(&rules)
+                    .await
+                    .map_err(|e| e.to_string())?;
+                println!("Focus: {rules}");
+            }
+            let state = if name.is_empty() {
+                controller
+                    .karma_service
+                    .advance_focus()
+                    .await
+                    .map_err(|e| e.to_string())?
+                    .map(|tick| tick.state)
+            } else {
+                let purpose = purpose
+                    .as_deref()
+                    .map(KarmaType::try_from)
+                    .transpose()
+                    .map_err(|e| e.to_string())?;
+                Some(
+                    controller
+                        .karma_service
+                        .start_focus(&name.join(" "), purpose)
+                        .await
+                        .map_err(|e| e.to_string())?,
+                )
+            };
+
+            let Some(state) = state else {
+                println!("No focus session is running");
+                return Ok(());
+            };
+            println!(
+                "{} ({:?}) pomodoro {}, {:?} until {}, {} done",
+                state.name,
+                state.purpose,
+                state.phase.pomodoro,
+                state.phase.phase,
+                settings.format(state.phase.ends_at),
+                state.completed
+            );
+            for interruption in &state.interruptions {
+                let by = if interruption.external {
+                    "by others"
+                } else {
+                    "by you"
+                };
+                println!(
+                    "  interrupted {by} at {} {}",
+                    settings.format(interruption.at),
+                    interruption.note
+                );
+            }
+            Ok(())
+        }
+        Command::Interrupt { note, external } => {
+            controller
+                .karma_service
+                .log_interruption(&note.join(" "), external)
+                .await
+                .map_err(|e| e.to_string())?;
+            println!("Interruption noted");
+            Ok(())
+        }
+        Command::Unfocus { rating } => {
+            let summary = controller
+                .karma_service
+                .stop_focus(rating)
+                .await
+                .map_err(|e| e.to_string())?;
+            println!(
+                "Focus session {} on {} ended, {} pomodoros, {} interruptions",
+                summary.focus.id.unwrap_or_default(),
+                summary.name,
+                summary.completed,
+                summary.interruptions
+            );
+            Ok(())
+        }
+        Command::RateFocus { id, rating } => {
+            controller
+                .karma_service
+                .rate_focus(id, rating)
+                .await
+                .map_err(|e| e.to_string())?;
+            println!("Rated focus session {id} {rating}/5");
+            Ok(())
+        }
+        Command::FocusHistory { limit } => {
+            let history = controller
+                .karma_service
+                .focus_history(limit)
+                .await
+                .map_err(|e| e.to_string())?;
+            for summary in &history {
+                let rating = summary
+                    .focus
+                    .rating
+                    .map(|rating| format!(", rated {rating}/5"))
+                    .unwrap_or_default();
+                let running = if summary.focus.ended_at.is_none() {
+                    ", running"
+                } else {
+                    ""
+                };
+                println!(
+                    "{:>4}  {} {} ({:?}) {} pomodoros, {} interruptions{rating}{running}",
+                    summary.focus.id.unwrap_or_default(),
+                    settings.format(summary.focus.started_at),
+                    summary.name,
+                    summary.purpose,
+                    summary.completed,
+                    summary.interruptions
+                );
+            }
+            if history.is_empty() {
+                println!("No focus sessions yet");
+            }
+            Ok(())
+        }
+        Command::Sleep { nights, rules } => {
+            if let Some(rules) = rules {
+                let rules = controller
+                    .karma_service
+                    .set_sleep_rules(&rules)
+                    .await
+                    .map_err(|e| e.to_string())?;
+                println!("Sleep: {rules}");
+            }
+            let report = controller
+                .karma_service
+                .sleep_report(nights)
+                .await
+                .map_err(|e| e.to_string())?;
+
+            println!(
+                "Nights {} to {}, target {}",
+                report.from,
+                report.to,
+                format_duration(report.target_seconds)
+            );
+            for night in &report.nights {
+                let open = if night.open { ", still asleep" } else { "" };
+                println!(
+                    "  {}  {}-{}  {}, debt {}{open}",
+                    night.night.format("%a %Y-%m-%d"),
+                    format_time_of_day(night.bedtime_of_day),
+                    format_time_of_day(night.wake_of_day),
+                    format_duration(night.slept_seconds),
+                    format_duration(night.debt_seconds)
+                );
+            }
+            if report.nights.is_empty() {
+                println!("  No sleep tracked");
+            }
+            if let Some(average) = report.average_seconds {
+                println!("Average {}", format_duration(average));
+            }
+            println!("Sleep debt {}", format_duration(report.debt_seconds));
+            if let (Some(bedtime), Some(wake)) = (report.average_bedtime, report.average_wake) {
+                println!(
+                    "Usually {} to {}",
+                    format_time_of_day(bedtime),
+                    format_time_of_day(wake)
+                );
+            }
+            if let (Some(consistency), Some(spread)) =
+                (report.consistency, report.bedtime_spread_seconds)
+            {
+                println!(
+                    "Bedtime consistency {consistency}/100, spread {}",
+                    format_duration(spread)
+                );
+            }
+            Ok(())
+        }
+        Command::Slept {
+            bedtime,
+            wake,
+            name,
+        } => {
+            let bedtime = parse_local_datetime(&bedtime).map_err(|e| e.to_string())?;
+            let wake = parse_local_datetime(&wake).map_err(|e| e.to_string())?;
+            let night = controller
+                .karma_service
+                .log_sleep(&name, bedtime, wake)
+                .await
+                .map_err(|e| e.to_string())?;
+            println!(
+                "Slept {} on the night of {}",
+                format_duration(night.slept_seconds),
+                night.night
+            );
+            Ok(())
+        }
+        Command::Achievements => {
+            let achievements = controller
+                .karma_service
+                .achievements()
+                .await
+                .map_err(|e| e.to_string())?;
+
+            for status in &achievements {
+                let progress = match status.unlocked_at {
+                    Some(at) => format!("unlocked {}", settings.local_date(at)),
+                    None => format!("{:.0}%", status.progress * 100.0),
+                };
+                println!(
+                    "{}: {}, {progress}",
+                    status.definition.name, status.definition.description
+                );
+            }
+            Ok(())
+        }
+        Command::Avoid { name, undo } => {
+            let polarity = if undo {
+                Polarity::Positive
+            } else {
+                Polarity::Negative
+            };
+            let karma = controller
+                .karma_service
+                .set_polarity(&name, polarity)
+                .await
+                .map_err(|e| e.to_string())?;
+            match polarity {
+                Polarity::Negative => println!("{} is a habit to avoid", karma.get_name()),
+                Polarity::Positive => println!("{} is no longer avoided", karma.get_name()),
+            }
+            Ok(())
+        }
+        Command::Relapse { name } => {
+            let relapse = controller
+                .karma_service
+                .log_relapse(&name)
+                .await
+                .map_err(|e| e.to_string())?;
+            println!("Relapse into {} recorded", relapse.template.get_name());
+            Ok(())
+        }
+        Command::Relapses { per } => {
+            let period = ReportPeriod::try_from(per.as_str()).map_err(|e| e.to_string())?;
+            let report = controller
+                .karma_service
+                .relapses(period)
+                .await
+                .map_err(|e| e.to_string())?;
+
+            if report.habits.is_empty() {
+                println!("No habit to avoid");
+            }
+            for habit in &report.habits {
+                println!(
+                    "{}: {} relapses, clean for {} (longest {}), {:?}, {:+.1} points",
+                    habit.name,
+                    habit.relapses,
+                    format_duration(habit.clean_seconds),
+                    format_duration(habit.longest_clean_seconds),
+                    habit.trend,
+                    habit.points
+                );
+                for count in &habit.history {
+                    println!("  {}  {:>3}", count.first_day, count.relapses);
+                }
+            }
+            Ok(())
+        }
+        Command::Streaks { freeze, unfreeze } => {
+            if let Some(day) = freeze {
+                let day = parse_date(&day).map_err(|e| e.to_string())?;
+                controller
+                    .karma_service
+                    .freeze_day(day)
+                    .await
+                    .map_err(|e| e.to_string())?;
+                println!("Froze {day}");
+            }
+            if let Some(day) = unfreeze {
+                let day = parse_date(&day).map_err(|e| e.to_string())?;
+                controller
+                    .karma_service
+                    .unfreeze_day(day)
+                    .await
+                    .map_err(|e| e.to_string())?;
+                println!("Unfroze {day}");
+            }
+
+            let streaks = controller
+                .karma_service
+                .streaks()
+                .await
+                .map_err(|e| e.to_string())?;
+            println!("{:>7}  {:>7}  NAME", "CURRENT", "LONGEST");
+            for streak in &streaks {
+                println!(
+                    "{:>7}  {:>7}  {}{}",
+                    streak.current,
+                    streak.longest,
+                    streak.name,
+                    if streak.at_risk { " (due today)" } else { "" }
+                );
+            }
+            Ok(())
+        }
+        Command::Plan => {
+            let report = controller
+                .karma_service
+                .run_daily_job()
+                .await
+                .map_err(|e| e.to_string())?;
+            println!(
+                "Planned {}, done {}, missed {}, carried over {}",
+                report.planned, report.completed, report.missed, report.carried_over
+            );
+            Ok(())
+        }
+    }
+}
+
+fn parse_time_arg(value: &str) -> Result<TimeArg, String> {
+    if let Ok(timestamp) = value.parse::<i64>() {
+        return Ok(TimeArg::Unix(timestamp));
+    }
+
+    NaiveDate::parse_from_str(value, "%Y-%m-%d")
+        .map(TimeArg::Date)
+        .map_err(|_| format!("{value} is neither YYYY-MM-DD nor unix seconds"))
+}
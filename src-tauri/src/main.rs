@@ -2,25 +2,165 @@
 #![cfg_attr(not(debug_assertions), windows_subsystem = "windows")]
 
 mod api;
+mod cli;
 mod model;
 mod service;
 mod storage;
 
-use api::karma_api::create::create_karma;
+use api::accounts_api::{
+    login::login,
+    personal_data::{delete_account, export_personal_data},
+    signup::{check_password, signup},
+    two_factor::{
+        begin_totp_enrolment, confirm_totp_enrolment, disable_totp, regenerate_backup_codes,
+    },
+};
+use api::achievement_api::list_achievements;
+use api::balance_api::{get_balance, get_balance_targets, set_balance_targets};
+use api::focus_api::{
+    focus_history, get_focus, get_focus_rules, log_interruption, rate_focus, set_focus_rules,
+    spawn_focus_timer, start_focus, stop_focus,
+};
+use api::goal_api::{add_goal, delete_goal, list_goals};
+use api::karma_api::{
+    create::create_karma,
+    list::list_karma,
+    resolve::resolve_karma,
+    search::{rebuild_search_index, search_karma},
+    start::start_karma,
+    track::{close_session, duration_totals, pause_session, resume_session, session_duration},
+};
+use api::plan_api::{add_plan_entry, remove_plan_entry, review_plan};
+use api::profiles_api::{
+    list::{current_profile, list_profiles},
+    manage::{create_profile, switch_profile},
+};
+use api::relapse_api::{list_relapses, log_relapse, set_avoided};
+use api::report_api::{deviation_analysis, karma_report, render_karma_report};
+use api::reward_api::{add_reward, delete_reward, list_redemptions, redeem_reward, reward_shop};
+use api::schedule_api::{
+    manage::{clear_karma_schedule, expand_karma_schedule, set_karma_schedule},
+    occurrences::{list_occurrences, resolve_occurrence},
+    spawn_daily_job,
+};
+use api::scoring_api::{get_points_balance, get_scoring_rules, set_scoring_rules};
+use api::set_app_handle;
+use api::settings_api::time::{get_time_settings, set_time_settings};
+use api::sleep_api::{get_sleep_rules, log_sleep, set_sleep_rules, sleep_report};
+use api::streak_api::{freeze_streak_day, list_streaks, unfreeze_streak_day};
+use api::suggestion_api::{
+    accept_schedule_suggestions, add_blocked_time, delete_blocked_time, get_suggestion_rules,
+    list_blocked_times, set_suggestion_rules, suggest_schedule,
+};
+use clap::Parser;
+use cli::Cli;
 use tracing::Level;
 use tracing_subscriber::FmtSubscriber;
-fn set_tracing() {
-    let subscriber = FmtSubscriber::builder()
-        .with_max_level(Level::DEBUG)
-        .finish();
+fn set_tracing(max_level: Level) {
+    let subscriber = FmtSubscriber::builder().with_max_level(max_level).finish();
     tracing::subscriber::set_global_default(subscriber).expect("setting default subscriber failed");
 }
 
 #[tokio::main]
 async fn main() {
-    set_tracing();
+    let cli = Cli::parse();
+    if let Some(command) = cli.command {
+        // Keep the output of the command line readable
+        set_tracing(Level::WARN);
+        if let Err(e) = cli::run(cli.profile, command).await {
+            eprintln!("{e}");
+            std::process::exit(1);
+        }
+        return;
+    }
+
+    set_tracing(Level::DEBUG);
+    spawn_daily_job();
+    spawn_focus_timer();
     tauri::Builder::default()
-        .invoke_handler(tauri::generate_handler![create_karma])
+        .setup(|app| {
+            set_app_handle(app.handle());
+            Ok(())
+        })
+        .invoke_handler(tauri::generate_handler![
+            create_karma,
+            start_karma,
+            pause_session,
+            resume_session,
+            close_session,
+            session_duration,
+            duration_totals,
+            list_karma,
+            resolve_karma,
+            search_karma,
+            rebuild_search_index,
+            signup,
+            check_password,
+            login,
+            begin_totp_enrolment,
+            confirm_totp_enrolment,
+            regenerate_backup_codes,
+            disable_totp,
+            export_personal_data,
+            delete_account,
+            list_profiles,
+            current_profile,
+            create_profile,
+            switch_profile,
+            karma_report,
+            deviation_analysis,
+            get_balance,
+            get_balance_targets,
+            set_balance_targets,
+            render_karma_report,
+            get_time_settings,
+            set_time_settings,
+            set_karma_schedule,
+            clear_karma_schedule,
+            expand_karma_schedule,
+            list_occurrences,
+            resolve_occurrence,
+            list_streaks,
+            freeze_streak_day,
+            unfreeze_streak_day,
+            list_goals,
+            add_goal,
+            delete_goal,
+            get_points_balance,
+            get_scoring_rules,
+            set_scoring_rules,
+            list_relapses,
+            log_relapse,
+            set_avoided,
+            reward_shop,
+            add_reward,
+            delete_reward,
+            redeem_reward,
+            list_redemptions,
+            list_achievements,
+            add_plan_entry,
+            remove_plan_entry,
+            review_plan,
+            suggest_schedule,
+            accept_schedule_suggestions,
+            get_suggestion_rules,
+            set_suggestion_rules,
+            list_blocked_times,
+            add_blocked_time,
+            delete_blocked_time,
+            start_focus,
+            get_focus,
+            log_interruption,
+            stop_focus,
+            rate_focus,
+            focus_history,
+            get_focus_rules,
+            set_focus_rules,
+            sleep_report,
+            log_sleep,
+            get_sleep_rules,
+            set_sleep_rules
+        ])
         .run(tauri::generate_context!())
         .expect("error while running tauri application");
 }
@@ -0,0 +1,378 @@
+use std::collections::BTreeMap;
+
+use chrono::NaiveDate;
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+use super::deviation::SessionOutcome;
+use super::duration::{session_duration, TrackedSession};
+use super::karma::{KarmaType, Polarity};
+use super::local_time::TimeSettings;
+
+const BUNDLED_ACHIEVEMENTS: &str = include_str!("../../resources/achievements.json");
+
+#[derive(Debug, Error, Serialize)]
+pub enum AchievementError {
+    #[error("Invalid achievement definitions: {0}")]
+    InvalidDefinitions(String),
+
+    #[error("Achievement {0} is defined twice")]
+    DuplicateId(String),
+}
+
+/// What has to be reached, declared in the achievements file
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum AchievementRule {
+    /// Closed sessions that ended as the type, of any type when missing
+    Sessions {
+        purpose: Option<KarmaType>,
+        count: u32,
+    },
+    /// Active hours of the sessions that ended as the type, of any type when missing
+    Hours {
+        purpose: Option<KarmaType>,
+        hours: f64,
+    },
+    /// Days in a row with a session of the type
+    Streak { purpose: KarmaType, days: u32 },
+    /// A week with at least `min_hours` of every type
+    BalancedWeek { min_hours: f64 },
+}
+
+impl AchievementRule {
+    /// Whether a session of `purpose` can bring the rule closer, rules about every
+    /// type always can
+    pub fn concerns(&self, purpose: &KarmaType) -> bool {
+        match self {
+            AchievementRule::Sessions { purpose: rule, .. }
+            | AchievementRule::Hours { purpose: rule, .. } => {
+                rule.as_ref().map_or(true, |rule| rule == purpose)
+            }
+            AchievementRule::Streak { purpose: rule, .. } => rule == purpose,
+            AchievementRule::BalancedWeek { .. } => true,
+        }
+    }
+
+    /// How far the facts are towards the rule, 1 once it's reached
+    pub fn progress(&self, facts: &AchievementFacts) -> f64 {
+        let ratio = |value: f64, target: f64| {
+            if target <= 0.0 {
+                1.0
+            } else {
+                (value / target).min(1.0)
+            }
+        };
+
+        match self {
+            AchievementRule::Sessions { purpose, count } => {
+                ratio(total(&facts.sessions, purpose) as f64, *count as f64)
+            }
+            AchievementRule::Hours { purpose, hours } => {
+                ratio(total(&facts.seconds, purpose) as f64 / 3600.0, *hours)
+            }
+            AchievementRule::Streak { purpose, days } => ratio(
+                facts.streaks.get(purpose).copied().unwrap_or_default() as f64,
+                *days as f64,
+            ),
+            AchievementRule::BalancedWeek { min_hours } => facts
+                .weeks
+                .values()
+                .map(|week| {
+                    KarmaType::ALL
+                        .iter()
+                        .map(|purpose| {
+                            let seconds = week.get(purpose).copied().unwrap_or_default();
+                            ratio(seconds as f64 / 3600.0, *min_hours)
+                        })
+                        .fold(1.0, f64::min)
+                })
+                .fold(0.0, f64::max),
+        }
+    }
+}
+
+fn total<T: Copy + Default + std::iter::Sum>(
+    per_type: &BTreeMap<KarmaType, T>,
+    purpose: &Option<KarmaType>,
+) -> T {
+    match purpose {
+        Some(purpose) => per_type.get(purpose).copied().unwrap_or_default(),
+        None => per_type.values().copied().sum(),
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct AchievementDefinition {
+    /// Unlocks are stored under it, it shouldn't change once shipped
+    pub id: String,
+    pub name: String,
+    pub description: String,
+    pub rule: AchievementRule,
+}
+
+/// Every achievement that can be unlocked, in the order they were declared
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct AchievementBook {
+    definitions: Vec<AchievementDefinition>,
+}
+
+impl AchievementBook {
+    /// The achievements shipped with the app
+    pub fn bundled() -> AchievementBook {
+        AchievementBook::from_json(BUNDLED_ACHIEVEMENTS)
+            .expect("the bundled achievements are valid")
+    }
+
+    /// A JSON array of definitions
+    pub fn from_json(json: &str) -> Result<AchievementBook, AchievementError> {
+        let definitions: Vec<AchievementDefinition> = serde_json::from_str(json)
+            .map_err(|e| AchievementError::InvalidDefinitions(e.to_string()))?;
+
+        let mut book = AchievementBook::default();
+        for definition in definitions {
+            if book.get(&definition.id).is_some() {
+                return Err(AchievementError::DuplicateId(definition.id));
+            }
+            book.definitions.push(definition);
+        }
+        Ok(book)
+    }
+
+    /// Adds the definitions of `other`, replacing the ones with the same id
+    pub fn merge(mut self, other: AchievementBook) -> AchievementBook {
+        for definition in other.definitions {
+            match self
+                .definitions
+                .iter_mut()
+                .find(|existing| existing.id == definition.id)
+            {
+                Some(existing) => *existing = definition,
+                None => self.definitions.push(definition),
+            }
+        }
+        self
+    }
+
+    pub fn get(&self, id: &str) -> Option<&AchievementDefinition> {
+        self.definitions
+            .iter()
+            .find(|definition| definition.id == id)
+    }
+
+    pub fn definitions(&self) -> &[AchievementDefinition] {
+        &self.definitions
+    }
+}
+
+/// What the rules are checked against
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct AchievementFacts {
+    pub sessions: BTreeMap<KarmaType, u32>,
+    pub seconds: BTreeMap<KarmaType, i64>,
+    /// Active seconds per type of every week, keyed by its first day
+    pub weeks: BTreeMap<NaiveDate, BTreeMap<KarmaType, i64>>,
+    /// The longest streak of each type so far
+    pub streaks: BTreeMap<KarmaType, u32>,
+}
+
+impl AchievementFacts {
+    /// Closed sessions count as the type they ended as, in the week they closed in.
+    /// Relapses into habits to avoid don't count.
+    pub fn collect(
+        sessions: &[TrackedSession],
+        streaks: BTreeMap<KarmaType, u32>,
+        now: i64,
+        settings: &TimeSettings,
+    ) -> AchievementFacts {
+        let mut facts = AchievementFacts {
+            streaks,
+            ..AchievementFacts::default()
+        };
+
+        for tracked in sessions
+            .iter()
+            .filter(|tracked| tracked.polarity == Polarity::Positive)
+        {
+            let Some(outcome) = SessionOutcome::of(tracked, settings) else {
+                continue;
+            };
+            let seconds = session_duration(tracked, now).active_seconds;
+
+            *facts.sessions.entry(outcome.actual.clone()).or_default() += 1;
+            *facts.seconds.entry(outcome.actual.clone()).or_default() += seconds;
+            *facts
+                .weeks
+                .entry(settings.week_of(outcome.closed_on))
+                .or_default()
+                .entry(outcome.actual)
+                .or_default() += seconds;
+        }
+
+        facts
+    }
+}
+
+/// When an achievement was unlocked, it stays unlocked whatever happens next
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct AchievementUnlock {
+    pub id: String,
+    pub unlocked_at: i64,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct AchievementStatus {
+    pub definition: AchievementDefinition,
+    /// From 0 to 1, 1 once unlocked
+    pub progress: f64,
+    pub unlocked_at: Option<i64>,
+}
+
+/// Every achievement of `book` in its order, unlocks whose definition is gone are
+/// left out
+pub fn achievement_statuses(
+    book: &AchievementBook,
+    facts: &AchievementFacts,
+    unlocks: &[AchievementUnlock],
+) -> Vec<AchievementStatus> {
+    book.definitions()
+        .iter()
+        .map(|definition| {
+            let unlocked_at = unlocks
+                .iter()
+                .find(|unlock| unlock.id == definition.id)
+                .map(|unlock| unlock.unlocked_at);
+            let progress = match unlocked_at {
+                Some(_) => 1.0,
+                None => definition.rule.progress(facts),
+            };
+
+            AchievementStatus {
+                definition: definition.clone(),
+                progress,
+                unlocked_at,
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod achievement_tests {
+    use super::*;
+    use crate::model::karma::{KarmaStatus, State};
+    use crate::model::karma_session::KarmaSession;
+
+    const HOUR: i64 = 60 * 60;
+    const DAY: i64 = 24 * HOUR;
+    // Monday 2024-01-01 00:00 UTC
+    const MONDAY: i64 = 1_704_067_200;
+
+    fn session(id: i32, purpose: KarmaType, start: i64, hours: i64) -> TrackedSession {
+        TrackedSession {
+            session: KarmaSession::with_id(id, id, purpose, None, start),
+            name: format!("Point {id}"),
+            polarity: Polarity::Positive,
+            statuses: vec![
+                KarmaStatus::new(id, State::Active, start),
+                KarmaStatus::new(id, State::Closed, start + hours * HOUR),
+            ],
+        }
+    }
+
+    #[test]
+    fn test_bundled_achievements_parse() {
+        let book = AchievementBook::bundled();
+        assert!(book.get("balanced_week").is_some());
+
+        assert!(AchievementBook::from_json("[{\"id\": \"x\"}]").is_err());
+        let custom = AchievementBook::from_json(
+            r#"[{"id": "first_session", "name": "Go", "description": "",
+            "rule": {"kind": "sessions", "count": 2}}]"#,
+        )
+        .unwrap();
+        let merged = book.clone().merge(custom);
+        assert_eq!(merged.definitions().len(), book.definitions().len());
+        assert_eq!(merged.get("first_session").unwrap().name, "Go");
+    }
+
+    #[test]
+    fn test_rules_progress() {
+        let settings = TimeSettings::default();
+        let mut sessions: Vec<TrackedSession> = KarmaType::ALL
+            .iter()
+            .enumerate()
+            .map(|(day, purpose)| {
+                session(day as i32, purpose.clone(), MONDAY + day as i64 * DAY, 1)
+            })
+            .collect();
+        // The next week only has Sport
+        sessions.push(session(9, KarmaType::Sport, MONDAY + 8 * DAY, 3));
+        let mut relapse = session(10, KarmaType::Sport, MONDAY + 9 * DAY, 1);
+        relapse.polarity = Polarity::Negative;
+        sessions.push(relapse);
+        let streaks = BTreeMap::from([(KarmaType::Sleeping, 6)]);
+
+        let facts = AchievementFacts::collect(&sessions, streaks, MONDAY + 10 * DAY, &settings);
+        let progress = |rule: AchievementRule| rule.progress(&facts);
+
+        assert_eq!(
+            progress(AchievementRule::Sessions {
+                purpose: Some(KarmaType::Sport),
+                count: 4
+            }),
+            0.5
+        );
+        assert_eq!(
+            progress(AchievementRule::Hours {
+                purpose: None,
+                hours: 4.0
+            }),
+            1.0
+        );
+        assert_eq!(
+            progress(AchievementRule::Streak {
+                purpose: KarmaType::Sleeping,
+                days: 30
+            }),
+            0.2
+        );
+        assert_eq!(
+            progress(AchievementRule::BalancedWeek { min_hours: 1.0 }),
+            1.0
+        );
+        assert_eq!(
+            progress(AchievementRule::BalancedWeek { min_hours: 2.0 }),
+            0.5
+        );
+
+        let book = AchievementBook::bundled();
+        let unlocks = vec![AchievementUnlock {
+            id: "learning_hours_100".to_string(),
+            unlocked_at: MONDAY,
+        }];
+        let statuses = achievement_statuses(&book, &facts, &unlocks);
+        let learning = statuses
+            .iter()
+            .find(|status| status.definition.id == "learning_hours_100")
+            .unwrap();
+        assert_eq!(learning.progress, 1.0);
+        assert_eq!(learning.unlocked_at, Some(MONDAY));
+    }
+
+    #[test]
+    fn test_rules_concern_their_types() {
+        let sport = AchievementRule::Streak {
+            purpose: KarmaType::Sport,
+            days: 7,
+        };
+        assert!(sport.concerns(&KarmaType::Sport));
+        assert!(!sport.concerns(&KarmaType::Work));
+
+        let any = AchievementRule::Sessions {
+            purpose: None,
+            count: 1,
+        };
+        assert!(any.concerns(&KarmaType::Work));
+        assert!(AchievementRule::BalancedWeek { min_hours: 1.0 }.concerns(&KarmaType::Social));
+    }
+}
@@ -0,0 +1,380 @@
+use std::collections::BTreeMap;
+use std::fmt;
+
+use chrono::{Datelike, NaiveDate, Weekday};
+use serde::Serialize;
+use thiserror::Error;
+
+use super::duration::{active_intervals, TrackedSession};
+use super::karma::{KarmaType, Polarity};
+use super::local_time::TimeSettings;
+
+pub const BALANCE_TARGETS_SETTING: &str = "balance_targets";
+/// Behind or ahead of the pace by less than this counts as on track, in seconds
+pub const SUGGESTION_TOLERANCE: i64 = 60 * 60;
+/// Weeks of history looked at to find the day a type is usually done on
+pub const HABIT_HISTORY_WEEKS: i64 = 8;
+
+const WEEK_HOURS: f64 = 7.0 * 24.0;
+
+#[derive(Debug, Error, Serialize)]
+pub enum BalanceError {
+    #[error("Invalid balance target {0}, expected type=hours per week such as sport=5")]
+    InvalidTarget(String),
+}
+
+/// Hours per week aimed for per type, types without a target don't count
+/// towards the balance
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct BalanceTargets {
+    pub hours_per_week: BTreeMap<KarmaType, f64>,
+}
+
+impl BalanceTargets {
+    /// Comma separated `type=hours`, e.g. `work=40, sport=5`
+    pub fn parse(value: &str) -> Result<BalanceTargets, BalanceError> {
+        let mut hours_per_week = BTreeMap::new();
+
+        for target in value
+            .split(',')
+            .map(str::trim)
+            .filter(|target| !target.is_empty())
+        {
+            let invalid = || BalanceError::InvalidTarget(target.to_string());
+            let (name, hours) = target.split_once('=').ok_or_else(invalid)?;
+            let purpose = KarmaType::from_name(name).ok_or_else(invalid)?;
+            let hours: f64 = hours.trim().parse().map_err(|_| invalid())?;
+            if !(0.0..=WEEK_HOURS).contains(&hours) {
+                return Err(invalid());
+            }
+
+            hours_per_week.insert(purpose, hours);
+        }
+
+        Ok(BalanceTargets { hours_per_week })
+    }
+
+    pub fn target_seconds(&self, purpose: &KarmaType) -> Option<i64> {
+        self.hours_per_week
+            .get(purpose)
+            .map(|hours| (hours * 3600.0).round() as i64)
+    }
+}
+
+impl Default for BalanceTargets {
+    fn default() -> Self {
+        BalanceTargets {
+            hours_per_week: BTreeMap::from([
+                (KarmaType::Work, 40.0),
+                (KarmaType::Social, 10.0),
+                (KarmaType::Sport, 5.0),
+                (KarmaType::Learning, 5.0),
+                (KarmaType::Sleeping, 56.0),
+            ]),
+        }
+    }
+}
+
+impl fmt::Display for BalanceTargets {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let targets: Vec<String> = self
+            .hours_per_week
+            .iter()
+            .map(|(purpose, hours)| format!("{}={hours}", format!("{purpose:?}").to_lowercase()))
+            .collect();
+        write!(f, "{}", targets.join(","))
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct CategoryBalance {
+    pub purpose: KarmaType,
+    /// For the whole week
+    pub target_seconds: i64,
+    /// The share of the target due by now, the week being partly over
+    pub expected_seconds: i64,
+    pub actual_seconds: i64,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize)]
+pub enum SuggestionKind {
+    Below,
+    Above,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct BalanceSuggestion {
+    pub purpose: KarmaType,
+    pub kind: SuggestionKind,
+    /// Left to reach the weekly target when below, past it when above
+    pub seconds: i64,
+    /// The day the type got the most time lately, when it has any history
+    pub usual_weekday: Option<Weekday>,
+    pub message: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct BalanceReport {
+    pub week_start: NaiveDate,
+    /// How much of the week is over, 0 to 1
+    pub elapsed: f64,
+    /// 100 when the tracked time is split exactly like the targets, none
+    /// before anything with a target was tracked
+    pub score: Option<u32>,
+    pub categories: Vec<CategoryBalance>,
+    /// The largest gaps first
+    pub suggestions: Vec<BalanceSuggestion>,
+}
+
+/// The weekday each type got the most active time on, relapses left out. Ties go
+/// to the day coming first in the configured week.
+pub fn weekday_habits(
+    sessions: &[TrackedSession],
+    from: i64,
+    to: i64,
+    now: i64,
+    settings: &TimeSettings,
+) -> BTreeMap<KarmaType, Weekday> {
+    let mut per_weekday: BTreeMap<KarmaType, [i64; 7]> = BTreeMap::new();
+
+    for tracked in sessions
+        .iter()
+        .filter(|tracked| tracked.polarity == Polarity::Positive)
+    {
+        for interval in active_intervals(&tracked.statuses, now)
+            .iter()
+            .filter_map(|interval| interval.clip(from, to))
+        {
+            let days = per_weekday
+                .entry(tracked.session.get_purpose())
+                .or_default();
+            for (day, seconds) in settings.split_by_day(interval.start, interval.end) {
+                days[day.weekday().num_days_from_monday() as usize] += seconds;
+            }
+        }
+    }
+
+    per_weekday
+        .into_iter()
+        .filter_map(|(purpose, days)| {
+            let mut best: Option<(Weekday, i64)> = None;
+            let mut weekday = settings.week_start;
+            for _ in 0..7 {
+                let seconds = days[weekday.num_days_from_monday() as usize];
+                if seconds > best.map(|(_, most)| most).unwrap_or(0) {
+                    best = Some((weekday, seconds));
+                }
+                weekday = weekday.succ();
+            }
+            best.map(|(weekday, _)| (purpose, weekday))
+        })
+        .collect()
+}
+
+/// Compares the time tracked this week against the targets. The score only looks at
+/// how the time is split between types, suggestions at how far each is from its pace.
+pub fn balance_report(
+    week_start: NaiveDate,
+    elapsed: f64,
+    actual: &BTreeMap<KarmaType, i64>,
+    targets: &BalanceTargets,
+    habits: &BTreeMap<KarmaType, Weekday>,
+) -> BalanceReport {
+    let elapsed = elapsed.clamp(0.0, 1.0);
+    let categories: Vec<CategoryBalance> = targets
+        .hours_per_week
+        .keys()
+        .filter_map(|purpose| {
+            let target_seconds = targets.target_seconds(purpose)?;
+            Some(CategoryBalance {
+                purpose: purpose.clone(),
+                target_seconds,
+                expected_seconds: (target_seconds as f64 * elapsed).round() as i64,
+                actual_seconds: actual.get(purpose).copied().unwrap_or_default(),
+            })
+        })
+        .collect();
+
+    let target_total: i64 = categories
+        .iter()
+        .map(|category| category.target_seconds)
+        .sum();
+    let actual_total: i64 = categories
+        .iter()
+        .map(|category| category.actual_seconds)
+        .sum();
+    let score = (target_total > 0 && actual_total > 0).then(|| {
+        let distance: f64 = categories
+            .iter()
+            .map(|category| {
+                (category.actual_seconds as f64 / actual_total as f64
+                    - category.target_seconds as f64 / target_total as f64)
+                    .abs()
+            })
+            .sum();
+        (100.0 * (1.0 - distance / 2.0)).round() as u32
+    });
+
+    let mut suggestions: Vec<BalanceSuggestion> = categories
+        .iter()
+        .filter(|category| category.target_seconds > 0)
+        .filter_map(|category| {
+            let usual_weekday = habits.get(&category.purpose).copied();
+            let name = format!("{:?}", category.purpose);
+
+            if category.actual_seconds > category.target_seconds + SUGGESTION_TOLERANCE {
+                let seconds = category.actual_seconds - category.target_seconds;
+                return Some(BalanceSuggestion {
+                    purpose: category.purpose.clone(),
+                    kind: SuggestionKind::Above,
+                    seconds,
+                    usual_weekday,
+                    message: format!(
+                        "You're {} over your {name} target this week",
+                        format_hours(seconds)
+                    ),
+                });
+            }
+
+            if category.actual_seconds + SUGGESTION_TOLERANCE < category.expected_seconds {
+                let seconds = category.target_seconds - category.actual_seconds;
+                let mut message = format!(
+                    "You're {} below your {name} target this week",
+                    format_hours(seconds)
+                );
+                if let Some(weekday) = usual_weekday {
+                    message.push_str(&format!(
+                        "; you usually do {name} on {}s",
+                        weekday_name(weekday)
+                    ));
+                }
+                return Some(BalanceSuggestion {
+                    purpose: category.purpose.clone(),
+                    kind: SuggestionKind::Below,
+                    seconds,
+                    usual_weekday,
+                    message,
+                });
+            }
+
+            None
+        })
+        .collect();
+    suggestions.sort_by(|a, b| {
+        b.seconds
+            .cmp(&a.seconds)
+            .then(a.kind.cmp(&b.kind))
+            .then(a.purpose.cmp(&b.purpose))
+    });
+
+    BalanceReport {
+        week_start,
+        elapsed,
+        score,
+        categories,
+        suggestions,
+    }
+}
+
+/// Whole hours when round, otherwise with one decimal, e.g. `4h` or `2.5h`
+fn format_hours(seconds: i64) -> String {
+    let hours = seconds as f64 / 3600.0;
+    if (hours - hours.round()).abs() < 0.05 {
+        format!("{}h", hours.round())
+    } else {
+        format!("{hours:.1}h")
+    }
+}
+
+fn weekday_name(weekday: Weekday) -> &'static str {
+    match weekday {
+        Weekday::Mon => "Monday",
+        Weekday::Tue => "Tuesday",
+        Weekday::Wed => "Wednesday",
+        Weekday::Thu => "Thursday",
+        Weekday::Fri => "Friday",
+        Weekday::Sat => "Saturday",
+        Weekday::Sun => "Sunday",
+    }
+}
+
+#[cfg(test)]
+mod balance_tests {
+    use super::*;
+    use crate::model::karma::{KarmaStatus, Polarity, State};
+    use crate::model::karma_session::KarmaSession;
+
+    const HOUR: i64 = 60 * 60;
+
+    #[test]
+    fn test_parse_targets() {
+        let targets = BalanceTargets::parse("Sport=5, work = 37.5").unwrap();
+        assert_eq!(targets.target_seconds(&KarmaType::Work), Some(135_000));
+        assert_eq!(targets.target_seconds(&KarmaType::Social), None);
+        assert_eq!(targets.to_string(), "work=37.5,sport=5");
+        assert_eq!(
+            BalanceTargets::parse(&targets.to_string()).unwrap(),
+            targets
+        );
+
+        assert!(BalanceTargets::parse("sport").is_err());
+        assert!(BalanceTargets::parse("chores=3").is_err());
+        assert!(BalanceTargets::parse("work=200").is_err());
+    }
+
+    #[test]
+    fn test_habits_pick_the_busiest_weekday() {
+        let settings = TimeSettings::default();
+        // 2024-01-02 and 2024-01-09 are Tuesdays, 2024-01-05 a Friday
+        let session = |id: i32, day: i64, hours: i64| {
+            let start = 1_704_067_200 + day * 24 * HOUR + 18 * HOUR;
+            TrackedSession {
+                session: KarmaSession::with_id(id, 1, KarmaType::Sport, None, start),
+                name: "Gym".to_string(),
+                polarity: Polarity::Positive,
+                statuses: vec![
+                    KarmaStatus::new(id, State::Active, start),
+                    KarmaStatus::new(id, State::Closed, start + hours * HOUR),
+                ],
+            }
+        };
+        let sessions = vec![session(1, 1, 1), session(2, 4, 1), session(3, 8, 1)];
+
+        let habits = weekday_habits(&sessions, 0, i64::MAX / 2, 0, &settings);
+        assert_eq!(habits.get(&KarmaType::Sport), Some(&Weekday::Tue));
+        assert_eq!(habits.get(&KarmaType::Work), None);
+    }
+
+    #[test]
+    fn test_score_and_suggestions() {
+        let targets = BalanceTargets::parse("work=30,sport=10").unwrap();
+        let habits = BTreeMap::from([(KarmaType::Sport, Weekday::Tue)]);
+        let week_start = NaiveDate::from_ymd_opt(2024, 1, 1).unwrap();
+
+        // Exactly the target mix, and on pace halfway through the week
+        let on_track = BTreeMap::from([(KarmaType::Work, 15 * HOUR), (KarmaType::Sport, 5 * HOUR)]);
+        let report = balance_report(week_start, 0.5, &on_track, &targets, &habits);
+        assert_eq!(report.score, Some(100));
+        assert!(report.suggestions.is_empty());
+
+        let lopsided = BTreeMap::from([(KarmaType::Work, 36 * HOUR), (KarmaType::Sport, 4 * HOUR)]);
+        let report = balance_report(week_start, 0.9, &lopsided, &targets, &habits);
+        // 90% against 75% work
+        assert_eq!(report.score, Some(85));
+        let messages: Vec<&str> = report
+            .suggestions
+            .iter()
+            .map(|suggestion| suggestion.message.as_str())
+            .collect();
+        assert_eq!(
+            messages,
+            vec![
+                "You're 6h below your Sport target this week; you usually do Sport on Tuesdays",
+                "You're 6h over your Work target this week",
+            ]
+        );
+
+        let nothing = balance_report(week_start, 0.1, &BTreeMap::new(), &targets, &habits);
+        assert_eq!(nothing.score, None);
+    }
+}
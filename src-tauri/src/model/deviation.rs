@@ -0,0 +1,343 @@
+use std::collections::BTreeMap;
+
+use chrono::NaiveDate;
+use serde::Serialize;
+
+use super::duration::TrackedSession;
+use super::karma::{KarmaType, State};
+use super::local_time::TimeSettings;
+use super::report::ReportPeriod;
+
+/// Points closed fewer times are too noisy to be called drifting
+pub const MIN_SESSIONS_FOR_DRIFT: u32 = 3;
+/// How many drifting points the analysis lists
+pub const TOP_DRIFTING: usize = 5;
+
+/// What a closed session was planned as and what it turned out to be
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct SessionOutcome {
+    pub session_id: i32,
+    pub template_id: i32,
+    pub name: String,
+    pub planned: KarmaType,
+    pub actual: KarmaType,
+    /// The local day the session was closed on
+    pub closed_on: NaiveDate,
+}
+
+impl SessionOutcome {
+    /// None while the session is still running. A session closed without a type
+    /// went as planned.
+    pub fn of(tracked: &TrackedSession, settings: &TimeSettings) -> Option<SessionOutcome> {
+        let closing = tracked
+            .statuses
+            .iter()
+            .rev()
+            .find(|status| status.state == State::Closed)?;
+        let planned = tracked.session.get_purpose();
+
+        Some(SessionOutcome {
+            session_id: tracked.session.get_id().unwrap_or_default(),
+            template_id: tracked.session.get_template_id(),
+            name: tracked.name.clone(),
+            actual: closing.closed_with.clone().unwrap_or(planned.clone()),
+            planned,
+            closed_on: settings.local_date(closing.timestamp),
+        })
+    }
+
+    pub fn deviated(&self) -> bool {
+        self.planned != self.actual
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct OutcomeCount {
+    pub actual: KarmaType,
+    pub sessions: u32,
+}
+
+/// How the sessions planned as one type ended
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct PurposeDeviation {
+    pub planned: KarmaType,
+    pub closed: u32,
+    pub deviated: u32,
+    pub rate: f64,
+    /// Every other type these sessions ended as, the most frequent first
+    pub became: Vec<OutcomeCount>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct DeviationTrendPoint {
+    pub period_start: NaiveDate,
+    pub closed: u32,
+    pub deviated: u32,
+    pub rate: f64,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct DriftingPoint {
+    pub template_id: i32,
+    pub name: String,
+    pub closed: u32,
+    pub deviated: u32,
+    pub rate: f64,
+    /// What it ends as most often when it doesn't go as planned
+    pub usually_becomes: Option<KarmaType>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct DeviationAnalysis {
+    pub first_day: NaiveDate,
+    pub last_day: NaiveDate,
+    pub closed: u32,
+    pub deviated: u32,
+    pub per_purpose: Vec<PurposeDeviation>,
+    /// One point per week or month, oldest first, periods without sessions included
+    pub trend: Vec<DeviationTrendPoint>,
+    /// Highest deviation rate first
+    pub drifting: Vec<DriftingPoint>,
+}
+
+fn rate(deviated: u32, closed: u32) -> f64 {
+    if closed == 0 {
+        0.0
+    } else {
+        deviated as f64 / closed as f64
+    }
+}
+
+fn most_frequent(counts: BTreeMap<KarmaType, u32>) -> Vec<OutcomeCount> {
+    let mut counts: Vec<OutcomeCount> = counts
+        .into_iter()
+        .map(|(actual, sessions)| OutcomeCount { actual, sessions })
+        .collect();
+    // Stable, ties stay in type order
+    counts.sort_by_key(|count| std::cmp::Reverse(count.sessions));
+    counts
+}
+
+/// Compares intent and outcome of the sessions closed between both days inclusive
+pub fn analyse_deviations(
+    outcomes: &[SessionOutcome],
+    first_day: NaiveDate,
+    last_day: NaiveDate,
+    trend_period: ReportPeriod,
+    settings: &TimeSettings,
+) -> DeviationAnalysis {
+    let outcomes: Vec<&SessionOutcome> = outcomes
+        .iter()
+        .filter(|outcome| outcome.closed_on >= first_day && outcome.closed_on <= last_day)
+        .collect();
+
+    let mut per_purpose: BTreeMap<KarmaType, (u32, BTreeMap<KarmaType, u32>)> = BTreeMap::new();
+    let mut per_point: BTreeMap<i32, (String, u32, BTreeMap<KarmaType, u32>)> = BTreeMap::new();
+    let mut per_period: BTreeMap<NaiveDate, (u32, u32)> = BTreeMap::new();
+
+    let mut period_start = trend_period.bounds(first_day, settings).0;
+    while period_start <= last_day {
+        per_period.insert(period_start, (0, 0));
+        period_start = trend_period.bounds(period_start, settings).1 + chrono::Duration::days(1);
+    }
+
+    for outcome in &outcomes {
+        let purpose = per_purpose.entry(outcome.planned.clone()).or_default();
+        purpose.0 += 1;
+
+        let point = per_point
+            .entry(outcome.template_id)
+            .or_insert_with(|| (outcome.name.clone(), 0, BTreeMap::new()));
+        point.1 += 1;
+
+        let period = per_period
+            .entry(trend_period.bounds(outcome.closed_on, settings).0)
+            .or_default();
+        period.0 += 1;
+
+        if outcome.deviated() {
+            *purpose.1.entry(outcome.actual.clone()).or_default() += 1;
+            *point.2.entry(outcome.actual.clone()).or_default() += 1;
+            period.1 += 1;
+        }
+    }
+
+    let per_purpose: Vec<PurposeDeviation> = per_purpose
+        .into_iter()
+        .map(|(planned, (closed, became))| {
+            let deviated = became.values().sum();
+            PurposeDeviation {
+                planned,
+                closed,
+                deviated,
+                rate: rate(deviated, closed),
+                became: most_frequent(became),
+            }
+        })
+        .collect();
+
+    let mut drifting: Vec<DriftingPoint> = per_point
+        .into_iter()
+        .filter(|(_, (_, closed, _))| *closed >= MIN_SESSIONS_FOR_DRIFT)
+        .map(|(template_id, (name, closed, became))| {
+            let deviated = became.values().sum();
+            DriftingPoint {
+                template_id,
+                name,
+                closed,
+                deviated,
+                rate: rate(deviated, closed),
+                usually_becomes: most_frequent(became)
+                    .into_iter()
+                    .next()
+                    .map(|count| count.actual),
+            }
+        })
+        .filter(|point| point.deviated > 0)
+        .collect();
+    drifting.sort_by(|a, b| {
+        b.rate
+            .total_cmp(&a.rate)
+            .then(b.closed.cmp(&a.closed))
+            .then(a.name.cmp(&b.name))
+    });
+    drifting.truncate(TOP_DRIFTING);
+
+    DeviationAnalysis {
+        first_day,
+        last_day,
+        closed: outcomes.len() as u32,
+        deviated: outcomes.iter().filter(|outcome| outcome.deviated()).count() as u32,
+        per_purpose,
+        trend: per_period
+            .into_iter()
+            .map(|(period_start, (closed, deviated))| DeviationTrendPoint {
+                period_start,
+                closed,
+                deviated,
+                rate: rate(deviated, closed),
+            })
+            .collect(),
+        drifting,
+    }
+}
+
+#[cfg(test)]
+mod deviation_tests {
+    use super::*;
+    use crate::model::karma::{KarmaStatus, Polarity};
+    use crate::model::karma_session::KarmaSession;
+
+    fn date(value: &str) -> NaiveDate {
+        NaiveDate::parse_from_str(value, "%Y-%m-%d").unwrap()
+    }
+
+    fn outcome(
+        template_id: i32,
+        planned: KarmaType,
+        actual: KarmaType,
+        day: &str,
+    ) -> SessionOutcome {
+        SessionOutcome {
+            session_id: 0,
+            template_id,
+            name: format!("Point {template_id}"),
+            planned,
+            actual,
+            closed_on: date(day),
+        }
+    }
+
+    #[test]
+    fn test_outcome_of_a_session() {
+        let settings = TimeSettings::default();
+        let mut tracked = TrackedSession {
+            session: KarmaSession::with_id(7, 1, KarmaType::Learning, None, 0),
+            name: "Course".to_string(),
+            polarity: Polarity::Positive,
+            statuses: vec![KarmaStatus::new(7, State::Active, 0)],
+        };
+        assert_eq!(SessionOutcome::of(&tracked, &settings), None);
+
+        tracked
+            .statuses
+            .push(KarmaStatus::new(7, State::Closed, 100));
+        assert!(!SessionOutcome::of(&tracked, &settings).unwrap().deviated());
+
+        tracked.statuses[1] =
+            KarmaStatus::with_closed_reason(7, State::Closed, 100, KarmaType::Social);
+        let outcome = SessionOutcome::of(&tracked, &settings).unwrap();
+        assert!(outcome.deviated());
+        assert_eq!(outcome.actual, KarmaType::Social);
+    }
+
+    #[test]
+    fn test_deviations_per_purpose_trend_and_drift() {
+        use KarmaType::*;
+
+        let outcomes = vec![
+            outcome(1, Learning, Social, "2024-01-01"),
+            outcome(1, Learning, Social, "2024-01-02"),
+            outcome(1, Learning, Learning, "2024-01-09"),
+            outcome(1, Learning, Work, "2024-01-10"),
+            outcome(2, Sport, Sport, "2024-01-03"),
+            outcome(2, Sport, Sport, "2024-01-04"),
+            outcome(2, Sport, Social, "2024-01-11"),
+            // Only two sessions, not enough to drift
+            outcome(3, Work, Social, "2024-01-05"),
+            outcome(3, Work, Social, "2024-01-12"),
+            // Outside the range
+            outcome(1, Learning, Social, "2024-02-01"),
+        ];
+
+        let analysis = analyse_deviations(
+            &outcomes,
+            date("2024-01-01"),
+            date("2024-01-21"),
+            ReportPeriod::Week,
+            &TimeSettings::default(),
+        );
+
+        assert_eq!(analysis.closed, 9);
+        assert_eq!(analysis.deviated, 6);
+
+        let learning = &analysis.per_purpose[2];
+        assert_eq!(learning.planned, Learning);
+        assert_eq!(learning.deviated, 3);
+        assert_eq!(
+            learning.became,
+            vec![
+                OutcomeCount {
+                    actual: Social,
+                    sessions: 2
+                },
+                OutcomeCount {
+                    actual: Work,
+                    sessions: 1
+                }
+            ]
+        );
+
+        let trend: Vec<(String, u32, u32)> = analysis
+            .trend
+            .iter()
+            .map(|point| (point.period_start.to_string(), point.closed, point.deviated))
+            .collect();
+        assert_eq!(
+            trend,
+            vec![
+                ("2024-01-01".to_string(), 5, 3),
+                ("2024-01-08".to_string(), 4, 3),
+                ("2024-01-15".to_string(), 0, 0),
+            ]
+        );
+
+        let drifting: Vec<&str> = analysis
+            .drifting
+            .iter()
+            .map(|point| point.name.as_str())
+            .collect();
+        assert_eq!(drifting, vec!["Point 1", "Point 2"]);
+        assert_eq!(analysis.drifting[0].usually_becomes, Some(Social));
+    }
+}
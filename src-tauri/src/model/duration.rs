@@ -0,0 +1,405 @@
+use std::collections::BTreeMap;
+
+use chrono::NaiveDate;
+use serde::Serialize;
+
+use super::karma::{KarmaStatus, KarmaType, Polarity, State};
+use super::karma_session::KarmaSession;
+use super::local_time::TimeSettings;
+
+/// A session with every status it went through, oldest first
+#[derive(Debug, Clone, Serialize)]
+pub struct TrackedSession {
+    pub session: KarmaSession,
+    /// The name of the template, for display
+    pub name: String,
+    /// Of the template, sessions of a habit to avoid are relapses
+    pub polarity: Polarity,
+    pub statuses: Vec<KarmaStatus>,
+}
+
+/// A stretch of time a session was active, `end` excluded
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub struct ActiveInterval {
+    pub start: i64,
+    pub end: i64,
+}
+
+impl ActiveInterval {
+    pub fn seconds(&self) -> i64 {
+        self.end - self.start
+    }
+
+    /// The part of the interval inside `[from, to)`, if any
+    pub fn clip(&self, from: i64, to: i64) -> Option<ActiveInterval> {
+        let clipped = ActiveInterval {
+            start: self.start.max(from),
+            end: self.end.min(to),
+        };
+        (clipped.start < clipped.end).then_some(clipped)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct SessionDuration {
+    pub session_id: i32,
+    pub active_seconds: i64,
+    /// Still active or paused, the active time runs up to now
+    pub open: bool,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct PointDuration {
+    pub template_id: i32,
+    pub name: String,
+    pub seconds: i64,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct CategoryDuration {
+    pub purpose: KarmaType,
+    pub seconds: i64,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct DayDuration {
+    pub day: NaiveDate,
+    pub seconds: i64,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct WeekDuration {
+    /// The first day of the week, as configured
+    pub week_start: NaiveDate,
+    pub seconds: i64,
+}
+
+/// Active time between `from` and `to`, the longest first for points,
+/// in type order for categories and chronologically for days and weeks
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct DurationTotals {
+    pub from: i64,
+    pub to: i64,
+    pub total_seconds: i64,
+    pub per_point: Vec<PointDuration>,
+    pub per_category: Vec<CategoryDuration>,
+    pub per_day: Vec<DayDuration>,
+    pub per_week: Vec<WeekDuration>,
+}
+
+/// Pairs the transitions of a session into the intervals it was active. An Active
+/// status opens an interval, Paused or Closed ends it, repeated statuses are ignored
+/// and an interval still open is counted up to `now`.
+pub fn active_intervals(statuses: &[KarmaStatus], now: i64) -> Vec<ActiveInterval> {
+    let mut intervals = Vec::new();
+    let mut started_at = None;
+
+    for status in statuses {
+        match (status.state.clone(), started_at) {
+            (State::Active, None) => started_at = Some(status.timestamp),
+            (State::Paused | State::Closed, Some(start)) => {
+                intervals.push(ActiveInterval {
+                    start,
+                    end: status.timestamp.max(start),
+                });
+                started_at = None;
+            }
+            _ => {}
+        }
+    }
+
+    if let Some(start) = started_at {
+        intervals.push(ActiveInterval {
+            start,
+            end: now.max(start),
+        });
+    }
+
+    intervals
+}
+
+pub fn session_duration(tracked: &TrackedSession, now: i64) -> SessionDuration {
+    let open = tracked
+        .statuses
+        .last()
+        .is_some_and(|status| status.state != State::Closed);
+
+    SessionDuration {
+        session_id: tracked.session.get_id().unwrap_or_default(),
+        active_seconds: active_intervals(&tracked.statuses, now)
+            .iter()
+            .map(ActiveInterval::seconds)
+            .sum(),
+        open,
+    }
+}
+
+/// Sums the active time of the sessions inside `[from, to)`. Time is attributed
+/// to the type of the session and split across days at local midnight.
+pub fn duration_totals(
+    sessions: &[TrackedSession],
+    from: i64,
+    to: i64,
+    now: i64,
+    settings: &TimeSettings,
+) -> DurationTotals {
+    let mut per_point: BTreeMap<i32, PointDuration> = BTreeMap::new();
+    let mut per_category: BTreeMap<KarmaType, i64> = BTreeMap::new();
+    let mut per_day: BTreeMap<NaiveDate, i64> = BTreeMap::new();
+    let mut per_week: BTreeMap<NaiveDate, i64> = BTreeMap::new();
+    let mut total_seconds = 0;
+
+    for tracked in sessions {
+        for interval in active_intervals(&tracked.statuses, now)
+            .iter()
+            .filter_map(|interval| interval.clip(from, to))
+        {
+            let seconds = interval.seconds();
+            total_seconds += seconds;
+
+            let template_id = tracked.session.get_template_id();
+            per_point
+                .entry(template_id)
+                .or_insert_with(|| PointDuration {
+                    template_id,
+                    name: tracked.name.clone(),
+                    seconds: 0,
+                })
+                .seconds += seconds;
+            *per_category
+                .entry(tracked.session.get_purpose())
+                .or_default() += seconds;
+
+            for (day, seconds) in settings.split_by_day(interval.start, interval.end) {
+                *per_day.entry(day).or_default() += seconds;
+                *per_week.entry(settings.week_of(day)).or_default() += seconds;
+            }
+        }
+    }
+
+    let mut per_point: Vec<PointDuration> = per_point.into_values().collect();
+    per_point.sort_by(|a, b| b.seconds.cmp(&a.seconds).then(a.name.cmp(&b.name)));
+
+    DurationTotals {
+        from,
+        to,
+        total_seconds,
+        per_point,
+        per_category: per_category
+            .into_iter()
+            .map(|(purpose, seconds)| CategoryDuration { purpose, seconds })
+            .collect(),
+        per_day: per_day
+            .into_iter()
+            .map(|(day, seconds)| DayDuration { day, seconds })
+            .collect(),
+        per_week: per_week
+            .into_iter()
+            .map(|(week_start, seconds)| WeekDuration {
+                week_start,
+                seconds,
+            })
+            .collect(),
+    }
+}
+
+#[cfg(test)]
+mod duration_tests {
+    use chrono::Weekday;
+
+    use super::*;
+    use crate::model::local_time::UserTimezone;
+
+    const HOUR: i64 = 60 * 60;
+    // 2024-01-01 00:00 UTC
+    const DAY_START: i64 = 1_704_067_200;
+
+    fn status(state: State, timestamp: i64) -> KarmaStatus {
+        KarmaStatus::new(1, state, timestamp)
+    }
+
+    fn tracked(
+        id: i32,
+        template_id: i32,
+        purpose: KarmaType,
+        statuses: Vec<KarmaStatus>,
+    ) -> TrackedSession {
+        TrackedSession {
+            session: KarmaSession::with_id(id, template_id, purpose, None, statuses[0].timestamp),
+            name: format!("Point {template_id}"),
+            polarity: Polarity::Positive,
+            statuses,
+        }
+    }
+
+    #[test]
+    fn test_pauses_are_not_counted() {
+        let statuses = vec![
+            status(State::Active, 0),
+            status(State::Paused, 100),
+            status(State::Paused, 150),
+            status(State::Active, 200),
+            status(State::Active, 250),
+            status(State::Closed, 300),
+        ];
+
+        assert_eq!(
+            active_intervals(&statuses, 1000),
+            vec![
+                ActiveInterval { start: 0, end: 100 },
+                ActiveInterval {
+                    start: 200,
+                    end: 300
+                }
+            ]
+        );
+    }
+
+    #[test]
+    fn test_open_session_counts_up_to_now() {
+        let session = tracked(
+            1,
+            1,
+            KarmaType::Work,
+            vec![status(State::Active, 100), status(State::Paused, 200)],
+        );
+        let duration = session_duration(&session, 1000);
+        assert_eq!(duration.active_seconds, 100);
+        assert!(duration.open);
+
+        let running = tracked(2, 1, KarmaType::Work, vec![status(State::Active, 100)]);
+        assert_eq!(session_duration(&running, 1000).active_seconds, 900);
+    }
+
+    #[test]
+    fn test_totals_per_point_category_and_day() {
+        let sessions = vec![
+            // 23:00 to 01:00 the next day
+            tracked(
+                1,
+                1,
+                KarmaType::Work,
+                vec![
+                    status(State::Active, DAY_START + 23 * HOUR),
+                    status(State::Closed, DAY_START + 25 * HOUR),
+                ],
+            ),
+            tracked(
+                2,
+                2,
+                KarmaType::Sport,
+                vec![
+                    status(State::Active, DAY_START + 30 * HOUR),
+                    status(State::Closed, DAY_START + 31 * HOUR),
+                ],
+            ),
+            // Started before the range, only the part inside counts
+            tracked(
+                3,
+                2,
+                KarmaType::Work,
+                vec![
+                    status(State::Active, DAY_START - HOUR),
+                    status(State::Closed, DAY_START + HOUR),
+                ],
+            ),
+        ];
+
+        let totals = duration_totals(
+            &sessions,
+            DAY_START,
+            DAY_START + 2 * 24 * HOUR,
+            0,
+            &TimeSettings::default(),
+        );
+
+        assert_eq!(totals.total_seconds, 4 * HOUR);
+        assert_eq!(
+            totals.per_point,
+            vec![
+                PointDuration {
+                    template_id: 1,
+                    name: "Point 1".to_string(),
+                    seconds: 2 * HOUR
+                },
+                PointDuration {
+                    template_id: 2,
+                    name: "Point 2".to_string(),
+                    seconds: 2 * HOUR
+                },
+            ]
+        );
+        assert_eq!(
+            totals.per_category,
+            vec![
+                CategoryDuration {
+                    purpose: KarmaType::Work,
+                    seconds: 3 * HOUR
+                },
+                CategoryDuration {
+                    purpose: KarmaType::Sport,
+                    seconds: HOUR
+                },
+            ]
+        );
+        let per_day: Vec<(String, i64)> = totals
+            .per_day
+            .iter()
+            .map(|day| (day.day.to_string(), day.seconds))
+            .collect();
+        assert_eq!(
+            per_day,
+            vec![
+                ("2024-01-01".to_string(), 2 * HOUR),
+                ("2024-01-02".to_string(), 2 * HOUR)
+            ]
+        );
+        assert_eq!(totals.per_week.len(), 1);
+    }
+
+    #[test]
+    fn test_days_follow_the_timezone() {
+        let settings = TimeSettings {
+            timezone: UserTimezone::parse("-05:00").unwrap(),
+            week_start: Weekday::Mon,
+        };
+        // 2024-01-01 01:00 to 07:00 UTC is 20:00 to 02:00 in New York, four hours
+        // on new year's eve and two on the first, and 2023-12-31 is a Sunday so
+        // each day lands in a different week
+        let sessions = vec![tracked(
+            1,
+            1,
+            KarmaType::Work,
+            vec![
+                status(State::Active, DAY_START + HOUR),
+                status(State::Closed, DAY_START + 7 * HOUR),
+            ],
+        )];
+
+        let totals = duration_totals(&sessions, 0, DAY_START + 24 * HOUR, 0, &settings);
+        let per_day: Vec<(String, i64)> = totals
+            .per_day
+            .iter()
+            .map(|day| (day.day.to_string(), day.seconds))
+            .collect();
+        assert_eq!(
+            per_day,
+            vec![
+                ("2023-12-31".to_string(), 4 * HOUR),
+                ("2024-01-01".to_string(), 2 * HOUR)
+            ]
+        );
+        let per_week: Vec<(String, i64)> = totals
+            .per_week
+            .iter()
+            .map(|week| (week.week_start.to_string(), week.seconds))
+            .collect();
+        assert_eq!(
+            per_week,
+            vec![
+                ("2023-12-25".to_string(), 4 * HOUR),
+                ("2024-01-01".to_string(), 2 * HOUR)
+            ]
+        );
+    }
+}
@@ -0,0 +1,381 @@
+use std::fmt;
+
+use serde::Serialize;
+use thiserror::Error;
+
+use super::karma::{KarmaType, State};
+
+pub const FOCUS_RULES_SETTING: &str = "focus_rules";
+
+#[derive(Debug, Error, Serialize)]
+pub enum FocusError {
+    #[error(
+        "Invalid focus rule {0}, expected work=minutes, short=minutes, long=minutes, \
+        every=pomodoros or max=pomodoros"
+    )]
+    InvalidRule(String),
+
+    #[error("Focus sessions are for work and learning, not {0:?}")]
+    NotFocusable(KarmaType),
+
+    #[error("Invalid focus rating {0}, expected 1 to 5")]
+    InvalidRating(u8),
+}
+
+/// Lengths of the Pomodoro phases
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct FocusRules {
+    pub work_minutes: u32,
+    pub short_break_minutes: u32,
+    pub long_break_minutes: u32,
+    /// A long break follows every this many pomodoros, a short one the others
+    pub long_break_every: u32,
+    /// The session ends by itself once this many pomodoros are done, so a
+    /// forgotten one doesn't run for days
+    pub max_pomodoros: u32,
+}
+
+impl FocusRules {
+    /// Comma separated `work=minutes`, `short=minutes`, `long=minutes`,
+    /// `every=pomodoros` and `max=pomodoros`, e.g. `work=50, short=10`. Rules left
+    /// out keep their value.
+    pub fn parse(value: &str) -> Result<FocusRules, FocusError> {
+        let mut rules = FocusRules::default();
+
+        for rule in value
+            .split(',')
+            .map(str::trim)
+            .filter(|rule| !rule.is_empty())
+        {
+            let invalid = || FocusError::InvalidRule(rule.to_string());
+            let (name, amount) = rule.split_once('=').ok_or_else(invalid)?;
+            let amount: u32 = amount
+                .trim()
+                .parse()
+                .ok()
+                .filter(|amount| (1..=4 * 60).contains(amount))
+                .ok_or_else(invalid)?;
+
+            match name.trim().to_lowercase().as_str() {
+                "work" => rules.work_minutes = amount,
+                "short" => rules.short_break_minutes = amount,
+                "long" => rules.long_break_minutes = amount,
+                "every" => rules.long_break_every = amount,
+                "max" => rules.max_pomodoros = amount,
+                _ => return Err(invalid()),
+            }
+        }
+
+        Ok(rules)
+    }
+
+    fn length(&self, phase: &FocusPhase) -> i64 {
+        let minutes = match phase {
+            FocusPhase::Work => self.work_minutes,
+            FocusPhase::ShortBreak => self.short_break_minutes,
+            FocusPhase::LongBreak => self.long_break_minutes,
+        };
+        minutes as i64 * 60
+    }
+
+    /// Seconds from the start of a pomodoro to the start of the one `long_break_every`
+    /// later, the phases repeat after that
+    fn round_length(&self) -> i64 {
+        let every = self.long_break_every as i64;
+        (every * self.work_minutes as i64
+            + (every - 1) * self.short_break_minutes as i64
+            + self.long_break_minutes as i64)
+            * 60
+    }
+}
+
+impl Default for FocusRules {
+    fn default() -> Self {
+        FocusRules {
+            work_minutes: 25,
+            short_break_minutes: 5,
+            long_break_minutes: 15,
+            long_break_every: 4,
+            max_pomodoros: 8,
+        }
+    }
+}
+
+impl fmt::Display for FocusRules {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "work={},short={},long={},every={},max={}",
+            self.work_minutes,
+            self.short_break_minutes,
+            self.long_break_minutes,
+            self.long_break_every,
+            self.max_pomodoros
+        )
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub enum FocusPhase {
+    Work,
+    ShortBreak,
+    LongBreak,
+}
+
+impl FocusPhase {
+    /// The state the session is in during the phase, breaks don't count as active
+    pub fn state(&self) -> State {
+        match self {
+            FocusPhase::Work => State::Active,
+            FocusPhase::ShortBreak | FocusPhase::LongBreak => State::Paused,
+        }
+    }
+}
+
+/// One phase of a focus session, in unix seconds
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct PhaseSpan {
+    pub phase: FocusPhase,
+    /// The pomodoro the phase belongs to, counted from 1, a break belongs to the
+    /// pomodoro it follows
+    pub pomodoro: u32,
+    pub starts_at: i64,
+    pub ends_at: i64,
+}
+
+impl PhaseSpan {
+    fn first(started_at: i64, rules: &FocusRules) -> PhaseSpan {
+        PhaseSpan {
+            phase: FocusPhase::Work,
+            pomodoro: 1,
+            starts_at: started_at,
+            ends_at: started_at + rules.length(&FocusPhase::Work),
+        }
+    }
+
+    fn next(&self, rules: &FocusRules) -> PhaseSpan {
+        let (phase, pomodoro) = match self.phase {
+            FocusPhase::Work if self.pomodoro % rules.long_break_every == 0 => {
+                (FocusPhase::LongBreak, self.pomodoro)
+            }
+            FocusPhase::Work => (FocusPhase::ShortBreak, self.pomodoro),
+            FocusPhase::ShortBreak | FocusPhase::LongBreak => (FocusPhase::Work, self.pomodoro + 1),
+        };
+        PhaseSpan {
+            starts_at: self.ends_at,
+            ends_at: self.ends_at + rules.length(&phase),
+            phase,
+            pomodoro,
+        }
+    }
+
+    /// Pomodoros whose work phase is over by the end of this phase
+    fn completed(&self) -> u32 {
+        match self.phase {
+            FocusPhase::Work => self.pomodoro - 1,
+            FocusPhase::ShortBreak | FocusPhase::LongBreak => self.pomodoro,
+        }
+    }
+}
+
+/// The phase a focus session started at `started_at` is in at `at`
+pub fn phase_at(started_at: i64, rules: &FocusRules, at: i64) -> PhaseSpan {
+    // Skip the whole rounds instead of walking through every phase of a long session
+    let rounds = (at - started_at).max(0) / rules.round_length();
+    let mut span = PhaseSpan::first(started_at + rounds * rules.round_length(), rules);
+    span.pomodoro += rounds as u32 * rules.long_break_every;
+
+    while span.ends_at <= at {
+        span = span.next(rules);
+    }
+    span
+}
+
+/// The phases starting after `after` up to `until` included, the moments the
+/// session changes state
+pub fn phases_between(
+    started_at: i64,
+    rules: &FocusRules,
+    after: i64,
+    until: i64,
+) -> Vec<PhaseSpan> {
+    let mut phases = Vec::new();
+    let mut span = phase_at(started_at, rules, after);
+    loop {
+        span = span.next(rules);
+        if span.starts_at > until {
+            return phases;
+        }
+        phases.push(span.clone());
+    }
+}
+
+/// A session run as a row of pomodoros, the phases switch it between active and paused
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct FocusSession {
+    pub id: Option<i32>,
+    pub session_id: i32,
+    pub started_at: i64,
+    /// As they were when the session started
+    pub rules: FocusRules,
+    pub ended_at: Option<i64>,
+    /// 1 to 5, given once it ended
+    pub rating: Option<u8>,
+}
+
+impl FocusSession {
+    /// The phase at `now`, or the one it ended in
+    pub fn phase(&self, now: i64) -> PhaseSpan {
+        phase_at(
+            self.started_at,
+            &self.rules,
+            self.ended_at.unwrap_or(now).min(self.ends_at()),
+        )
+    }
+
+    /// When the work phase of the last pomodoro the rules allow is over
+    pub fn ends_at(&self) -> i64 {
+        let rules = &self.rules;
+        let pomodoros = rules.max_pomodoros as i64;
+        let long_breaks = (pomodoros - 1) / rules.long_break_every as i64;
+        let short_breaks = pomodoros - 1 - long_breaks;
+        self.started_at
+            + (pomodoros * rules.work_minutes as i64
+                + short_breaks * rules.short_break_minutes as i64
+                + long_breaks * rules.long_break_minutes as i64)
+                * 60
+    }
+
+    /// Pomodoros worked through to the end by `now`
+    pub fn completed(&self, now: i64) -> u32 {
+        self.phase(now).completed()
+    }
+}
+
+pub fn validate_rating(rating: u8) -> Result<u8, FocusError> {
+    match rating {
+        1..=5 => Ok(rating),
+        _ => Err(FocusError::InvalidRating(rating)),
+    }
+}
+
+/// Something that broke the focus during a session
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Interruption {
+    pub id: Option<i32>,
+    pub focus_id: i32,
+    pub at: i64,
+    pub note: String,
+    /// Caused by someone or something else rather than by the user
+    pub external: bool,
+}
+
+/// The running focus session as the timer reports it every second
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct FocusState {
+    pub focus: FocusSession,
+    pub name: String,
+    pub purpose: KarmaType,
+    pub phase: PhaseSpan,
+    pub remaining_seconds: i64,
+    pub completed: u32,
+    pub interruptions: Vec<Interruption>,
+}
+
+/// What the timer found when it caught up with the running focus session
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct FocusTick {
+    pub state: FocusState,
+    /// The phases that started since the last tick, oldest first
+    pub entered: Vec<PhaseSpan>,
+}
+
+/// A past focus session
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct FocusSummary {
+    pub focus: FocusSession,
+    pub name: String,
+    pub purpose: KarmaType,
+    pub completed: u32,
+    pub interruptions: usize,
+}
+
+#[cfg(test)]
+mod focus_tests {
+    use super::*;
+
+    const MINUTE: i64 = 60;
+
+    #[test]
+    fn test_parse_rules() {
+        let rules = FocusRules::parse("work=50, short=10").unwrap();
+        assert_eq!(rules.work_minutes, 50);
+        assert_eq!(rules.long_break_minutes, 15);
+        assert_eq!(FocusRules::parse(&rules.to_string()).unwrap(), rules);
+        assert_eq!(FocusRules::parse("").unwrap(), FocusRules::default());
+
+        assert!(FocusRules::parse("work=0").is_err());
+        assert!(FocusRules::parse("every=2,nap=20").is_err());
+        assert!(FocusRules::parse("short").is_err());
+        assert!(validate_rating(0).is_err());
+        assert_eq!(validate_rating(5).unwrap(), 5);
+    }
+
+    #[test]
+    fn test_phases_follow_the_rules() {
+        let rules = FocusRules::parse("work=25,short=5,long=15,every=2").unwrap();
+        let start = 1_000_000;
+
+        let phase = phase_at(start, &rules, start);
+        assert_eq!((phase.phase, phase.pomodoro), (FocusPhase::Work, 1));
+        let phase = phase_at(start, &rules, start + 25 * MINUTE);
+        assert_eq!((phase.phase, phase.pomodoro), (FocusPhase::ShortBreak, 1));
+        assert_eq!(phase.ends_at, start + 30 * MINUTE);
+        let phase = phase_at(start, &rules, start + 55 * MINUTE);
+        assert_eq!((phase.phase, phase.pomodoro), (FocusPhase::LongBreak, 2));
+
+        // A round is 25 + 5 + 25 + 15 minutes
+        let phase = phase_at(start, &rules, start + 3 * 70 * MINUTE + 26 * MINUTE);
+        assert_eq!((&phase.phase, phase.pomodoro), (&FocusPhase::ShortBreak, 7));
+        assert_eq!(phase.starts_at, start + 3 * 70 * MINUTE + 25 * MINUTE);
+        assert_eq!(phase.completed(), 7);
+
+        let phases = phases_between(start, &rules, start + 10 * MINUTE, start + 70 * MINUTE);
+        let states: Vec<(FocusPhase, i64)> = phases
+            .into_iter()
+            .map(|phase| (phase.phase, (phase.starts_at - start) / MINUTE))
+            .collect();
+        assert_eq!(
+            states,
+            vec![
+                (FocusPhase::ShortBreak, 25),
+                (FocusPhase::Work, 30),
+                (FocusPhase::LongBreak, 55),
+                (FocusPhase::Work, 70),
+            ]
+        );
+
+        let focus = FocusSession {
+            id: Some(1),
+            session_id: 1,
+            started_at: start,
+            rules,
+            ended_at: Some(start + 40 * MINUTE),
+            rating: None,
+        };
+        assert_eq!(focus.completed(start + 500 * MINUTE), 1);
+
+        // Three pomodoros end after 25 + 5 + 25 + 15 + 25 minutes, and stay done
+        let focus = FocusSession {
+            rules: FocusRules {
+                max_pomodoros: 3,
+                ..focus.rules
+            },
+            ended_at: None,
+            ..focus
+        };
+        assert_eq!(focus.ends_at(), start + 95 * MINUTE);
+        assert_eq!(focus.completed(start + 94 * MINUTE), 2);
+        assert_eq!(focus.completed(start + 500 * MINUTE), 3);
+    }
+}
@@ -0,0 +1,374 @@
+use chrono::{Duration, NaiveDate};
+use serde::Serialize;
+use thiserror::Error;
+
+use super::duration::{active_intervals, TrackedSession};
+use super::local_time::TimeSettings;
+use super::report::ReportPeriod;
+use super::subject::KarmaSubject;
+
+/// How many past periods are listed next to the current one
+pub const GOAL_HISTORY_PERIODS: usize = 12;
+
+#[derive(Debug, Error, Serialize)]
+pub enum GoalError {
+    #[error("Unknown goal metric {0}, expected hours, daily_hours or sessions")]
+    UnknownMetric(String),
+
+    #[error("Unknown goal comparison {0}, expected at_least or at_most")]
+    UnknownComparison(String),
+
+    #[error("Invalid goal target {0}, expected a positive number")]
+    InvalidTarget(f64),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub enum GoalMetric {
+    /// Active time over the period
+    Hours,
+    /// Active time per day, averaged over the days of the period gone by
+    DailyHours,
+    /// Sessions started in the period
+    Sessions,
+}
+
+impl GoalMetric {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            GoalMetric::Hours => "hours",
+            GoalMetric::DailyHours => "daily_hours",
+            GoalMetric::Sessions => "sessions",
+        }
+    }
+}
+
+impl TryFrom<&str> for GoalMetric {
+    type Error = GoalError;
+
+    fn try_from(value: &str) -> Result<Self, Self::Error> {
+        match value.to_lowercase().replace('-', "_").as_str() {
+            "hours" | "h" => Ok(GoalMetric::Hours),
+            "daily_hours" | "daily" => Ok(GoalMetric::DailyHours),
+            "sessions" => Ok(GoalMetric::Sessions),
+            other => Err(GoalError::UnknownMetric(other.to_string())),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub enum GoalComparison {
+    AtLeast,
+    AtMost,
+}
+
+impl GoalComparison {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            GoalComparison::AtLeast => "at_least",
+            GoalComparison::AtMost => "at_most",
+        }
+    }
+}
+
+impl TryFrom<&str> for GoalComparison {
+    type Error = GoalError;
+
+    fn try_from(value: &str) -> Result<Self, Self::Error> {
+        match value.to_lowercase().replace('-', "_").as_str() {
+            "at_least" | ">=" => Ok(GoalComparison::AtLeast),
+            "at_most" | "<=" => Ok(GoalComparison::AtMost),
+            other => Err(GoalError::UnknownComparison(other.to_string())),
+        }
+    }
+}
+
+/// For example at least 5 hours of Learning per week
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Goal {
+    pub id: Option<i32>,
+    pub subject: KarmaSubject,
+    /// The template name or the type, for display
+    pub name: String,
+    pub metric: GoalMetric,
+    pub comparison: GoalComparison,
+    /// Hours or sessions, depending on the metric
+    pub target: f64,
+    pub period: ReportPeriod,
+    pub created_at: i64,
+}
+
+impl Goal {
+    pub fn validate(&self) -> Result<(), GoalError> {
+        if !self.target.is_finite() || self.target <= 0.0 {
+            return Err(GoalError::InvalidTarget(self.target));
+        }
+        Ok(())
+    }
+
+    /// For example `Learning >= 5h/week` or `Sleeping >= 7h/day on average per week`
+    pub fn describe(&self) -> String {
+        let comparison = match self.comparison {
+            GoalComparison::AtLeast => ">=",
+            GoalComparison::AtMost => "<=",
+        };
+        let period = self.period.noun();
+        match self.metric {
+            GoalMetric::Hours => format!("{} {comparison} {}h/{period}", self.name, self.target),
+            GoalMetric::DailyHours => format!(
+                "{} {comparison} {}h/day on average per {period}",
+                self.name, self.target
+            ),
+            GoalMetric::Sessions => format!(
+                "{} {comparison} {} sessions/{period}",
+                self.name, self.target
+            ),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub enum GoalState {
+    /// Under way and keeping pace
+    OnTrack,
+    /// Under way and falling behind, or close to going over a maximum
+    AtRisk,
+    Achieved,
+    Missed,
+}
+
+/// How a goal went in one period
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct GoalProgress {
+    pub first_day: NaiveDate,
+    pub last_day: NaiveDate,
+    /// Hours or sessions so far
+    pub value: f64,
+    /// `value` against the target, 1 when exactly reached
+    pub ratio: f64,
+    pub state: GoalState,
+}
+
+/// A goal with the current period and the previous ones, the most recent first
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct GoalStatus {
+    pub goal: Goal,
+    pub description: String,
+    pub current: GoalProgress,
+    pub history: Vec<GoalProgress>,
+}
+
+/// Measures the goal over `[first_day, last_day]` as of `now`. Periods not over yet are
+/// judged by where the current pace leads, finished ones are achieved or missed.
+pub fn goal_progress(
+    goal: &Goal,
+    sessions: &[TrackedSession],
+    first_day: NaiveDate,
+    last_day: NaiveDate,
+    now: i64,
+    settings: &TimeSettings,
+) -> GoalProgress {
+    let from = settings.day_start(first_day);
+    let to = settings.next_day_start(last_day);
+    let sessions: Vec<&TrackedSession> = sessions
+        .iter()
+        .filter(|tracked| goal.subject.matches(tracked))
+        .collect();
+    let active_hours = |until: i64| {
+        let seconds: i64 = sessions
+            .iter()
+            .flat_map(|tracked| active_intervals(&tracked.statuses, now))
+            .filter_map(|interval| interval.clip(from, until.min(now)))
+            .map(|interval| interval.seconds())
+            .sum();
+        seconds as f64 / 3600.0
+    };
+
+    let value = match goal.metric {
+        GoalMetric::Hours => active_hours(to),
+        GoalMetric::DailyHours => {
+            // Only whole days count, until the first one is over
+            let today = settings.local_date(now);
+            let days =
+                ((last_day.min(today - Duration::days(1)) - first_day).num_days() + 1).max(1);
+            active_hours(settings.day_start(first_day + Duration::days(days))) / days as f64
+        }
+        GoalMetric::Sessions => sessions
+            .iter()
+            .filter(|tracked| {
+                let started_at = tracked.session.get_started_at();
+                started_at >= from && started_at < to
+            })
+            .count() as f64,
+    };
+
+    let elapsed = ((now - from) as f64 / (to - from) as f64).clamp(0.0, 1.0);
+    let over = now >= to;
+    // An average already is a pace, totals grow with the period
+    let projected = match goal.metric {
+        GoalMetric::DailyHours => value,
+        GoalMetric::Hours | GoalMetric::Sessions if elapsed > 0.0 => value / elapsed,
+        GoalMetric::Hours | GoalMetric::Sessions => 0.0,
+    };
+
+    let state = match goal.comparison {
+        GoalComparison::AtLeast if value >= goal.target => GoalState::Achieved,
+        GoalComparison::AtLeast if over => GoalState::Missed,
+        GoalComparison::AtLeast if projected >= goal.target => GoalState::OnTrack,
+        GoalComparison::AtLeast => GoalState::AtRisk,
+        // A total can't come back down, an average can
+        GoalComparison::AtMost
+            if value > goal.target && (over || goal.metric != GoalMetric::DailyHours) =>
+        {
+            GoalState::Missed
+        }
+        GoalComparison::AtMost if over => GoalState::Achieved,
+        GoalComparison::AtMost if projected > goal.target => GoalState::AtRisk,
+        GoalComparison::AtMost => GoalState::OnTrack,
+    };
+
+    GoalProgress {
+        first_day,
+        last_day,
+        value,
+        ratio: value / goal.target,
+        state,
+    }
+}
+
+/// The current period of the goal and up to `GOAL_HISTORY_PERIODS` before it,
+/// none before the goal was created
+pub fn goal_status(
+    goal: Goal,
+    sessions: &[TrackedSession],
+    now: i64,
+    settings: &TimeSettings,
+) -> GoalStatus {
+    let today = settings.local_date(now);
+    let (first_day, last_day) = goal.period.bounds(today, settings);
+    let current = goal_progress(&goal, sessions, first_day, last_day, now, settings);
+
+    let created_on = goal
+        .period
+        .bounds(settings.local_date(goal.created_at), settings)
+        .0;
+    let mut history = Vec::new();
+    let mut first_day = first_day;
+    while history.len() < GOAL_HISTORY_PERIODS {
+        first_day = goal.period.previous(first_day);
+        if first_day < created_on {
+            break;
+        }
+        let (first_day, last_day) = goal.period.bounds(first_day, settings);
+        history.push(goal_progress(
+            &goal, sessions, first_day, last_day, now, settings,
+        ));
+    }
+
+    GoalStatus {
+        description: goal.describe(),
+        goal,
+        current,
+        history,
+    }
+}
+
+#[cfg(test)]
+mod goal_tests {
+    use super::*;
+    use crate::model::karma::{KarmaStatus, KarmaType, Polarity, State};
+    use crate::model::karma_session::KarmaSession;
+    use crate::model::recurrence::parse_date;
+
+    const HOUR: i64 = 60 * 60;
+    const DAY: i64 = 24 * HOUR;
+    // Monday 2024-01-01 00:00 UTC
+    const MONDAY: i64 = 1_704_067_200;
+
+    fn session(id: i32, purpose: KarmaType, start: i64, hours: i64) -> TrackedSession {
+        TrackedSession {
+            session: KarmaSession::with_id(id, id, purpose, None, start),
+            name: format!("Point {id}"),
+            polarity: Polarity::Positive,
+            statuses: vec![
+                KarmaStatus::new(id, State::Active, start),
+                KarmaStatus::new(id, State::Closed, start + hours * HOUR),
+            ],
+        }
+    }
+
+    fn goal(
+        subject: KarmaSubject,
+        metric: GoalMetric,
+        comparison: GoalComparison,
+        target: f64,
+    ) -> Goal {
+        Goal {
+            id: Some(1),
+            subject,
+            name: "Learning".to_string(),
+            metric,
+            comparison,
+            target,
+            period: ReportPeriod::Week,
+            created_at: MONDAY - 7 * DAY,
+        }
+    }
+
+    #[test]
+    fn test_states_follow_the_pace() {
+        let settings = TimeSettings::default();
+        let learning = KarmaSubject::Category(KarmaType::Learning);
+        let sessions = vec![
+            session(1, KarmaType::Learning, MONDAY + 9 * HOUR, 2),
+            session(2, KarmaType::Social, MONDAY + 12 * HOUR, 3),
+        ];
+        let first = parse_date("2024-01-01").unwrap();
+        let last = parse_date("2024-01-07").unwrap();
+
+        // 2h in the first two days of the week is the pace for 7h
+        let hours = goal(
+            learning.clone(),
+            GoalMetric::Hours,
+            GoalComparison::AtLeast,
+            5.0,
+        );
+        let progress = goal_progress(&hours, &sessions, first, last, MONDAY + 2 * DAY, &settings);
+        assert_eq!(progress.value, 2.0);
+        assert_eq!(progress.state, GoalState::OnTrack);
+
+        let progress = goal_progress(&hours, &sessions, first, last, MONDAY + 5 * DAY, &settings);
+        assert_eq!(progress.state, GoalState::AtRisk);
+
+        let progress = goal_progress(&hours, &sessions, first, last, MONDAY + 7 * DAY, &settings);
+        assert_eq!(progress.state, GoalState::Missed);
+        assert_eq!(progress.ratio, 0.4);
+
+        let count = goal(learning, GoalMetric::Sessions, GoalComparison::AtMost, 1.0);
+        let progress = goal_progress(&count, &sessions, first, last, MONDAY + 7 * DAY, &settings);
+        assert_eq!(progress.state, GoalState::Achieved);
+    }
+
+    #[test]
+    fn test_daily_average_over_whole_days() {
+        let settings = TimeSettings::default();
+        let sleep = goal(
+            KarmaSubject::Category(KarmaType::Sleeping),
+            GoalMetric::DailyHours,
+            GoalComparison::AtLeast,
+            7.0,
+        );
+        // 8h and 5h nights, each starting the evening before
+        let sessions = vec![
+            session(1, KarmaType::Sleeping, MONDAY - 2 * HOUR, 8),
+            session(2, KarmaType::Sleeping, MONDAY + DAY - HOUR, 5),
+        ];
+
+        let status = goal_status(sleep, &sessions, MONDAY + 2 * DAY + 12 * HOUR, &settings);
+        // Monday got 7h and Tuesday 4h, Wednesday isn't over
+        assert_eq!(status.current.value, 5.5);
+        assert_eq!(status.current.state, GoalState::AtRisk);
+        assert_eq!(status.history.len(), 1);
+        assert_eq!(status.history[0].first_day.to_string(), "2023-12-25");
+        assert_eq!(status.history[0].state, GoalState::Missed);
+    }
+}
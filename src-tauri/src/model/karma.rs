@@ -1,7 +1,9 @@
-use serde::Serialize;
+use serde::{Deserialize, Serialize};
 use sqlx::Encode;
 use sqlx::{sqlite::SqliteRow, Error as SqlxError, FromRow, Row};
 use thiserror::Error;
+
+use super::local_time::UserTimezone;
 //todo: add name in karma model
 
 #[derive(Debug, Error, Serialize)]
@@ -13,11 +15,17 @@ pub enum KarmaError {
     UnsupportedStatus(String),
 }
 
+/// A reusable activity definition, sessions are started from it
 #[derive(Debug, Clone, Encode, Serialize)]
 pub struct KarmaPoint {
     id: Option<i32>,
+    /// The default type of the sessions
     purpose: KarmaType,
     name: String,
+    created_at: i64,
+    /// The default planned duration of the sessions, in seconds
+    default_duration: Option<i64>,
+    polarity: Polarity,
 }
 
 impl KarmaPoint {
@@ -29,10 +37,13 @@ impl KarmaPoint {
             id: None,
             purpose,
             name,
+            created_at: chrono::Utc::now().timestamp(),
+            default_duration: None,
+            polarity: Polarity::Positive,
         }
     }
 
-    pub fn with_id(id: i32, purpose: KarmaType, name: String) -> KarmaPoint {
+    pub fn with_id(id: i32, purpose: KarmaType, name: String, created_at: i64) -> KarmaPoint {
         // Initially we set the closing type to the purpose
         // assuming it will be closed correctly, and we change that at
         // closing time
@@ -40,9 +51,22 @@ impl KarmaPoint {
             id: Some(id),
             purpose,
             name,
+            created_at,
+            default_duration: None,
+            polarity: Polarity::Positive,
         }
     }
 
+    pub fn with_default_duration(mut self, default_duration: Option<i64>) -> KarmaPoint {
+        self.default_duration = default_duration;
+        self
+    }
+
+    pub fn with_polarity(mut self, polarity: Polarity) -> KarmaPoint {
+        self.polarity = polarity;
+        self
+    }
+
     pub fn get_purpose(&self) -> KarmaType {
         self.purpose.clone()
     }
@@ -54,47 +78,94 @@ impl KarmaPoint {
     pub fn get_id(&self) -> Option<i32> {
         self.id
     }
+
+    pub fn get_created_at(&self) -> i64 {
+        self.created_at
+    }
+
+    pub fn get_default_duration(&self) -> Option<i64> {
+        self.default_duration
+    }
+
+    pub fn get_polarity(&self) -> Polarity {
+        self.polarity
+    }
 }
 
 impl<'r> FromRow<'r, SqliteRow> for KarmaPoint {
     fn from_row(row: &'r SqliteRow) -> Result<Self, SqlxError> {
         let id = row.try_get("id")?;
         let name: String = row.try_get("name")?;
+        let created_at: i64 = row.try_get("created_at")?;
+        let default_duration: Option<i64> = row.try_get("default_duration")?;
+        let polarity: i32 = row.try_get("polarity")?;
 
         let purpose: i32 = row.try_get("purpose")?;
         let purpose = purpose
             .try_into()
             .map_err(|e| SqlxError::Decode(Box::new(e)))?;
 
-        Ok(KarmaPoint::with_id(id, purpose, name))
+        Ok(KarmaPoint::with_id(id, purpose, name, created_at)
+            .with_default_duration(default_duration)
+            .with_polarity(polarity.into()))
+    }
+}
+
+/// Whether a karma point is something to do or a habit to avoid, a session of
+/// a habit to avoid is a relapse
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize)]
+pub enum Polarity {
+    #[default]
+    Positive = 1,
+    Negative = -1,
+}
+
+impl From<i32> for Polarity {
+    fn from(value: i32) -> Polarity {
+        if value < 0 {
+            Polarity::Negative
+        } else {
+            Polarity::Positive
+        }
     }
 }
 
 impl<'r> FromRow<'r, SqliteRow> for KarmaStatus {
     fn from_row(row: &'r SqliteRow) -> Result<Self, SqlxError> {
-        let karma_id = row.try_get("karma_id")?;
+        let session_id = row.try_get("session_id")?;
         let current_state: String = row.try_get("current_state")?;
         let timestamp: i64 = row.try_get("timestamp")?;
 
-        let closed_with: i32 = row.try_get("closed_with")?;
-        if closed_with == 0 {
-            return Ok(KarmaStatus::new(karma_id, current_state.into(), timestamp));
-        }
+        let timezone: Option<String> = row.try_get("timezone")?;
+        let utc_offset: i32 = row.try_get("utc_offset")?;
+
+        let closed_with: Option<i32> = row.try_get("closed_with")?;
+        let Some(closed_with) = closed_with.filter(|closed_with| *closed_with != 0) else {
+            return Ok(KarmaStatus {
+                timezone,
+                utc_offset,
+                ..KarmaStatus::new(session_id, current_state.into(), timestamp)
+            });
+        };
 
         let closed_with: KarmaType = closed_with
             .try_into()
             .map_err(|e| SqlxError::Decode(Box::new(e)))?;
 
-        Ok(KarmaStatus::with_closed_reason(
-            karma_id,
-            current_state.into(),
-            timestamp,
-            closed_with,
-        ))
+        Ok(KarmaStatus {
+            timezone,
+            utc_offset,
+            ..KarmaStatus::with_closed_reason(
+                session_id,
+                current_state.into(),
+                timestamp,
+                closed_with,
+            )
+        })
     }
 }
 
-#[derive(Debug, Clone, PartialEq, Serialize)]
+#[derive(Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize)]
 pub enum KarmaType {
     Work = 1,
     Social = 2,
@@ -103,6 +174,24 @@ pub enum KarmaType {
     Sleeping = 5,
 }
 
+impl KarmaType {
+    /// Every type, in their numeric order
+    pub const ALL: [KarmaType; 5] = [
+        KarmaType::Work,
+        KarmaType::Social,
+        KarmaType::Sport,
+        KarmaType::Learning,
+        KarmaType::Sleeping,
+    ];
+
+    /// The type called `name`, ignoring case
+    pub fn from_name(name: &str) -> Option<KarmaType> {
+        KarmaType::ALL
+            .into_iter()
+            .find(|purpose| format!("{purpose:?}").eq_ignore_ascii_case(name.trim()))
+    }
+}
+
 impl TryFrom<i32> for KarmaType {
     type Error = KarmaError;
     fn try_from(value: i32) -> Result<Self, Self::Error> {
@@ -120,56 +209,75 @@ impl TryFrom<i32> for KarmaType {
 #[derive(Debug, Clone, PartialEq, Serialize)]
 pub enum State {
     Active,
+    /// Suspended, the time until the next transition doesn't count
+    Paused,
     Closed,
 }
 
 impl From<String> for State {
     fn from(value: String) -> State {
-        if value.to_lowercase() == *"active" {
-            State::Active
-        } else {
-            State::Closed
+        match value.to_lowercase().as_str() {
+            "active" => State::Active,
+            "paused" => State::Paused,
+            _ => State::Closed,
         }
     }
 }
 
 #[derive(Debug, Clone, PartialEq, Serialize)]
 pub struct KarmaStatus {
-    pub karma_id: i32,
+    pub session_id: i32,
     pub closed_with: Option<KarmaType>,
     pub state: State,
     pub timestamp: i64,
+    /// The timezone of the user when the status was written, missing for
+    /// statuses from before timezones were recorded
+    pub timezone: Option<String>,
+    /// Seconds east of UTC at `timestamp` in that timezone
+    pub utc_offset: i32,
 }
 
 impl KarmaStatus {
-    pub fn new(karma_id: i32, state: State, timestamp: i64) -> KarmaStatus {
+    pub fn new(session_id: i32, state: State, timestamp: i64) -> KarmaStatus {
         KarmaStatus {
-            karma_id,
+            session_id,
             closed_with: None,
             state,
             timestamp,
+            timezone: None,
+            utc_offset: 0,
         }
     }
 
     pub fn with_closed_reason(
-        karma_id: i32,
+        session_id: i32,
         state: State,
         timestamp: i64,
         closed_with: KarmaType,
     ) -> KarmaStatus {
         KarmaStatus {
-            karma_id,
+            session_id,
             closed_with: Some(closed_with),
             state,
             timestamp,
+            timezone: None,
+            utc_offset: 0,
         }
     }
+
+    /// Records the timezone the status was written in
+    pub fn in_timezone(mut self, timezone: &UserTimezone) -> KarmaStatus {
+        self.timezone = Some(timezone.to_string());
+        self.utc_offset = timezone.offset_at(self.timestamp);
+        self
+    }
 }
 
 impl ToString for State {
     fn to_string(&self) -> String {
         match self {
             State::Active => format!("active"),
+            State::Paused => format!("paused"),
             State::Closed => format!("closed"),
         }
     }
@@ -181,6 +289,7 @@ impl TryFrom<&str> for State {
     fn try_from(value: &str) -> Result<Self, Self::Error> {
         match value {
             "active" => Ok(State::Active),
+            "paused" => Ok(State::Paused),
             "closed" => Ok(State::Closed),
             state => Err(KarmaError::UnsupportedStatus(state.to_string())),
         }
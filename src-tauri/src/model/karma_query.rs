@@ -0,0 +1,137 @@
+use data_encoding::BASE64URL_NOPAD;
+use serde::Serialize;
+use thiserror::Error;
+
+use super::karma::{KarmaPoint, KarmaType, State};
+
+pub const DEFAULT_PAGE_SIZE: u32 = 50;
+pub const MAX_PAGE_SIZE: u32 = 200;
+
+#[derive(Debug, Error, Serialize)]
+pub enum KarmaQueryError {
+    #[error("Invalid pagination cursor")]
+    InvalidCursor,
+
+    #[error("Unsupported sort order {0}")]
+    UnsupportedSort(String),
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize)]
+pub enum KarmaSort {
+    #[default]
+    CreatedAt,
+    /// The latest status change, or the creation time for points without any
+    LastActivity,
+}
+
+impl TryFrom<&str> for KarmaSort {
+    type Error = KarmaQueryError;
+
+    fn try_from(value: &str) -> Result<Self, Self::Error> {
+        match value.to_lowercase().replace(['-', '_'], "").as_str() {
+            "created" | "createdat" | "creation" => Ok(KarmaSort::CreatedAt),
+            "activity" | "lastactivity" => Ok(KarmaSort::LastActivity),
+            other => Err(KarmaQueryError::UnsupportedSort(other.to_string())),
+        }
+    }
+}
+
+/// Position after the last item of a page. Pages are keyed on the sort value and the id
+/// rather than an offset, so points inserted meanwhile don't shift the following pages.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct KarmaCursor {
+    pub sort_key: i64,
+    pub id: i32,
+}
+
+impl KarmaCursor {
+    pub fn encode(&self) -> String {
+        BASE64URL_NOPAD.encode(format!("{}:{}", self.sort_key, self.id).as_bytes())
+    }
+
+    pub fn decode(cursor: &str) -> Result<KarmaCursor, KarmaQueryError> {
+        let decoded = BASE64URL_NOPAD
+            .decode(cursor.as_bytes())
+            .ok()
+            .and_then(|bytes| String::from_utf8(bytes).ok())
+            .ok_or(KarmaQueryError::InvalidCursor)?;
+
+        let (sort_key, id) = decoded
+            .split_once(':')
+            .ok_or(KarmaQueryError::InvalidCursor)?;
+
+        Ok(KarmaCursor {
+            sort_key: sort_key
+                .parse()
+                .map_err(|_| KarmaQueryError::InvalidCursor)?,
+            id: id.parse().map_err(|_| KarmaQueryError::InvalidCursor)?,
+        })
+    }
+}
+
+/// Filters for listing karma points, newest first. The date range
+/// applies to the field the list is sorted by, both bounds inclusive.
+#[derive(Debug, Clone, Default)]
+pub struct KarmaQuery {
+    pub purpose: Option<KarmaType>,
+    pub state: Option<State>,
+    pub from: Option<i64>,
+    pub to: Option<i64>,
+    pub name_prefix: Option<String>,
+    pub sort: KarmaSort,
+    pub limit: u32,
+    pub after: Option<KarmaCursor>,
+}
+
+impl KarmaQuery {
+    /// The page size actually used, a zero limit means the default one
+    pub fn page_size(&self) -> u32 {
+        match self.limit {
+            0 => DEFAULT_PAGE_SIZE,
+            limit => limit.min(MAX_PAGE_SIZE),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct KarmaListItem {
+    pub karma: KarmaPoint,
+    /// The state of the latest status, none when the point was never started
+    pub state: Option<State>,
+    pub last_activity: i64,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct KarmaPage {
+    pub items: Vec<KarmaListItem>,
+    /// Pass it back to get the next page, none on the last page
+    pub next_cursor: Option<String>,
+}
+
+#[cfg(test)]
+mod karma_query_tests {
+    use super::*;
+
+    #[test]
+    fn test_cursor_round_trip() {
+        let cursor = KarmaCursor {
+            sort_key: 1_700_000_000,
+            id: 42,
+        };
+
+        assert_eq!(KarmaCursor::decode(&cursor.encode()).unwrap(), cursor);
+        assert!(matches!(
+            KarmaCursor::decode("not a cursor"),
+            Err(KarmaQueryError::InvalidCursor)
+        ));
+    }
+
+    #[test]
+    fn test_page_size() {
+        let mut query = KarmaQuery::default();
+        assert_eq!(query.page_size(), DEFAULT_PAGE_SIZE);
+
+        query.limit = 10_000;
+        assert_eq!(query.page_size(), MAX_PAGE_SIZE);
+    }
+}
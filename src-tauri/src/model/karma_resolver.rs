@@ -0,0 +1,191 @@
+use serde::Serialize;
+use thiserror::Error;
+
+use super::karma_query::KarmaListItem;
+
+/// Below this a fuzzy match is more likely another point than a typo
+pub const MIN_FUZZY_SIMILARITY: f64 = 0.6;
+pub const MAX_CANDIDATES: usize = 5;
+
+// A typo early in a long name is matched against the start of the name only,
+// which is weaker evidence than matching the whole name
+const PARTIAL_FUZZY_PENALTY: f64 = 0.9;
+
+#[derive(Debug, Error, Serialize)]
+pub enum KarmaResolveError {
+    #[error("No karma point matches {0}")]
+    NotFound(String),
+
+    #[error("{name} matches several karma points: {}", candidate_names(.candidates))]
+    Ambiguous {
+        name: String,
+        /// Most similar first, then most recently active
+        candidates: Vec<KarmaCandidate>,
+    },
+}
+
+/// How a name matched, from the weakest to the strongest
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize)]
+pub enum MatchKind {
+    Fuzzy,
+    Prefix,
+    CaseInsensitive,
+    Exact,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct KarmaCandidate {
+    pub item: KarmaListItem,
+    pub kind: MatchKind,
+    /// Between 0 and 1, 1 being the same name
+    pub similarity: f64,
+}
+
+fn candidate_names(candidates: &[KarmaCandidate]) -> String {
+    candidates
+        .iter()
+        .map(|candidate| candidate.item.karma.get_name())
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+/// Picks the karma point meant by `name`. Only the strongest kind of match counts, so
+/// an exact name wins over prefixes of longer names and a prefix wins over typos.
+/// Several matches of that kind are reported back instead of guessing.
+pub fn resolve(name: &str, items: Vec<KarmaListItem>) -> Result<KarmaListItem, KarmaResolveError> {
+    let name = name.trim();
+    if name.is_empty() {
+        return Err(KarmaResolveError::NotFound(name.to_string()));
+    }
+
+    let mut candidates: Vec<KarmaCandidate> = items
+        .into_iter()
+        .filter_map(|item| {
+            let (kind, similarity) = match_name(name, &item.karma.get_name())?;
+            Some(KarmaCandidate {
+                item,
+                kind,
+                similarity,
+            })
+        })
+        .collect();
+
+    let Some(best_kind) = candidates.iter().map(|candidate| candidate.kind).max() else {
+        return Err(KarmaResolveError::NotFound(name.to_string()));
+    };
+    candidates.retain(|candidate| candidate.kind == best_kind);
+
+    if candidates.len() == 1 {
+        return Ok(candidates.remove(0).item);
+    }
+
+    candidates.sort_by(|a, b| {
+        b.similarity
+            .total_cmp(&a.similarity)
+            .then(b.item.last_activity.cmp(&a.item.last_activity))
+    });
+    candidates.truncate(MAX_CANDIDATES);
+
+    Err(KarmaResolveError::Ambiguous {
+        name: name.to_string(),
+        candidates,
+    })
+}
+
+fn match_name(query: &str, name: &str) -> Option<(MatchKind, f64)> {
+    if query == name {
+        return Some((MatchKind::Exact, 1.0));
+    }
+
+    let query = query.to_lowercase();
+    let name = name.to_lowercase();
+    let query_length = query.chars().count();
+    let name_length = name.chars().count();
+
+    if query == name {
+        return Some((MatchKind::CaseInsensitive, 1.0));
+    }
+
+    if name.starts_with(&query) {
+        return Some((MatchKind::Prefix, query_length as f64 / name_length as f64));
+    }
+
+    let whole = strsim::normalized_damerau_levenshtein(&query, &name);
+    let start: String = name.chars().take(query_length).collect();
+    let partial = if start.chars().count() < name_length {
+        strsim::normalized_damerau_levenshtein(&query, &start) * PARTIAL_FUZZY_PENALTY
+    } else {
+        0.0
+    };
+
+    let similarity = whole.max(partial);
+    (similarity >= MIN_FUZZY_SIMILARITY).then_some((MatchKind::Fuzzy, similarity))
+}
+
+#[cfg(test)]
+mod karma_resolver_tests {
+    use super::*;
+    use crate::model::karma::{KarmaPoint, KarmaType};
+
+    fn items(names: &[(&str, i64)]) -> Vec<KarmaListItem> {
+        names
+            .iter()
+            .enumerate()
+            .map(|(id, (name, last_activity))| KarmaListItem {
+                karma: KarmaPoint::with_id(id as i32, KarmaType::Work, name.to_string(), 0),
+                state: None,
+                last_activity: *last_activity,
+            })
+            .collect()
+    }
+
+    fn resolved_name(name: &str, names: &[(&str, i64)]) -> String {
+        resolve(name, items(names)).unwrap().karma.get_name()
+    }
+
+    #[test]
+    fn test_strongest_match_wins() {
+        let names = [("Run", 0), ("run club", 0), ("Running", 0)];
+
+        assert_eq!(resolved_name("Run", &names), "Run");
+        assert_eq!(resolved_name("RUN CLUB", &names), "run club");
+        assert_eq!(resolved_name("runn", &names), "Running");
+        assert_eq!(resolved_name("rnu clb", &names), "run club");
+    }
+
+    #[test]
+    fn test_typo_in_prefix() {
+        let names = [("Morning meditation", 0), ("Evening walk", 0)];
+
+        assert_eq!(resolved_name("mornig", &names), "Morning meditation");
+    }
+
+    #[test]
+    fn test_ambiguous_candidates_are_ranked() {
+        let names = [("Reading", 10), ("Read papers", 30), ("Read novels", 20)];
+
+        match resolve("read", items(&names)) {
+            Err(KarmaResolveError::Ambiguous { candidates, .. }) => {
+                let ranked: Vec<String> = candidates
+                    .iter()
+                    .map(|candidate| candidate.item.karma.get_name())
+                    .collect();
+                // Both "Read ..." points are as similar, the most recently active comes first
+                assert_eq!(ranked, vec!["Reading", "Read papers", "Read novels"]);
+            }
+            other => panic!("expected an ambiguous match, got {other:?}"),
+        }
+    }
+
+    #[test]
+    fn test_not_found() {
+        assert!(matches!(
+            resolve("swimming", items(&[("Reading", 0)])),
+            Err(KarmaResolveError::NotFound(_))
+        ));
+        assert!(matches!(
+            resolve("  ", items(&[("Reading", 0)])),
+            Err(KarmaResolveError::NotFound(_))
+        ));
+    }
+}
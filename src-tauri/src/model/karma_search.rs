@@ -0,0 +1,105 @@
+use serde::Serialize;
+
+use super::karma::KarmaPoint;
+
+/// Markers wrapped around matches by the index, split out before reaching the frontend
+/// so karma names are never interpreted as markup
+pub const HIGHLIGHT_START: char = '\u{2}';
+pub const HIGHLIGHT_END: char = '\u{3}';
+
+pub const DEFAULT_SEARCH_LIMIT: u32 = 20;
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct SnippetPart {
+    pub text: String,
+    pub highlighted: bool,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct KarmaSearchResult {
+    pub karma: KarmaPoint,
+    /// bm25 score, lower is a better match
+    pub rank: f64,
+    pub snippet: Vec<SnippetPart>,
+}
+
+/// Turns free text into an FTS5 query matching every word as a prefix, so typing
+/// stays forgiving and user input can never be a syntax error
+pub fn to_fts_query(input: &str) -> Option<String> {
+    let terms: Vec<String> = input
+        .split(|ch: char| !ch.is_alphanumeric())
+        .filter(|term| !term.is_empty())
+        .map(|term| format!("\"{term}\"*"))
+        .collect();
+
+    if terms.is_empty() {
+        None
+    } else {
+        Some(terms.join(" "))
+    }
+}
+
+pub fn split_highlights(text: &str) -> Vec<SnippetPart> {
+    let mut parts = Vec::new();
+    let mut current = String::new();
+    let mut highlighted = false;
+
+    for ch in text.chars() {
+        if ch == HIGHLIGHT_START || ch == HIGHLIGHT_END {
+            if !current.is_empty() {
+                parts.push(SnippetPart {
+                    text: std::mem::take(&mut current),
+                    highlighted,
+                });
+            }
+            highlighted = ch == HIGHLIGHT_START;
+        } else {
+            current.push(ch);
+        }
+    }
+
+    if !current.is_empty() {
+        parts.push(SnippetPart {
+            text: current,
+            highlighted,
+        });
+    }
+
+    parts
+}
+
+#[cfg(test)]
+mod karma_search_tests {
+    use super::*;
+
+    #[test]
+    fn test_to_fts_query() {
+        assert_eq!(
+            to_fts_query("morning \"run\" OR-"),
+            Some("\"morning\"* \"run\"* \"OR\"*".to_string())
+        );
+        assert_eq!(to_fts_query(" *- "), None);
+    }
+
+    #[test]
+    fn test_split_highlights() {
+        let parts = split_highlights("Morning \u{2}run\u{3} club");
+        assert_eq!(
+            parts,
+            vec![
+                SnippetPart {
+                    text: "Morning ".to_string(),
+                    highlighted: false
+                },
+                SnippetPart {
+                    text: "run".to_string(),
+                    highlighted: true
+                },
+                SnippetPart {
+                    text: " club".to_string(),
+                    highlighted: false
+                },
+            ]
+        );
+    }
+}
@@ -0,0 +1,127 @@
+use serde::Serialize;
+use sqlx::{sqlite::SqliteRow, Error as SqlxError, FromRow, Row};
+
+use super::karma::{KarmaPoint, KarmaStatus, KarmaType};
+
+/// One occurrence of a karma point. The point is the reusable template holding the
+/// name and defaults, every time the activity is done a new session is started.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct KarmaSession {
+    id: Option<i32>,
+    template_id: i32,
+    purpose: KarmaType,
+    /// Seconds, taken from the template unless given when starting
+    planned_duration: Option<i64>,
+    started_at: i64,
+}
+
+impl KarmaSession {
+    /// A session using the defaults of `template`, which must have been stored already
+    pub fn from_template(template: &KarmaPoint, started_at: i64) -> KarmaSession {
+        KarmaSession {
+            id: None,
+            template_id: template.get_id().unwrap_or_default(),
+            purpose: template.get_purpose(),
+            planned_duration: template.get_default_duration(),
+            started_at,
+        }
+    }
+
+    pub fn with_id(
+        id: i32,
+        template_id: i32,
+        purpose: KarmaType,
+        planned_duration: Option<i64>,
+        started_at: i64,
+    ) -> KarmaSession {
+        KarmaSession {
+            id: Some(id),
+            template_id,
+            purpose,
+            planned_duration,
+            started_at,
+        }
+    }
+
+    pub fn with_purpose(mut self, purpose: KarmaType) -> KarmaSession {
+        self.purpose = purpose;
+        self
+    }
+
+    pub fn with_planned_duration(mut self, planned_duration: Option<i64>) -> KarmaSession {
+        self.planned_duration = planned_duration;
+        self
+    }
+
+    pub fn get_id(&self) -> Option<i32> {
+        self.id
+    }
+
+    pub fn get_template_id(&self) -> i32 {
+        self.template_id
+    }
+
+    pub fn get_purpose(&self) -> KarmaType {
+        self.purpose.clone()
+    }
+
+    pub fn get_planned_duration(&self) -> Option<i64> {
+        self.planned_duration
+    }
+
+    pub fn get_started_at(&self) -> i64 {
+        self.started_at
+    }
+}
+
+impl<'r> FromRow<'r, SqliteRow> for KarmaSession {
+    fn from_row(row: &'r SqliteRow) -> Result<Self, SqlxError> {
+        let id = row.try_get("id")?;
+        let template_id = row.try_get("template_id")?;
+        let planned_duration = row.try_get("planned_duration")?;
+        let started_at = row.try_get("started_at")?;
+
+        let purpose: i32 = row.try_get("purpose")?;
+        let purpose = purpose
+            .try_into()
+            .map_err(|e| SqlxError::Decode(Box::new(e)))?;
+
+        Ok(KarmaSession::with_id(
+            id,
+            template_id,
+            purpose,
+            planned_duration,
+            started_at,
+        ))
+    }
+}
+
+/// What starting an activity produced, the template is new when the name wasn't known
+#[derive(Debug, Clone, Serialize)]
+pub struct StartedSession {
+    pub template: KarmaPoint,
+    pub session: KarmaSession,
+    pub status: KarmaStatus,
+}
+
+#[cfg(test)]
+mod karma_session_tests {
+    use super::*;
+
+    #[test]
+    fn test_session_defaults_come_from_template() {
+        let template = KarmaPoint::with_id(3, KarmaType::Sport, "Morning run".to_string(), 0)
+            .with_default_duration(Some(1800));
+
+        let session = KarmaSession::from_template(&template, 100);
+        assert_eq!(session.get_template_id(), 3);
+        assert_eq!(session.get_purpose(), KarmaType::Sport);
+        assert_eq!(session.get_planned_duration(), Some(1800));
+
+        let session = session
+            .with_purpose(KarmaType::Social)
+            .with_planned_duration(None);
+        assert_eq!(session.get_purpose(), KarmaType::Social);
+        assert_eq!(session.get_planned_duration(), None);
+    }
+}
@@ -0,0 +1,311 @@
+use std::fmt;
+use std::str::FromStr;
+
+use chrono::{
+    DateTime, Datelike, Duration, FixedOffset, NaiveDate, NaiveDateTime, NaiveTime, Offset,
+    TimeZone, Weekday,
+};
+use chrono_tz::Tz;
+use serde::{Serialize, Serializer};
+use thiserror::Error;
+
+pub const TIMEZONE_SETTING: &str = "timezone";
+pub const WEEK_START_SETTING: &str = "week_start";
+
+#[derive(Debug, Error, Serialize)]
+pub enum TimeSettingsError {
+    #[error(
+        "Unknown timezone {0}, expected a name such as Europe/Berlin or an offset such as +02:00"
+    )]
+    Timezone(String),
+
+    #[error("Unknown week day {0}")]
+    WeekStart(String),
+}
+
+/// Where the user is, a named zone follows its daylight saving rules while an
+/// offset stays the same all year
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum UserTimezone {
+    Named(Tz),
+    Offset(FixedOffset),
+}
+
+impl UserTimezone {
+    /// The zone the operating system is set to, UTC when it can't be told
+    pub fn system() -> UserTimezone {
+        iana_time_zone::get_timezone()
+            .ok()
+            .and_then(|name| UserTimezone::parse(&name).ok())
+            .unwrap_or_default()
+    }
+
+    /// Accepts an IANA name, `UTC`/`Z` or an offset as `+HH:MM`, `-HHMM` or `+HH`
+    pub fn parse(value: &str) -> Result<UserTimezone, TimeSettingsError> {
+        let value = value.trim();
+        if let Ok(tz) = Tz::from_str(value) {
+            return Ok(UserTimezone::Named(tz));
+        }
+        if value.eq_ignore_ascii_case("z") {
+            return Ok(UserTimezone::default());
+        }
+
+        let invalid = || TimeSettingsError::Timezone(value.to_string());
+        let sign = match value.chars().next() {
+            Some('+') => 1,
+            Some('-') => -1,
+            _ => return Err(invalid()),
+        };
+        let digits: String = value[1..].chars().filter(|ch| *ch != ':').collect();
+        if !digits.chars().all(|ch| ch.is_ascii_digit()) {
+            return Err(invalid());
+        }
+        let (hours, minutes) = match digits.len() {
+            1 | 2 => (digits.parse::<i32>().map_err(|_| invalid())?, 0),
+            4 => (
+                digits[..2].parse::<i32>().map_err(|_| invalid())?,
+                digits[2..].parse::<i32>().map_err(|_| invalid())?,
+            ),
+            _ => return Err(invalid()),
+        };
+        if minutes >= 60 {
+            return Err(invalid());
+        }
+
+        FixedOffset::east_opt(sign * (hours * 3600 + minutes * 60))
+            .map(UserTimezone::Offset)
+            .ok_or_else(invalid)
+    }
+
+    /// Seconds to add to UTC to get the local time at `timestamp`
+    pub fn offset_at(&self, timestamp: i64) -> i32 {
+        let Some(utc) = DateTime::from_timestamp(timestamp, 0) else {
+            return 0;
+        };
+        match self {
+            UserTimezone::Named(tz) => tz
+                .offset_from_utc_datetime(&utc.naive_utc())
+                .fix()
+                .local_minus_utc(),
+            UserTimezone::Offset(offset) => offset.local_minus_utc(),
+        }
+    }
+
+    pub fn local_datetime(&self, timestamp: i64) -> NaiveDateTime {
+        let utc = DateTime::from_timestamp(timestamp, 0).unwrap_or_default();
+        utc.naive_utc() + Duration::seconds(self.offset_at(timestamp) as i64)
+    }
+
+    /// The unix time of a wall clock time. When clocks go back the first of the two
+    /// moments is used, a time skipped when they go forward is moved past the gap.
+    pub fn timestamp_of(&self, local: NaiveDateTime) -> i64 {
+        match self {
+            UserTimezone::Named(tz) => match tz.from_local_datetime(&local).earliest() {
+                Some(time) => time.timestamp(),
+                None => {
+                    let before_gap = tz
+                        .offset_from_utc_datetime(&(local - Duration::days(1)))
+                        .fix();
+                    (local - Duration::seconds(before_gap.local_minus_utc() as i64))
+                        .and_utc()
+                        .timestamp()
+                }
+            },
+            UserTimezone::Offset(offset) => (local
+                - Duration::seconds(offset.local_minus_utc() as i64))
+            .and_utc()
+            .timestamp(),
+        }
+    }
+}
+
+impl Default for UserTimezone {
+    fn default() -> Self {
+        UserTimezone::Named(Tz::UTC)
+    }
+}
+
+impl fmt::Display for UserTimezone {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            UserTimezone::Named(tz) => write!(f, "{}", tz.name()),
+            UserTimezone::Offset(offset) => write!(f, "{offset}"),
+        }
+    }
+}
+
+impl Serialize for UserTimezone {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+/// How timestamps are turned into the days and weeks the user lives in
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub struct TimeSettings {
+    pub timezone: UserTimezone,
+    pub week_start: Weekday,
+}
+
+impl TimeSettings {
+    /// The system zone and weeks starting on Monday, used until the user picks others
+    pub fn system() -> TimeSettings {
+        TimeSettings {
+            timezone: UserTimezone::system(),
+            ..TimeSettings::default()
+        }
+    }
+
+    pub fn parse_week_start(value: &str) -> Result<Weekday, TimeSettingsError> {
+        Weekday::from_str(value.trim()).map_err(|_| TimeSettingsError::WeekStart(value.to_string()))
+    }
+
+    pub fn local_date(&self, timestamp: i64) -> NaiveDate {
+        self.timezone.local_datetime(timestamp).date()
+    }
+
+    /// When `date` starts locally, a day may last 23 or 25 hours around DST changes
+    pub fn day_start(&self, date: NaiveDate) -> i64 {
+        self.timezone.timestamp_of(date.and_time(NaiveTime::MIN))
+    }
+
+    pub fn next_day_start(&self, date: NaiveDate) -> i64 {
+        self.day_start(date + Duration::days(1))
+    }
+
+    /// `seconds` after local midnight on the wall clock
+    pub fn at_time_of_day(&self, date: NaiveDate, seconds: u32) -> i64 {
+        self.timezone
+            .timestamp_of(date.and_time(NaiveTime::MIN) + Duration::seconds(seconds as i64))
+    }
+
+    /// The first day of the week `date` falls in
+    pub fn week_of(&self, date: NaiveDate) -> NaiveDate {
+        let days_into_week = (date.weekday().num_days_from_monday() + 7
+            - self.week_start.num_days_from_monday())
+            % 7;
+        date - Duration::days(days_into_week as i64)
+    }
+
+    pub fn format(&self, timestamp: i64) -> String {
+        self.timezone
+            .local_datetime(timestamp)
+            .format("%Y-%m-%d %H:%M")
+            .to_string()
+    }
+
+    /// Splits `[start, end)` at local midnights, so each part belongs to one day
+    pub fn split_by_day(&self, start: i64, end: i64) -> Vec<(NaiveDate, i64)> {
+        let mut parts = Vec::new();
+        let mut start = start;
+
+        while start < end {
+            let day = self.local_date(start);
+            let part_end = end.min(self.next_day_start(day));
+            if part_end <= start {
+                break;
+            }
+
+            parts.push((day, part_end - start));
+            start = part_end;
+        }
+
+        parts
+    }
+}
+
+impl Default for TimeSettings {
+    fn default() -> Self {
+        TimeSettings {
+            timezone: UserTimezone::default(),
+            week_start: Weekday::Mon,
+        }
+    }
+}
+
+#[cfg(test)]
+mod local_time_tests {
+    use super::*;
+
+    const HOUR: i64 = 60 * 60;
+
+    fn berlin() -> TimeSettings {
+        TimeSettings {
+            timezone: UserTimezone::parse("Europe/Berlin").unwrap(),
+            week_start: Weekday::Sun,
+        }
+    }
+
+    fn date(value: &str) -> NaiveDate {
+        NaiveDate::parse_from_str(value, "%Y-%m-%d").unwrap()
+    }
+
+    #[test]
+    fn test_parse_timezones() {
+        assert_eq!(UserTimezone::parse("UTC").unwrap().to_string(), "UTC");
+        assert_eq!(
+            UserTimezone::parse("+02:00").unwrap().offset_at(0),
+            2 * 3600
+        );
+        assert_eq!(
+            UserTimezone::parse("-0530").unwrap().offset_at(0),
+            -(5 * 3600 + 30 * 60)
+        );
+        assert!(matches!(
+            UserTimezone::parse("Mars/Olympus"),
+            Err(TimeSettingsError::Timezone(_))
+        ));
+        assert!(UserTimezone::parse("+25:00").is_err());
+    }
+
+    #[test]
+    fn test_days_around_dst_changes() {
+        let settings = berlin();
+
+        // Clocks go forward on 2024-03-31 and back on 2024-10-27
+        let spring = date("2024-03-31");
+        assert_eq!(
+            settings.next_day_start(spring) - settings.day_start(spring),
+            23 * HOUR
+        );
+        let autumn = date("2024-10-27");
+        assert_eq!(
+            settings.next_day_start(autumn) - settings.day_start(autumn),
+            25 * HOUR
+        );
+
+        // 02:30 doesn't exist that morning, it becomes 03:30 summer time
+        assert_eq!(
+            settings.at_time_of_day(spring, 2 * 3600 + 1800),
+            settings.day_start(spring) + 2 * HOUR + 1800
+        );
+    }
+
+    #[test]
+    fn test_split_across_midnight_and_dst() {
+        let settings = berlin();
+        // 2024-03-30 23:00 to 2024-03-31 04:00 on the wall clock, 4 real hours
+        let start = settings.at_time_of_day(date("2024-03-30"), 23 * 3600);
+        let end = settings.at_time_of_day(date("2024-03-31"), 4 * 3600);
+
+        assert_eq!(
+            settings.split_by_day(start, end),
+            vec![(date("2024-03-30"), HOUR), (date("2024-03-31"), 3 * HOUR)]
+        );
+        assert_eq!(settings.format(start), "2024-03-30 23:00");
+    }
+
+    #[test]
+    fn test_week_start() {
+        let settings = berlin();
+        // A Wednesday
+        assert_eq!(settings.week_of(date("2024-01-03")), date("2023-12-31"));
+        assert_eq!(settings.week_of(date("2023-12-31")), date("2023-12-31"));
+        assert_eq!(
+            TimeSettings::default().week_of(date("2024-01-03")),
+            date("2024-01-01")
+        );
+        assert!(TimeSettings::parse_week_start("sunday").is_ok());
+        assert!(TimeSettings::parse_week_start("someday").is_err());
+    }
+}
@@ -1,2 +1,28 @@
+pub mod achievement;
+pub mod balance;
+pub mod deviation;
+pub mod duration;
+pub mod focus;
+pub mod goal;
 pub mod karma;
+pub mod karma_query;
+pub mod karma_resolver;
+pub mod karma_search;
+pub mod karma_session;
+pub mod local_time;
+pub mod password_hasher;
+pub mod password_policy;
+pub mod plan;
+pub mod profile;
+pub mod recurrence;
+pub mod relapse;
+pub mod report;
+pub mod reward;
+pub mod schedule;
+pub mod scoring;
+pub mod sleep;
+pub mod streak;
+pub mod subject;
+pub mod suggestion;
+pub mod totp;
 pub mod user;
@@ -0,0 +1,286 @@
+use argon2::{
+    password_hash::{
+        rand_core::OsRng, Error as PhcError, PasswordHash, PasswordHasher as _,
+        PasswordVerifier as _, SaltString,
+    },
+    Algorithm, Argon2, Params, Version,
+};
+use bcrypt::BcryptError;
+use thiserror::Error;
+
+pub const DEFAULT_BCRYPT_COST: u32 = bcrypt::DEFAULT_COST;
+
+// OWASP recommended minimum for Argon2id
+pub const DEFAULT_ARGON2_MEMORY_KIB: u32 = 19 * 1024;
+pub const DEFAULT_ARGON2_ITERATIONS: u32 = 2;
+pub const DEFAULT_ARGON2_PARALLELISM: u32 = 1;
+
+#[derive(Error, Debug)]
+pub enum PasswordHashError {
+    #[error("Bcrypt failure: {0}")]
+    Bcrypt(#[from] BcryptError),
+
+    #[error("Argon2 failure: {0}")]
+    Argon2(String),
+
+    #[error("Unrecognised password hash format")]
+    UnknownFormat,
+}
+
+impl From<PhcError> for PasswordHashError {
+    fn from(value: PhcError) -> Self {
+        PasswordHashError::Argon2(value.to_string())
+    }
+}
+
+impl From<argon2::Error> for PasswordHashError {
+    fn from(value: argon2::Error) -> Self {
+        PasswordHashError::Argon2(value.to_string())
+    }
+}
+
+/// The algorithm and parameters of a hash, either the ones a hasher produces
+/// or the ones recognised from a stored hash
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HashScheme {
+    Bcrypt {
+        cost: u32,
+    },
+    Argon2id {
+        memory_kib: u32,
+        iterations: u32,
+        parallelism: u32,
+    },
+}
+
+impl HashScheme {
+    /// Recognises the scheme of a stored hash from its format:
+    /// `$2a$`/`$2b$`/`$2x$`/`$2y$` for bcrypt and the PHC `$argon2id$` string for Argon2id
+    pub fn detect(hash: &str) -> Result<HashScheme, PasswordHashError> {
+        if let Some(rest) = ["$2a$", "$2b$", "$2x$", "$2y$"]
+            .iter()
+            .find_map(|prefix| hash.strip_prefix(prefix))
+        {
+            let cost = rest
+                .get(..2)
+                .and_then(|cost| cost.parse::<u32>().ok())
+                .ok_or(PasswordHashError::UnknownFormat)?;
+            return Ok(HashScheme::Bcrypt { cost });
+        }
+
+        if hash.starts_with("$argon2id$") {
+            let parsed = PasswordHash::new(hash)?;
+            let params = Params::try_from(&parsed)?;
+            return Ok(HashScheme::Argon2id {
+                memory_kib: params.m_cost(),
+                iterations: params.t_cost(),
+                parallelism: params.p_cost(),
+            });
+        }
+
+        Err(PasswordHashError::UnknownFormat)
+    }
+
+    /// Whether a hash produced with this scheme is weaker than one produced with `policy`.
+    /// Argon2id is considered stronger than bcrypt, so we never downgrade from it.
+    pub fn is_weaker_than(&self, policy: &HashScheme) -> bool {
+        match (self, policy) {
+            (HashScheme::Bcrypt { cost }, HashScheme::Bcrypt { cost: policy_cost }) => {
+                cost < policy_cost
+            }
+            (HashScheme::Bcrypt { .. }, HashScheme::Argon2id { .. }) => true,
+            (HashScheme::Argon2id { .. }, HashScheme::Bcrypt { .. }) => false,
+            (
+                HashScheme::Argon2id {
+                    memory_kib,
+                    iterations,
+                    parallelism,
+                },
+                HashScheme::Argon2id {
+                    memory_kib: policy_memory,
+                    iterations: policy_iterations,
+                    parallelism: policy_parallelism,
+                },
+            ) => {
+                memory_kib < policy_memory
+                    || iterations < policy_iterations
+                    || parallelism < policy_parallelism
+            }
+        }
+    }
+}
+
+pub trait PasswordHasher: std::fmt::Debug + Send + Sync {
+    /// The scheme every new hash is produced with
+    fn scheme(&self) -> HashScheme;
+
+    fn hash(&self, password: &str) -> Result<String, PasswordHashError>;
+
+    /// Checks a password against a stored hash of any supported format,
+    /// not only the one this hasher produces
+    fn verify(&self, password: &str, hash: &str) -> Result<bool, PasswordHashError> {
+        verify_password(password, hash)
+    }
+
+    /// Whether a stored hash should be replaced by one produced with the current policy
+    fn needs_rehash(&self, hash: &str) -> bool {
+        match HashScheme::detect(hash) {
+            Ok(stored) => stored.is_weaker_than(&self.scheme()),
+            Err(_) => true,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+#[allow(dead_code)]
+pub struct BcryptHasher {
+    cost: u32,
+}
+
+#[allow(dead_code)]
+impl BcryptHasher {
+    pub fn new(cost: u32) -> BcryptHasher {
+        BcryptHasher { cost }
+    }
+}
+
+impl Default for BcryptHasher {
+    fn default() -> Self {
+        BcryptHasher::new(DEFAULT_BCRYPT_COST)
+    }
+}
+
+impl PasswordHasher for BcryptHasher {
+    fn scheme(&self) -> HashScheme {
+        HashScheme::Bcrypt { cost: self.cost }
+    }
+
+    fn hash(&self, password: &str) -> Result<String, PasswordHashError> {
+        Ok(bcrypt::hash(password, self.cost)?)
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Argon2Hasher {
+    memory_kib: u32,
+    iterations: u32,
+    parallelism: u32,
+}
+
+impl Argon2Hasher {
+    pub fn new(memory_kib: u32, iterations: u32, parallelism: u32) -> Argon2Hasher {
+        Argon2Hasher {
+            memory_kib,
+            iterations,
+            parallelism,
+        }
+    }
+}
+
+impl Default for Argon2Hasher {
+    fn default() -> Self {
+        Argon2Hasher::new(
+            DEFAULT_ARGON2_MEMORY_KIB,
+            DEFAULT_ARGON2_ITERATIONS,
+            DEFAULT_ARGON2_PARALLELISM,
+        )
+    }
+}
+
+impl PasswordHasher for Argon2Hasher {
+    fn scheme(&self) -> HashScheme {
+        HashScheme::Argon2id {
+            memory_kib: self.memory_kib,
+            iterations: self.iterations,
+            parallelism: self.parallelism,
+        }
+    }
+
+    fn hash(&self, password: &str) -> Result<String, PasswordHashError> {
+        let params = Params::new(self.memory_kib, self.iterations, self.parallelism, None)?;
+        let argon2 = Argon2::new(Algorithm::Argon2id, Version::V0x13, params);
+        let salt = SaltString::generate(&mut OsRng);
+
+        Ok(argon2
+            .hash_password(password.as_bytes(), &salt)?
+            .to_string())
+    }
+}
+
+/// The hasher used for new passwords when none is configured explicitly
+pub fn default_hasher() -> Box<dyn PasswordHasher> {
+    Box::<Argon2Hasher>::default()
+}
+
+/// Verifies a password against a stored hash, dispatching on the hash format
+pub fn verify_password(password: &str, hash: &str) -> Result<bool, PasswordHashError> {
+    match HashScheme::detect(hash)? {
+        HashScheme::Bcrypt { .. } => Ok(bcrypt::verify(password, hash)?),
+        HashScheme::Argon2id { .. } => {
+            let parsed = PasswordHash::new(hash)?;
+            match Argon2::default().verify_password(password.as_bytes(), &parsed) {
+                Ok(()) => Ok(true),
+                Err(PhcError::Password) => Ok(false),
+                Err(e) => Err(e.into()),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod password_hasher_tests {
+    use super::*;
+
+    // Cheap parameters so the tests stay fast, the policy comparisons don't depend on them
+    fn weak_argon2() -> Argon2Hasher {
+        Argon2Hasher::new(1024, 1, 1)
+    }
+
+    #[test]
+    fn test_detect_bcrypt() {
+        let hash = BcryptHasher::new(4).hash("V1@eflsjdfnsdf").unwrap();
+        assert_eq!(
+            HashScheme::detect(&hash).unwrap(),
+            HashScheme::Bcrypt { cost: 4 }
+        );
+    }
+
+    #[test]
+    fn test_detect_argon2id() {
+        let hash = weak_argon2().hash("V1@eflsjdfnsdf").unwrap();
+        assert_eq!(HashScheme::detect(&hash).unwrap(), weak_argon2().scheme());
+    }
+
+    #[test]
+    fn test_detect_unknown_format() {
+        assert!(matches!(
+            HashScheme::detect("plaintext"),
+            Err(PasswordHashError::UnknownFormat)
+        ));
+    }
+
+    #[test]
+    fn test_verify_any_format() {
+        let bcrypt_hash = BcryptHasher::new(4).hash("V1@eflsjdfnsdf").unwrap();
+        let argon2_hash = weak_argon2().hash("V1@eflsjdfnsdf").unwrap();
+
+        let hasher = weak_argon2();
+        assert!(hasher.verify("V1@eflsjdfnsdf", &bcrypt_hash).unwrap());
+        assert!(hasher.verify("V1@eflsjdfnsdf", &argon2_hash).unwrap());
+        assert!(!hasher.verify("wrong", &bcrypt_hash).unwrap());
+        assert!(!hasher.verify("wrong", &argon2_hash).unwrap());
+    }
+
+    #[test]
+    fn test_needs_rehash() {
+        let bcrypt_hash = BcryptHasher::new(4).hash("V1@eflsjdfnsdf").unwrap();
+        let argon2_hash = weak_argon2().hash("V1@eflsjdfnsdf").unwrap();
+
+        assert!(BcryptHasher::new(5).needs_rehash(&bcrypt_hash));
+        assert!(!BcryptHasher::new(4).needs_rehash(&bcrypt_hash));
+        assert!(weak_argon2().needs_rehash(&bcrypt_hash));
+        assert!(!BcryptHasher::new(12).needs_rehash(&argon2_hash));
+        assert!(Argon2Hasher::new(2048, 1, 1).needs_rehash(&argon2_hash));
+        assert!(!weak_argon2().needs_rehash(&argon2_hash));
+    }
+}
@@ -0,0 +1,368 @@
+use std::collections::HashSet;
+use std::path::Path;
+use std::sync::Arc;
+
+use serde::Serialize;
+use sha1::{Digest, Sha1};
+
+pub const DEFAULT_MIN_PASSWORD_SIZE: usize = 8;
+pub const DEFAULT_SPECIAL_CHARACTERS: &str = "!@#$%^&*().,:; ";
+pub const DEFAULT_MIN_ENTROPY_BITS: f64 = 40.0;
+
+const BUNDLED_BLOCKLIST: &str = include_str!("../../resources/common_passwords.txt");
+
+// Sizes of the character pools used by the entropy estimate
+const LOWERCASE_POOL: f64 = 26.0;
+const UPPERCASE_POOL: f64 = 26.0;
+const DIGIT_POOL: f64 = 10.0;
+const SYMBOL_POOL: f64 = 33.0;
+
+/// A rule the password failed, every failing rule is reported at once
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub enum PasswordRuleViolation {
+    TooShort { min: usize, actual: usize },
+    NoDigit,
+    NoSpecialChar { allowed: String },
+    NoUppercaseLetter,
+    NoLowercaseLetter,
+    TooWeak { min_bits: f64, estimated_bits: f64 },
+    Breached,
+}
+
+impl std::fmt::Display for PasswordRuleViolation {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            PasswordRuleViolation::TooShort { min, .. } => {
+                write!(f, "Password size should be at least {min}")
+            }
+            PasswordRuleViolation::NoDigit => write!(f, "Password should contain at least 1 digit"),
+            PasswordRuleViolation::NoSpecialChar { allowed } => write!(
+                f,
+                "Password should contain at least 1 special char out of \"{allowed}\""
+            ),
+            PasswordRuleViolation::NoUppercaseLetter => {
+                write!(f, "Password should contain at least 1 uppercase letter")
+            }
+            PasswordRuleViolation::NoLowercaseLetter => {
+                write!(f, "Password should contain at least 1 lowercase letter")
+            }
+            PasswordRuleViolation::TooWeak {
+                min_bits,
+                estimated_bits,
+            } => write!(
+                f,
+                "Password is too easy to guess ({estimated_bits:.0} bits, at least {min_bits:.0} required)"
+            ),
+            PasswordRuleViolation::Breached => {
+                write!(f, "Password appears in a list of common or breached passwords")
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+pub enum PasswordStrength {
+    VeryWeak,
+    Weak,
+    Reasonable,
+    Strong,
+    VeryStrong,
+}
+
+impl PasswordStrength {
+    pub fn from_entropy(bits: f64) -> PasswordStrength {
+        match bits {
+            b if b < 28.0 => PasswordStrength::VeryWeak,
+            b if b < 36.0 => PasswordStrength::Weak,
+            b if b < 60.0 => PasswordStrength::Reasonable,
+            b if b < 128.0 => PasswordStrength::Strong,
+            _ => PasswordStrength::VeryStrong,
+        }
+    }
+}
+
+/// The outcome of checking a password against a policy, sent as is to the UI
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct PasswordReport {
+    pub entropy_bits: f64,
+    pub strength: PasswordStrength,
+    pub violations: Vec<PasswordRuleViolation>,
+}
+
+impl PasswordReport {
+    pub fn is_acceptable(&self) -> bool {
+        self.violations.is_empty()
+    }
+}
+
+impl std::fmt::Display for PasswordReport {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let violations: Vec<String> = self.violations.iter().map(|v| v.to_string()).collect();
+        write!(f, "{}", violations.join("; "))
+    }
+}
+
+/// Common and breached passwords. Entries are either plain passwords, compared
+/// case-insensitively, or SHA-1 hashes as found in the offline breach dumps
+/// (`<40 hex chars>` optionally followed by `:<count>`)
+#[derive(Debug, Clone, Default)]
+pub struct PasswordBlocklist {
+    plain: HashSet<String>,
+    sha1: HashSet<String>,
+}
+
+impl PasswordBlocklist {
+    /// The list shipped with the app
+    pub fn bundled() -> PasswordBlocklist {
+        let mut blocklist = PasswordBlocklist::default();
+        blocklist.extend_from_str(BUNDLED_BLOCKLIST);
+        blocklist
+    }
+
+    /// A user supplied list, one entry per line
+    pub fn from_file(path: &Path) -> std::io::Result<PasswordBlocklist> {
+        let mut blocklist = PasswordBlocklist::default();
+        blocklist.extend_from_str(&std::fs::read_to_string(path)?);
+        Ok(blocklist)
+    }
+
+    pub fn extend_from_str(&mut self, content: &str) {
+        for line in content.lines().map(str::trim) {
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+
+            let candidate_hash = line.split(':').next().unwrap_or(line);
+            if candidate_hash.len() == 40 && candidate_hash.chars().all(|c| c.is_ascii_hexdigit()) {
+                self.sha1.insert(candidate_hash.to_uppercase());
+            } else {
+                self.plain.insert(line.to_lowercase());
+            }
+        }
+    }
+
+    pub fn merge(mut self, other: PasswordBlocklist) -> PasswordBlocklist {
+        self.plain.extend(other.plain);
+        self.sha1.extend(other.sha1);
+        self
+    }
+
+    pub fn contains(&self, password: &str) -> bool {
+        if self.plain.contains(&password.to_lowercase()) {
+            return true;
+        }
+
+        if self.sha1.is_empty() {
+            return false;
+        }
+
+        let digest = Sha1::digest(password.as_bytes());
+        let hex: String = digest.iter().map(|byte| format!("{byte:02X}")).collect();
+        self.sha1.contains(&hex)
+    }
+}
+
+/// The rules a new password has to satisfy
+#[derive(Debug, Clone)]
+pub struct PasswordPolicy {
+    pub min_length: usize,
+    pub require_digit: bool,
+    pub require_uppercase: bool,
+    pub require_lowercase: bool,
+    /// When set, the password must contain at least one of these characters
+    pub special_characters: Option<String>,
+    pub min_entropy_bits: f64,
+    pub blocklist: Option<Arc<PasswordBlocklist>>,
+}
+
+impl Default for PasswordPolicy {
+    fn default() -> Self {
+        PasswordPolicy {
+            min_length: DEFAULT_MIN_PASSWORD_SIZE,
+            require_digit: true,
+            require_uppercase: true,
+            require_lowercase: false,
+            special_characters: Some(DEFAULT_SPECIAL_CHARACTERS.to_string()),
+            min_entropy_bits: DEFAULT_MIN_ENTROPY_BITS,
+            blocklist: Some(Arc::new(PasswordBlocklist::bundled())),
+        }
+    }
+}
+
+impl PasswordPolicy {
+    pub fn with_blocklist(mut self, blocklist: PasswordBlocklist) -> PasswordPolicy {
+        self.blocklist = Some(Arc::new(blocklist));
+        self
+    }
+
+    pub fn check(&self, password: &str) -> PasswordReport {
+        let mut violations = Vec::new();
+
+        let length = password.chars().count();
+        if length < self.min_length {
+            violations.push(PasswordRuleViolation::TooShort {
+                min: self.min_length,
+                actual: length,
+            });
+        }
+
+        if self.require_digit && !password.chars().any(|ch| ch.is_ascii_digit()) {
+            violations.push(PasswordRuleViolation::NoDigit);
+        }
+
+        if let Some(allowed) = &self.special_characters {
+            if !password.chars().any(|ch| allowed.contains(ch)) {
+                violations.push(PasswordRuleViolation::NoSpecialChar {
+                    allowed: allowed.clone(),
+                });
+            }
+        }
+
+        if self.require_uppercase && !password.chars().any(char::is_uppercase) {
+            violations.push(PasswordRuleViolation::NoUppercaseLetter);
+        }
+
+        if self.require_lowercase && !password.chars().any(char::is_lowercase) {
+            violations.push(PasswordRuleViolation::NoLowercaseLetter);
+        }
+
+        let breached = self
+            .blocklist
+            .as_ref()
+            .map(|blocklist| blocklist.contains(password))
+            .unwrap_or(false);
+
+        // A listed password is one of the first guesses of any attacker,
+        // whatever its character mix says
+        let entropy_bits = if breached {
+            0.0
+        } else {
+            estimate_entropy(password)
+        };
+
+        if entropy_bits < self.min_entropy_bits && !breached {
+            violations.push(PasswordRuleViolation::TooWeak {
+                min_bits: self.min_entropy_bits,
+                estimated_bits: entropy_bits,
+            });
+        }
+
+        if breached {
+            violations.push(PasswordRuleViolation::Breached);
+        }
+
+        PasswordReport {
+            entropy_bits,
+            strength: PasswordStrength::from_entropy(entropy_bits),
+            violations,
+        }
+    }
+}
+
+/// Estimates the entropy in bits from the size of the character pools the password draws
+/// from. Characters repeating the previous one or continuing a run like `abc` or `321`
+/// barely add to the guessing effort, so they only count for a single bit.
+pub fn estimate_entropy(password: &str) -> f64 {
+    let chars: Vec<char> = password.chars().collect();
+    if chars.is_empty() {
+        return 0.0;
+    }
+
+    let mut pool = 0.0;
+    if chars.iter().any(|ch| ch.is_lowercase()) {
+        pool += LOWERCASE_POOL;
+    }
+    if chars.iter().any(|ch| ch.is_uppercase()) {
+        pool += UPPERCASE_POOL;
+    }
+    if chars.iter().any(|ch| ch.is_ascii_digit()) {
+        pool += DIGIT_POOL;
+    }
+    if chars.iter().any(|ch| !ch.is_alphanumeric()) {
+        pool += SYMBOL_POOL;
+    }
+
+    let bits_per_char = f64::log2(pool);
+    let mut entropy = bits_per_char;
+    for window in chars.windows(2) {
+        let (previous, current) = (window[0] as i64, window[1] as i64);
+        if (current - previous).abs() <= 1 {
+            entropy += 1.0;
+        } else {
+            entropy += bits_per_char;
+        }
+    }
+
+    entropy
+}
+
+#[cfg(test)]
+mod password_policy_tests {
+    use super::*;
+
+    #[test]
+    fn test_reports_every_failing_rule() {
+        let report = PasswordPolicy::default().check("abc");
+
+        assert!(!report.is_acceptable());
+        assert!(report
+            .violations
+            .contains(&PasswordRuleViolation::TooShort { min: 8, actual: 3 }));
+        assert!(report.violations.contains(&PasswordRuleViolation::NoDigit));
+        assert!(report
+            .violations
+            .contains(&PasswordRuleViolation::NoUppercaseLetter));
+        assert!(matches!(
+            report.violations[..],
+            [.., PasswordRuleViolation::TooWeak { .. }]
+        ));
+    }
+
+    #[test]
+    fn test_accepts_strong_password() {
+        let report = PasswordPolicy::default().check("V1@eflsjdfnsdf");
+
+        assert!(report.is_acceptable(), "{report}");
+        assert_eq!(report.strength, PasswordStrength::Strong);
+    }
+
+    #[test]
+    fn test_bundled_blocklist() {
+        let report = PasswordPolicy::default().check("P@ssw0rd!");
+
+        assert_eq!(report.violations, vec![PasswordRuleViolation::Breached]);
+        assert_eq!(report.strength, PasswordStrength::VeryWeak);
+    }
+
+    #[test]
+    fn test_sha1_blocklist_entries() {
+        let mut blocklist = PasswordBlocklist::default();
+        let digest = Sha1::digest("V1@eflsjdfnsdf".as_bytes());
+        let hex: String = digest.iter().map(|byte| format!("{byte:02x}")).collect();
+        blocklist.extend_from_str(&format!("{hex}:42\n"));
+
+        assert!(blocklist.contains("V1@eflsjdfnsdf"));
+        assert!(!blocklist.contains("v1@eflsjdfnsdf"));
+    }
+
+    #[test]
+    fn test_sequences_lower_entropy() {
+        assert!(estimate_entropy("Abcdefgh1!") < estimate_entropy("Aqzmxkwp1!"));
+        assert!(estimate_entropy("Aaaaaaaa1!") < estimate_entropy("Aqzmxkwp1!"));
+    }
+
+    #[test]
+    fn test_configurable_rules() {
+        let policy = PasswordPolicy {
+            min_length: 4,
+            require_digit: false,
+            require_uppercase: false,
+            special_characters: None,
+            min_entropy_bits: 0.0,
+            blocklist: None,
+            ..PasswordPolicy::default()
+        };
+
+        assert!(policy.check("pass").is_acceptable());
+    }
+}
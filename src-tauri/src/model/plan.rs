@@ -0,0 +1,383 @@
+use chrono::NaiveDate;
+use serde::Serialize;
+use thiserror::Error;
+
+use super::deviation::SessionOutcome;
+use super::duration::{session_duration, TrackedSession};
+use super::karma::{KarmaType, State};
+use super::local_time::TimeSettings;
+
+#[derive(Debug, Error, Serialize)]
+pub enum PlanError {
+    #[error("Invalid planned duration of {0} seconds, expected a positive one")]
+    InvalidDuration(i64),
+
+    #[error("{0} has no default duration, give the planned one")]
+    MissingDuration(String),
+
+    #[error("{0} is over, only today and the days after can be planned")]
+    PastDay(NaiveDate),
+}
+
+/// A karma point meant to be done on a planned day
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct PlanEntry {
+    pub id: Option<i32>,
+    pub day: NaiveDate,
+    /// Entries of a day are done and reviewed in this order
+    pub position: u32,
+    pub template_id: i32,
+    /// The name of the template, for display
+    pub name: String,
+    pub purpose: KarmaType,
+    /// Seconds
+    pub duration: i64,
+    /// Local time of day it should start at, in seconds since midnight
+    pub starts_at: Option<u32>,
+    /// The session of the point that carried it out, linked once the day is over
+    pub session_id: Option<i32>,
+    /// The day it was left unfinished on before being carried over
+    pub carried_from: Option<NaiveDate>,
+    /// The day is over, it was reviewed and what was left carried over
+    pub settled: bool,
+}
+
+impl PlanEntry {
+    pub fn validate(&self, today: NaiveDate) -> Result<(), PlanError> {
+        if self.duration <= 0 {
+            return Err(PlanError::InvalidDuration(self.duration));
+        }
+        if self.day < today {
+            return Err(PlanError::PastDay(self.day));
+        }
+        Ok(())
+    }
+}
+
+/// What happened to a planned entry
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub enum PlanOutcome {
+    /// Done as the planned type for at least the planned time
+    Done,
+    /// Done as the planned type, for less than the planned time
+    Partial,
+    /// Done as another type, or its time slot went to something else
+    Replaced,
+    /// The day is over and nothing happened
+    Skipped,
+    /// Not done yet or still running, the day isn't over
+    Pending,
+}
+
+/// A session as the review shows it
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct ReviewedSession {
+    pub session_id: i32,
+    pub name: String,
+    /// What it ended as, what it was started as while still running
+    pub purpose: KarmaType,
+    pub active_seconds: i64,
+    /// Still active or paused
+    pub open: bool,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct EntryReview {
+    pub entry: PlanEntry,
+    pub outcome: PlanOutcome,
+    /// The session of the planned point
+    pub session: Option<ReviewedSession>,
+    /// The unplanned session that took the time slot of an entry nobody did
+    pub replaced_by: Option<ReviewedSession>,
+}
+
+impl EntryReview {
+    /// The planned time still to do as planned, carried over once the day is over
+    pub fn remaining_seconds(&self) -> i64 {
+        match (self.outcome, &self.session) {
+            (PlanOutcome::Done | PlanOutcome::Partial | PlanOutcome::Pending, Some(session)) => {
+                (self.entry.duration - session.active_seconds).max(0)
+            }
+            _ => self.entry.duration,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct PlanReview {
+    pub day: NaiveDate,
+    /// In plan order
+    pub entries: Vec<EntryReview>,
+    /// Sessions of the day no entry accounts for, in start order
+    pub unplanned: Vec<ReviewedSession>,
+    pub planned_seconds: i64,
+    /// Spent on entries as planned, up to their planned duration
+    pub done_seconds: i64,
+    /// Whether the day is over, nothing is pending anymore then
+    pub over: bool,
+}
+
+/// Matches the sessions started on the plan's day to its entries. An entry takes the
+/// session it was linked to, or else the first one left of its point. Entries
+/// with a time slot and no session of their own are then replaced by the first
+/// session left that overlaps the slot. `entries` must all be of `day`.
+pub fn review_plan(
+    day: NaiveDate,
+    entries: &[PlanEntry],
+    sessions: &[TrackedSession],
+    now: i64,
+    settings: &TimeSettings,
+) -> PlanReview {
+    let mut sessions: Vec<&TrackedSession> = sessions
+        .iter()
+        .filter(|tracked| settings.local_date(tracked.session.get_started_at()) == day)
+        .collect();
+    sessions.sort_by_key(|tracked| tracked.session.get_started_at());
+    let mut used = vec![false; sessions.len()];
+    let over = now >= settings.next_day_start(day);
+
+    let matched: Vec<Option<usize>> = entries
+        .iter()
+        .map(|entry| {
+            let index = sessions.iter().enumerate().position(|(index, tracked)| {
+                !used[index]
+                    && match entry.session_id {
+                        Some(session_id) => tracked.session.get_id() == Some(session_id),
+                        None => tracked.session.get_template_id() == entry.template_id,
+                    }
+            })?;
+            used[index] = true;
+            Some(index)
+        })
+        .collect();
+
+    let mut replaced: Vec<Option<usize>> = vec![None; entries.len()];
+    for (position, entry) in entries.iter().enumerate() {
+        let (None, Some(starts_at)) = (matched[position], entry.starts_at) else {
+            continue;
+        };
+        let slot_start = settings.at_time_of_day(day, starts_at);
+        let slot_end = slot_start + entry.duration;
+        replaced[position] = sessions.iter().enumerate().position(|(index, tracked)| {
+            let started_at = tracked.session.get_started_at();
+            !used[index] && started_at < slot_end && session_end(tracked, now) > slot_start
+        });
+        if let Some(index) = replaced[position] {
+            used[index] = true;
+        }
+    }
+
+    let reviewed = |index: usize| reviewed_session(sessions[index], now, settings);
+    let entries: Vec<EntryReview> = entries
+        .iter()
+        .enumerate()
+        .map(|(position, entry)| {
+            let session = matched[position].map(reviewed);
+            let replaced_by = replaced[position].map(reviewed);
+            let outcome = match (&session, &replaced_by) {
+                (Some(session), _) => {
+                    if session.open {
+                        PlanOutcome::Pending
+                    } else if session.purpose != entry.purpose {
+                        PlanOutcome::Replaced
+                    } else if session.active_seconds >= entry.duration {
+                        PlanOutcome::Done
+                    } else {
+                        PlanOutcome::Partial
+                    }
+                }
+                (None, Some(_)) => PlanOutcome::Replaced,
+                (None, None) if over => PlanOutcome::Skipped,
+                (None, None) => PlanOutcome::Pending,
+            };
+
+            EntryReview {
+                entry: entry.clone(),
+                outcome,
+                session,
+                replaced_by,
+            }
+        })
+        .collect();
+
+    let unplanned = (0..sessions.len())
+        .filter(|index| !used[*index])
+        .map(reviewed)
+        .collect();
+
+    PlanReview {
+        day,
+        planned_seconds: entries.iter().map(|review| review.entry.duration).sum(),
+        done_seconds: entries
+            .iter()
+            .filter(|review| review.outcome != PlanOutcome::Replaced)
+            .map(|review| review.entry.duration - review.remaining_seconds())
+            .sum(),
+        entries,
+        unplanned,
+        over,
+    }
+}
+
+fn session_end(tracked: &TrackedSession, now: i64) -> i64 {
+    tracked
+        .statuses
+        .last()
+        .filter(|status| status.state == State::Closed)
+        .map_or(now, |status| status.timestamp)
+}
+
+fn reviewed_session(
+    tracked: &TrackedSession,
+    now: i64,
+    settings: &TimeSettings,
+) -> ReviewedSession {
+    let duration = session_duration(tracked, now);
+
+    ReviewedSession {
+        session_id: tracked.session.get_id().unwrap_or_default(),
+        name: tracked.name.clone(),
+        purpose: SessionOutcome::of(tracked, settings)
+            .map_or(tracked.session.get_purpose(), |outcome| outcome.actual),
+        active_seconds: duration.active_seconds,
+        open: duration.open,
+    }
+}
+
+/// The unfinished entries of a reviewed day, moved to `to` with the time left.
+/// Entries whose session still runs aren't unfinished yet.
+pub fn carry_over(review: &PlanReview, to: NaiveDate) -> Vec<PlanEntry> {
+    review
+        .entries
+        .iter()
+        .filter(|entry| entry.outcome != PlanOutcome::Pending && entry.remaining_seconds() > 0)
+        .map(|entry| PlanEntry {
+            id: None,
+            day: to,
+            position: 0,
+            duration: entry.remaining_seconds(),
+            session_id: None,
+            carried_from: Some(review.day),
+            settled: false,
+            ..entry.entry.clone()
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod plan_tests {
+    use super::*;
+    use crate::model::karma::{KarmaStatus, Polarity};
+    use crate::model::karma_session::KarmaSession;
+    use crate::model::recurrence::parse_date;
+
+    const HOUR: i64 = 60 * 60;
+    // Monday 2024-01-01 00:00 UTC
+    const MONDAY: i64 = 1_704_067_200;
+
+    fn entry(template_id: i32, purpose: KarmaType, hours: i64, at: Option<u32>) -> PlanEntry {
+        PlanEntry {
+            id: Some(template_id),
+            day: parse_date("2024-01-01").unwrap(),
+            position: template_id as u32,
+            template_id,
+            name: format!("Point {template_id}"),
+            purpose,
+            duration: hours * HOUR,
+            starts_at: at,
+            session_id: None,
+            carried_from: None,
+            settled: false,
+        }
+    }
+
+    fn session(
+        id: i32,
+        template_id: i32,
+        purpose: KarmaType,
+        start: i64,
+        hours: i64,
+        closed_with: Option<KarmaType>,
+    ) -> TrackedSession {
+        let mut closed = KarmaStatus::new(id, State::Closed, start + hours * HOUR);
+        closed.closed_with = closed_with;
+        TrackedSession {
+            session: KarmaSession::with_id(id, template_id, purpose, None, start),
+            name: format!("Point {template_id}"),
+            polarity: Polarity::Positive,
+            statuses: vec![KarmaStatus::new(id, State::Active, start), closed],
+        }
+    }
+
+    #[test]
+    fn test_review_and_carry_over() {
+        let settings = TimeSettings::default();
+        let day = parse_date("2024-01-01").unwrap();
+        let entries = vec![
+            entry(1, KarmaType::Work, 2, None),
+            entry(2, KarmaType::Learning, 2, None),
+            entry(3, KarmaType::Sport, 1, Some(18 * 3600)),
+            entry(4, KarmaType::Learning, 1, Some(20 * 3600)),
+            entry(5, KarmaType::Social, 1, None),
+        ];
+        let sessions = vec![
+            session(1, 1, KarmaType::Work, MONDAY + 9 * HOUR, 3, None),
+            session(2, 2, KarmaType::Learning, MONDAY + 13 * HOUR, 1, None),
+            // Point 4 was done as work, point 3's slot went to an unplanned game night
+            session(
+                3,
+                4,
+                KarmaType::Learning,
+                MONDAY + 15 * HOUR,
+                1,
+                Some(KarmaType::Work),
+            ),
+            session(4, 9, KarmaType::Social, MONDAY + 17 * HOUR, 2, None),
+            session(5, 8, KarmaType::Sleeping, MONDAY + 22 * HOUR, 1, None),
+        ];
+
+        // At noon only the first session happened
+        let review = review_plan(day, &entries, &sessions[..1], MONDAY + 12 * HOUR, &settings);
+        assert!(!review.over);
+        assert_eq!(review.entries[0].outcome, PlanOutcome::Done);
+        assert_eq!(review.entries[1].outcome, PlanOutcome::Pending);
+        assert!(review.unplanned.is_empty());
+
+        let review = review_plan(day, &entries, &sessions, MONDAY + 26 * HOUR, &settings);
+        let outcomes: Vec<PlanOutcome> = review.entries.iter().map(|entry| entry.outcome).collect();
+        assert_eq!(
+            outcomes,
+            vec![
+                PlanOutcome::Done,
+                PlanOutcome::Partial,
+                PlanOutcome::Replaced,
+                PlanOutcome::Replaced,
+                PlanOutcome::Skipped
+            ]
+        );
+        assert_eq!(
+            review.entries[2].replaced_by.as_ref().unwrap().session_id,
+            4
+        );
+        assert_eq!(
+            review.entries[3].session.as_ref().unwrap().purpose,
+            KarmaType::Work
+        );
+        assert_eq!(review.unplanned[0].session_id, 5);
+        assert_eq!(review.planned_seconds, 7 * HOUR);
+        assert_eq!(review.done_seconds, 3 * HOUR);
+
+        let tuesday = parse_date("2024-01-02").unwrap();
+        let carried = carry_over(&review, tuesday);
+        assert_eq!(
+            carried
+                .iter()
+                .map(|entry| (entry.template_id, entry.duration))
+                .collect::<Vec<_>>(),
+            vec![(2, HOUR), (3, HOUR), (4, HOUR), (5, HOUR)]
+        );
+        assert!(carried
+            .iter()
+            .all(|entry| entry.day == tuesday && entry.carried_from == Some(day)));
+    }
+}
@@ -0,0 +1,94 @@
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+pub const DEFAULT_PROFILE_NAME: &str = "default";
+// The database the app used before profiles existed, kept for the default profile
+pub const DEFAULT_PROFILE_DB_FILE: &str = "karma_db.sqlite";
+
+const MAX_PROFILE_NAME_SIZE: usize = 50;
+
+#[derive(Debug, Error, Serialize)]
+pub enum ProfileError {
+    #[error("Profile name should be between 1 and {MAX_PROFILE_NAME_SIZE} characters")]
+    NameSize,
+
+    #[error("Profile name may only contain letters, digits, spaces, '-' and '_'")]
+    InvalidName,
+}
+
+/// A separate set of karma data, e.g. "work" and "personal" or one per person
+/// sharing the machine, each backed by its own database file
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Profile {
+    pub name: String,
+    pub db_file: String,
+}
+
+impl Profile {
+    pub fn new(name: &str) -> Result<Profile, ProfileError> {
+        let name = name.trim();
+
+        if name.is_empty() || name.chars().count() > MAX_PROFILE_NAME_SIZE {
+            return Err(ProfileError::NameSize);
+        }
+
+        if !name
+            .chars()
+            .all(|ch| ch.is_alphanumeric() || ch == ' ' || ch == '-' || ch == '_')
+        {
+            return Err(ProfileError::InvalidName);
+        }
+
+        if name == DEFAULT_PROFILE_NAME {
+            return Ok(Profile::default());
+        }
+
+        let slug: String = name
+            .chars()
+            .map(|ch| {
+                if ch.is_ascii_alphanumeric() {
+                    ch.to_ascii_lowercase()
+                } else {
+                    '_'
+                }
+            })
+            .collect();
+
+        Ok(Profile {
+            name: name.to_string(),
+            db_file: format!("karma_{slug}.sqlite"),
+        })
+    }
+}
+
+impl Default for Profile {
+    fn default() -> Self {
+        Profile {
+            name: DEFAULT_PROFILE_NAME.to_string(),
+            db_file: DEFAULT_PROFILE_DB_FILE.to_string(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod profile_tests {
+    use super::*;
+
+    #[test]
+    fn test_profile_db_file() {
+        let profile = Profile::new(" Work stuff ").unwrap();
+        assert_eq!(profile.name, "Work stuff");
+        assert_eq!(profile.db_file, "karma_work_stuff.sqlite");
+
+        assert_eq!(Profile::new("default").unwrap(), Profile::default());
+    }
+
+    #[test]
+    fn test_invalid_profile_names() {
+        assert!(matches!(Profile::new("  "), Err(ProfileError::NameSize)));
+        assert!(matches!(
+            Profile::new("../etc"),
+            Err(ProfileError::InvalidName)
+        ));
+    }
+}
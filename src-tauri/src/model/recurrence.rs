@@ -0,0 +1,427 @@
+use std::collections::BTreeSet;
+
+use chrono::{Datelike, Duration, Months, NaiveDate, NaiveTime, Timelike, Weekday};
+use serde::Serialize;
+use thiserror::Error;
+
+#[derive(Debug, Error, Serialize)]
+pub enum RecurrenceError {
+    #[error("Invalid recurrence rule: {0}")]
+    Rule(String),
+
+    #[error("Invalid date {0}, expected YYYY-MM-DD")]
+    Date(String),
+
+    #[error("Invalid time of day {0}, expected HH:MM")]
+    TimeOfDay(String),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub enum Frequency {
+    Daily,
+    Weekly,
+    Monthly,
+}
+
+/// The repeating part of a schedule, a subset of the iCalendar RRULE:
+/// `FREQ=DAILY|WEEKLY|MONTHLY;INTERVAL=n;BYDAY=MO,WE;BYMONTHDAY=d;UNTIL=YYYYMMDD;COUNT=n`
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct RecurrenceRule {
+    pub frequency: Frequency,
+    /// Every how many days, weeks or months
+    pub interval: u32,
+    /// Weekly rules only, the weekday of the start date when empty
+    pub weekdays: Vec<Weekday>,
+    /// Monthly rules only, the day of the start date when none. Months without
+    /// that day are skipped, as iCalendar does.
+    pub month_day: Option<u32>,
+    /// Last possible date, inclusive
+    pub until: Option<NaiveDate>,
+    /// Number of occurrences from the start date, exceptions included
+    pub count: Option<u32>,
+}
+
+impl RecurrenceRule {
+    pub fn parse(rule: &str) -> Result<RecurrenceRule, RecurrenceError> {
+        let invalid = |reason: &str| RecurrenceError::Rule(format!("{reason} in {rule}"));
+        let body = rule.trim();
+        let body = body
+            .strip_prefix("RRULE:")
+            .or_else(|| body.strip_prefix("rrule:"))
+            .unwrap_or(body);
+
+        let mut frequency = None;
+        let mut interval = 1;
+        let mut weekdays = Vec::new();
+        let mut month_day = None;
+        let mut until = None;
+        let mut count = None;
+
+        for part in body.split(';').filter(|part| !part.trim().is_empty()) {
+            let (key, value) = part
+                .split_once('=')
+                .ok_or_else(|| invalid(&format!("missing value for {part}")))?;
+            let value = value.trim();
+
+            match key.trim().to_uppercase().as_str() {
+                "FREQ" => {
+                    frequency = Some(match value.to_uppercase().as_str() {
+                        "DAILY" => Frequency::Daily,
+                        "WEEKLY" => Frequency::Weekly,
+                        "MONTHLY" => Frequency::Monthly,
+                        other => return Err(invalid(&format!("unsupported frequency {other}"))),
+                    })
+                }
+                "INTERVAL" => {
+                    interval = value
+                        .parse()
+                        .ok()
+                        .filter(|interval| *interval > 0)
+                        .ok_or_else(|| invalid("the interval must be a positive number"))?
+                }
+                "BYDAY" => {
+                    for day in value.split(',') {
+                        let weekday = parse_weekday(day)
+                            .ok_or_else(|| invalid(&format!("unknown weekday {day}")))?;
+                        if !weekdays.contains(&weekday) {
+                            weekdays.push(weekday);
+                        }
+                    }
+                    weekdays.sort_by_key(|weekday| weekday.num_days_from_monday());
+                }
+                "BYMONTHDAY" => {
+                    month_day = Some(
+                        value
+                            .parse()
+                            .ok()
+                            .filter(|day| (1..=31).contains(day))
+                            .ok_or_else(|| invalid("the month day must be between 1 and 31"))?,
+                    )
+                }
+                "UNTIL" => until = Some(parse_date(value)?),
+                "COUNT" => {
+                    count = Some(
+                        value
+                            .parse()
+                            .ok()
+                            .filter(|count| *count > 0)
+                            .ok_or_else(|| invalid("the count must be a positive number"))?,
+                    )
+                }
+                other => return Err(invalid(&format!("unsupported part {other}"))),
+            }
+        }
+
+        let frequency = frequency.ok_or_else(|| invalid("missing FREQ"))?;
+        if !weekdays.is_empty() && frequency != Frequency::Weekly {
+            return Err(invalid("BYDAY is only supported by weekly rules"));
+        }
+        if month_day.is_some() && frequency != Frequency::Monthly {
+            return Err(invalid("BYMONTHDAY is only supported by monthly rules"));
+        }
+        if until.is_some() && count.is_some() {
+            return Err(invalid("UNTIL and COUNT can't be combined"));
+        }
+
+        Ok(RecurrenceRule {
+            frequency,
+            interval,
+            weekdays,
+            month_day,
+            until,
+            count,
+        })
+    }
+}
+
+impl std::fmt::Display for RecurrenceRule {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let frequency = match self.frequency {
+            Frequency::Daily => "DAILY",
+            Frequency::Weekly => "WEEKLY",
+            Frequency::Monthly => "MONTHLY",
+        };
+        write!(f, "FREQ={frequency}")?;
+
+        if self.interval != 1 {
+            write!(f, ";INTERVAL={}", self.interval)?;
+        }
+        if !self.weekdays.is_empty() {
+            let days: Vec<&str> = self.weekdays.iter().map(|day| weekday_code(*day)).collect();
+            write!(f, ";BYDAY={}", days.join(","))?;
+        }
+        if let Some(month_day) = self.month_day {
+            write!(f, ";BYMONTHDAY={month_day}")?;
+        }
+        if let Some(until) = self.until {
+            write!(f, ";UNTIL={}", until.format("%Y%m%d"))?;
+        }
+        if let Some(count) = self.count {
+            write!(f, ";COUNT={count}")?;
+        }
+        Ok(())
+    }
+}
+
+/// A rule anchored to a start date, with the dates skipped on purpose
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Recurrence {
+    pub rule: RecurrenceRule,
+    pub starts_on: NaiveDate,
+    /// Seconds after midnight the occurrences are due, midnight when none
+    pub start_time: Option<u32>,
+    pub exceptions: BTreeSet<NaiveDate>,
+}
+
+impl Recurrence {
+    /// The dates the rule falls on between `from` and `to`, both inclusive, in order
+    pub fn occurrences(&self, from: NaiveDate, to: NaiveDate) -> Vec<NaiveDate> {
+        let mut dates = Vec::new();
+        let last = match self.rule.until {
+            Some(until) => to.min(until),
+            None => to,
+        };
+        if last < self.starts_on || last < from {
+            return dates;
+        }
+
+        // With a count every occurrence since the start matters,
+        // otherwise the periods before `from` can be skipped
+        let first_period = match self.rule.count {
+            Some(_) => 0,
+            None => self.period_containing(from.max(self.starts_on)),
+        };
+
+        let mut generated = 0;
+        for period in first_period.. {
+            let Some(period_start) = self.period_start(period) else {
+                break;
+            };
+            if period_start > last {
+                break;
+            }
+
+            for date in self.dates_in_period(period_start) {
+                if date < self.starts_on {
+                    continue;
+                }
+                if date > last {
+                    return dates;
+                }
+                if self.rule.count.is_some_and(|count| generated >= count) {
+                    return dates;
+                }
+                generated += 1;
+
+                if date >= from && !self.exceptions.contains(&date) {
+                    dates.push(date);
+                }
+            }
+        }
+
+        dates
+    }
+
+    fn first_week_start(&self) -> NaiveDate {
+        self.starts_on - Duration::days(self.starts_on.weekday().num_days_from_monday() as i64)
+    }
+
+    fn period_containing(&self, date: NaiveDate) -> u32 {
+        let interval = self.rule.interval;
+        match self.rule.frequency {
+            Frequency::Daily => ((date - self.starts_on).num_days() as u32) / interval,
+            Frequency::Weekly => {
+                ((date - self.first_week_start()).num_days() as u32) / (7 * interval)
+            }
+            Frequency::Monthly => {
+                let months = (date.year() - self.starts_on.year()) * 12 + date.month() as i32
+                    - self.starts_on.month() as i32;
+                months.max(0) as u32 / interval
+            }
+        }
+    }
+
+    /// The first day of the nth period, a day, week or month depending on the frequency
+    fn period_start(&self, period: u32) -> Option<NaiveDate> {
+        let steps = period.checked_mul(self.rule.interval)?;
+        match self.rule.frequency {
+            Frequency::Daily => self
+                .starts_on
+                .checked_add_signed(Duration::days(steps as i64)),
+            Frequency::Weekly => self
+                .first_week_start()
+                .checked_add_signed(Duration::weeks(steps as i64)),
+            Frequency::Monthly => self
+                .starts_on
+                .with_day(1)?
+                .checked_add_months(Months::new(steps)),
+        }
+    }
+
+    fn dates_in_period(&self, period_start: NaiveDate) -> Vec<NaiveDate> {
+        match self.rule.frequency {
+            Frequency::Daily => vec![period_start],
+            Frequency::Weekly => {
+                let weekdays = if self.rule.weekdays.is_empty() {
+                    vec![self.starts_on.weekday()]
+                } else {
+                    self.rule.weekdays.clone()
+                };
+                weekdays
+                    .iter()
+                    .map(|weekday| {
+                        period_start + Duration::days(weekday.num_days_from_monday() as i64)
+                    })
+                    .collect()
+            }
+            Frequency::Monthly => {
+                let day = self.rule.month_day.unwrap_or(self.starts_on.day());
+                period_start.with_day(day).into_iter().collect()
+            }
+        }
+    }
+}
+
+pub fn parse_date(value: &str) -> Result<NaiveDate, RecurrenceError> {
+    NaiveDate::parse_from_str(value, "%Y-%m-%d")
+        .or_else(|_| NaiveDate::parse_from_str(value, "%Y%m%d"))
+        .map_err(|_| RecurrenceError::Date(value.to_string()))
+}
+
+/// Parses `HH:MM` into seconds after midnight
+pub fn parse_time_of_day(value: &str) -> Result<u32, RecurrenceError> {
+    NaiveTime::parse_from_str(value, "%H:%M")
+        .map(|time| time.num_seconds_from_midnight())
+        .map_err(|_| RecurrenceError::TimeOfDay(value.to_string()))
+}
+
+/// Seconds after midnight as `HH:MM`
+pub fn format_time_of_day(seconds: u32) -> String {
+    format!("{:02}:{:02}", seconds / 3600, seconds % 3600 / 60)
+}
+
+/// A weekday by its two letter iCalendar code such as MO
+pub fn parse_weekday(code: &str) -> Option<Weekday> {
+    match code.trim().to_uppercase().as_str() {
+        "MO" => Some(Weekday::Mon),
+        "TU" => Some(Weekday::Tue),
+        "WE" => Some(Weekday::Wed),
+        "TH" => Some(Weekday::Thu),
+        "FR" => Some(Weekday::Fri),
+        "SA" => Some(Weekday::Sat),
+        "SU" => Some(Weekday::Sun),
+        _ => None,
+    }
+}
+
+pub fn weekday_code(weekday: Weekday) -> &'static str {
+    match weekday {
+        Weekday::Mon => "MO",
+        Weekday::Tue => "TU",
+        Weekday::Wed => "WE",
+        Weekday::Thu => "TH",
+        Weekday::Fri => "FR",
+        Weekday::Sat => "SA",
+        Weekday::Sun => "SU",
+    }
+}
+
+#[cfg(test)]
+mod recurrence_tests {
+    use super::*;
+
+    fn date(value: &str) -> NaiveDate {
+        parse_date(value).unwrap()
+    }
+
+    fn recurrence(rule: &str, starts_on: &str) -> Recurrence {
+        Recurrence {
+            rule: RecurrenceRule::parse(rule).unwrap(),
+            starts_on: date(starts_on),
+            start_time: None,
+            exceptions: BTreeSet::new(),
+        }
+    }
+
+    fn formatted(dates: Vec<NaiveDate>) -> Vec<String> {
+        dates.iter().map(|date| date.to_string()).collect()
+    }
+
+    #[test]
+    fn test_parse_round_trip() {
+        let rule = RecurrenceRule::parse("RRULE:freq=weekly;interval=2;byday=FR,MO;until=20241231")
+            .unwrap();
+
+        assert_eq!(rule.weekdays, vec![Weekday::Mon, Weekday::Fri]);
+        assert_eq!(
+            rule.to_string(),
+            "FREQ=WEEKLY;INTERVAL=2;BYDAY=MO,FR;UNTIL=20241231"
+        );
+        assert_eq!(RecurrenceRule::parse(&rule.to_string()).unwrap(), rule);
+    }
+
+    #[test]
+    fn test_parse_rejects_invalid_rules() {
+        for rule in [
+            "INTERVAL=2",
+            "FREQ=YEARLY",
+            "FREQ=DAILY;BYDAY=MO",
+            "FREQ=WEEKLY;BYMONTHDAY=3",
+            "FREQ=DAILY;COUNT=3;UNTIL=20240101",
+            "FREQ=DAILY;INTERVAL=0",
+        ] {
+            assert!(RecurrenceRule::parse(rule).is_err(), "{rule}");
+        }
+    }
+
+    #[test]
+    fn test_daily_with_interval_and_exceptions() {
+        let mut every_other_day = recurrence("FREQ=DAILY;INTERVAL=2", "2024-01-01");
+        every_other_day.exceptions.insert(date("2024-01-05"));
+
+        assert_eq!(
+            formatted(every_other_day.occurrences(date("2024-01-02"), date("2024-01-09"))),
+            vec!["2024-01-03", "2024-01-07", "2024-01-09"]
+        );
+    }
+
+    #[test]
+    fn test_weekly_on_weekdays() {
+        // 2024-01-03 is a Wednesday
+        let gym = recurrence("FREQ=WEEKLY;BYDAY=MO,WE,FR", "2024-01-03");
+
+        assert_eq!(
+            formatted(gym.occurrences(date("2024-01-01"), date("2024-01-10"))),
+            vec!["2024-01-03", "2024-01-05", "2024-01-08", "2024-01-10"]
+        );
+    }
+
+    #[test]
+    fn test_monthly_skips_short_months() {
+        let end_of_month = recurrence("FREQ=MONTHLY;BYMONTHDAY=31", "2024-01-31");
+
+        assert_eq!(
+            formatted(end_of_month.occurrences(date("2024-01-01"), date("2024-05-31"))),
+            vec!["2024-01-31", "2024-03-31", "2024-05-31"]
+        );
+    }
+
+    #[test]
+    fn test_until_and_count() {
+        let until = recurrence("FREQ=DAILY;UNTIL=2024-01-03", "2024-01-01");
+        assert_eq!(
+            until
+                .occurrences(date("2024-01-01"), date("2024-12-31"))
+                .len(),
+            3
+        );
+
+        // Exceptions still use up the count
+        let mut count = recurrence("FREQ=WEEKLY;COUNT=3", "2024-01-01");
+        count.exceptions.insert(date("2024-01-08"));
+        assert_eq!(
+            formatted(count.occurrences(date("2024-01-05"), date("2024-12-31"))),
+            vec!["2024-01-15"]
+        );
+    }
+}
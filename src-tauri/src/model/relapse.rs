@@ -0,0 +1,236 @@
+use serde::Serialize;
+
+use chrono::NaiveDate;
+
+use super::duration::TrackedSession;
+use super::karma::KarmaPoint;
+use super::local_time::TimeSettings;
+use super::report::ReportPeriod;
+use super::scoring::{award, ScoringRules};
+
+/// How many periods the relapse history goes back, the current one included
+pub const RELAPSE_HISTORY_PERIODS: usize = 8;
+
+/// Relapses that started in one period
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct RelapseCount {
+    pub first_day: NaiveDate,
+    pub last_day: NaiveDate,
+    pub relapses: u32,
+}
+
+/// How often relapses happen lately, the recent half of the finished periods
+/// against the older half
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub enum RelapseTrend {
+    Improving,
+    Steady,
+    Worsening,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct HabitRelapses {
+    pub template_id: i32,
+    pub name: String,
+    pub relapses: u32,
+    pub last_relapse: Option<i64>,
+    /// Since the last relapse, since the habit was created without any
+    pub clean_seconds: i64,
+    /// The longest time without relapse, the current stretch included
+    pub longest_clean_seconds: i64,
+    /// Oldest first, ending with the current period, never before the habit was
+    /// created or first relapsed into
+    pub history: Vec<RelapseCount>,
+    pub trend: RelapseTrend,
+    /// What the relapses still weigh on the points balance today
+    pub points: f64,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct RelapseReport {
+    pub period: ReportPeriod,
+    /// In name order
+    pub habits: Vec<HabitRelapses>,
+    pub points: f64,
+}
+
+/// The relapses into `habit`, `sessions` may hold the ones of other habits
+pub fn habit_relapses(
+    habit: &KarmaPoint,
+    sessions: &[TrackedSession],
+    period: ReportPeriod,
+    rules: &ScoringRules,
+    now: i64,
+    settings: &TimeSettings,
+) -> HabitRelapses {
+    let template_id = habit.get_id().unwrap_or_default();
+    let relapses: Vec<&TrackedSession> = sessions
+        .iter()
+        .filter(|tracked| tracked.session.get_template_id() == template_id)
+        .filter(|tracked| tracked.session.get_started_at() <= now)
+        .collect();
+    let mut times: Vec<i64> = relapses
+        .iter()
+        .map(|tracked| tracked.session.get_started_at())
+        .collect();
+    times.sort_unstable();
+
+    // Sessions may have been tracked before it became a habit to avoid
+    let since = times.first().map_or(habit.get_created_at(), |first| {
+        habit.get_created_at().min(*first)
+    });
+    let last_relapse = times.last().copied();
+    let clean_seconds = (now - last_relapse.unwrap_or(since)).max(0);
+    let mut longest_clean_seconds = clean_seconds;
+    let mut previous = since;
+    for time in &times {
+        longest_clean_seconds = longest_clean_seconds.max(time - previous);
+        previous = *time;
+    }
+
+    let today = settings.local_date(now);
+    let created_on = settings.local_date(since);
+    let mut history = Vec::new();
+    let (mut first_day, mut last_day) = period.bounds(today, settings);
+    while history.len() < RELAPSE_HISTORY_PERIODS && last_day >= created_on {
+        let relapses = times
+            .iter()
+            .map(|time| settings.local_date(*time))
+            .filter(|day| *day >= first_day && *day <= last_day)
+            .count() as u32;
+        history.push(RelapseCount {
+            first_day,
+            last_day,
+            relapses,
+        });
+        (first_day, last_day) = period.bounds(period.previous(first_day), settings);
+    }
+    history.reverse();
+
+    let points = relapses
+        .iter()
+        .filter_map(|tracked| award(tracked, rules, now, settings))
+        .map(|award| award.points * rules.remaining((today - award.day).num_days()))
+        .fold(0.0, |points, award| points + award);
+
+    HabitRelapses {
+        template_id,
+        name: habit.get_name(),
+        relapses: times.len() as u32,
+        last_relapse,
+        clean_seconds,
+        longest_clean_seconds,
+        trend: relapse_trend(&history),
+        history,
+        points,
+    }
+}
+
+fn relapse_trend(history: &[RelapseCount]) -> RelapseTrend {
+    // The current period isn't over, it would always look better
+    let finished = &history[..history.len().saturating_sub(1)];
+    let (older, recent) = finished.split_at(finished.len() / 2);
+    if older.is_empty() {
+        return RelapseTrend::Steady;
+    }
+
+    let average = |counts: &[RelapseCount]| {
+        counts.iter().map(|count| count.relapses).sum::<u32>() as f64 / counts.len() as f64
+    };
+    let (older, recent) = (average(older), average(recent));
+    if recent < older {
+        RelapseTrend::Improving
+    } else if recent > older {
+        RelapseTrend::Worsening
+    } else {
+        RelapseTrend::Steady
+    }
+}
+
+/// Every habit to avoid of `habits` with its relapses among `sessions`
+pub fn relapse_report(
+    habits: &[KarmaPoint],
+    sessions: &[TrackedSession],
+    period: ReportPeriod,
+    rules: &ScoringRules,
+    now: i64,
+    settings: &TimeSettings,
+) -> RelapseReport {
+    let mut habits: Vec<HabitRelapses> = habits
+        .iter()
+        .map(|habit| habit_relapses(habit, sessions, period, rules, now, settings))
+        .collect();
+    habits.sort_by(|a, b| a.name.cmp(&b.name));
+
+    RelapseReport {
+        period,
+        points: habits
+            .iter()
+            .fold(0.0, |points, habit| points + habit.points),
+        habits,
+    }
+}
+
+#[cfg(test)]
+mod relapse_tests {
+    use super::*;
+    use crate::model::karma::{KarmaStatus, KarmaType, Polarity, State};
+    use crate::model::karma_session::KarmaSession;
+
+    const HOUR: i64 = 60 * 60;
+    const DAY: i64 = 24 * HOUR;
+    // Monday 2024-01-01 00:00 UTC
+    const MONDAY: i64 = 1_704_067_200;
+
+    fn relapse(id: i32, at: i64) -> TrackedSession {
+        TrackedSession {
+            session: KarmaSession::with_id(id, 1, KarmaType::Social, None, at),
+            name: "Doomscrolling".to_string(),
+            polarity: Polarity::Negative,
+            statuses: vec![
+                KarmaStatus::new(id, State::Active, at),
+                KarmaStatus::new(id, State::Closed, at),
+            ],
+        }
+    }
+
+    #[test]
+    fn test_relapses_over_the_weeks() {
+        let settings = TimeSettings::default();
+        let rules = ScoringRules::parse("relapse=2, decay=0").unwrap();
+        let habit = KarmaPoint::with_id(1, KarmaType::Social, "Doomscrolling".to_string(), MONDAY)
+            .with_polarity(Polarity::Negative);
+        // Three relapses in the first week, one in each of the next two
+        let sessions = vec![
+            relapse(1, MONDAY + HOUR),
+            relapse(2, MONDAY + DAY),
+            relapse(3, MONDAY + 2 * DAY),
+            relapse(4, MONDAY + 8 * DAY),
+            relapse(5, MONDAY + 17 * DAY),
+        ];
+        let now = MONDAY + 22 * DAY;
+
+        let habit = habit_relapses(
+            &habit,
+            &sessions,
+            ReportPeriod::Week,
+            &rules,
+            now,
+            &settings,
+        );
+        assert_eq!(habit.relapses, 5);
+        assert_eq!(habit.last_relapse, Some(MONDAY + 17 * DAY));
+        assert_eq!(habit.clean_seconds, 5 * DAY);
+        assert_eq!(habit.longest_clean_seconds, 9 * DAY);
+        assert_eq!(
+            habit
+                .history
+                .iter()
+                .map(|count| count.relapses)
+                .collect::<Vec<_>>(),
+            vec![3, 1, 1, 0]
+        );
+        assert_eq!(habit.trend, RelapseTrend::Improving);
+        assert_eq!(habit.points, -10.0);
+    }
+}
@@ -0,0 +1,717 @@
+use std::collections::BTreeMap;
+use std::fmt::Write;
+
+use chrono::{Datelike, Duration, Months, NaiveDate};
+use serde::Serialize;
+use thiserror::Error;
+
+use super::duration::{duration_totals, TrackedSession};
+use super::karma::{KarmaType, Polarity};
+use super::local_time::TimeSettings;
+use super::schedule::{KarmaOccurrence, OccurrenceStatus};
+
+/// How many karma points the report lists as the most frequent
+pub const TOP_POINTS: usize = 5;
+
+#[derive(Debug, Error, Serialize)]
+pub enum ReportError {
+    #[error("Unknown report period {0}, expected week or month")]
+    UnknownPeriod(String),
+
+    #[error("Unknown report format {0}, expected markdown or text")]
+    UnknownFormat(String),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub enum ReportPeriod {
+    Week,
+    Month,
+}
+
+impl ReportPeriod {
+    /// The first and last day of the period `date` falls in
+    pub fn bounds(&self, date: NaiveDate, settings: &TimeSettings) -> (NaiveDate, NaiveDate) {
+        let first = match self {
+            ReportPeriod::Week => settings.week_of(date),
+            ReportPeriod::Month => date.with_day(1).unwrap_or(date),
+        };
+        (first, self.next(first) - Duration::days(1))
+    }
+
+    pub fn previous(&self, first: NaiveDate) -> NaiveDate {
+        match self {
+            ReportPeriod::Week => first - Duration::days(7),
+            ReportPeriod::Month => first - Months::new(1),
+        }
+    }
+
+    fn next(&self, first: NaiveDate) -> NaiveDate {
+        match self {
+            ReportPeriod::Week => first + Duration::days(7),
+            ReportPeriod::Month => first + Months::new(1),
+        }
+    }
+
+    fn adjective(&self) -> &'static str {
+        match self {
+            ReportPeriod::Week => "Weekly",
+            ReportPeriod::Month => "Monthly",
+        }
+    }
+
+    pub fn noun(&self) -> &'static str {
+        match self {
+            ReportPeriod::Week => "week",
+            ReportPeriod::Month => "month",
+        }
+    }
+}
+
+impl TryFrom<&str> for ReportPeriod {
+    type Error = ReportError;
+
+    fn try_from(value: &str) -> Result<Self, Self::Error> {
+        match value.to_lowercase().as_str() {
+            "week" | "weekly" => Ok(ReportPeriod::Week),
+            "month" | "monthly" => Ok(ReportPeriod::Month),
+            other => Err(ReportError::UnknownPeriod(other.to_string())),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ReportFormat {
+    Markdown,
+    Text,
+}
+
+impl TryFrom<&str> for ReportFormat {
+    type Error = ReportError;
+
+    fn try_from(value: &str) -> Result<Self, Self::Error> {
+        match value.to_lowercase().as_str() {
+            "markdown" | "md" => Ok(ReportFormat::Markdown),
+            "text" | "txt" => Ok(ReportFormat::Text),
+            other => Err(ReportError::UnknownFormat(other.to_string())),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct TypeStats {
+    pub purpose: KarmaType,
+    pub seconds: i64,
+    /// Sessions started in the period
+    pub sessions: u32,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct PointStats {
+    pub template_id: i32,
+    pub name: String,
+    pub sessions: u32,
+    pub seconds: i64,
+}
+
+/// Scheduled occurrences due in the period, the planned ones are still pending
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct CompletionStats {
+    pub done: u32,
+    pub missed: u32,
+    pub skipped: u32,
+    pub planned: u32,
+}
+
+impl CompletionStats {
+    /// Done out of the settled occurrences, none when nothing was settled
+    pub fn rate(&self) -> Option<f64> {
+        let settled = self.done + self.missed + self.skipped;
+        (settled > 0).then(|| self.done as f64 / settled as f64)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct PeriodStats {
+    pub first_day: NaiveDate,
+    pub last_day: NaiveDate,
+    pub total_seconds: i64,
+    pub sessions: u32,
+    /// In type order, only the types with time or sessions
+    pub per_type: Vec<TypeStats>,
+    /// The most frequent first, at most `TOP_POINTS`, habits to avoid left out
+    pub top_points: Vec<PointStats>,
+    /// The habits to avoid relapsed into, the most relapses first
+    pub relapses: Vec<PointStats>,
+    pub completion: CompletionStats,
+    pub completion_rate: Option<f64>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct TypeChange {
+    pub purpose: KarmaType,
+    pub seconds: i64,
+    pub sessions: i64,
+    /// Relative to the previous period, none when only this one had time
+    pub percent: Option<f64>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct ReportChanges {
+    pub seconds: i64,
+    pub sessions: i64,
+    pub percent: Option<f64>,
+    pub per_type: Vec<TypeChange>,
+    /// In percentage points
+    pub completion_rate: Option<f64>,
+    pub relapses: i64,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct KarmaReport {
+    pub period: ReportPeriod,
+    pub current: PeriodStats,
+    pub previous: PeriodStats,
+    pub changes: ReportChanges,
+}
+
+/// Time is clipped to the period, sessions count in the period they started in, relapses are
+/// kept apart from the totals
+pub fn period_stats(
+    first_day: NaiveDate,
+    last_day: NaiveDate,
+    sessions: &[TrackedSession],
+    occurrences: &[KarmaOccurrence],
+    now: i64,
+    settings: &TimeSettings,
+) -> PeriodStats {
+    let from = settings.day_start(first_day);
+    let to = settings.next_day_start(last_day);
+    let (avoided_sessions, kept_sessions): (Vec<TrackedSession>, Vec<TrackedSession>) = sessions
+        .iter()
+        .cloned()
+        .partition(|tracked| tracked.polarity == Polarity::Negative);
+    let totals = duration_totals(&kept_sessions, from, to, now, settings);
+    let relapse_totals = duration_totals(&avoided_sessions, from, to, now, settings);
+
+    let mut per_type: BTreeMap<KarmaType, TypeStats> = totals
+        .per_category
+        .iter()
+        .map(|category| {
+            let stats = TypeStats {
+                purpose: category.purpose.clone(),
+                seconds: category.seconds,
+                sessions: 0,
+            };
+            (category.purpose.clone(), stats)
+        })
+        .collect();
+    let avoided: Vec<i32> = avoided_sessions
+        .iter()
+        .map(|tracked| tracked.session.get_template_id())
+        .collect();
+    let mut per_point: BTreeMap<i32, PointStats> = totals
+        .per_point
+        .iter()
+        .chain(&relapse_totals.per_point)
+        .map(|point| {
+            let stats = PointStats {
+                template_id: point.template_id,
+                name: point.name.clone(),
+                sessions: 0,
+                seconds: point.seconds,
+            };
+            (point.template_id, stats)
+        })
+        .collect();
+
+    let mut session_count = 0;
+    for tracked in sessions {
+        let started_at = tracked.session.get_started_at();
+        if started_at < from || started_at >= to {
+            continue;
+        }
+        if tracked.polarity != Polarity::Negative {
+            session_count += 1;
+
+            let purpose = tracked.session.get_purpose();
+            per_type
+                .entry(purpose.clone())
+                .or_insert_with(|| TypeStats {
+                    purpose,
+                    seconds: 0,
+                    sessions: 0,
+                })
+                .sessions += 1;
+        }
+
+        let template_id = tracked.session.get_template_id();
+        per_point
+            .entry(template_id)
+            .or_insert_with(|| PointStats {
+                template_id,
+                name: tracked.name.clone(),
+                sessions: 0,
+                seconds: 0,
+            })
+            .sessions += 1;
+    }
+
+    let (mut relapses, mut top_points): (Vec<PointStats>, Vec<PointStats>) = per_point
+        .into_values()
+        .partition(|point| avoided.contains(&point.template_id));
+    for points in [&mut top_points, &mut relapses] {
+        points.sort_by(|a, b| {
+            b.sessions
+                .cmp(&a.sessions)
+                .then(b.seconds.cmp(&a.seconds))
+                .then(a.name.cmp(&b.name))
+        });
+    }
+    top_points.truncate(TOP_POINTS);
+    relapses.retain(|point| point.sessions > 0);
+
+    let mut completion = CompletionStats::default();
+    for occurrence in occurrences
+        .iter()
+        .filter(|occurrence| occurrence.due_on >= first_day && occurrence.due_on <= last_day)
+    {
+        match occurrence.status {
+            OccurrenceStatus::Done => completion.done += 1,
+            OccurrenceStatus::Missed => completion.missed += 1,
+            OccurrenceStatus::Skipped => completion.skipped += 1,
+            OccurrenceStatus::Planned => completion.planned += 1,
+        }
+    }
+
+    PeriodStats {
+        first_day,
+        last_day,
+        total_seconds: totals.total_seconds,
+        sessions: session_count,
+        per_type: per_type.into_values().collect(),
+        top_points,
+        relapses,
+        completion_rate: completion.rate(),
+        completion,
+    }
+}
+
+impl PeriodStats {
+    pub fn relapse_count(&self) -> u32 {
+        self.relapses.iter().map(|point| point.sessions).sum()
+    }
+}
+
+pub fn compare(period: ReportPeriod, current: PeriodStats, previous: PeriodStats) -> KarmaReport {
+    let mut purposes: Vec<KarmaType> = current
+        .per_type
+        .iter()
+        .chain(previous.per_type.iter())
+        .map(|stats| stats.purpose.clone())
+        .collect();
+    purposes.sort();
+    purposes.dedup();
+
+    let per_type = purposes
+        .into_iter()
+        .map(|purpose| {
+            let find = |stats: &PeriodStats| {
+                stats
+                    .per_type
+                    .iter()
+                    .find(|type_stats| type_stats.purpose == purpose)
+                    .map(|type_stats| (type_stats.seconds, type_stats.sessions as i64))
+                    .unwrap_or_default()
+            };
+            let (seconds, sessions) = find(&current);
+            let (previous_seconds, previous_sessions) = find(&previous);
+            TypeChange {
+                purpose,
+                seconds: seconds - previous_seconds,
+                sessions: sessions - previous_sessions,
+                percent: percent_change(previous_seconds, seconds),
+            }
+        })
+        .collect();
+
+    let changes = ReportChanges {
+        seconds: current.total_seconds - previous.total_seconds,
+        sessions: current.sessions as i64 - previous.sessions as i64,
+        percent: percent_change(previous.total_seconds, current.total_seconds),
+        per_type,
+        completion_rate: current
+            .completion_rate
+            .zip(previous.completion_rate)
+            .map(|(current, previous)| (current - previous) * 100.0),
+        relapses: current.relapse_count() as i64 - previous.relapse_count() as i64,
+    };
+
+    KarmaReport {
+        period,
+        current,
+        previous,
+        changes,
+    }
+}
+
+fn percent_change(previous: i64, current: i64) -> Option<f64> {
+    match (previous, current) {
+        (0, 0) => Some(0.0),
+        (0, _) => None,
+        _ => Some((current - previous) as f64 * 100.0 / previous as f64),
+    }
+}
+
+/// Hours and minutes, e.g. `3h 05m`
+pub fn format_duration(seconds: i64) -> String {
+    format!("{}h {:02}m", seconds / 3600, seconds % 3600 / 60)
+}
+
+fn format_signed_duration(seconds: i64) -> String {
+    let sign = if seconds < 0 { "-" } else { "+" };
+    format!("{sign}{}", format_duration(seconds.abs()))
+}
+
+fn format_percent(percent: Option<f64>) -> String {
+    percent
+        .map(|percent| {
+            if percent.round() == 0.0 {
+                "0%".to_string()
+            } else {
+                format!("{percent:+.0}%")
+            }
+        })
+        .unwrap_or_else(|| "new".to_string())
+}
+
+impl KarmaReport {
+    pub fn render(&self, format: ReportFormat) -> String {
+        match format {
+            ReportFormat::Markdown => self.to_markdown(),
+            ReportFormat::Text => self.to_plain_text(),
+        }
+    }
+
+    fn title(&self) -> String {
+        format!(
+            "{} report, {} to {}",
+            self.period.adjective(),
+            self.current.first_day,
+            self.current.last_day
+        )
+    }
+
+    fn summary(&self, bold: &str) -> String {
+        format!(
+            "Tracked {bold}{}{bold} over {bold}{}{bold} sessions \
+            ({}, {:+} sessions, {} on the previous {}).",
+            format_duration(self.current.total_seconds),
+            self.current.sessions,
+            format_signed_duration(self.changes.seconds),
+            self.changes.sessions,
+            format_percent(self.changes.percent),
+            self.period.noun()
+        )
+    }
+
+    fn completion_line(&self) -> String {
+        let completion = &self.current.completion;
+        let settled = completion.done + completion.missed + completion.skipped;
+        let Some(rate) = self.current.completion_rate else {
+            return "No scheduled occurrence was due.".to_string();
+        };
+
+        let mut line = format!(
+            "{} of {settled} scheduled occurrences done ({:.0}%), {} missed, {} skipped.",
+            completion.done,
+            rate * 100.0,
+            completion.missed,
+            completion.skipped
+        );
+        if let Some(change) = self.changes.completion_rate {
+            let _ = write!(
+                line,
+                " {change:+.0} points on the previous {}.",
+                self.period.noun()
+            );
+        }
+        line
+    }
+
+    /// None when no habit to avoid was relapsed into in either period
+    fn relapse_line(&self) -> Option<String> {
+        let count = self.current.relapse_count();
+        if count == 0 && self.changes.relapses == 0 {
+            return None;
+        }
+
+        let habits: Vec<String> = self
+            .current
+            .relapses
+            .iter()
+            .map(|point| format!("{}: {}", point.name, point.sessions))
+            .collect();
+        let mut line = match count {
+            0 => "No relapse".to_string(),
+            1 => "1 relapse".to_string(),
+            count => format!("{count} relapses"),
+        };
+        if !habits.is_empty() {
+            let _ = write!(line, " ({})", habits.join(", "));
+        }
+        let _ = write!(
+            line,
+            ", {:+} on the previous {}.",
+            self.changes.relapses,
+            self.period.noun()
+        );
+        Some(line)
+    }
+
+    fn type_change(&self, purpose: &KarmaType) -> String {
+        self.changes
+            .per_type
+            .iter()
+            .find(|change| &change.purpose == purpose)
+            .map(|change| format_percent(change.percent))
+            .unwrap_or_default()
+    }
+
+    pub fn to_markdown(&self) -> String {
+        let mut markdown = format!("# {}\n\n{}\n", self.title(), self.summary("**"));
+
+        markdown.push_str("\n## Time per type\n\n");
+        if self.current.per_type.is_empty() {
+            markdown.push_str("Nothing tracked.\n");
+        } else {
+            markdown.push_str("| Type | Time | Sessions | Change |\n|---|---:|---:|---:|\n");
+            for stats in &self.current.per_type {
+                let _ = writeln!(
+                    markdown,
+                    "| {:?} | {} | {} | {} |",
+                    stats.purpose,
+                    format_duration(stats.seconds),
+                    stats.sessions,
+                    self.type_change(&stats.purpose)
+                );
+            }
+        }
+
+        markdown.push_str("\n## Most frequent\n\n");
+        if self.current.top_points.is_empty() {
+            markdown.push_str("No sessions.\n");
+        }
+        for (position, point) in self.current.top_points.iter().enumerate() {
+            let _ = writeln!(
+                markdown,
+                "{}. {}, {} sessions, {}",
+                position + 1,
+                point.name,
+                point.sessions,
+                format_duration(point.seconds)
+            );
+        }
+
+        let _ = write!(markdown, "\n## Completion\n\n{}\n", self.completion_line());
+        if let Some(line) = self.relapse_line() {
+            let _ = write!(markdown, "\n## Relapses\n\n{line}\n");
+        }
+        markdown
+    }
+
+    pub fn to_plain_text(&self) -> String {
+        let title = self.title();
+        let mut text = format!(
+            "{title}\n{}\n\n{}\n",
+            "=".repeat(title.chars().count()),
+            self.summary("")
+        );
+
+        text.push_str("\nTime per type\n");
+        if self.current.per_type.is_empty() {
+            text.push_str("  Nothing tracked\n");
+        }
+        for stats in &self.current.per_type {
+            let _ = writeln!(
+                text,
+                "  {:<9} {:>9}  {:>3} sessions  {:>6}",
+                format!("{:?}", stats.purpose),
+                format_duration(stats.seconds),
+                stats.sessions,
+                self.type_change(&stats.purpose)
+            );
+        }
+
+        text.push_str("\nMost frequent\n");
+        if self.current.top_points.is_empty() {
+            text.push_str("  No sessions\n");
+        }
+        for (position, point) in self.current.top_points.iter().enumerate() {
+            let _ = writeln!(
+                text,
+                "  {}. {:<24} {:>3} sessions  {:>9}",
+                position + 1,
+                point.name,
+                point.sessions,
+                format_duration(point.seconds)
+            );
+        }
+
+        let _ = write!(text, "\nCompletion\n  {}\n", self.completion_line());
+        if let Some(line) = self.relapse_line() {
+            let _ = write!(text, "\nRelapses\n  {line}\n");
+        }
+        text
+    }
+}
+
+#[cfg(test)]
+mod report_tests {
+    use super::*;
+    use crate::model::karma::{KarmaStatus, State};
+    use crate::model::karma_session::KarmaSession;
+
+    const HOUR: i64 = 60 * 60;
+
+    fn date(value: &str) -> NaiveDate {
+        NaiveDate::parse_from_str(value, "%Y-%m-%d").unwrap()
+    }
+
+    fn session(
+        id: i32,
+        template_id: i32,
+        purpose: KarmaType,
+        start: i64,
+        hours: i64,
+    ) -> TrackedSession {
+        TrackedSession {
+            session: KarmaSession::with_id(id, template_id, purpose, None, start),
+            name: format!("Point {template_id}"),
+            polarity: Polarity::Positive,
+            statuses: vec![
+                KarmaStatus::new(id, State::Active, start),
+                KarmaStatus::new(id, State::Closed, start + hours * HOUR),
+            ],
+        }
+    }
+
+    fn occurrence(due_on: &str, status: OccurrenceStatus) -> KarmaOccurrence {
+        KarmaOccurrence {
+            id: None,
+            template_id: 1,
+            name: "Point 1".to_string(),
+            due_on: date(due_on),
+            due_at: 0,
+            day_start: 0,
+            day_end: 0,
+            status,
+            session_id: None,
+        }
+    }
+
+    #[test]
+    fn test_period_bounds() {
+        let settings = TimeSettings::default();
+        assert_eq!(
+            ReportPeriod::Week.bounds(date("2024-01-03"), &settings),
+            (date("2024-01-01"), date("2024-01-07"))
+        );
+        assert_eq!(
+            ReportPeriod::Month.bounds(date("2024-02-14"), &settings),
+            (date("2024-02-01"), date("2024-02-29"))
+        );
+        assert_eq!(
+            ReportPeriod::Month.previous(date("2024-03-01")),
+            date("2024-02-01")
+        );
+        assert!(ReportPeriod::try_from("year").is_err());
+    }
+
+    #[test]
+    fn test_report_against_previous_week() {
+        let settings = TimeSettings::default();
+        let settings = &settings;
+        let monday = settings.day_start(date("2024-01-08"));
+        let previous_monday = settings.day_start(date("2024-01-01"));
+        let sessions = vec![
+            session(1, 1, KarmaType::Sport, previous_monday, 1),
+            session(2, 1, KarmaType::Sport, monday, 1),
+            session(3, 1, KarmaType::Sport, monday + 24 * HOUR, 2),
+            session(4, 2, KarmaType::Work, monday + 48 * HOUR, 3),
+            TrackedSession {
+                polarity: Polarity::Negative,
+                ..session(5, 3, KarmaType::Social, monday + 72 * HOUR, 1)
+            },
+        ];
+        let occurrences = vec![
+            occurrence("2024-01-01", OccurrenceStatus::Missed),
+            occurrence("2024-01-03", OccurrenceStatus::Done),
+            occurrence("2024-01-08", OccurrenceStatus::Done),
+            occurrence("2024-01-10", OccurrenceStatus::Done),
+            occurrence("2024-01-12", OccurrenceStatus::Planned),
+        ];
+
+        let (first, last) = ReportPeriod::Week.bounds(date("2024-01-10"), settings);
+        let current = period_stats(first, last, &sessions, &occurrences, 0, settings);
+        let previous_first = ReportPeriod::Week.previous(first);
+        let previous = period_stats(
+            previous_first,
+            first - Duration::days(1),
+            &sessions,
+            &occurrences,
+            0,
+            settings,
+        );
+        let report = compare(ReportPeriod::Week, current, previous);
+
+        assert_eq!(report.current.total_seconds, 6 * HOUR);
+        assert_eq!(report.current.sessions, 3);
+        assert_eq!(report.current.top_points[0].name, "Point 1");
+        assert_eq!(report.current.top_points[0].sessions, 2);
+        assert_eq!(report.current.completion_rate, Some(1.0));
+        assert_eq!(report.current.completion.planned, 1);
+        assert_eq!(report.changes.seconds, 5 * HOUR);
+        assert_eq!(report.changes.completion_rate, Some(50.0));
+        assert_eq!(report.current.top_points.len(), 2);
+        assert_eq!(report.current.relapse_count(), 1);
+        assert_eq!(
+            report.changes.per_type,
+            vec![
+                TypeChange {
+                    purpose: KarmaType::Work,
+                    seconds: 3 * HOUR,
+                    sessions: 1,
+                    percent: None,
+                },
+                TypeChange {
+                    purpose: KarmaType::Sport,
+                    seconds: 2 * HOUR,
+                    sessions: 1,
+                    percent: Some(200.0),
+                },
+            ]
+        );
+
+        let markdown = report.to_markdown();
+        assert!(markdown.starts_with("# Weekly report, 2024-01-08 to 2024-01-14\n"));
+        assert!(markdown.contains("| Sport | 3h 00m | 2 | +200% |"));
+        assert!(markdown.contains("2 of 2 scheduled occurrences done (100%)"));
+        assert!(
+            markdown.contains("## Relapses\n\n1 relapse (Point 3: 1), +1 on the previous week.")
+        );
+
+        let text = report.to_plain_text();
+        assert!(text.contains("Tracked 6h 00m over 3 sessions (+5h 00m, +2 sessions, +500%"));
+        assert!(!text.contains('|'));
+    }
+
+    #[test]
+    fn test_percent_of_an_empty_period() {
+        assert_eq!(percent_change(0, 0), Some(0.0));
+        assert_eq!(percent_change(0, HOUR), None);
+        assert_eq!(percent_change(HOUR, 0), Some(-100.0));
+        assert_eq!(format_percent(percent_change(0, 0)), "0%");
+        assert_eq!(format_percent(percent_change(0, HOUR)), "new");
+        assert_eq!(format_percent(percent_change(HOUR, 3 * HOUR)), "+200%");
+    }
+}
@@ -0,0 +1,269 @@
+use std::fmt;
+
+use chrono::NaiveDate;
+use serde::Serialize;
+use thiserror::Error;
+
+use super::local_time::TimeSettings;
+use super::report::ReportPeriod;
+
+#[derive(Debug, Error, Serialize)]
+pub enum RewardError {
+    #[error("A reward needs a name")]
+    MissingName,
+
+    #[error("Invalid cost {0}, expected a positive number of points")]
+    InvalidCost(f64),
+
+    #[error("Invalid limit {0}, expected e.g. once per week or 3 per month")]
+    InvalidLimit(String),
+
+    #[error("There is already a reward called {0}")]
+    DuplicateName(String),
+
+    #[error("{name} costs {cost:.1} points, the balance is {balance:.1}")]
+    InsufficientBalance {
+        name: String,
+        cost: f64,
+        balance: f64,
+    },
+
+    #[error("{name} was already redeemed {limit}, next from {next}")]
+    LimitReached {
+        name: String,
+        limit: RewardLimit,
+        next: NaiveDate,
+    },
+}
+
+/// At most `times` redemptions per calendar week or month
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub struct RewardLimit {
+    pub times: u32,
+    pub period: ReportPeriod,
+}
+
+impl RewardLimit {
+    /// `once per week`, `twice a month`, `3 per week` or `3/month`
+    pub fn parse(value: &str) -> Result<RewardLimit, RewardError> {
+        let invalid = || RewardError::InvalidLimit(value.to_string());
+        let value = value.trim().to_lowercase();
+        let (times, period) = value
+            .split_once('/')
+            .or_else(|| value.split_once(" per "))
+            .or_else(|| value.split_once(" a "))
+            .ok_or_else(invalid)?;
+
+        let times = match times.trim() {
+            "once" => 1,
+            "twice" => 2,
+            times => times.parse().map_err(|_| invalid())?,
+        };
+        if times == 0 {
+            return Err(invalid());
+        }
+        let period = ReportPeriod::try_from(period.trim()).map_err(|_| invalid())?;
+
+        Ok(RewardLimit { times, period })
+    }
+}
+
+impl fmt::Display for RewardLimit {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.times {
+            1 => write!(f, "once per {}", self.period.noun()),
+            2 => write!(f, "twice per {}", self.period.noun()),
+            times => write!(f, "{times} times per {}", self.period.noun()),
+        }
+    }
+}
+
+/// Something the user treats themselves to with earned points
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Reward {
+    pub id: Option<i32>,
+    pub name: String,
+    pub cost: f64,
+    pub limit: Option<RewardLimit>,
+    pub created_at: i64,
+}
+
+impl Reward {
+    pub fn validate(&self) -> Result<(), RewardError> {
+        if self.name.trim().is_empty() {
+            return Err(RewardError::MissingName);
+        }
+        if !self.cost.is_finite() || self.cost <= 0.0 {
+            return Err(RewardError::InvalidCost(self.cost));
+        }
+        Ok(())
+    }
+}
+
+/// A reward spent, name and cost are kept as they were at the time
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Redemption {
+    pub id: Option<i32>,
+    pub reward_id: i32,
+    pub name: String,
+    pub cost: f64,
+    pub redeemed_at: i64,
+    /// The local day of `redeemed_at`, the ledger counts it on that day
+    pub redeemed_on: NaiveDate,
+}
+
+/// A reward as offered in the shop today
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct RewardStatus {
+    pub reward: Reward,
+    /// In the current period of the limit, none without a limit
+    pub redeemed: Option<u32>,
+    pub affordable: bool,
+    /// When the limit is reached, the first day it can be redeemed again
+    pub available_from: Option<NaiveDate>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct RewardShop {
+    pub balance: f64,
+    pub rewards: Vec<RewardStatus>,
+}
+
+/// Where `reward` stands given the balance and every earlier redemption
+pub fn reward_status(
+    reward: &Reward,
+    redemptions: &[Redemption],
+    balance: f64,
+    today: NaiveDate,
+    settings: &TimeSettings,
+) -> RewardStatus {
+    let mut redeemed = None;
+    let mut available_from = None;
+    if let Some(limit) = reward.limit {
+        let (first_day, last_day) = limit.period.bounds(today, settings);
+        let count = redemptions
+            .iter()
+            .filter(|redemption| redemption.reward_id == reward.id.unwrap_or_default())
+            .filter(|redemption| {
+                redemption.redeemed_on >= first_day && redemption.redeemed_on <= last_day
+            })
+            .count() as u32;
+        redeemed = Some(count);
+        if count >= limit.times {
+            available_from = last_day.succ_opt();
+        }
+    }
+
+    RewardStatus {
+        reward: reward.clone(),
+        redeemed,
+        affordable: reward.cost <= balance,
+        available_from,
+    }
+}
+
+/// Why `status` can't be redeemed, if it can't
+pub fn check_redemption(status: &RewardStatus, balance: f64) -> Result<(), RewardError> {
+    if let (Some(limit), Some(next)) = (status.reward.limit, status.available_from) {
+        return Err(RewardError::LimitReached {
+            name: status.reward.name.clone(),
+            limit,
+            next,
+        });
+    }
+    if !status.affordable {
+        return Err(RewardError::InsufficientBalance {
+            name: status.reward.name.clone(),
+            cost: status.reward.cost,
+            balance,
+        });
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod reward_tests {
+    use super::*;
+    use crate::model::recurrence::parse_date;
+
+    fn redemption(day: &str) -> Redemption {
+        Redemption {
+            id: None,
+            reward_id: 1,
+            name: "Cinema".to_string(),
+            cost: 10.0,
+            redeemed_at: 0,
+            redeemed_on: parse_date(day).unwrap(),
+        }
+    }
+
+    #[test]
+    fn test_parse_limit() {
+        let weekly = RewardLimit::parse("once per week").unwrap();
+        assert_eq!(
+            weekly,
+            RewardLimit {
+                times: 1,
+                period: ReportPeriod::Week
+            }
+        );
+        assert_eq!(RewardLimit::parse(&weekly.to_string()).unwrap(), weekly);
+        assert_eq!(RewardLimit::parse("3/Month").unwrap().times, 3);
+        assert_eq!(
+            RewardLimit::parse("twice a month").unwrap().to_string(),
+            "twice per month"
+        );
+
+        assert!(RewardLimit::parse("0 per week").is_err());
+        assert!(RewardLimit::parse("once per year").is_err());
+        assert!(RewardLimit::parse("often").is_err());
+    }
+
+    #[test]
+    fn test_limit_resets_with_the_period() {
+        let settings = TimeSettings::default();
+        let reward = Reward {
+            id: Some(1),
+            name: "Cinema".to_string(),
+            cost: 10.0,
+            limit: Some(RewardLimit::parse("once per week").unwrap()),
+            created_at: 0,
+        };
+        let redemptions = vec![redemption("2024-01-02")];
+
+        let status = reward_status(
+            &reward,
+            &redemptions,
+            12.0,
+            parse_date("2024-01-04").unwrap(),
+            &settings,
+        );
+        assert_eq!(status.redeemed, Some(1));
+        assert_eq!(status.available_from, parse_date("2024-01-08").ok());
+        assert!(matches!(
+            check_redemption(&status, 12.0),
+            Err(RewardError::LimitReached { .. })
+        ));
+
+        let status = reward_status(
+            &reward,
+            &redemptions,
+            8.0,
+            parse_date("2024-01-08").unwrap(),
+            &settings,
+        );
+        assert_eq!(status.redeemed, Some(0));
+        assert!(matches!(
+            check_redemption(&status, 8.0),
+            Err(RewardError::InsufficientBalance { .. })
+        ));
+        let status = reward_status(
+            &reward,
+            &redemptions,
+            10.0,
+            parse_date("2024-01-08").unwrap(),
+            &settings,
+        );
+        assert!(check_redemption(&status, 10.0).is_ok());
+    }
+}
@@ -0,0 +1,86 @@
+use chrono::NaiveDate;
+use serde::Serialize;
+
+use super::recurrence::Recurrence;
+
+/// How many days ahead the daily job plans occurrences
+pub const PLANNING_HORIZON_DAYS: i64 = 7;
+
+/// The recurrence of a karma template, a template has at most one
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct KarmaSchedule {
+    pub template_id: i32,
+    pub recurrence: Recurrence,
+    /// Occurrences up to this date are already planned, none when nothing is
+    pub planned_until: Option<NaiveDate>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub enum OccurrenceStatus {
+    Planned,
+    Done,
+    /// The day passed without a session, waiting for the user to resolve it
+    Missed,
+    Skipped,
+}
+
+impl OccurrenceStatus {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            OccurrenceStatus::Planned => "planned",
+            OccurrenceStatus::Done => "done",
+            OccurrenceStatus::Missed => "missed",
+            OccurrenceStatus::Skipped => "skipped",
+        }
+    }
+}
+
+impl TryFrom<&str> for OccurrenceStatus {
+    type Error = String;
+
+    fn try_from(value: &str) -> Result<Self, Self::Error> {
+        match value.to_lowercase().as_str() {
+            "planned" => Ok(OccurrenceStatus::Planned),
+            "done" => Ok(OccurrenceStatus::Done),
+            "missed" => Ok(OccurrenceStatus::Missed),
+            "skipped" => Ok(OccurrenceStatus::Skipped),
+            other => Err(other.to_string()),
+        }
+    }
+}
+
+/// A planned instance of a scheduled template
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct KarmaOccurrence {
+    pub id: Option<i32>,
+    pub template_id: i32,
+    /// The name of the template, for display
+    pub name: String,
+    pub due_on: NaiveDate,
+    pub due_at: i64,
+    /// `due_on` in the user's timezone when planned, sessions started in
+    /// `[day_start, day_end)` fulfil the occurrence
+    pub day_start: i64,
+    pub day_end: i64,
+    pub status: OccurrenceStatus,
+    /// The session that fulfilled it
+    pub session_id: Option<i32>,
+}
+
+/// How the user settles a missed or planned occurrence
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OccurrenceResolution {
+    /// Done after all, optionally linked to the session that did it
+    Done { session_id: Option<i32> },
+    /// Not meant to happen this time
+    Skipped,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct DailyJobReport {
+    pub planned: u64,
+    pub completed: u64,
+    pub missed: u64,
+    /// Unfinished plan entries moved to today
+    pub carried_over: u64,
+}
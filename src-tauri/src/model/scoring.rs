@@ -0,0 +1,532 @@
+use std::collections::BTreeMap;
+use std::fmt;
+
+use chrono::{Duration, NaiveDate};
+use serde::Serialize;
+use thiserror::Error;
+
+use super::deviation::SessionOutcome;
+use super::duration::{session_duration, TrackedSession};
+use super::karma::{KarmaType, Polarity};
+use super::local_time::TimeSettings;
+use super::reward::Redemption;
+
+pub const SCORING_RULES_SETTING: &str = "scoring_rules";
+/// Days the ledger covers when none are asked for
+pub const DEFAULT_LEDGER_DAYS: i64 = 30;
+
+#[derive(Debug, Error, Serialize)]
+pub enum ScoringError {
+    #[error(
+        "Invalid scoring rule {0}, expected type=points per hour, deviation=points, \
+        relapse=points or decay=days"
+    )]
+    InvalidRule(String),
+}
+
+/// How sessions turn into points
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct ScoringRules {
+    /// Points per active hour of the type a session turned out to be, negative
+    /// weights deduct
+    pub weights: BTreeMap<KarmaType, f64>,
+    /// Deducted from a session that ended as another type than planned
+    pub deviation_penalty: f64,
+    /// Deducted from every relapse into a habit to avoid, on top of its time
+    pub relapse_penalty: f64,
+    /// Points fade out linearly over this many days, never when 0
+    pub decay_days: u32,
+}
+
+impl ScoringRules {
+    /// Comma separated `type=points`, `deviation=points`, `relapse=points` and
+    /// `decay=days`, e.g. `sport=2, deviation=1, decay=30`. Types left out keep
+    /// their weight.
+    pub fn parse(value: &str) -> Result<ScoringRules, ScoringError> {
+        let mut rules = ScoringRules::default();
+
+        for rule in value
+            .split(',')
+            .map(str::trim)
+            .filter(|rule| !rule.is_empty())
+        {
+            let invalid = || ScoringError::InvalidRule(rule.to_string());
+            let (name, amount) = rule.split_once('=').ok_or_else(invalid)?;
+            let amount = amount.trim();
+
+            let penalty = || {
+                amount
+                    .parse()
+                    .ok()
+                    .filter(|penalty: &f64| penalty.is_finite() && *penalty >= 0.0)
+                    .ok_or_else(invalid)
+            };
+
+            match name.trim().to_lowercase().as_str() {
+                "deviation" => rules.deviation_penalty = penalty()?,
+                "relapse" => rules.relapse_penalty = penalty()?,
+                "decay" => rules.decay_days = amount.parse().map_err(|_| invalid())?,
+                name => {
+                    let purpose = KarmaType::from_name(name).ok_or_else(invalid)?;
+                    let weight: f64 = amount
+                        .parse()
+                        .ok()
+                        .filter(|weight: &f64| weight.is_finite())
+                        .ok_or_else(invalid)?;
+                    rules.weights.insert(purpose, weight);
+                }
+            }
+        }
+
+        Ok(rules)
+    }
+
+    pub fn weight(&self, purpose: &KarmaType) -> f64 {
+        self.weights.get(purpose).copied().unwrap_or_default()
+    }
+
+    /// What is left of a point `age_days` after it was earned
+    pub fn remaining(&self, age_days: i64) -> f64 {
+        if self.decay_days == 0 {
+            return 1.0;
+        }
+        (1.0 - age_days as f64 / self.decay_days as f64).max(0.0)
+    }
+}
+
+impl Default for ScoringRules {
+    fn default() -> Self {
+        ScoringRules {
+            weights: BTreeMap::from([
+                (KarmaType::Work, 1.0),
+                (KarmaType::Social, 1.0),
+                (KarmaType::Sport, 2.0),
+                (KarmaType::Learning, 2.0),
+                (KarmaType::Sleeping, 0.5),
+            ]),
+            deviation_penalty: 1.0,
+            relapse_penalty: 2.0,
+            decay_days: 30,
+        }
+    }
+}
+
+impl fmt::Display for ScoringRules {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut rules: Vec<String> = self
+            .weights
+            .iter()
+            .map(|(purpose, weight)| format!("{}={weight}", format!("{purpose:?}").to_lowercase()))
+            .collect();
+        rules.push(format!("deviation={}", self.deviation_penalty));
+        rules.push(format!("relapse={}", self.relapse_penalty));
+        rules.push(format!("decay={}", self.decay_days));
+        write!(f, "{}", rules.join(","))
+    }
+}
+
+/// The points a closed session earned, on the day it was closed
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct PointAward {
+    pub session_id: i32,
+    pub template_id: i32,
+    pub name: String,
+    pub day: NaiveDate,
+    pub active_seconds: i64,
+    pub points: f64,
+    /// For example `2.0h of Sport at 2/h, planned as Learning: -1`
+    pub reason: String,
+}
+
+/// What a closed session is worth, none while it's still running. A relapse into a
+/// habit to avoid costs its time at the weight of its type and the relapse penalty.
+pub fn award(
+    tracked: &TrackedSession,
+    rules: &ScoringRules,
+    now: i64,
+    settings: &TimeSettings,
+) -> Option<PointAward> {
+    let outcome = SessionOutcome::of(tracked, settings)?;
+    let active_seconds = session_duration(tracked, now).active_seconds;
+    let hours = active_seconds as f64 / 3600.0;
+    let weight = rules.weight(&outcome.actual);
+
+    if tracked.polarity == Polarity::Negative {
+        return Some(PointAward {
+            session_id: outcome.session_id,
+            template_id: outcome.template_id,
+            name: outcome.name,
+            day: outcome.closed_on,
+            active_seconds,
+            points: -(weight.abs() * hours + rules.relapse_penalty),
+            reason: format!(
+                "Relapse, {hours:.1}h of {:?} at -{}/h: -{}",
+                outcome.actual,
+                weight.abs(),
+                rules.relapse_penalty
+            ),
+        });
+    }
+
+    let mut points = weight * hours;
+    let mut reason = format!("{hours:.1}h of {:?} at {weight}/h", outcome.actual);
+    if outcome.deviated() {
+        points -= rules.deviation_penalty;
+        reason.push_str(&format!(
+            ", planned as {:?}: -{}",
+            outcome.planned, rules.deviation_penalty
+        ));
+    }
+
+    Some(PointAward {
+        session_id: outcome.session_id,
+        template_id: outcome.template_id,
+        name: outcome.name,
+        day: outcome.closed_on,
+        active_seconds,
+        points,
+        reason,
+    })
+}
+
+/// How the balance changed on one day
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct LedgerDay {
+    pub day: NaiveDate,
+    pub awards: Vec<PointAward>,
+    pub earned: f64,
+    pub redemptions: Vec<Redemption>,
+    pub spent: f64,
+    /// Lost to decay during the day, points earned before fading out
+    pub decayed: f64,
+    /// At the end of the day
+    pub balance: f64,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct PointsBalance {
+    pub balance: f64,
+    pub rules: ScoringRules,
+    /// Every day of the range, the most recent first
+    pub ledger: Vec<LedgerDay>,
+}
+
+/// What is left of the points earned. Redemptions spend the oldest points still
+/// worth something first, so spent points leave with the award they came from
+/// instead of fading out of the balance on their own.
+struct Purse<'a> {
+    rules: &'a ScoringRules,
+    awards: Vec<&'a PointAward>,
+    redemptions: Vec<&'a Redemption>,
+    next_award: usize,
+    next_redemption: usize,
+    /// The day of each award and its points not spent yet, as earned that day
+    earned: Vec<(NaiveDate, f64)>,
+    /// Spent beyond what was left, which only stale redemptions can lead to
+    overdrawn: f64,
+}
+
+impl<'a> Purse<'a> {
+    fn new(
+        awards: &'a [PointAward],
+        redemptions: &'a [Redemption],
+        rules: &'a ScoringRules,
+    ) -> Purse<'a> {
+        let mut awards: Vec<_> = awards.iter().collect();
+        awards.sort_by_key(|award| award.day);
+        let mut redemptions: Vec<_> = redemptions.iter().collect();
+        redemptions.sort_by_key(|redemption| (redemption.redeemed_on, redemption.redeemed_at));
+
+        Purse {
+            rules,
+            awards,
+            redemptions,
+            next_award: 0,
+            next_redemption: 0,
+            earned: Vec::new(),
+            overdrawn: 0.0,
+        }
+    }
+
+    /// The balance at the end of `day`, days have to be asked in order
+    fn balance_on(&mut self, day: NaiveDate) -> f64 {
+        loop {
+            let award = self
+                .awards
+                .get(self.next_award)
+                .copied()
+                .filter(|award| award.day <= day);
+            let redemption = self
+                .redemptions
+                .get(self.next_redemption)
+                .copied()
+                .filter(|redemption| redemption.redeemed_on <= day);
+
+            // Points earned on the day of a redemption can already be spent
+            match (award, redemption) {
+                (Some(award), Some(redemption)) if award.day > redemption.redeemed_on => {
+                    self.spend(redemption)
+                }
+                (Some(award), _) => {
+                    self.earned.push((award.day, award.points));
+                    self.next_award += 1;
+                }
+                (None, Some(redemption)) => self.spend(redemption),
+                (None, None) => break,
+            }
+        }
+
+        self.earned
+            .iter()
+            .fold(0.0, |balance, (earned_on, points)| {
+                balance + points * self.rules.remaining((day - *earned_on).num_days())
+            })
+            - self.overdrawn
+    }
+
+    fn spend(&mut self, redemption: &Redemption) {
+        self.next_redemption += 1;
+
+        let mut cost = redemption.cost;
+        for (earned_on, points) in &mut self.earned {
+            if cost <= 0.0 {
+                break;
+            }
+            // Relapse penalties and faded out awards have nothing left to spend
+            let remaining = self
+                .rules
+                .remaining((redemption.redeemed_on - *earned_on).num_days());
+            let worth = *points * remaining;
+            if worth <= 0.0 {
+                continue;
+            }
+            let used = worth.min(cost);
+            *points -= used / remaining;
+            cost -= used;
+        }
+        self.overdrawn += cost.max(0.0);
+    }
+}
+
+/// Builds the ledger of `[first_day, last_day]`. Which points a redemption spent
+/// depends on everything earned and redeemed before, so both should go back to
+/// the start.
+pub fn points_balance(
+    awards: &[PointAward],
+    redemptions: &[Redemption],
+    first_day: NaiveDate,
+    last_day: NaiveDate,
+    rules: &ScoringRules,
+) -> PointsBalance {
+    let mut purse = Purse::new(awards, redemptions, rules);
+
+    let mut per_day: BTreeMap<NaiveDate, Vec<PointAward>> = BTreeMap::new();
+    for award in awards {
+        per_day.entry(award.day).or_default().push(award.clone());
+    }
+    let mut spent_per_day: BTreeMap<NaiveDate, Vec<Redemption>> = BTreeMap::new();
+    for redemption in redemptions {
+        spent_per_day
+            .entry(redemption.redeemed_on)
+            .or_default()
+            .push(redemption.clone());
+    }
+
+    let mut ledger = Vec::new();
+    let mut previous = purse.balance_on(first_day - Duration::days(1));
+    let mut day = first_day;
+    while day <= last_day {
+        let awards = per_day.remove(&day).unwrap_or_default();
+        // Folded from 0.0, summing nothing gives -0.0
+        let earned = awards
+            .iter()
+            .fold(0.0, |earned, award| earned + award.points);
+        let redemptions = spent_per_day.remove(&day).unwrap_or_default();
+        let spent = redemptions
+            .iter()
+            .fold(0.0, |spent, redemption| spent + redemption.cost);
+        let balance = purse.balance_on(day);
+
+        ledger.push(LedgerDay {
+            day,
+            awards,
+            earned,
+            redemptions,
+            spent,
+            decayed: previous + earned - spent - balance,
+            balance,
+        });
+        previous = balance;
+        day += Duration::days(1);
+    }
+    ledger.reverse();
+
+    PointsBalance {
+        balance: previous,
+        rules: rules.clone(),
+        ledger,
+    }
+}
+
+#[cfg(test)]
+mod scoring_tests {
+    use super::*;
+    use crate::model::karma::{KarmaStatus, State};
+    use crate::model::karma_session::KarmaSession;
+    use crate::model::recurrence::parse_date;
+
+    const HOUR: i64 = 60 * 60;
+
+    #[test]
+    fn test_parse_rules() {
+        let rules = ScoringRules::parse("Sport=3, social=-1, deviation=2, decay=10").unwrap();
+        assert_eq!(rules.weight(&KarmaType::Sport), 3.0);
+        assert_eq!(rules.weight(&KarmaType::Social), -1.0);
+        assert_eq!(rules.weight(&KarmaType::Work), 1.0);
+        assert_eq!(rules.deviation_penalty, 2.0);
+        assert_eq!(rules.remaining(5), 0.5);
+        assert_eq!(rules.remaining(12), 0.0);
+        assert_eq!(ScoringRules::parse(&rules.to_string()).unwrap(), rules);
+
+        assert!(ScoringRules::parse("chores=1").is_err());
+        assert!(ScoringRules::parse("deviation=-1").is_err());
+        assert!(ScoringRules::parse("relapse=-2").is_err());
+        assert!(ScoringRules::parse("decay=soon").is_err());
+    }
+
+    #[test]
+    fn test_award_follows_weight_and_deviation() {
+        let settings = TimeSettings::default();
+        let rules = ScoringRules::default();
+        let mut tracked = TrackedSession {
+            session: KarmaSession::with_id(4, 1, KarmaType::Learning, None, 0),
+            name: "Course".to_string(),
+            polarity: Polarity::Positive,
+            statuses: vec![KarmaStatus::new(4, State::Active, 0)],
+        };
+        assert_eq!(award(&tracked, &rules, HOUR, &settings), None);
+
+        tracked
+            .statuses
+            .push(KarmaStatus::new(4, State::Closed, 2 * HOUR));
+        let kept = award(&tracked, &rules, 3 * HOUR, &settings).unwrap();
+        assert_eq!(kept.points, 4.0);
+        assert_eq!(kept.reason, "2.0h of Learning at 2/h");
+
+        tracked.statuses[1] =
+            KarmaStatus::with_closed_reason(4, State::Closed, 2 * HOUR, KarmaType::Social);
+        let deviated = award(&tracked, &rules, 3 * HOUR, &settings).unwrap();
+        assert_eq!(deviated.points, 1.0);
+        assert_eq!(
+            deviated.reason,
+            "2.0h of Social at 1/h, planned as Learning: -1"
+        );
+
+        tracked.polarity = Polarity::Negative;
+        let relapse = award(&tracked, &rules, 3 * HOUR, &settings).unwrap();
+        assert_eq!(relapse.points, -4.0);
+        assert_eq!(relapse.reason, "Relapse, 2.0h of Social at -1/h: -2");
+    }
+
+    #[test]
+    fn test_ledger_explains_decay() {
+        let rules = ScoringRules::parse("decay=4").unwrap();
+        let award = |day: &str, points: f64| PointAward {
+            session_id: 1,
+            template_id: 1,
+            name: "Run".to_string(),
+            day: parse_date(day).unwrap(),
+            active_seconds: HOUR,
+            points,
+            reason: String::new(),
+        };
+        // Before the range, half of it is gone by its first day
+        let awards = vec![award("2024-01-01", 8.0), award("2024-01-03", 4.0)];
+
+        let balance = points_balance(
+            &awards,
+            &[],
+            parse_date("2024-01-03").unwrap(),
+            parse_date("2024-01-05").unwrap(),
+            &rules,
+        );
+        let days: Vec<(String, f64, f64, f64)> = balance
+            .ledger
+            .iter()
+            .map(|day| (day.day.to_string(), day.earned, day.decayed, day.balance))
+            .collect();
+        assert_eq!(
+            days,
+            vec![
+                ("2024-01-05".to_string(), 0.0, 3.0, 2.0),
+                ("2024-01-04".to_string(), 0.0, 3.0, 5.0),
+                ("2024-01-03".to_string(), 4.0, 2.0, 8.0),
+            ]
+        );
+        assert_eq!(balance.balance, 2.0);
+    }
+
+    #[test]
+    fn test_redemptions_spend_the_oldest_points() {
+        let rules = ScoringRules::parse("decay=4").unwrap();
+        let award = |day: &str, points: f64| PointAward {
+            session_id: 1,
+            template_id: 1,
+            name: "Run".to_string(),
+            day: parse_date(day).unwrap(),
+            active_seconds: HOUR,
+            points,
+            reason: String::new(),
+        };
+        let redemption = |day: &str, cost: f64| Redemption {
+            id: None,
+            reward_id: 1,
+            name: "Cinema".to_string(),
+            cost,
+            redeemed_at: 0,
+            redeemed_on: parse_date(day).unwrap(),
+        };
+        let balances = |awards: &[PointAward], redemptions: &[Redemption]| -> Vec<f64> {
+            points_balance(
+                awards,
+                redemptions,
+                parse_date("2024-01-03").unwrap(),
+                parse_date("2024-01-05").unwrap(),
+                &rules,
+            )
+            .ledger
+            .iter()
+            .map(|day| day.balance)
+            .collect()
+        };
+
+        // Half of the 8 points are left on the 3rd and 3 of them are spent, the last
+        // one fades out with them instead of the spent points dragging the balance
+        // below zero
+        let awards = vec![award("2024-01-01", 8.0)];
+        assert_eq!(
+            balances(&awards, &[redemption("2024-01-03", 3.0)]),
+            vec![0.0, 0.5, 1.0]
+        );
+
+        // What is left of the 8 points pays for it all, the 4 points keep fading
+        // out at their own pace
+        let awards = vec![award("2024-01-01", 8.0), award("2024-01-02", 4.0)];
+        let balance = points_balance(
+            &awards,
+            &[redemption("2024-01-03", 4.0)],
+            parse_date("2024-01-03").unwrap(),
+            parse_date("2024-01-05").unwrap(),
+            &rules,
+        );
+        let days: Vec<(f64, f64, f64)> = balance
+            .ledger
+            .iter()
+            .map(|day| (day.spent, day.decayed, day.balance))
+            .collect();
+        assert_eq!(
+            days,
+            vec![(0.0, 1.0, 1.0), (0.0, 1.0, 2.0), (4.0, 3.0, 3.0)]
+        );
+    }
+}
@@ -0,0 +1,450 @@
+use std::collections::BTreeMap;
+use std::fmt;
+
+use chrono::{Duration, NaiveDate, NaiveDateTime, NaiveTime};
+use serde::Serialize;
+use thiserror::Error;
+
+use super::deviation::SessionOutcome;
+use super::duration::{active_intervals, ActiveInterval, TrackedSession};
+use super::karma::{KarmaType, Polarity, State};
+use super::local_time::TimeSettings;
+
+pub const SLEEP_RULES_SETTING: &str = "sleep_rules";
+/// A night measured against the bedtime spread giving a consistency of zero
+const INCONSISTENT_SPREAD: f64 = 2.0 * 60.0 * 60.0;
+/// The longest sleep that can be logged after the fact
+const MAX_SLEEP: i64 = 24 * 60 * 60;
+const DAY_SECONDS: i64 = 24 * 60 * 60;
+const NOON: u32 = 12 * 60 * 60;
+
+#[derive(Debug, Error, Serialize)]
+pub enum SleepError {
+    #[error("Invalid sleep rule {0}, expected target=hours or window=nights")]
+    InvalidRule(String),
+
+    #[error("Invalid time {0}, expected YYYY-MM-DD HH:MM")]
+    InvalidTime(String),
+
+    #[error("Waking up has to come after going to bed, and within a day")]
+    InvalidSleep,
+
+    #[error("Sleep can only be logged once it's over")]
+    NotOver,
+}
+
+/// What sleep is measured against
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct SleepRules {
+    /// Sleep needed per night
+    pub target_hours: f64,
+    /// Nights the debt and the consistency are worked out over
+    pub window_nights: u32,
+}
+
+impl SleepRules {
+    /// Comma separated `target=hours` and `window=nights`, e.g. `target=7.5`. Rules
+    /// left out keep their value.
+    pub fn parse(value: &str) -> Result<SleepRules, SleepError> {
+        let mut rules = SleepRules::default();
+
+        for rule in value
+            .split(',')
+            .map(str::trim)
+            .filter(|rule| !rule.is_empty())
+        {
+            let invalid = || SleepError::InvalidRule(rule.to_string());
+            let (name, amount) = rule.split_once('=').ok_or_else(invalid)?;
+            let amount = amount.trim();
+
+            match name.trim().to_lowercase().as_str() {
+                "target" => {
+                    rules.target_hours = amount
+                        .parse()
+                        .ok()
+                        .filter(|hours: &f64| *hours > 0.0 && *hours <= 24.0)
+                        .ok_or_else(invalid)?
+                }
+                "window" => {
+                    rules.window_nights = amount
+                        .parse()
+                        .ok()
+                        .filter(|nights| (1..=366).contains(nights))
+                        .ok_or_else(invalid)?
+                }
+                _ => return Err(invalid()),
+            }
+        }
+
+        Ok(rules)
+    }
+
+    pub fn target_seconds(&self) -> i64 {
+        (self.target_hours * 3600.0).round() as i64
+    }
+}
+
+impl Default for SleepRules {
+    fn default() -> Self {
+        SleepRules {
+            target_hours: 8.0,
+            window_nights: 14,
+        }
+    }
+}
+
+impl fmt::Display for SleepRules {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "target={},window={}",
+            self.target_hours, self.window_nights
+        )
+    }
+}
+
+/// `YYYY-MM-DD HH:MM`, a `T` between the date and the time works too
+pub fn parse_local_datetime(value: &str) -> Result<NaiveDateTime, SleepError> {
+    let value = value.trim();
+    NaiveDateTime::parse_from_str(value, "%Y-%m-%d %H:%M")
+        .or_else(|_| NaiveDateTime::parse_from_str(value, "%Y-%m-%dT%H:%M"))
+        .map_err(|_| SleepError::InvalidTime(value.to_string()))
+}
+
+/// Checks a sleep logged after the fact, in unix seconds
+pub fn validate_sleep(bedtime: i64, wake: i64, now: i64) -> Result<(), SleepError> {
+    if wake <= bedtime || wake - bedtime > MAX_SLEEP {
+        return Err(SleepError::InvalidSleep);
+    }
+    if wake > now {
+        return Err(SleepError::NotOver);
+    }
+    Ok(())
+}
+
+/// The night sleep from `bedtime` to `wake` belongs to, named after the evening it
+/// starts. Sleep counts for the night its middle falls in, a middle before noon
+/// belongs to the evening before, so going to bed after midnight or sleeping in
+/// past noon still counts for the right night.
+pub fn night_of(bedtime: i64, wake: i64, settings: &TimeSettings) -> NaiveDate {
+    let middle = bedtime + (wake - bedtime) / 2;
+    (settings.timezone.local_datetime(middle) - Duration::seconds(NOON as i64)).date()
+}
+
+/// The sleep of one night, from all the sleeping sessions attributed to it
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Night {
+    /// The date of the evening it starts
+    pub night: NaiveDate,
+    /// When the first session of the night started, unix seconds
+    pub bedtime: i64,
+    /// When the last one ended, now while still asleep
+    pub wake: i64,
+    /// Local time of day in seconds
+    pub bedtime_of_day: u32,
+    pub wake_of_day: u32,
+    /// Paused time left out
+    pub slept_seconds: i64,
+    pub sessions: Vec<i32>,
+    /// Still asleep
+    pub open: bool,
+    /// The sleep debt once this night is counted, still open nights don't count
+    pub debt_seconds: i64,
+}
+
+impl Night {
+    /// Seconds from noon of the night's date, so times around midnight compare
+    fn since_noon(&self, timestamp: i64, settings: &TimeSettings) -> i64 {
+        timestamp - settings.at_time_of_day(self.night, NOON)
+    }
+}
+
+/// Sleep over the nights of a window
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct SleepReport {
+    /// The first and last night of the window
+    pub from: NaiveDate,
+    pub to: NaiveDate,
+    pub target_seconds: i64,
+    /// Tracked nights only, oldest first
+    pub nights: Vec<Night>,
+    /// Over the finished nights
+    pub average_seconds: Option<i64>,
+    /// What is missing against the target, built up over the window. Sleeping more
+    /// pays it back but never banks sleep for later. Untracked nights don't count.
+    pub debt_seconds: i64,
+    /// Local times of day in seconds
+    pub average_bedtime: Option<u32>,
+    pub average_wake: Option<u32>,
+    /// Standard deviation of the bedtimes
+    pub bedtime_spread_seconds: Option<i64>,
+    /// 100 when going to bed at the same time every night down to 0 for a spread of
+    /// two hours or more, from two nights on
+    pub consistency: Option<u32>,
+}
+
+/// Groups the sleeping sessions into nights. A session counts as sleep when it
+/// ended as sleep, or while still running when it was started as sleep. Relapses
+/// into a habit to avoid are never sleep.
+pub fn sleep_nights(sessions: &[TrackedSession], now: i64, settings: &TimeSettings) -> Vec<Night> {
+    let mut nights: BTreeMap<NaiveDate, Night> = BTreeMap::new();
+
+    for tracked in sessions {
+        if tracked.polarity == Polarity::Negative {
+            continue;
+        }
+        let purpose = SessionOutcome::of(tracked, settings)
+            .map_or(tracked.session.get_purpose(), |outcome| outcome.actual);
+        if purpose != KarmaType::Sleeping {
+            continue;
+        }
+        let intervals = active_intervals(&tracked.statuses, now);
+        let (Some(first), Some(last)) = (intervals.first(), intervals.last()) else {
+            continue;
+        };
+        let (bedtime, wake) = (first.start, last.end);
+        let open = tracked
+            .statuses
+            .last()
+            .is_some_and(|status| status.state != State::Closed);
+        let slept: i64 = intervals.iter().map(ActiveInterval::seconds).sum();
+
+        let day = night_of(bedtime, wake, settings);
+        let night = nights.entry(day).or_insert_with(|| Night {
+            night: day,
+            bedtime,
+            wake,
+            bedtime_of_day: 0,
+            wake_of_day: 0,
+            slept_seconds: 0,
+            sessions: Vec::new(),
+            open: false,
+            debt_seconds: 0,
+        });
+        night.bedtime = night.bedtime.min(bedtime);
+        night.wake = night.wake.max(wake);
+        night.slept_seconds += slept;
+        night
+            .sessions
+            .push(tracked.session.get_id().unwrap_or_default());
+        night.open |= open;
+    }
+
+    nights
+        .into_values()
+        .map(|night| Night {
+            bedtime_of_day: time_of_day(night.bedtime, settings),
+            wake_of_day: time_of_day(night.wake, settings),
+            ..night
+        })
+        .collect()
+}
+
+fn time_of_day(timestamp: i64, settings: &TimeSettings) -> u32 {
+    let local = settings.timezone.local_datetime(timestamp);
+    (local - local.date().and_time(NaiveTime::MIN)).num_seconds() as u32
+}
+
+/// Sleep debt and consistency over the nights `from` to `to`
+pub fn sleep_report(
+    nights: Vec<Night>,
+    from: NaiveDate,
+    to: NaiveDate,
+    settings: &TimeSettings,
+    rules: &SleepRules,
+) -> SleepReport {
+    let target = rules.target_seconds();
+    let mut debt = 0;
+    let nights: Vec<Night> = nights
+        .into_iter()
+        .filter(|night| night.night >= from && night.night <= to)
+        .map(|night| {
+            if !night.open {
+                debt = (debt + target - night.slept_seconds).max(0);
+            }
+            Night {
+                debt_seconds: debt,
+                ..night
+            }
+        })
+        .collect();
+
+    let finished: Vec<&Night> = nights.iter().filter(|night| !night.open).collect();
+    let average_seconds = mean(finished.iter().map(|night| night.slept_seconds));
+
+    let bedtimes: Vec<i64> = nights
+        .iter()
+        .map(|night| night.since_noon(night.bedtime, settings))
+        .collect();
+    let wakes = finished
+        .iter()
+        .map(|night| night.since_noon(night.wake, settings));
+    let of_day = |since_noon: i64| ((since_noon + NOON as i64).rem_euclid(DAY_SECONDS)) as u32;
+
+    let spread = (bedtimes.len() >= 2).then(|| {
+        let average = bedtimes.iter().sum::<i64>() as f64 / bedtimes.len() as f64;
+        let variance = bedtimes
+            .iter()
+            .map(|bedtime| (*bedtime as f64 - average).powi(2))
+            .sum::<f64>()
+            / bedtimes.len() as f64;
+        variance.sqrt()
+    });
+
+    SleepReport {
+        from,
+        to,
+        target_seconds: target,
+        average_seconds,
+        debt_seconds: debt,
+        average_bedtime: mean(bedtimes.iter().copied()).map(of_day),
+        average_wake: mean(wakes).map(of_day),
+        bedtime_spread_seconds: spread.map(|spread| spread.round() as i64),
+        consistency: spread
+            .map(|spread| ((1.0 - spread / INCONSISTENT_SPREAD).max(0.0) * 100.0).round() as u32),
+        nights,
+    }
+}
+
+fn mean(values: impl Iterator<Item = i64>) -> Option<i64> {
+    let (sum, count) = values.fold((0, 0), |(sum, count), value| (sum + value, count + 1));
+    (count > 0).then(|| sum / count)
+}
+
+#[cfg(test)]
+mod sleep_tests {
+    use super::*;
+    use crate::model::karma::KarmaStatus;
+    use crate::model::karma_session::KarmaSession;
+    use crate::model::local_time::UserTimezone;
+
+    const HOUR: i64 = 60 * 60;
+    // Monday 2024-01-01 00:00 UTC
+    const MONDAY: i64 = 1_704_067_200;
+
+    fn settings() -> TimeSettings {
+        TimeSettings {
+            timezone: UserTimezone::parse("UTC").unwrap(),
+            ..TimeSettings::default()
+        }
+    }
+
+    fn sleep(
+        id: i32,
+        bedtime: i64,
+        wake: Option<i64>,
+        closed_with: Option<KarmaType>,
+    ) -> TrackedSession {
+        let mut statuses = vec![KarmaStatus::new(id, State::Active, bedtime)];
+        if let Some(wake) = wake {
+            statuses.push(match closed_with {
+                Some(actual) => KarmaStatus::with_closed_reason(id, State::Closed, wake, actual),
+                None => KarmaStatus::new(id, State::Closed, wake),
+            });
+        }
+        TrackedSession {
+            session: KarmaSession::with_id(id, 1, KarmaType::Sleeping, None, bedtime),
+            name: "Sleep".to_string(),
+            polarity: Polarity::Positive,
+            statuses,
+        }
+    }
+
+    #[test]
+    fn test_parse_rules() {
+        let rules = SleepRules::parse("target=7.5").unwrap();
+        assert_eq!(rules.target_seconds(), 7 * HOUR + HOUR / 2);
+        assert_eq!(rules.window_nights, 14);
+        assert_eq!(SleepRules::parse(&rules.to_string()).unwrap(), rules);
+        assert!(SleepRules::parse("target=25").is_err());
+        assert!(SleepRules::parse("window=0").is_err());
+        assert!(SleepRules::parse("nap=1").is_err());
+
+        assert!(parse_local_datetime("2024-01-01T23:30").is_ok());
+        assert!(parse_local_datetime("2024-01-01 23:30").is_ok());
+        assert!(parse_local_datetime("23:30").is_err());
+        assert!(validate_sleep(10, 5, 20).is_err());
+        assert!(validate_sleep(10, 20, 15).is_err());
+        assert!(validate_sleep(10, 20, 20).is_ok());
+    }
+
+    #[test]
+    fn test_nights_across_midnight() {
+        let settings = settings();
+        // Before and after midnight both belong to the night of the evening before
+        assert_eq!(
+            night_of(MONDAY + 23 * HOUR, MONDAY + 31 * HOUR, &settings).to_string(),
+            "2024-01-01"
+        );
+        assert_eq!(
+            night_of(MONDAY + 26 * HOUR, MONDAY + 35 * HOUR, &settings).to_string(),
+            "2024-01-01"
+        );
+        assert_eq!(
+            night_of(MONDAY + 14 * HOUR, MONDAY + 15 * HOUR, &settings).to_string(),
+            "2024-01-01"
+        );
+
+        let sessions = vec![
+            // Monday night, woken up in the middle of it
+            sleep(1, MONDAY + 23 * HOUR, Some(MONDAY + 27 * HOUR), None),
+            sleep(2, MONDAY + 28 * HOUR, Some(MONDAY + 31 * HOUR), None),
+            // Tuesday night after midnight, 6 hours
+            sleep(3, MONDAY + 49 * HOUR, Some(MONDAY + 55 * HOUR), None),
+            // Snoozing on as a habit to avoid is a relapse, not sleep
+            TrackedSession {
+                polarity: Polarity::Negative,
+                ..sleep(6, MONDAY + 55 * HOUR, Some(MONDAY + 57 * HOUR), None)
+            },
+            // Planned as sleep but it was work in the end
+            sleep(
+                4,
+                MONDAY + 58 * HOUR,
+                Some(MONDAY + 59 * HOUR),
+                Some(KarmaType::Work),
+            ),
+            // Wednesday night, 9 hours and still asleep
+            sleep(5, MONDAY + 70 * HOUR, None, None),
+        ];
+        let nights = sleep_nights(&sessions, MONDAY + 79 * HOUR, &settings);
+        let summary: Vec<(String, i64, bool)> = nights
+            .iter()
+            .map(|night| {
+                (
+                    night.night.to_string(),
+                    night.slept_seconds / HOUR,
+                    night.open,
+                )
+            })
+            .collect();
+        assert_eq!(
+            summary,
+            vec![
+                ("2024-01-01".to_string(), 7, false),
+                ("2024-01-02".to_string(), 6, false),
+                ("2024-01-03".to_string(), 9, true),
+            ]
+        );
+        assert_eq!(nights[0].sessions, vec![1, 2]);
+        assert_eq!(nights[0].bedtime_of_day, 23 * 3600);
+        assert_eq!(nights[0].wake_of_day, 7 * 3600);
+
+        let report = sleep_report(
+            nights,
+            NaiveDate::from_ymd_opt(2024, 1, 1).unwrap(),
+            NaiveDate::from_ymd_opt(2024, 1, 3).unwrap(),
+            &settings,
+            &SleepRules::default(),
+        );
+        // 1 hour short, then 2 more, the open night doesn't count yet
+        assert_eq!(report.debt_seconds, 3 * HOUR);
+        assert_eq!(report.nights[2].debt_seconds, 3 * HOUR);
+        assert_eq!(report.average_seconds, Some(6 * HOUR + HOUR / 2));
+        // Bedtimes 23:00, 01:00 and 22:00 average to 23:20
+        assert_eq!(report.average_bedtime, Some(23 * 3600 + 20 * 60));
+        assert_eq!(report.average_wake, Some(7 * 3600));
+        let spread = report.bedtime_spread_seconds.unwrap();
+        assert!((4480..4500).contains(&spread));
+        assert_eq!(report.consistency, Some(38));
+    }
+}
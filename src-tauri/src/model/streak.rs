@@ -0,0 +1,207 @@
+use std::collections::BTreeSet;
+
+use chrono::{Duration, NaiveDate};
+use serde::Serialize;
+
+use super::recurrence::Recurrence;
+use super::subject::KarmaSubject;
+
+/// How a due day counts towards a streak
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DayOutcome {
+    Done,
+    Missed,
+    /// Frozen or skipped, neither extends nor breaks the streak
+    Excused,
+}
+
+/// A streak counted up to the end of `settled_until`, so it can be carried on
+/// from there instead of going through the whole history again
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct StreakState {
+    pub scope: KarmaSubject,
+    pub current: u32,
+    pub longest: u32,
+    /// The first done day of the current streak
+    pub current_since: Option<NaiveDate>,
+    pub settled_until: Option<NaiveDate>,
+}
+
+impl StreakState {
+    pub fn new(scope: KarmaSubject) -> StreakState {
+        StreakState {
+            scope,
+            current: 0,
+            longest: 0,
+            current_since: None,
+            settled_until: None,
+        }
+    }
+
+    /// Counts the due days of `[from, to]` in order, `outcome` is only asked for due days
+    pub fn settle(
+        &mut self,
+        due_days: &[NaiveDate],
+        to: NaiveDate,
+        outcome: impl Fn(NaiveDate) -> DayOutcome,
+    ) {
+        for day in due_days
+            .iter()
+            .filter(|day| Some(**day) > self.settled_until && **day <= to)
+        {
+            match outcome(*day) {
+                DayOutcome::Done => {
+                    self.current += 1;
+                    self.current_since.get_or_insert(*day);
+                    self.longest = self.longest.max(self.current);
+                }
+                DayOutcome::Missed => {
+                    self.current = 0;
+                    self.current_since = None;
+                }
+                DayOutcome::Excused => {}
+            }
+        }
+        self.settled_until = Some(self.settled_until.map_or(to, |until| until.max(to)));
+    }
+}
+
+/// A streak as shown to the user, today counts once it's done but can't break it yet
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Streak {
+    pub scope: KarmaSubject,
+    /// The template name or the type
+    pub name: String,
+    pub current: u32,
+    pub longest: u32,
+    pub current_since: Option<NaiveDate>,
+    pub due_today: bool,
+    pub done_today: bool,
+    /// Due today and not done yet, the streak breaks at midnight
+    pub at_risk: bool,
+}
+
+impl Streak {
+    pub fn of(state: &StreakState, name: String, due_today: bool, done_today: bool) -> Streak {
+        let today = state.settled_until.and_then(|until| until.succ_opt());
+        let (current, current_since) = if due_today && done_today {
+            (state.current + 1, state.current_since.or(today))
+        } else {
+            (state.current, state.current_since)
+        };
+
+        Streak {
+            scope: state.scope.clone(),
+            name,
+            current,
+            longest: state.longest.max(current),
+            current_since,
+            due_today,
+            done_today,
+            at_risk: current > 0 && due_today && !done_today,
+        }
+    }
+}
+
+/// The days of `[from, to]` a scope expects something on, the scheduled ones for a
+/// template and every day for a category
+pub fn due_days(recurrence: Option<&Recurrence>, from: NaiveDate, to: NaiveDate) -> Vec<NaiveDate> {
+    match recurrence {
+        Some(recurrence) => recurrence.occurrences(from, to),
+        None => {
+            let mut days = Vec::new();
+            let mut day = from;
+            while day <= to {
+                days.push(day);
+                day += Duration::days(1);
+            }
+            days
+        }
+    }
+}
+
+/// Done when something happened that day, excused when frozen or skipped, missed otherwise
+pub fn day_outcome(
+    day: NaiveDate,
+    done: &BTreeSet<NaiveDate>,
+    excused: &BTreeSet<NaiveDate>,
+) -> DayOutcome {
+    if done.contains(&day) {
+        DayOutcome::Done
+    } else if excused.contains(&day) {
+        DayOutcome::Excused
+    } else {
+        DayOutcome::Missed
+    }
+}
+
+#[cfg(test)]
+mod streak_tests {
+    use super::*;
+    use crate::model::karma::KarmaType;
+    use crate::model::recurrence::{parse_date, RecurrenceRule};
+
+    fn days(values: &[&str]) -> BTreeSet<NaiveDate> {
+        values.iter().map(|day| parse_date(day).unwrap()).collect()
+    }
+
+    #[test]
+    fn test_rest_days_and_freezes_dont_break() {
+        // Monday, Wednesday and Friday from 2024-01-01
+        let recurrence = Recurrence {
+            rule: RecurrenceRule::parse("FREQ=WEEKLY;BYDAY=MO,WE,FR").unwrap(),
+            starts_on: parse_date("2024-01-01").unwrap(),
+            start_time: None,
+            exceptions: BTreeSet::new(),
+        };
+        let done = days(&["2024-01-01", "2024-01-03", "2024-01-08", "2024-01-10"]);
+        let frozen = days(&["2024-01-05"]);
+
+        let first = parse_date("2024-01-01").unwrap();
+        let yesterday = parse_date("2024-01-11").unwrap();
+        let mut state = StreakState::new(KarmaSubject::Template(1));
+        state.settle(
+            &due_days(Some(&recurrence), first, yesterday),
+            yesterday,
+            |day| day_outcome(day, &done, &frozen),
+        );
+        assert_eq!(state.current, 4);
+        assert_eq!(state.current_since, Some(first));
+
+        // Friday the 12th is due and not done yet
+        let streak = Streak::of(&state, "Gym".to_string(), true, false);
+        assert!(streak.at_risk);
+
+        // Carried on incrementally, the missed Friday ends it
+        let monday = parse_date("2024-01-15").unwrap();
+        state.settle(
+            &due_days(Some(&recurrence), yesterday + Duration::days(1), monday),
+            monday,
+            |day| day_outcome(day, &days(&["2024-01-15"]), &BTreeSet::new()),
+        );
+        assert_eq!(state.current, 1);
+        assert_eq!(state.longest, 4);
+        assert_eq!(state.current_since, Some(monday));
+    }
+
+    #[test]
+    fn test_today_counts_once_done() {
+        let yesterday = parse_date("2024-01-02").unwrap();
+        let mut state = StreakState::new(KarmaSubject::Category(KarmaType::Sport));
+        state.settle(
+            &due_days(None, parse_date("2024-01-01").unwrap(), yesterday),
+            yesterday,
+            |_| DayOutcome::Done,
+        );
+
+        let streak = Streak::of(&state, "Sport".to_string(), true, true);
+        assert_eq!(streak.current, 3);
+        assert_eq!(streak.longest, 3);
+        assert!(!streak.at_risk);
+        assert_eq!(streak.scope.to_string(), "category:3");
+        assert_eq!(
+            KarmaSubject::from_parts("category", 3),
+            Some(KarmaSubject::Category(KarmaType::Sport))
+        );
+    }
+}
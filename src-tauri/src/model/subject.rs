@@ -0,0 +1,63 @@
+use std::fmt;
+
+use serde::{Serialize, Serializer};
+
+use super::duration::TrackedSession;
+use super::karma::{KarmaType, Polarity};
+
+/// What goals and streaks are about: one template, or every session of a type
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+pub enum KarmaSubject {
+    Template(i32),
+    Category(KarmaType),
+}
+
+impl KarmaSubject {
+    /// Stored as a kind and an id, the template id or the numeric type
+    pub fn kind(&self) -> &'static str {
+        match self {
+            KarmaSubject::Template(_) => "template",
+            KarmaSubject::Category(_) => "category",
+        }
+    }
+
+    pub fn id(&self) -> i32 {
+        match self {
+            KarmaSubject::Template(template_id) => *template_id,
+            KarmaSubject::Category(purpose) => purpose.clone() as i32,
+        }
+    }
+
+    pub fn from_parts(kind: &str, id: i32) -> Option<KarmaSubject> {
+        match kind {
+            "template" => Some(KarmaSubject::Template(id)),
+            "category" => KarmaType::try_from(id).ok().map(KarmaSubject::Category),
+            _ => None,
+        }
+    }
+
+    /// Relapses are never about the subject, even when it's their type
+    pub fn matches(&self, tracked: &TrackedSession) -> bool {
+        if tracked.polarity == Polarity::Negative {
+            return false;
+        }
+        match self {
+            KarmaSubject::Template(template_id) => {
+                tracked.session.get_template_id() == *template_id
+            }
+            KarmaSubject::Category(purpose) => tracked.session.get_purpose() == *purpose,
+        }
+    }
+}
+
+impl fmt::Display for KarmaSubject {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}:{}", self.kind(), self.id())
+    }
+}
+
+impl Serialize for KarmaSubject {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
@@ -0,0 +1,696 @@
+use std::collections::BTreeMap;
+use std::fmt;
+
+use chrono::{Datelike, NaiveDate, Timelike, Weekday};
+use serde::Serialize;
+use thiserror::Error;
+
+use super::deviation::SessionOutcome;
+use super::duration::{active_intervals, TrackedSession};
+use super::goal::{Goal, GoalComparison, GoalMetric};
+use super::karma::{KarmaType, Polarity};
+use super::local_time::TimeSettings;
+use super::recurrence::{format_time_of_day, parse_time_of_day, parse_weekday, weekday_code};
+use super::report::ReportPeriod;
+use super::subject::KarmaSubject;
+
+pub const SUGGESTION_RULES_SETTING: &str = "suggestion_rules";
+
+const DAY_SECONDS: u32 = 24 * 60 * 60;
+
+#[derive(Debug, Error, Serialize)]
+pub enum SuggestionError {
+    #[error(
+        "Invalid suggestion rule {0}, expected daily=hours, block=minutes, \
+        from=HH:MM or to=HH:MM"
+    )]
+    InvalidRule(String),
+
+    #[error("Invalid blocked time {0}, expected weekdays such as MO,TU then HH:MM-HH:MM")]
+    InvalidBlockedTime(String),
+}
+
+/// How the suggested schedule is laid out
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct SuggestionRules {
+    /// The most hours planned on a day, commitments included
+    pub daily_hours: f64,
+    /// Length of a suggested session, longer ones are made of several
+    pub block_minutes: u32,
+    /// Nothing is suggested before this local time of day, in seconds
+    pub day_starts: u32,
+    /// Nor after this one
+    pub day_ends: u32,
+}
+
+impl SuggestionRules {
+    /// Comma separated `daily=hours`, `block=minutes`, `from=HH:MM` and `to=HH:MM`,
+    /// e.g. `daily=6, from=07:30`. Rules left out keep their value.
+    pub fn parse(value: &str) -> Result<SuggestionRules, SuggestionError> {
+        let mut rules = SuggestionRules::default();
+
+        for rule in value
+            .split(',')
+            .map(str::trim)
+            .filter(|rule| !rule.is_empty())
+        {
+            let invalid = || SuggestionError::InvalidRule(rule.to_string());
+            let (name, amount) = rule.split_once('=').ok_or_else(invalid)?;
+            let amount = amount.trim();
+
+            match name.trim().to_lowercase().as_str() {
+                "daily" => {
+                    rules.daily_hours = amount
+                        .parse()
+                        .ok()
+                        .filter(|hours: &f64| *hours > 0.0 && *hours <= 24.0)
+                        .ok_or_else(invalid)?
+                }
+                "block" => {
+                    rules.block_minutes = amount
+                        .parse()
+                        .ok()
+                        .filter(|minutes| (5..=24 * 60).contains(minutes))
+                        .ok_or_else(invalid)?
+                }
+                "from" => rules.day_starts = parse_time_of_day(amount).map_err(|_| invalid())?,
+                "to" => {
+                    // 24:00 isn't a time of day but the natural end of one
+                    rules.day_ends = match amount {
+                        "24:00" => DAY_SECONDS,
+                        amount => parse_time_of_day(amount).map_err(|_| invalid())?,
+                    }
+                }
+                _ => return Err(invalid()),
+            }
+        }
+
+        if rules.day_starts >= rules.day_ends {
+            return Err(SuggestionError::InvalidRule(value.to_string()));
+        }
+        Ok(rules)
+    }
+
+    fn block_seconds(&self) -> i64 {
+        self.block_minutes as i64 * 60
+    }
+}
+
+impl Default for SuggestionRules {
+    fn default() -> Self {
+        SuggestionRules {
+            daily_hours: 8.0,
+            block_minutes: 60,
+            day_starts: 8 * 3600,
+            day_ends: 22 * 3600,
+        }
+    }
+}
+
+impl fmt::Display for SuggestionRules {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let day_ends = match self.day_ends {
+            DAY_SECONDS => "24:00".to_string(),
+            day_ends => format_time_of_day(day_ends),
+        };
+        write!(
+            f,
+            "daily={},block={},from={},to={day_ends}",
+            self.daily_hours,
+            self.block_minutes,
+            format_time_of_day(self.day_starts)
+        )
+    }
+}
+
+/// Time kept free of suggestions every week, such as office hours
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct BlockedTime {
+    pub id: Option<i32>,
+    pub label: String,
+    /// Every day when empty
+    pub weekdays: Vec<Weekday>,
+    /// Local times of day, in seconds
+    pub starts_at: u32,
+    pub ends_at: u32,
+}
+
+impl BlockedTime {
+    /// `MO,TU,WE 09:00-17:00`, or only the times for every day
+    pub fn parse(label: &str, value: &str) -> Result<BlockedTime, SuggestionError> {
+        let invalid = || SuggestionError::InvalidBlockedTime(value.to_string());
+        let value = value.trim();
+        let (days, times) = value.rsplit_once(' ').unwrap_or(("", value));
+        let (starts_at, ends_at) = times.split_once('-').ok_or_else(invalid)?;
+        let starts_at = parse_time_of_day(starts_at.trim()).map_err(|_| invalid())?;
+        let ends_at = match ends_at.trim() {
+            "24:00" => DAY_SECONDS,
+            ends_at => parse_time_of_day(ends_at).map_err(|_| invalid())?,
+        };
+        if starts_at >= ends_at {
+            return Err(invalid());
+        }
+
+        let mut weekdays = Vec::new();
+        for day in days.split(',').filter(|day| !day.trim().is_empty()) {
+            let weekday = parse_weekday(day).ok_or_else(invalid)?;
+            if !weekdays.contains(&weekday) {
+                weekdays.push(weekday);
+            }
+        }
+        weekdays.sort_by_key(|weekday| weekday.num_days_from_monday());
+
+        Ok(BlockedTime {
+            id: None,
+            label: label.trim().to_string(),
+            weekdays,
+            starts_at,
+            ends_at,
+        })
+    }
+
+    pub fn applies_on(&self, day: NaiveDate) -> bool {
+        self.weekdays.is_empty() || self.weekdays.contains(&day.weekday())
+    }
+}
+
+impl fmt::Display for BlockedTime {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if !self.weekdays.is_empty() {
+            let days: Vec<&str> = self.weekdays.iter().map(|day| weekday_code(*day)).collect();
+            write!(f, "{} ", days.join(","))?;
+        }
+        let ends_at = match self.ends_at {
+            DAY_SECONDS => "24:00".to_string(),
+            ends_at => format_time_of_day(ends_at),
+        };
+        write!(f, "{}-{ends_at}", format_time_of_day(self.starts_at))
+    }
+}
+
+/// Time already taken on a day of the proposal
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Commitment {
+    pub day: NaiveDate,
+    pub name: String,
+    /// Counts towards the type's target and the daily limit, blocked time has none
+    pub purpose: Option<KarmaType>,
+    /// Local time of day in seconds, no particular time when missing
+    pub starts_at: Option<u32>,
+    pub duration: i64,
+}
+
+impl Commitment {
+    pub fn blocked(day: NaiveDate, blocked: &BlockedTime) -> Commitment {
+        Commitment {
+            day,
+            name: blocked.label.clone(),
+            purpose: None,
+            starts_at: Some(blocked.starts_at),
+            duration: (blocked.ends_at - blocked.starts_at) as i64,
+        }
+    }
+}
+
+/// What the goals on a type ask of a week, in seconds
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct SuggestionTarget {
+    pub weekly: i64,
+    /// The most time a day may get
+    pub daily_cap: Option<i64>,
+}
+
+/// The weekly targets of the types with an at least goal in hours. Daily goals count
+/// for every day of the week, at most goals cap what is suggested.
+pub fn suggestion_targets(goals: &[Goal]) -> BTreeMap<KarmaType, SuggestionTarget> {
+    let mut targets: BTreeMap<KarmaType, SuggestionTarget> = BTreeMap::new();
+    let mut weekly_caps: BTreeMap<KarmaType, i64> = BTreeMap::new();
+
+    for goal in goals {
+        let KarmaSubject::Category(purpose) = &goal.subject else {
+            continue;
+        };
+        let seconds = (goal.target * 3600.0).round() as i64;
+        let weekly = match (goal.metric, goal.period) {
+            (GoalMetric::Hours, ReportPeriod::Week) => seconds,
+            (GoalMetric::DailyHours, _) => seconds * 7,
+            _ => continue,
+        };
+
+        match goal.comparison {
+            GoalComparison::AtLeast => {
+                let target = targets.entry(purpose.clone()).or_default();
+                target.weekly = target.weekly.max(weekly);
+            }
+            GoalComparison::AtMost => {
+                if goal.metric == GoalMetric::DailyHours {
+                    let target = targets.entry(purpose.clone()).or_default();
+                    target.daily_cap =
+                        Some(target.daily_cap.map_or(seconds, |cap| cap.min(seconds)));
+                }
+                let cap = weekly_caps.entry(purpose.clone()).or_insert(weekly);
+                *cap = (*cap).min(weekly);
+            }
+        }
+    }
+
+    for (purpose, cap) in weekly_caps {
+        if let Some(target) = targets.get_mut(&purpose) {
+            target.weekly = target.weekly.min(cap);
+        }
+    }
+    targets
+}
+
+/// The activity a type got the most time from
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct UsualActivity {
+    pub template_id: i32,
+    pub name: String,
+}
+
+/// When and with what each type was done before
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct SuggestionHistory {
+    /// Active seconds per local hour of the day
+    pub hours: BTreeMap<KarmaType, [i64; 24]>,
+    pub activities: BTreeMap<KarmaType, UsualActivity>,
+}
+
+impl SuggestionHistory {
+    /// Time counts as the type sessions ended as, relapses don't count
+    pub fn collect(
+        sessions: &[TrackedSession],
+        from: i64,
+        to: i64,
+        now: i64,
+        settings: &TimeSettings,
+    ) -> SuggestionHistory {
+        let mut history = SuggestionHistory::default();
+        let mut per_activity: BTreeMap<KarmaType, BTreeMap<(i32, String), i64>> = BTreeMap::new();
+
+        for tracked in sessions
+            .iter()
+            .filter(|tracked| tracked.polarity == Polarity::Positive)
+        {
+            let purpose = SessionOutcome::of(tracked, settings)
+                .map_or(tracked.session.get_purpose(), |outcome| outcome.actual);
+            let hours = history.hours.entry(purpose.clone()).or_insert([0; 24]);
+
+            for interval in active_intervals(&tracked.statuses, now)
+                .iter()
+                .filter_map(|interval| interval.clip(from, to))
+            {
+                *per_activity
+                    .entry(purpose.clone())
+                    .or_default()
+                    .entry((tracked.session.get_template_id(), tracked.name.clone()))
+                    .or_default() += interval.seconds();
+
+                // Up to each local full hour, so offsets of half hours work too
+                let mut start = interval.start;
+                while start < interval.end {
+                    let local = settings.timezone.local_datetime(start);
+                    let into_hour = (local.minute() * 60 + local.second()) as i64;
+                    let end = (start + 3600 - into_hour).min(interval.end);
+                    hours[local.hour() as usize] += end - start;
+                    start = end;
+                }
+            }
+        }
+
+        history.activities = per_activity
+            .into_iter()
+            .filter_map(|(purpose, activities)| {
+                // The most time first, then the first name
+                let ((template_id, name), _) =
+                    activities
+                        .into_iter()
+                        .min_by(|(a, a_seconds), (b, b_seconds)| {
+                            b_seconds.cmp(a_seconds).then(a.1.cmp(&b.1))
+                        })?;
+                Some((purpose, UsualActivity { template_id, name }))
+            })
+            .collect();
+        history
+    }
+
+    /// How much the type was done at the hours `[starts_at, starts_at + duration)` cover
+    fn preference(&self, purpose: &KarmaType, starts_at: i64, duration: i64) -> i64 {
+        let Some(hours) = self.hours.get(purpose) else {
+            return 0;
+        };
+        let first = (starts_at / 3600) as usize;
+        let last = ((starts_at + duration - 1) / 3600) as usize;
+        hours[first.min(23)..=last.min(23)].iter().sum()
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct SuggestedSession {
+    pub day: NaiveDate,
+    /// Local time of day, in seconds
+    pub starts_at: u32,
+    pub duration: i64,
+    pub purpose: KarmaType,
+    /// The activity usually done as the type, it can't be planned without one
+    pub activity: Option<UsualActivity>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct CategoryProposal {
+    pub purpose: KarmaType,
+    pub target_seconds: i64,
+    /// Tracked this week so far
+    pub done_seconds: i64,
+    /// By recurring activities and planned entries
+    pub committed_seconds: i64,
+    pub suggested_seconds: i64,
+    /// Still missing once no room was left within the limits
+    pub unmet_seconds: i64,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct ScheduleProposal {
+    pub week_start: NaiveDate,
+    /// Days of the week still to come, the only ones suggestions go to
+    pub days: Vec<NaiveDate>,
+    /// By day and time
+    pub suggestions: Vec<SuggestedSession>,
+    pub categories: Vec<CategoryProposal>,
+}
+
+/// Placements compare by type time on the day, the negated preference, the day index
+/// and the start
+type PlacementKey = (i64, i64, usize, i64);
+
+struct DayPlanning {
+    day: NaiveDate,
+    /// Local `[start, end)` in seconds of the day
+    busy: Vec<(i64, i64)>,
+    load: i64,
+    per_type: BTreeMap<KarmaType, i64>,
+}
+
+impl DayPlanning {
+    fn is_free(&self, start: i64, end: i64) -> bool {
+        self.busy
+            .iter()
+            .all(|(busy_start, busy_end)| end <= *busy_start || start >= *busy_end)
+    }
+
+    fn of_type(&self, purpose: &KarmaType) -> i64 {
+        self.per_type.get(purpose).copied().unwrap_or_default()
+    }
+}
+
+/// Fills `days` with blocks of the types still short of their target, one block at
+/// a time to the type furthest from it. A block goes to the day with the least of its
+/// type so far, then to the hours the type was done at most before, then to the
+/// earliest day and time. The daily limits and the busy time are never exceeded, so
+/// the same input always gives the same proposal.
+pub fn suggest_schedule(
+    week_start: NaiveDate,
+    days: &[NaiveDate],
+    targets: &BTreeMap<KarmaType, SuggestionTarget>,
+    done: &BTreeMap<KarmaType, i64>,
+    commitments: &[Commitment],
+    history: &SuggestionHistory,
+    rules: &SuggestionRules,
+) -> ScheduleProposal {
+    let block = rules.block_seconds();
+    let daily_limit = (rules.daily_hours * 3600.0).round() as i64;
+    let (day_starts, day_ends) = (rules.day_starts as i64, rules.day_ends as i64);
+
+    let mut planning: Vec<DayPlanning> = days
+        .iter()
+        .map(|day| DayPlanning {
+            day: *day,
+            busy: Vec::new(),
+            load: 0,
+            per_type: BTreeMap::new(),
+        })
+        .collect();
+    let mut committed: BTreeMap<KarmaType, i64> = BTreeMap::new();
+    for commitment in commitments {
+        let Some(day) = planning.iter_mut().find(|day| day.day == commitment.day) else {
+            continue;
+        };
+        if let Some(starts_at) = commitment.starts_at {
+            day.busy
+                .push((starts_at as i64, starts_at as i64 + commitment.duration));
+        }
+        if let Some(purpose) = &commitment.purpose {
+            day.load += commitment.duration;
+            *day.per_type.entry(purpose.clone()).or_default() += commitment.duration;
+            *committed.entry(purpose.clone()).or_default() += commitment.duration;
+        }
+    }
+
+    let amount = |map: &BTreeMap<KarmaType, i64>, purpose: &KarmaType| {
+        map.get(purpose).copied().unwrap_or_default()
+    };
+    let mut remaining: BTreeMap<KarmaType, i64> = targets
+        .iter()
+        .map(|(purpose, target)| {
+            let left = target.weekly - amount(done, purpose) - amount(&committed, purpose);
+            (purpose.clone(), left.max(0))
+        })
+        .collect();
+    let mut open: Vec<KarmaType> = remaining
+        .iter()
+        .filter(|(_, seconds)| **seconds > 0)
+        .map(|(purpose, _)| purpose.clone())
+        .collect();
+
+    let mut suggestions: Vec<SuggestedSession> = Vec::new();
+    // Ties go to the type coming first
+    while let Some(purpose) = open
+        .iter()
+        .rev()
+        .max_by_key(|purpose| remaining[*purpose])
+        .cloned()
+    {
+        let duration = remaining[&purpose].min(block);
+        let cap = targets[&purpose].daily_cap;
+
+        let mut best: Option<(PlacementKey, usize, i64)> = None;
+        for (index, day) in planning.iter().enumerate() {
+            if day.load + duration > daily_limit
+                || cap.is_some_and(|cap| day.of_type(&purpose) + duration > cap)
+            {
+                continue;
+            }
+            let mut start = day_starts;
+            while start + duration <= day_ends {
+                if day.is_free(start, start + duration) {
+                    let key = (
+                        day.of_type(&purpose),
+                        -history.preference(&purpose, start, duration),
+                        index,
+                        start,
+                    );
+                    if best.as_ref().map_or(true, |(best, _, _)| key < *best) {
+                        best = Some((key, index, start));
+                    }
+                }
+                start += block;
+            }
+        }
+
+        let Some((_, index, start)) = best else {
+            open.retain(|open| *open != purpose);
+            continue;
+        };
+        let day = &mut planning[index];
+        day.busy.push((start, start + duration));
+        day.load += duration;
+        *day.per_type.entry(purpose.clone()).or_default() += duration;
+        suggestions.push(SuggestedSession {
+            day: day.day,
+            starts_at: start as u32,
+            duration,
+            purpose: purpose.clone(),
+            activity: history.activities.get(&purpose).cloned(),
+        });
+
+        let left = remaining.entry(purpose.clone()).or_default();
+        *left -= duration;
+        if *left <= 0 {
+            open.retain(|open| *open != purpose);
+        }
+    }
+
+    // Back to back blocks of a type make one longer session
+    suggestions.sort_by_key(|suggestion| (suggestion.day, suggestion.starts_at));
+    let mut merged: Vec<SuggestedSession> = Vec::new();
+    for suggestion in suggestions {
+        match merged.last_mut() {
+            Some(last)
+                if last.day == suggestion.day
+                    && last.purpose == suggestion.purpose
+                    && last.starts_at as i64 + last.duration == suggestion.starts_at as i64 =>
+            {
+                last.duration += suggestion.duration;
+            }
+            _ => merged.push(suggestion),
+        }
+    }
+
+    let categories = targets
+        .iter()
+        .map(|(purpose, target)| CategoryProposal {
+            purpose: purpose.clone(),
+            target_seconds: target.weekly,
+            done_seconds: amount(done, purpose),
+            committed_seconds: amount(&committed, purpose),
+            suggested_seconds: merged
+                .iter()
+                .filter(|suggestion| suggestion.purpose == *purpose)
+                .map(|suggestion| suggestion.duration)
+                .sum(),
+            unmet_seconds: amount(&remaining, purpose),
+        })
+        .collect();
+
+    ScheduleProposal {
+        week_start,
+        days: days.to_vec(),
+        suggestions: merged,
+        categories,
+    }
+}
+
+#[cfg(test)]
+mod suggestion_tests {
+    use super::*;
+    use crate::model::recurrence::parse_date;
+
+    const HOUR: i64 = 60 * 60;
+
+    #[test]
+    fn test_parse_rules_and_blocked_time() {
+        let rules = SuggestionRules::parse("daily=3, block=30, from=07:30").unwrap();
+        assert_eq!(rules.daily_hours, 3.0);
+        assert_eq!(rules.block_minutes, 30);
+        assert_eq!(rules.day_starts, 7 * 3600 + 1800);
+        assert_eq!(SuggestionRules::parse(&rules.to_string()).unwrap(), rules);
+        assert!(SuggestionRules::parse("from=22:00, to=08:00").is_err());
+        assert!(SuggestionRules::parse("daily=0").is_err());
+
+        let office = BlockedTime::parse("Office", "fr,MO 09:00-17:00").unwrap();
+        assert_eq!(office.weekdays, vec![Weekday::Mon, Weekday::Fri]);
+        assert_eq!(office.to_string(), "MO,FR 09:00-17:00");
+        assert!(office.applies_on(parse_date("2024-01-01").unwrap()));
+        assert!(!office.applies_on(parse_date("2024-01-02").unwrap()));
+        assert!(BlockedTime::parse("Night", "23:00-24:00")
+            .unwrap()
+            .applies_on(parse_date("2024-01-02").unwrap()));
+        assert!(BlockedTime::parse("Office", "MO 17:00-09:00").is_err());
+    }
+
+    #[test]
+    fn test_suggestions_fill_targets_within_limits() {
+        let rules = SuggestionRules::parse("daily=3, block=60, from=08:00, to=12:00").unwrap();
+        let days: Vec<NaiveDate> = ["2024-01-01", "2024-01-02", "2024-01-03"]
+            .iter()
+            .map(|day| parse_date(day).unwrap())
+            .collect();
+        let targets = BTreeMap::from([
+            (
+                KarmaType::Learning,
+                SuggestionTarget {
+                    weekly: 5 * HOUR,
+                    daily_cap: None,
+                },
+            ),
+            (
+                KarmaType::Sport,
+                SuggestionTarget {
+                    weekly: 2 * HOUR,
+                    daily_cap: Some(HOUR),
+                },
+            ),
+        ]);
+        let done = BTreeMap::from([(KarmaType::Learning, HOUR)]);
+        let commitments = vec![
+            Commitment {
+                day: days[0],
+                name: "Standup".to_string(),
+                purpose: Some(KarmaType::Work),
+                starts_at: Some(9 * 3600),
+                duration: 2 * HOUR,
+            },
+            Commitment::blocked(
+                days[1],
+                &BlockedTime::parse("Doctor", "08:00-10:00").unwrap(),
+            ),
+        ];
+        let mut history = SuggestionHistory::default();
+        history.hours.insert(KarmaType::Learning, {
+            let mut hours = [0; 24];
+            hours[9] = 10 * HOUR;
+            hours[10] = HOUR;
+            hours
+        });
+        history.hours.insert(KarmaType::Sport, {
+            let mut hours = [0; 24];
+            hours[11] = 5 * HOUR;
+            hours
+        });
+
+        let proposal = suggest_schedule(
+            days[0],
+            &days,
+            &targets,
+            &done,
+            &commitments,
+            &history,
+            &rules,
+        );
+        let suggestions: Vec<(NaiveDate, u32, i64, KarmaType)> = proposal
+            .suggestions
+            .iter()
+            .map(|suggestion| {
+                (
+                    suggestion.day,
+                    suggestion.starts_at / 3600,
+                    suggestion.duration / HOUR,
+                    suggestion.purpose.clone(),
+                )
+            })
+            .collect();
+        assert_eq!(
+            suggestions,
+            vec![
+                (days[0], 11, 1, KarmaType::Sport),
+                (days[1], 10, 1, KarmaType::Learning),
+                (days[1], 11, 1, KarmaType::Sport),
+                (days[2], 8, 3, KarmaType::Learning),
+            ]
+        );
+        assert!(proposal
+            .categories
+            .iter()
+            .all(|category| category.unmet_seconds == 0));
+
+        // Without room left the rest is reported as unmet
+        let rules = SuggestionRules::parse("daily=1").unwrap();
+        let proposal = suggest_schedule(
+            days[0],
+            &days,
+            &targets,
+            &done,
+            &commitments,
+            &history,
+            &rules,
+        );
+        let learning = &proposal.categories[1];
+        assert_eq!(learning.purpose, KarmaType::Learning);
+        assert_eq!(
+            learning.suggested_seconds + learning.unmet_seconds,
+            4 * HOUR
+        );
+        assert!(learning.unmet_seconds > 0);
+    }
+}
@@ -0,0 +1,220 @@
+use data_encoding::{BASE32_NOPAD, HEXLOWER};
+use hmac::{Hmac, Mac};
+use rand::{distributions::Slice, Rng, RngCore};
+use sha1::Sha1;
+use sha2::Sha256;
+use thiserror::Error;
+
+pub const TOTP_ISSUER: &str = "Karma Manager";
+pub const TOTP_DIGITS: u32 = 6;
+pub const TOTP_PERIOD: i64 = 30;
+/// How many periods before and after the current one are still accepted
+pub const TOTP_SKEW_STEPS: i64 = 1;
+
+const SECRET_SIZE: usize = 20;
+const BACKUP_CODE_COUNT: usize = 10;
+const BACKUP_CODE_SIZE: usize = 10;
+const BACKUP_CODE_ALPHABET: &[char] = &[
+    'a', 'b', 'c', 'd', 'e', 'f', 'g', 'h', 'j', 'k', 'm', 'n', 'p', 'q', 'r', 's', 't', 'u', 'v',
+    'w', 'x', 'y', 'z', '2', '3', '4', '5', '6', '7', '8', '9',
+];
+
+#[derive(Debug, Error)]
+pub enum TotpError {
+    #[error("The TOTP secret is not valid base32")]
+    InvalidSecret,
+}
+
+/// RFC 6238 time based one time passwords, using the defaults every
+/// authenticator app understands: HMAC-SHA1, 6 digits, 30 second periods
+#[derive(Debug, Clone, PartialEq)]
+pub struct Totp {
+    secret: Vec<u8>,
+}
+
+impl Totp {
+    pub fn generate() -> Totp {
+        let mut secret = vec![0u8; SECRET_SIZE];
+        rand::thread_rng().fill_bytes(&mut secret);
+        Totp { secret }
+    }
+
+    pub fn from_base32(secret: &str) -> Result<Totp, TotpError> {
+        let secret = BASE32_NOPAD
+            .decode(secret.trim_end_matches('=').as_bytes())
+            .map_err(|_| TotpError::InvalidSecret)?;
+        Ok(Totp { secret })
+    }
+
+    pub fn to_base32(&self) -> String {
+        BASE32_NOPAD.encode(&self.secret)
+    }
+
+    /// The `otpauth://` URI authenticator apps read from a QR code
+    pub fn provisioning_uri(&self, account: &str) -> String {
+        format!(
+            "otpauth://totp/{issuer}:{account}?secret={secret}&issuer={issuer}&algorithm=SHA1&digits={TOTP_DIGITS}&period={TOTP_PERIOD}",
+            issuer = percent_encode(TOTP_ISSUER),
+            account = percent_encode(account),
+            secret = self.to_base32(),
+        )
+    }
+
+    pub fn step(timestamp: i64) -> i64 {
+        timestamp.div_euclid(TOTP_PERIOD)
+    }
+
+    pub fn code_at_step(&self, step: i64) -> String {
+        // The key size is not restricted for HMAC, this can't fail
+        let mut mac = Hmac::<Sha1>::new_from_slice(&self.secret).expect("HMAC accepts any key");
+        mac.update(&(step as u64).to_be_bytes());
+        let digest = mac.finalize().into_bytes();
+
+        // Dynamic truncation, RFC 4226 section 5.3
+        let offset = (digest[digest.len() - 1] & 0x0f) as usize;
+        let binary = u32::from_be_bytes([
+            digest[offset] & 0x7f,
+            digest[offset + 1],
+            digest[offset + 2],
+            digest[offset + 3],
+        ]);
+
+        format!(
+            "{:0width$}",
+            binary % 10u32.pow(TOTP_DIGITS),
+            width = TOTP_DIGITS as usize
+        )
+    }
+
+    #[allow(dead_code)]
+    pub fn code_at(&self, timestamp: i64) -> String {
+        self.code_at_step(Totp::step(timestamp))
+    }
+
+    /// Returns the step the code matched, within the allowed clock skew
+    pub fn verify(&self, code: &str, timestamp: i64) -> Option<i64> {
+        let code: String = code.chars().filter(|ch| !ch.is_whitespace()).collect();
+        let current = Totp::step(timestamp);
+
+        (current - TOTP_SKEW_STEPS..=current + TOTP_SKEW_STEPS)
+            .find(|step| constant_time_eq(self.code_at_step(*step).as_bytes(), code.as_bytes()))
+    }
+
+    /// Backup codes are random enough for a fast hash, keyed with the secret
+    /// so the same code never hashes alike for two accounts
+    pub fn backup_code_hash(&self, code: &str) -> String {
+        let mut mac = Hmac::<Sha256>::new_from_slice(&self.secret).expect("HMAC accepts any key");
+        mac.update(normalize_backup_code(code).as_bytes());
+        HEXLOWER.encode(&mac.finalize().into_bytes())
+    }
+}
+
+/// Single use codes to log in when the authenticator is lost, shown to the user once
+pub fn generate_backup_codes() -> Vec<String> {
+    let alphabet = Slice::new(BACKUP_CODE_ALPHABET).expect("the alphabet is not empty");
+    let mut rng = rand::thread_rng();
+
+    (0..BACKUP_CODE_COUNT)
+        .map(|_| {
+            let code: String = (&mut rng)
+                .sample_iter(&alphabet)
+                .take(BACKUP_CODE_SIZE)
+                .collect();
+            format!(
+                "{}-{}",
+                &code[..BACKUP_CODE_SIZE / 2],
+                &code[BACKUP_CODE_SIZE / 2..]
+            )
+        })
+        .collect()
+}
+
+/// Backup codes are accepted regardless of case and separators
+pub fn normalize_backup_code(code: &str) -> String {
+    code.chars()
+        .filter(|ch| ch.is_ascii_alphanumeric())
+        .map(|ch| ch.to_ascii_lowercase())
+        .collect()
+}
+
+fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0u8, |acc, (x, y)| acc | (x ^ y)) == 0
+}
+
+fn percent_encode(value: &str) -> String {
+    value
+        .bytes()
+        .map(|byte| match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'_' | b'.' | b'~' => {
+                (byte as char).to_string()
+            }
+            _ => format!("%{byte:02X}"),
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod totp_tests {
+    use super::*;
+
+    // The RFC 6238 appendix B secret, "12345678901234567890" in ASCII
+    fn rfc_totp() -> Totp {
+        Totp {
+            secret: b"12345678901234567890".to_vec(),
+        }
+    }
+
+    #[test]
+    fn test_rfc_6238_vectors() {
+        let totp = rfc_totp();
+
+        // The RFC lists 8 digit codes, we keep the last 6
+        assert_eq!(totp.code_at(59), "287082");
+        assert_eq!(totp.code_at(1111111109), "081804");
+        assert_eq!(totp.code_at(1234567890), "005924");
+        assert_eq!(totp.code_at(2000000000), "279037");
+    }
+
+    #[test]
+    fn test_verify_with_skew() {
+        let totp = rfc_totp();
+        let code = totp.code_at(1111111109);
+
+        assert_eq!(totp.verify(&code, 1111111109), Some(37037036));
+        assert_eq!(totp.verify(&code, 1111111109 + TOTP_PERIOD), Some(37037036));
+        assert_eq!(totp.verify(&code, 1111111109 - TOTP_PERIOD), Some(37037036));
+        assert_eq!(totp.verify(&code, 1111111109 + 3 * TOTP_PERIOD), None);
+    }
+
+    #[test]
+    fn test_base32_round_trip() {
+        let totp = Totp::generate();
+        assert_eq!(Totp::from_base32(&totp.to_base32()).unwrap(), totp);
+    }
+
+    #[test]
+    fn test_provisioning_uri() {
+        let uri = rfc_totp().provisioning_uri("vlad onis");
+        assert_eq!(
+            uri,
+            "otpauth://totp/Karma%20Manager:vlad%20onis?secret=GEZDGNBVGY3TQOJQGEZDGNBVGY3TQOJQ\
+            &issuer=Karma%20Manager&algorithm=SHA1&digits=6&period=30"
+        );
+    }
+
+    #[test]
+    fn test_backup_codes() {
+        let codes = generate_backup_codes();
+        assert_eq!(codes.len(), BACKUP_CODE_COUNT);
+        assert_eq!(
+            normalize_backup_code(&codes[0].to_uppercase()).len(),
+            BACKUP_CODE_SIZE
+        );
+
+        let totp = rfc_totp();
+        let hash = totp.backup_code_hash(&codes[0]);
+        assert_eq!(hash.len(), 64);
+        assert_eq!(totp.backup_code_hash(&codes[0].to_uppercase()), hash);
+        assert_ne!(Totp::generate().backup_code_hash(&codes[0]), hash);
+    }
+}
@@ -1,9 +1,10 @@
-use bcrypt::{BcryptError, DEFAULT_COST};
 use sqlx::{sqlite::SqliteRow, Error as SqlxError, FromRow, Row};
 use thiserror::Error;
 
+use super::password_hasher::{default_hasher, PasswordHashError, PasswordHasher};
+use super::password_policy::{PasswordPolicy, PasswordReport};
+
 const MIN_USERNAME_SIZE: usize = 6;
-const MIN_PASSWORD_SIZE: usize = 8;
 
 #[derive(Error, Debug)]
 #[allow(dead_code)]
@@ -18,20 +19,11 @@ pub enum UserError {
 #[derive(Error, Debug)]
 #[allow(dead_code)]
 pub enum PasswordError {
-    #[error("Failed to hash the password")]
-    PasswordHash(#[from] BcryptError),
-
-    #[error("Password size should be at least {MIN_PASSWORD_SIZE}")]
-    PasswordSize,
-
-    #[error("Password should cointain at least 1 digit")]
-    NoDigit,
+    #[error("Failed to hash the password: {0}")]
+    PasswordHash(#[from] PasswordHashError),
 
-    #[error("Password should cointain at least 1 special char")]
-    NoSpecialChar,
-
-    #[error("Password should cointain at least 1 uppercase digit")]
-    NoUppercaseLetter,
+    #[error("Password rejected by the password policy: {0}")]
+    Policy(PasswordReport),
 
     #[error("Failed to convert from db row")]
     DbRow(#[from] SqlxError),
@@ -58,8 +50,22 @@ pub struct User {
 #[allow(dead_code)]
 impl User {
     pub fn new(username: &str, password: &str) -> Result<User, UserError> {
+        User::with_policy(
+            username,
+            password,
+            &PasswordPolicy::default(),
+            default_hasher().as_ref(),
+        )
+    }
+
+    pub fn with_policy(
+        username: &str,
+        password: &str,
+        policy: &PasswordPolicy,
+        hasher: &dyn PasswordHasher,
+    ) -> Result<User, UserError> {
         let username = Username::new(username)?;
-        let hashed_password = Password::new(password)?;
+        let hashed_password = Password::with_policy(password, policy, hasher)?;
 
         Ok(User {
             username,
@@ -96,44 +102,46 @@ impl Username {
 }
 
 impl Password {
+    #[allow(dead_code)]
     pub fn new(password: &str) -> Result<Password, PasswordError> {
-        let special_characters = "!@#$%^&*().,:; ";
-        let mut contains_special_char = false;
-        let mut contains_uppercase_letter = false;
-        let mut contains_digit = false;
-
-        if password.len() < MIN_PASSWORD_SIZE {
-            return Err(PasswordError::PasswordSize);
-        }
-
-        for ch in password.chars() {
-            if special_characters.contains(ch) {
-                contains_special_char = true;
-            }
-
-            if ch.is_uppercase() {
-                contains_uppercase_letter = true;
-            }
+        Password::with_policy(
+            password,
+            &PasswordPolicy::default(),
+            default_hasher().as_ref(),
+        )
+    }
 
-            if ch.is_ascii_digit() {
-                contains_digit = true;
-            }
+    pub fn with_policy(
+        password: &str,
+        policy: &PasswordPolicy,
+        hasher: &dyn PasswordHasher,
+    ) -> Result<Password, PasswordError> {
+        let report = policy.check(password);
+        if !report.is_acceptable() {
+            return Err(PasswordError::Policy(report));
         }
 
-        if !contains_digit {
-            return Err(PasswordError::NoDigit);
-        }
+        let hashed_password = hasher.hash(password)?;
+        Ok(Password(hashed_password))
+    }
 
-        if !contains_special_char {
-            return Err(PasswordError::NoSpecialChar);
-        }
+    /// Hashes an already accepted password again, e.g. to upgrade it to the current
+    /// hashing policy, without re-checking it against the password rules
+    pub fn rehash(password: &str, hasher: &dyn PasswordHasher) -> Result<Password, PasswordError> {
+        Ok(Password(hasher.hash(password)?))
+    }
 
-        if !contains_uppercase_letter {
-            return Err(PasswordError::NoUppercaseLetter);
-        }
+    /// Checks a plain text password against this hash, whatever algorithm produced it
+    pub fn verify(
+        &self,
+        password: &str,
+        hasher: &dyn PasswordHasher,
+    ) -> Result<bool, PasswordError> {
+        Ok(hasher.verify(password, &self.0)?)
+    }
 
-        let hashed_password = bcrypt::hash(password, DEFAULT_COST)?;
-        Ok(Password(hashed_password))
+    pub fn needs_rehash(&self, hasher: &dyn PasswordHasher) -> bool {
+        hasher.needs_rehash(&self.0)
     }
 
     pub fn from_hashed(pass: &str) -> Password {
@@ -163,7 +171,6 @@ impl<'r> FromRow<'r, SqliteRow> for User {
 #[cfg(test)]
 mod user_tests {
     use super::*;
-    use bcrypt;
 
     #[test]
     #[ignore]
@@ -205,6 +212,9 @@ mod user_tests {
     pub fn test_valid_username() {
         let user = User::new("vladonzis", "V1@eflsjdfnsdf").unwrap();
         assert_eq!(user.username, Username("vladonzis".to_string()));
-        bcrypt::verify("V1@eflsjdfnsdf", &user.hashed_password.0).unwrap();
+        assert!(user
+            .hashed_password
+            .verify("V1@eflsjdfnsdf", default_hasher().as_ref())
+            .unwrap());
     }
 }
@@ -1,22 +1,96 @@
+use std::sync::{Arc, OnceLock};
+
 use thiserror::Error;
 
+use crate::model::password_hasher::{default_hasher, PasswordHashError, PasswordHasher};
+use crate::model::password_policy::{PasswordPolicy, PasswordReport};
+use crate::model::totp::TotpError;
+use crate::model::user::{Password, PasswordError, UserError};
+use crate::service::clock::{Clock, SystemClock};
 use crate::storage::db::{DbManager, DbManagerError};
 
 #[derive(Error, Debug)]
 pub enum AccountsServiceError {
     #[error("Failed to create the db manager: {0}")]
     DbManager(#[from] DbManagerError),
+
+    #[error("Invalid user: {0}")]
+    InvalidUser(#[from] UserError),
+
+    #[error("Failed to check the password: {0}")]
+    PasswordCheck(#[from] PasswordError),
+
+    #[error("Failed to hash a secret: {0}")]
+    Hashing(#[from] PasswordHashError),
+
+    #[error("Invalid username or password")]
+    InvalidCredentials,
+
+    #[error("A second factor code is required")]
+    SecondFactorRequired,
+
+    #[error("Invalid or already used second factor code")]
+    InvalidSecondFactor,
+
+    #[error("Two factor authentication is already enabled")]
+    TwoFactorAlreadyEnabled,
+
+    #[error("Two factor authentication enrolment was not started")]
+    TwoFactorNotEnrolled,
+
+    #[error("Stored TOTP secret is corrupted: {0}")]
+    Totp(#[from] TotpError),
+
+    #[error("Other accounts share this profile, its karma data is not yours alone to export")]
+    SharedProfile,
+
+    #[error("Failed to export the personal data: {0}")]
+    Export(String),
 }
 
-#[allow(dead_code)]
+#[derive(Debug)]
 pub struct AccountsService {
-    db_manager: DbManager,
+    pub(super) db_manager: DbManager,
+    pub(super) hasher: Box<dyn PasswordHasher>,
+    /// Hashed with `hasher` on the first login of an unknown username
+    pub(super) dummy_password: OnceLock<Password>,
+    pub(super) password_policy: PasswordPolicy,
+    pub(super) clock: Arc<dyn Clock>,
 }
 
-#[allow(dead_code)]
 impl AccountsService {
     pub async fn new(db_url: &str) -> Result<AccountsService, AccountsServiceError> {
+        AccountsService::with_hasher(db_url, default_hasher()).await
+    }
+
+    pub async fn with_hasher(
+        db_url: &str,
+        hasher: Box<dyn PasswordHasher>,
+    ) -> Result<AccountsService, AccountsServiceError> {
         let db_manager = DbManager::new(db_url).await?;
-        Ok(AccountsService { db_manager })
+        Ok(AccountsService {
+            db_manager,
+            hasher,
+            dummy_password: OnceLock::new(),
+            password_policy: PasswordPolicy::default(),
+            clock: Arc::new(SystemClock),
+        })
+    }
+
+    #[cfg(test)]
+    pub fn with_clock(mut self, clock: Arc<dyn Clock>) -> AccountsService {
+        self.clock = clock;
+        self
+    }
+
+    pub fn with_password_policy(mut self, password_policy: PasswordPolicy) -> AccountsService {
+        self.password_policy = password_policy;
+        self
+    }
+
+    /// Checks a candidate password without creating anything, so the UI
+    /// can show every failing rule while the user types
+    pub fn check_password(&self, password: &str) -> PasswordReport {
+        self.password_policy.check(password)
     }
 }
@@ -1 +1,142 @@
+use sqlx::Error as SqlxError;
+use tracing::{info, warn};
 
+use crate::model::user::{Password, User};
+use crate::storage::db::DbManagerError;
+use crate::storage::user_repository::UserRepository;
+
+use super::acounts_service::{AccountsService, AccountsServiceError};
+
+const DUMMY_PASSWORD: &str = "no such user";
+
+impl AccountsService {
+    /// Checks the credentials, including the second factor when the user enrolled one, and,
+    /// when the stored hash was produced by a weaker algorithm or cost than the current
+    /// policy, transparently replaces it with a fresh hash
+    pub async fn login(
+        &self,
+        username: &str,
+        password: &str,
+        second_factor: Option<&str>,
+    ) -> Result<User, AccountsServiceError> {
+        let mut user = self.verify_password(username, password).await?;
+        self.verify_second_factor(username, second_factor).await?;
+
+        if user.hashed_password.needs_rehash(self.hasher.as_ref()) {
+            let rehashed = Password::rehash(password, self.hasher.as_ref())?;
+
+            // The user already proved who they are, a failed upgrade must not lock them out
+            match self.db_manager.update_password(username, &rehashed).await {
+                Ok(()) => {
+                    info!("Upgraded the password hash of {username}");
+                    user.hashed_password = rehashed;
+                }
+                Err(e) => warn!("Failed to upgrade the password hash of {username}: {e}"),
+            }
+        }
+
+        Ok(user)
+    }
+
+    pub(super) async fn verify_password(
+        &self,
+        username: &str,
+        password: &str,
+    ) -> Result<User, AccountsServiceError> {
+        let user = match self.db_manager.get_user(username).await {
+            Ok(user) => user,
+            Err(DbManagerError::OpenConnection(SqlxError::RowNotFound)) => {
+                // Costs as much as a wrong password, so response times don't
+                // tell which usernames exist
+                self.dummy_password()?
+                    .verify(password, self.hasher.as_ref())?;
+                return Err(AccountsServiceError::InvalidCredentials);
+            }
+            Err(e) => return Err(e.into()),
+        };
+
+        if !user
+            .hashed_password
+            .verify(password, self.hasher.as_ref())?
+        {
+            return Err(AccountsServiceError::InvalidCredentials);
+        }
+
+        Ok(user)
+    }
+
+    fn dummy_password(&self) -> Result<&Password, AccountsServiceError> {
+        if let Some(dummy) = self.dummy_password.get() {
+            return Ok(dummy);
+        }
+        let dummy = Password::rehash(DUMMY_PASSWORD, self.hasher.as_ref())?;
+        Ok(self.dummy_password.get_or_init(|| dummy))
+    }
+}
+
+#[cfg(test)]
+mod login_tests {
+    use super::*;
+    use crate::model::password_hasher::{Argon2Hasher, BcryptHasher, HashScheme};
+    use crate::storage::common_utilities_tests::setup_once;
+
+    #[tokio::test]
+    async fn test_login_upgrades_weaker_hash() {
+        setup_once().await;
+        let legacy = AccountsService::with_hasher("test_db.sqlite", Box::new(BcryptHasher::new(4)))
+            .await
+            .unwrap();
+        legacy.signup("rehashuser", "V1@eflsjdfnsdf").await.unwrap();
+
+        let current =
+            AccountsService::with_hasher("test_db.sqlite", Box::new(Argon2Hasher::new(1024, 1, 1)))
+                .await
+                .unwrap();
+
+        assert!(matches!(
+            current.login("rehashuser", "wrong", None).await,
+            Err(AccountsServiceError::InvalidCredentials)
+        ));
+
+        let user = current
+            .login("rehashuser", "V1@eflsjdfnsdf", None)
+            .await
+            .unwrap();
+        let stored = current.db_manager.get_user("rehashuser").await.unwrap();
+        assert_eq!(user.hashed_password, stored.hashed_password);
+        assert!(matches!(
+            HashScheme::detect(&stored.hashed_password.get_password()).unwrap(),
+            HashScheme::Argon2id { .. }
+        ));
+
+        // The upgraded hash keeps working
+        current
+            .login("rehashuser", "V1@eflsjdfnsdf", None)
+            .await
+            .unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_login_unknown_user() {
+        setup_once().await;
+        let service =
+            AccountsService::with_hasher("test_db.sqlite", Box::new(BcryptHasher::new(4)))
+                .await
+                .unwrap();
+
+        assert!(matches!(
+            service.login("nosuchuser", "V1@eflsjdfnsdf", None).await,
+            Err(AccountsServiceError::InvalidCredentials)
+        ));
+        // The password was still checked, against a hash of the configured scheme
+        let dummy = service.dummy_password.get().unwrap();
+        assert!(matches!(
+            HashScheme::detect(&dummy.get_password()).unwrap(),
+            HashScheme::Bcrypt { cost: 4 }
+        ));
+        assert!(matches!(
+            service.login("nosuchuser", DUMMY_PASSWORD, None).await,
+            Err(AccountsServiceError::InvalidCredentials)
+        ));
+    }
+}
@@ -1,5 +1,7 @@
-/// Currently everything related to accounts is dropped as login and auth is a bit beyond
-/// the scope of this app
+/// Local accounts: signup against the password policy, login with transparent
+/// rehashing and an optional TOTP second factor
 pub mod acounts_service;
 pub mod login;
+pub mod personal_data;
 pub mod signup;
+pub mod two_factor;
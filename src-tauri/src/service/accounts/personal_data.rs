@@ -0,0 +1,420 @@
+use std::collections::BTreeMap;
+use std::io::{Cursor, Write};
+
+use chrono::NaiveDate;
+use serde::Serialize;
+use tracing::info;
+use zip::{write::FileOptions, CompressionMethod, ZipWriter};
+
+use crate::model::achievement::AchievementUnlock;
+use crate::model::focus::{FocusSession, Interruption};
+use crate::model::goal::Goal;
+use crate::model::karma::{KarmaPoint, KarmaStatus};
+use crate::model::karma_session::KarmaSession;
+use crate::model::plan::PlanEntry;
+use crate::model::reward::{Redemption, Reward};
+use crate::model::schedule::{KarmaOccurrence, KarmaSchedule};
+use crate::model::suggestion::BlockedTime;
+use crate::storage::achievement_repository::AchievementRepository;
+use crate::storage::blocked_time_repository::BlockedTimeRepository;
+use crate::storage::focus_repository::FocusRepository;
+use crate::storage::goal_repository::GoalRepository;
+use crate::storage::karma_repository::KarmaRepository;
+use crate::storage::plan_repository::PlanRepository;
+use crate::storage::reward_repository::RewardRepository;
+use crate::storage::schedule_repository::ScheduleRepository;
+use crate::storage::settings_repository::SettingsRepository;
+use crate::storage::streak_repository::StreakRepository;
+use crate::storage::two_factor_repository::TwoFactorRepository;
+use crate::storage::user_repository::UserRepository;
+
+use super::acounts_service::{AccountsService, AccountsServiceError};
+
+const EXPORT_README: &str = "\
+Karma Manager personal data export
+
+personal_data.json  everything below in a single document
+user.json           your account, the password hash and TOTP secret are never exported
+karma_points.csv    every karma point, the templates sessions are started from
+karma_sessions.csv  every session of every karma point
+karma_statuses.csv  every state change of every session, timestamps in unix seconds (UTC)
+                    with the timezone and its offset in seconds at the time, when known
+schedules.json      the recurrence of every scheduled karma point and its occurrences
+settings.json       the timezone, scoring, sleep, focus, suggestion and other preferences
+goals.json          every goal
+streaks.json        the days frozen so they don't break a streak
+rewards.json        every reward and every redemption
+achievements.json   when each achievement was unlocked
+plan.json           the karma points planned per day
+blocked_time.json   the time kept free of schedule suggestions
+focus.json          every focus session and its interruptions
+
+Karma data belongs to the profile the account lives in, exports are only made
+while the account is the only one in its profile.
+";
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ExportedUser {
+    pub username: String,
+    pub two_factor_enabled: bool,
+    pub unused_backup_codes: usize,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct PersonalDataExport {
+    pub exported_at: i64,
+    pub user: ExportedUser,
+    pub karma_points: Vec<KarmaPoint>,
+    pub karma_sessions: Vec<KarmaSession>,
+    pub karma_statuses: Vec<KarmaStatus>,
+    pub schedules: Vec<KarmaSchedule>,
+    pub occurrences: Vec<KarmaOccurrence>,
+    pub settings: BTreeMap<String, String>,
+    pub goals: Vec<Goal>,
+    pub streak_freezes: Vec<NaiveDate>,
+    pub rewards: Vec<Reward>,
+    pub redemptions: Vec<Redemption>,
+    pub achievement_unlocks: Vec<AchievementUnlock>,
+    pub plan_entries: Vec<PlanEntry>,
+    pub blocked_times: Vec<BlockedTime>,
+    pub focus_sessions: Vec<FocusSession>,
+    pub focus_interruptions: Vec<Interruption>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct AccountDeletion {
+    pub karma_data_deleted: bool,
+}
+
+impl PersonalDataExport {
+    /// A self contained zip with the json documents, csv tables and a readme
+    pub fn to_zip(&self) -> Result<Vec<u8>, AccountsServiceError> {
+        let mut archive = ZipWriter::new(Cursor::new(Vec::new()));
+        let options = FileOptions::default().compression_method(CompressionMethod::Deflated);
+
+        let files = [
+            ("README.txt", EXPORT_README.to_string()),
+            ("personal_data.json", to_json(self)?),
+            ("user.json", to_json(&self.user)?),
+            ("karma_points.csv", self.karma_points_csv()),
+            ("karma_sessions.csv", self.karma_sessions_csv()),
+            ("karma_statuses.csv", self.karma_statuses_csv()),
+            (
+                "schedules.json",
+                to_json(&serde_json::json!({
+                    "schedules": self.schedules,
+                    "occurrences": self.occurrences,
+                }))?,
+            ),
+            ("settings.json", to_json(&self.settings)?),
+            ("goals.json", to_json(&self.goals)?),
+            (
+                "streaks.json",
+                to_json(&serde_json::json!({ "freezes": self.streak_freezes }))?,
+            ),
+            (
+                "rewards.json",
+                to_json(&serde_json::json!({
+                    "rewards": self.rewards,
+                    "redemptions": self.redemptions,
+                }))?,
+            ),
+            ("achievements.json", to_json(&self.achievement_unlocks)?),
+            ("plan.json", to_json(&self.plan_entries)?),
+            ("blocked_time.json", to_json(&self.blocked_times)?),
+            (
+                "focus.json",
+                to_json(&serde_json::json!({
+                    "sessions": self.focus_sessions,
+                    "interruptions": self.focus_interruptions,
+                }))?,
+            ),
+        ];
+
+        for (name, content) in files {
+            archive.start_file(name, options).map_err(export_error)?;
+            archive
+                .write_all(content.as_bytes())
+                .map_err(export_error)?;
+        }
+
+        Ok(archive.finish().map_err(export_error)?.into_inner())
+    }
+
+    fn karma_points_csv(&self) -> String {
+        let mut csv = String::from("id,name,purpose,default_duration\n");
+        for karma in &self.karma_points {
+            let id = karma.get_id().map(|id| id.to_string()).unwrap_or_default();
+            let default_duration = karma
+                .get_default_duration()
+                .map(|duration| duration.to_string())
+                .unwrap_or_default();
+            csv.push_str(&format!(
+                "{id},{},{:?},{default_duration}\n",
+                csv_field(&karma.get_name()),
+                karma.get_purpose()
+            ));
+        }
+        csv
+    }
+
+    fn karma_sessions_csv(&self) -> String {
+        let mut csv = String::from("id,karma_id,purpose,planned_duration,started_at\n");
+        for session in &self.karma_sessions {
+            let id = session
+                .get_id()
+                .map(|id| id.to_string())
+                .unwrap_or_default();
+            let planned_duration = session
+                .get_planned_duration()
+                .map(|duration| duration.to_string())
+                .unwrap_or_default();
+            csv.push_str(&format!(
+                "{id},{},{:?},{planned_duration},{}\n",
+                session.get_template_id(),
+                session.get_purpose(),
+                session.get_started_at()
+            ));
+        }
+        csv
+    }
+
+    fn karma_statuses_csv(&self) -> String {
+        let mut csv = String::from("session_id,state,closed_with,timestamp,timezone,utc_offset\n");
+        for status in &self.karma_statuses {
+            let closed_with = status
+                .closed_with
+                .as_ref()
+                .map(|karma_type| format!("{karma_type:?}"))
+                .unwrap_or_default();
+            csv.push_str(&format!(
+                "{},{},{closed_with},{},{},{}\n",
+                status.session_id,
+                status.state.to_string(),
+                status.timestamp,
+                status.timezone.as_deref().unwrap_or_default(),
+                status.utc_offset
+            ));
+        }
+        csv
+    }
+}
+
+impl AccountsService {
+    /// Everything stored about the user, a second factor is required when enrolled.
+    /// Refused while other accounts share the profile, the karma data is theirs too.
+    pub async fn export_personal_data(
+        &self,
+        username: &str,
+        password: &str,
+        second_factor: Option<&str>,
+    ) -> Result<PersonalDataExport, AccountsServiceError> {
+        let user = self.verify_password(username, password).await?;
+        self.verify_second_factor(username, second_factor).await?;
+
+        if self.db_manager.count_users().await? > 1 {
+            return Err(AccountsServiceError::SharedProfile);
+        }
+
+        let two_factor_enabled = self
+            .db_manager
+            .get_totp(username)
+            .await?
+            .is_some_and(|record| record.confirmed);
+        let unused_backup_codes = self
+            .db_manager
+            .get_unused_backup_codes(username)
+            .await?
+            .len();
+
+        Ok(PersonalDataExport {
+            exported_at: self.clock.now(),
+            user: ExportedUser {
+                username: user.username.get_username(),
+                two_factor_enabled,
+                unused_backup_codes,
+            },
+            karma_points: self.db_manager.get_all_karma().await?,
+            karma_sessions: self.db_manager.get_all_sessions().await?,
+            karma_statuses: self.db_manager.get_all_karma_statuses().await?,
+            schedules: self.db_manager.get_schedules().await?,
+            occurrences: self.db_manager.get_all_occurrences().await?,
+            settings: self
+                .db_manager
+                .get_all_settings()
+                .await?
+                .into_iter()
+                .collect(),
+            goals: self.db_manager.get_goals().await?,
+            streak_freezes: self.db_manager.get_all_freezes().await?,
+            rewards: self.db_manager.get_rewards().await?,
+            redemptions: self.db_manager.get_redemptions(i64::MIN).await?,
+            achievement_unlocks: self.db_manager.get_unlocks().await?,
+            plan_entries: self.db_manager.get_all_plan_entries().await?,
+            blocked_times: self.db_manager.get_blocked_times().await?,
+            focus_sessions: self.db_manager.get_focus_sessions(i64::MAX).await?,
+            focus_interruptions: self.db_manager.get_all_interruptions().await?,
+        })
+    }
+
+    /// Deletes the account for good: the rows go in one transaction and the
+    /// database is vacuumed afterwards so nothing survives in free pages
+    pub async fn delete_account(
+        &self,
+        username: &str,
+        password: &str,
+        second_factor: Option<&str>,
+    ) -> Result<AccountDeletion, AccountsServiceError> {
+        self.verify_password(username, password).await?;
+        self.verify_second_factor(username, second_factor).await?;
+
+        let karma_data_deleted = self.db_manager.delete_user(username).await?;
+        self.db_manager.vacuum().await?;
+        info!("Deleted the account of {username}, karma data deleted: {karma_data_deleted}");
+
+        Ok(AccountDeletion { karma_data_deleted })
+    }
+}
+
+fn to_json<T: Serialize>(value: &T) -> Result<String, AccountsServiceError> {
+    serde_json::to_string_pretty(value).map_err(export_error)
+}
+
+fn export_error(error: impl std::fmt::Display) -> AccountsServiceError {
+    AccountsServiceError::Export(error.to_string())
+}
+
+/// Quotes a field when it contains a separator, a quote or a line break
+fn csv_field(value: &str) -> String {
+    if value.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value.to_string()
+    }
+}
+
+#[cfg(test)]
+mod personal_data_tests {
+    use std::io::Read;
+
+    use super::*;
+    use crate::model::karma::KarmaType;
+    use crate::model::password_hasher::Argon2Hasher;
+    use crate::storage::db::DbManager;
+
+    #[test]
+    fn test_csv_field() {
+        assert_eq!(csv_field("Morning run"), "Morning run");
+        assert_eq!(
+            csv_field("Run, then \"swim\""),
+            "\"Run, then \"\"swim\"\"\""
+        );
+    }
+
+    #[tokio::test]
+    async fn test_export_and_delete_account() {
+        // A database of its own, deleting the last account wipes the karma data
+        let db_url = "test_personal_data.sqlite";
+        let _ = std::fs::remove_file(db_url);
+
+        let service = AccountsService::with_hasher(db_url, Box::new(Argon2Hasher::new(1024, 1, 1)))
+            .await
+            .unwrap();
+        service
+            .signup("exportuser", "V1@eflsjdfnsdf")
+            .await
+            .unwrap();
+        service
+            .db_manager
+            .insert_karma(KarmaPoint::new(
+                KarmaType::Social,
+                "Dinner, friends".to_string(),
+            ))
+            .await
+            .unwrap();
+        service
+            .db_manager
+            .set_setting("timezone", "Europe/Brussels")
+            .await
+            .unwrap();
+        service
+            .db_manager
+            .insert_freeze(NaiveDate::from_ymd_opt(2024, 1, 2).unwrap())
+            .await
+            .unwrap();
+
+        // Not while someone else's activity is in the same profile
+        service
+            .signup("profilemate", "V1@eflsjdfnsdf")
+            .await
+            .unwrap();
+        assert!(matches!(
+            service
+                .export_personal_data("exportuser", "V1@eflsjdfnsdf", None)
+                .await,
+            Err(AccountsServiceError::SharedProfile)
+        ));
+        let deletion = service
+            .delete_account("profilemate", "V1@eflsjdfnsdf", None)
+            .await
+            .unwrap();
+        assert!(!deletion.karma_data_deleted);
+
+        let export = service
+            .export_personal_data("exportuser", "V1@eflsjdfnsdf", None)
+            .await
+            .unwrap();
+        assert_eq!(export.user.username, "exportuser");
+        assert_eq!(export.karma_points.len(), 1);
+        assert_eq!(export.settings["timezone"], "Europe/Brussels");
+        assert_eq!(export.streak_freezes.len(), 1);
+
+        let mut archive = zip::ZipArchive::new(Cursor::new(export.to_zip().unwrap())).unwrap();
+        let mut user_json = String::new();
+        archive
+            .by_name("user.json")
+            .unwrap()
+            .read_to_string(&mut user_json)
+            .unwrap();
+        assert!(!user_json.contains("argon2"));
+        let mut karma_csv = String::new();
+        archive
+            .by_name("karma_points.csv")
+            .unwrap()
+            .read_to_string(&mut karma_csv)
+            .unwrap();
+        assert_eq!(
+            karma_csv,
+            "id,name,purpose,default_duration\n1,\"Dinner, friends\",Social,\n"
+        );
+        let mut streaks_json = String::new();
+        archive
+            .by_name("streaks.json")
+            .unwrap()
+            .read_to_string(&mut streaks_json)
+            .unwrap();
+        assert!(streaks_json.contains("2024-01-02"));
+        for name in [
+            "schedules.json",
+            "settings.json",
+            "focus.json",
+            "rewards.json",
+        ] {
+            assert!(archive.by_name(name).is_ok(), "{name} missing");
+        }
+
+        let deletion = service
+            .delete_account("exportuser", "V1@eflsjdfnsdf", None)
+            .await
+            .unwrap();
+        assert!(deletion.karma_data_deleted);
+        assert!(matches!(
+            service.login("exportuser", "V1@eflsjdfnsdf", None).await,
+            Err(AccountsServiceError::InvalidCredentials)
+        ));
+
+        let db = DbManager::new(db_url).await.unwrap();
+        assert!(db.get_all_karma().await.unwrap().is_empty());
+        assert!(db.search_karma("dinner", 10).await.unwrap().is_empty());
+    }
+}
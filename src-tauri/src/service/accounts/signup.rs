@@ -1 +1,21 @@
+use crate::model::user::User;
+use crate::storage::user_repository::UserRepository;
 
+use super::acounts_service::{AccountsService, AccountsServiceError};
+
+impl AccountsService {
+    pub async fn signup(
+        &self,
+        username: &str,
+        password: &str,
+    ) -> Result<User, AccountsServiceError> {
+        let user = User::with_policy(
+            username,
+            password,
+            &self.password_policy,
+            self.hasher.as_ref(),
+        )?;
+
+        Ok(self.db_manager.insert_user(user).await?)
+    }
+}
@@ -0,0 +1,288 @@
+use serde::Serialize;
+use tracing::info;
+
+use crate::model::totp::{generate_backup_codes, Totp};
+use crate::storage::two_factor_repository::TwoFactorRepository;
+
+use super::acounts_service::{AccountsService, AccountsServiceError};
+
+/// What the UI needs to show the QR code, the secret is shown too for manual entry
+#[derive(Debug, Clone, Serialize)]
+pub struct TotpEnrolment {
+    pub secret: String,
+    pub provisioning_uri: String,
+}
+
+impl AccountsService {
+    /// Generates a new secret, two factor login is only enforced once
+    /// the user confirms it with a first code
+    pub async fn begin_totp_enrolment(
+        &self,
+        username: &str,
+        password: &str,
+    ) -> Result<TotpEnrolment, AccountsServiceError> {
+        self.verify_password(username, password).await?;
+
+        if let Some(record) = self.db_manager.get_totp(username).await? {
+            if record.confirmed {
+                return Err(AccountsServiceError::TwoFactorAlreadyEnabled);
+            }
+        }
+
+        let totp = Totp::generate();
+        let secret = totp.to_base32();
+        self.db_manager
+            .upsert_totp_secret(username, &secret)
+            .await?;
+
+        Ok(TotpEnrolment {
+            provisioning_uri: totp.provisioning_uri(username),
+            secret,
+        })
+    }
+
+    /// Enables two factor login and returns the backup codes, they are never shown again
+    pub async fn confirm_totp_enrolment(
+        &self,
+        username: &str,
+        code: &str,
+    ) -> Result<Vec<String>, AccountsServiceError> {
+        let record = self
+            .db_manager
+            .get_totp(username)
+            .await?
+            .ok_or(AccountsServiceError::TwoFactorNotEnrolled)?;
+
+        if record.confirmed {
+            return Err(AccountsServiceError::TwoFactorAlreadyEnabled);
+        }
+
+        let totp = Totp::from_base32(&record.secret)?;
+        let step = totp
+            .verify(code, self.clock.now())
+            .ok_or(AccountsServiceError::InvalidSecondFactor)?;
+
+        self.db_manager.confirm_totp(username, step).await?;
+        info!("Enabled two factor authentication for {username}");
+
+        self.store_backup_codes(username, &totp).await
+    }
+
+    /// Replaces every backup code, used or not
+    pub async fn regenerate_backup_codes(
+        &self,
+        username: &str,
+        password: &str,
+        code: &str,
+    ) -> Result<Vec<String>, AccountsServiceError> {
+        self.verify_password(username, password).await?;
+        self.require_second_factor(username, code).await?;
+
+        let record = self
+            .db_manager
+            .get_totp(username)
+            .await?
+            .ok_or(AccountsServiceError::TwoFactorNotEnrolled)?;
+        let totp = Totp::from_base32(&record.secret)?;
+        self.store_backup_codes(username, &totp).await
+    }
+
+    pub async fn disable_totp(
+        &self,
+        username: &str,
+        password: &str,
+        code: &str,
+    ) -> Result<(), AccountsServiceError> {
+        self.verify_password(username, password).await?;
+        self.require_second_factor(username, code).await?;
+
+        self.db_manager.delete_totp(username).await?;
+        info!("Disabled two factor authentication for {username}");
+        Ok(())
+    }
+
+    /// Accepts either a TOTP code or an unused backup code. Users who never
+    /// confirmed an enrolment pass without a code.
+    pub(super) async fn verify_second_factor(
+        &self,
+        username: &str,
+        code: Option<&str>,
+    ) -> Result<(), AccountsServiceError> {
+        let record = match self.db_manager.get_totp(username).await? {
+            Some(record) if record.confirmed => record,
+            _ => return Ok(()),
+        };
+
+        let code = code.ok_or(AccountsServiceError::SecondFactorRequired)?;
+
+        let totp = Totp::from_base32(&record.secret)?;
+        if let Some(step) = totp.verify(code, self.clock.now()) {
+            // A code can only be used once, even inside its validity window, the
+            // update only goes through for a step later than the last one used
+            if !self
+                .db_manager
+                .set_totp_last_used_step(username, step)
+                .await?
+            {
+                return Err(AccountsServiceError::InvalidSecondFactor);
+            }
+            return Ok(());
+        }
+
+        // Looked up by its hash and marked used in the same statement, so two
+        // logins can't both spend it
+        if !self
+            .db_manager
+            .use_backup_code(username, &totp.backup_code_hash(code), self.clock.now())
+            .await?
+        {
+            return Err(AccountsServiceError::InvalidSecondFactor);
+        }
+        info!("{username} logged in with a backup code");
+        Ok(())
+    }
+
+    async fn require_second_factor(
+        &self,
+        username: &str,
+        code: &str,
+    ) -> Result<(), AccountsServiceError> {
+        match self.db_manager.get_totp(username).await? {
+            Some(record) if record.confirmed => {
+                self.verify_second_factor(username, Some(code)).await
+            }
+            _ => Err(AccountsServiceError::TwoFactorNotEnrolled),
+        }
+    }
+
+    async fn store_backup_codes(
+        &self,
+        username: &str,
+        totp: &Totp,
+    ) -> Result<Vec<String>, AccountsServiceError> {
+        let codes = generate_backup_codes();
+        let code_hashes = codes
+            .iter()
+            .map(|code| totp.backup_code_hash(code))
+            .collect();
+
+        self.db_manager
+            .replace_backup_codes(username, code_hashes)
+            .await?;
+        Ok(codes)
+    }
+}
+
+#[cfg(test)]
+mod two_factor_tests {
+    use std::sync::Arc;
+
+    use super::*;
+    use crate::model::password_hasher::Argon2Hasher;
+    use crate::model::totp::TOTP_PERIOD;
+    use crate::service::clock::{Clock, FixedClock};
+    use crate::storage::common_utilities_tests::setup_once;
+
+    async fn service(clock: Arc<FixedClock>) -> AccountsService {
+        setup_once().await;
+        AccountsService::with_hasher("test_db.sqlite", Box::new(Argon2Hasher::new(1024, 1, 1)))
+            .await
+            .unwrap()
+            .with_clock(clock)
+    }
+
+    #[tokio::test]
+    async fn test_totp_login_flow() {
+        let clock = Arc::new(FixedClock::new(1_700_000_000));
+        let service = service(clock.clone()).await;
+        service.signup("totpuser", "V1@eflsjdfnsdf").await.unwrap();
+
+        let enrolment = service
+            .begin_totp_enrolment("totpuser", "V1@eflsjdfnsdf")
+            .await
+            .unwrap();
+        assert!(enrolment.provisioning_uri.starts_with("otpauth://totp/"));
+        let totp = Totp::from_base32(&enrolment.secret).unwrap();
+
+        // Not enforced until confirmed
+        service
+            .login("totpuser", "V1@eflsjdfnsdf", None)
+            .await
+            .unwrap();
+
+        let backup_codes = service
+            .confirm_totp_enrolment("totpuser", &totp.code_at(clock.now()))
+            .await
+            .unwrap();
+
+        assert!(matches!(
+            service.login("totpuser", "V1@eflsjdfnsdf", None).await,
+            Err(AccountsServiceError::SecondFactorRequired)
+        ));
+
+        // The confirmation code can't be replayed
+        assert!(matches!(
+            service
+                .login(
+                    "totpuser",
+                    "V1@eflsjdfnsdf",
+                    Some(&totp.code_at(clock.now()))
+                )
+                .await,
+            Err(AccountsServiceError::InvalidSecondFactor)
+        ));
+
+        // One period later, with the phone lagging one period behind
+        clock.advance(2 * TOTP_PERIOD);
+        let lagging_code = totp.code_at(clock.now() - TOTP_PERIOD);
+        service
+            .login("totpuser", "V1@eflsjdfnsdf", Some(&lagging_code))
+            .await
+            .unwrap();
+
+        assert!(matches!(
+            service
+                .login("totpuser", "V1@eflsjdfnsdf", Some("abcde-fghjk"))
+                .await,
+            Err(AccountsServiceError::InvalidSecondFactor)
+        ));
+
+        // Backup codes work once, whatever their case
+        let backup_code = backup_codes[0].to_uppercase();
+        service
+            .login("totpuser", "V1@eflsjdfnsdf", Some(&backup_code))
+            .await
+            .unwrap();
+        assert!(matches!(
+            service
+                .login("totpuser", "V1@eflsjdfnsdf", Some(&backup_code))
+                .await,
+            Err(AccountsServiceError::InvalidSecondFactor)
+        ));
+
+        // Two logins racing with the same code, only one of them gets in
+        clock.advance(TOTP_PERIOD);
+        let code = totp.code_at(clock.now());
+        let (first, second) = tokio::join!(
+            service.login("totpuser", "V1@eflsjdfnsdf", Some(&code)),
+            service.login("totpuser", "V1@eflsjdfnsdf", Some(&code))
+        );
+        assert_eq!(
+            [first.is_ok(), second.is_ok()]
+                .iter()
+                .filter(|ok| **ok)
+                .count(),
+            1
+        );
+
+        clock.advance(TOTP_PERIOD);
+        service
+            .disable_totp("totpuser", "V1@eflsjdfnsdf", &totp.code_at(clock.now()))
+            .await
+            .unwrap();
+        service
+            .login("totpuser", "V1@eflsjdfnsdf", None)
+            .await
+            .unwrap();
+    }
+}
@@ -0,0 +1,44 @@
+/// Source of the current time in unix seconds, the same unit stored in `karma_status`.
+/// Services take it as a dependency so time sensitive logic can be tested.
+pub trait Clock: std::fmt::Debug + Send + Sync {
+    fn now(&self) -> i64;
+}
+
+#[derive(Debug, Default, Clone, Copy)]
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now(&self) -> i64 {
+        chrono::Utc::now().timestamp()
+    }
+}
+
+#[cfg(test)]
+pub use clock_tests::FixedClock;
+
+#[cfg(test)]
+mod clock_tests {
+    use std::sync::atomic::{AtomicI64, Ordering};
+
+    use super::Clock;
+
+    /// A clock that only moves when told to
+    #[derive(Debug, Default)]
+    pub struct FixedClock(AtomicI64);
+
+    impl FixedClock {
+        pub fn new(now: i64) -> FixedClock {
+            FixedClock(AtomicI64::new(now))
+        }
+
+        pub fn advance(&self, seconds: i64) {
+            self.0.fetch_add(seconds, Ordering::SeqCst);
+        }
+    }
+
+    impl Clock for FixedClock {
+        fn now(&self) -> i64 {
+            self.0.load(Ordering::SeqCst)
+        }
+    }
+}
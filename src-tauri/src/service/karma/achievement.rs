@@ -0,0 +1,204 @@
+use std::collections::BTreeMap;
+
+use crate::model::achievement::{
+    achievement_statuses, AchievementDefinition, AchievementFacts, AchievementRule,
+    AchievementStatus,
+};
+use crate::model::karma::KarmaType;
+use crate::model::subject::KarmaSubject;
+use crate::storage::KarmaStorage;
+
+use super::karma_service::{KarmaService, KarmaServiceError};
+
+impl<R: KarmaStorage> KarmaService<R> {
+    /// Every achievement with its progress, unlocking the ones reached meanwhile
+    pub async fn achievements(&self) -> Result<Vec<AchievementStatus>, KarmaServiceError> {
+        self.unlock_achievements(None).await?;
+
+        let definitions: Vec<_> = self.achievements.definitions().iter().collect();
+        let facts = self.achievement_facts(&definitions).await?;
+        let unlocks = self.karma_repository.get_unlocks().await?;
+        Ok(achievement_statuses(&self.achievements, &facts, &unlocks))
+    }
+
+    /// Stores and announces the achievements reached but not unlocked yet. Only the
+    /// locked ones sessions of the `touched` types can bring closer are looked at,
+    /// every locked one when none are given.
+    pub(super) async fn unlock_achievements(
+        &self,
+        touched: Option<&[KarmaType]>,
+    ) -> Result<Vec<AchievementStatus>, KarmaServiceError> {
+        let unlocks = self.karma_repository.get_unlocks().await?;
+        let pending: Vec<&AchievementDefinition> = self
+            .achievements
+            .definitions()
+            .iter()
+            .filter(|definition| unlocks.iter().all(|unlock| unlock.id != definition.id))
+            .filter(|definition| {
+                touched.map_or(true, |touched| {
+                    touched
+                        .iter()
+                        .any(|purpose| definition.rule.concerns(purpose))
+                })
+            })
+            .collect();
+        if pending.is_empty() {
+            return Ok(Vec::new());
+        }
+
+        let facts = self.achievement_facts(&pending).await?;
+        let now = self.clock.now();
+        let mut unlocked = Vec::new();
+        for definition in pending {
+            if definition.rule.progress(&facts) < 1.0 {
+                continue;
+            }
+            // Another command may have unlocked it meanwhile, announce it once
+            if self
+                .karma_repository
+                .insert_unlock(&definition.id, now)
+                .await?
+            {
+                unlocked.push(AchievementStatus {
+                    definition: definition.clone(),
+                    progress: 1.0,
+                    unlocked_at: Some(now),
+                });
+            }
+        }
+
+        if !unlocked.is_empty() {
+            self.notifier.achievements_unlocked(&unlocked);
+        }
+        Ok(unlocked)
+    }
+
+    /// What the rules of `definitions` are checked against, leaving out what none of
+    /// them looks at: the sessions are only loaded for rules about sessions or hours
+    /// and only the streaks of the types some rule is about are counted
+    async fn achievement_facts(
+        &self,
+        definitions: &[&AchievementDefinition],
+    ) -> Result<AchievementFacts, KarmaServiceError> {
+        let settings = self.time_settings().await?;
+        let now = self.clock.now();
+        let today = settings.local_date(now);
+
+        let mut streaks = BTreeMap::new();
+        let mut needs_sessions = false;
+        for definition in definitions {
+            match &definition.rule {
+                AchievementRule::Streak { purpose, .. } => {
+                    if streaks.contains_key(purpose) {
+                        continue;
+                    }
+                    let streak = self
+                        .streak(
+                            KarmaSubject::Category(purpose.clone()),
+                            format!("{purpose:?}"),
+                            None,
+                            today,
+                            &settings,
+                        )
+                        .await?;
+                    streaks.insert(purpose.clone(), streak.longest);
+                }
+                _ => needs_sessions = true,
+            }
+        }
+
+        let sessions = if needs_sessions {
+            self.karma_repository
+                .get_tracked_sessions(0, now + 1)
+                .await?
+        } else {
+            Vec::new()
+        };
+        Ok(AchievementFacts::collect(
+            &sessions, streaks, now, &settings,
+        ))
+    }
+}
+
+#[cfg(test)]
+mod achievement_tests {
+    use std::sync::{Arc, Mutex};
+
+    use super::*;
+    use crate::model::achievement::AchievementBook;
+    use crate::model::karma::KarmaType;
+    use crate::service::clock::Clock;
+    use crate::service::notifier::Notifier;
+    use crate::storage::common_utilities_tests::karma_service_at;
+
+    const HOUR: i64 = 60 * 60;
+
+    /// Keeps the ids of what was announced
+    #[derive(Debug, Default)]
+    struct RecordingNotifier(Mutex<Vec<String>>);
+
+    impl Notifier for RecordingNotifier {
+        fn achievements_unlocked(&self, unlocked: &[AchievementStatus]) {
+            let mut announced = self.0.lock().unwrap();
+            announced.extend(unlocked.iter().map(|status| status.definition.id.clone()));
+        }
+    }
+
+    #[tokio::test]
+    async fn test_statuses_unlock_achievements_once() {
+        let book = AchievementBook::from_json(
+            r#"[
+                {"id": "first", "name": "First", "description": "",
+                "rule": {"kind": "sessions", "count": 1}},
+                {"id": "sport", "name": "Sporty", "description": "",
+                "rule": {"kind": "hours", "purpose": "Sport", "hours": 3}}
+            ]"#,
+        )
+        .unwrap();
+        let notifier = Arc::new(RecordingNotifier::default());
+        let (service, clock) = karma_service_at("achievement", "2024-01-01 09:00").await;
+        let start = clock.now();
+        let service = service.map(|service| {
+            service
+                .with_achievements(book)
+                .with_notifier(notifier.clone())
+        });
+
+        let started = service
+            .start_karma("Run", Some(KarmaType::Sport), None)
+            .await
+            .unwrap();
+        assert!(notifier.0.lock().unwrap().is_empty());
+        clock.advance(2 * HOUR);
+        service
+            .close_session(started.session.get_id().unwrap(), None)
+            .await
+            .unwrap();
+        assert_eq!(*notifier.0.lock().unwrap(), vec!["first".to_string()]);
+
+        let achievements = service.achievements().await.unwrap();
+        assert_eq!(achievements[0].unlocked_at, Some(start + 2 * HOUR));
+        assert!((achievements[1].progress - 2.0 / 3.0).abs() < 1e-9);
+        assert_eq!(achievements[1].unlocked_at, None);
+
+        let started = service
+            .start_karma("Run", Some(KarmaType::Sport), None)
+            .await
+            .unwrap();
+        clock.advance(HOUR);
+        service
+            .close_session(started.session.get_id().unwrap(), None)
+            .await
+            .unwrap();
+        assert_eq!(
+            *notifier.0.lock().unwrap(),
+            vec!["first".to_string(), "sport".to_string()]
+        );
+        assert!(service
+            .achievements()
+            .await
+            .unwrap()
+            .iter()
+            .all(|status| status.progress == 1.0));
+    }
+}
@@ -0,0 +1,140 @@
+use std::collections::BTreeMap;
+
+use chrono::Duration;
+
+use crate::model::balance::{
+    balance_report, weekday_habits, BalanceReport, BalanceTargets, BALANCE_TARGETS_SETTING,
+    HABIT_HISTORY_WEEKS,
+};
+use crate::model::duration::duration_totals;
+use crate::model::karma::Polarity;
+use crate::storage::KarmaStorage;
+
+use super::karma_service::{KarmaService, KarmaServiceError};
+
+impl<R: KarmaStorage> KarmaService<R> {
+    /// The targets set by the user, a default mix until then
+    pub async fn balance_targets(&self) -> Result<BalanceTargets, KarmaServiceError> {
+        match self
+            .karma_repository
+            .get_setting(BALANCE_TARGETS_SETTING)
+            .await?
+        {
+            Some(targets) => Ok(BalanceTargets::parse(&targets)?),
+            None => Ok(BalanceTargets::default()),
+        }
+    }
+
+    /// Replaces every target, types left out stop counting towards the balance
+    pub async fn set_balance_targets(
+        &self,
+        targets: &str,
+    ) -> Result<BalanceTargets, KarmaServiceError> {
+        let targets = BalanceTargets::parse(targets)?;
+        self.karma_repository
+            .set_setting(BALANCE_TARGETS_SETTING, &targets.to_string())
+            .await?;
+        Ok(targets)
+    }
+
+    /// How this week's tracked time compares with the targets, with suggestions
+    /// based on the days each type was done on in the previous weeks
+    pub async fn balance(&self) -> Result<BalanceReport, KarmaServiceError> {
+        let settings = self.time_settings().await?;
+        let targets = self.balance_targets().await?;
+        let now = self.clock.now();
+
+        let week_start = settings.week_of(settings.local_date(now));
+        let from = settings.day_start(week_start);
+        let to = settings.day_start(week_start + Duration::days(7));
+
+        // Time lost to relapses isn't time spent on their type
+        let sessions: Vec<_> = self
+            .karma_repository
+            .get_tracked_sessions(from, to)
+            .await?
+            .into_iter()
+            .filter(|tracked| tracked.polarity == Polarity::Positive)
+            .collect();
+        let actual: BTreeMap<_, _> = duration_totals(&sessions, from, to, now, &settings)
+            .per_category
+            .into_iter()
+            .map(|category| (category.purpose, category.seconds))
+            .collect();
+
+        let history_from = settings.day_start(week_start - Duration::weeks(HABIT_HISTORY_WEEKS));
+        let habits = weekday_habits(
+            &self
+                .karma_repository
+                .get_tracked_sessions(history_from, from)
+                .await?,
+            history_from,
+            from,
+            now,
+            &settings,
+        );
+
+        let elapsed = (now - from) as f64 / (to - from) as f64;
+        Ok(balance_report(
+            week_start, elapsed, &actual, &targets, &habits,
+        ))
+    }
+}
+
+#[cfg(test)]
+mod balance_tests {
+    use crate::model::karma::KarmaType;
+    use crate::storage::common_utilities_tests::karma_service_at;
+
+    const HOUR: i64 = 60 * 60;
+    const DAY: i64 = 24 * HOUR;
+
+    #[tokio::test]
+    async fn test_balance_uses_last_weeks_habits() {
+        let (service, clock) = karma_service_at("balance", "2024-01-02 18:00").await;
+        service
+            .set_balance_targets("sport=5, work=20")
+            .await
+            .unwrap();
+
+        let gym = service
+            .start_karma("Gym", Some(KarmaType::Sport), None)
+            .await
+            .unwrap();
+        clock.advance(HOUR);
+        service
+            .close_session(gym.session.get_id().unwrap(), None)
+            .await
+            .unwrap();
+
+        // The next week, on Thursday evening, with a bit of work done
+        clock.advance(9 * DAY);
+        let work = service
+            .start_karma("Project", Some(KarmaType::Work), None)
+            .await
+            .unwrap();
+        clock.advance(3 * HOUR);
+        service
+            .close_session(work.session.get_id().unwrap(), None)
+            .await
+            .unwrap();
+
+        let balance = service.balance().await.unwrap();
+        assert_eq!(balance.week_start.to_string(), "2024-01-08");
+        // Only work so far, against a 80/20 mix
+        assert_eq!(balance.score, Some(80));
+
+        let messages: Vec<&str> = balance
+            .suggestions
+            .iter()
+            .map(|suggestion| suggestion.message.as_str())
+            .collect();
+        assert_eq!(
+            messages,
+            vec![
+                "You're 17h below your Work target this week",
+                "You're 5h below your Sport target this week; you usually do Sport on Tuesdays",
+            ]
+        );
+    }
+}
@@ -0,0 +1,90 @@
+use chrono::{Duration, NaiveDate};
+
+use crate::model::deviation::{analyse_deviations, DeviationAnalysis, SessionOutcome};
+use crate::model::karma::Polarity;
+use crate::model::report::ReportPeriod;
+use crate::storage::KarmaStorage;
+
+use super::karma_service::{KarmaService, KarmaServiceError};
+
+/// How far back the analysis looks when no start is given
+pub const DEFAULT_DEVIATION_DAYS: i64 = 90;
+
+impl<R: KarmaStorage> KarmaService<R> {
+    /// How often sessions closed between both days ended as something else than
+    /// planned, by default over the last `DEFAULT_DEVIATION_DAYS` up to today.
+    /// Relapses were never planned, so they are left out.
+    pub async fn deviation_analysis(
+        &self,
+        first_day: Option<NaiveDate>,
+        last_day: Option<NaiveDate>,
+        trend_period: ReportPeriod,
+    ) -> Result<DeviationAnalysis, KarmaServiceError> {
+        let settings = self.time_settings().await?;
+        let last_day = last_day.unwrap_or_else(|| settings.local_date(self.clock.now()));
+        let first_day = first_day.unwrap_or(last_day - Duration::days(DEFAULT_DEVIATION_DAYS - 1));
+
+        let outcomes: Vec<SessionOutcome> = self
+            .karma_repository
+            .get_tracked_sessions(
+                settings.day_start(first_day),
+                settings.next_day_start(last_day),
+            )
+            .await?
+            .iter()
+            .filter(|tracked| tracked.polarity == Polarity::Positive)
+            .filter_map(|tracked| SessionOutcome::of(tracked, &settings))
+            .collect();
+
+        Ok(analyse_deviations(
+            &outcomes,
+            first_day,
+            last_day,
+            trend_period,
+            &settings,
+        ))
+    }
+}
+
+#[cfg(test)]
+mod deviation_tests {
+    use super::*;
+    use crate::model::karma::KarmaType;
+    use crate::storage::common_utilities_tests::karma_service_at;
+
+    #[tokio::test]
+    async fn test_closed_with_counts_as_deviation() {
+        let (service, clock) = karma_service_at("deviation", "2024-01-10 09:00").await;
+
+        for closed_with in [Some(KarmaType::Social), None, Some(KarmaType::Social)] {
+            let started = service
+                .start_karma("Study group", Some(KarmaType::Learning), None)
+                .await
+                .unwrap();
+            clock.advance(60 * 60);
+            service
+                .close_session(started.session.get_id().unwrap(), closed_with)
+                .await
+                .unwrap();
+        }
+        // Still running, no outcome yet
+        service.start_karma("study", None, None).await.unwrap();
+
+        let analysis = service
+            .deviation_analysis(None, None, ReportPeriod::Month)
+            .await
+            .unwrap();
+        assert_eq!(analysis.closed, 3);
+        assert_eq!(analysis.deviated, 2);
+        assert_eq!(analysis.per_purpose[0].planned, KarmaType::Learning);
+        assert_eq!(analysis.drifting[0].name, "Study group");
+        assert_eq!(
+            analysis.drifting[0].usually_becomes,
+            Some(KarmaType::Social)
+        );
+        assert_eq!(
+            analysis.trend.last().unwrap().period_start.to_string(),
+            "2024-01-01"
+        );
+    }
+}
@@ -0,0 +1,373 @@
+use crate::model::focus::{
+    phases_between, validate_rating, FocusError, FocusRules, FocusSession, FocusState,
+    FocusSummary, FocusTick, Interruption, PhaseSpan, FOCUS_RULES_SETTING,
+};
+use crate::model::karma::{KarmaType, State};
+use crate::model::karma_resolver;
+use crate::storage::KarmaStorage;
+
+use super::karma_service::{KarmaService, KarmaServiceError};
+
+impl<R: KarmaStorage> KarmaService<R> {
+    /// The rules set by the user, 25 minute pomodoros until then
+    pub async fn focus_rules(&self) -> Result<FocusRules, KarmaServiceError> {
+        match self
+            .karma_repository
+            .get_setting(FOCUS_RULES_SETTING)
+            .await?
+        {
+            Some(rules) => Ok(FocusRules::parse(&rules)?),
+            None => Ok(FocusRules::default()),
+        }
+    }
+
+    /// Changes the given rules and keeps the others, running focus sessions keep
+    /// the rules they started with
+    pub async fn set_focus_rules(&self, rules: &str) -> Result<FocusRules, KarmaServiceError> {
+        let current = self.focus_rules().await?.to_string();
+        let rules = FocusRules::parse(&format!("{current},{rules}"))?;
+        self.karma_repository
+            .set_setting(FOCUS_RULES_SETTING, &rules.to_string())
+            .await?;
+        Ok(rules)
+    }
+
+    /// Starts a session of `name` as in `start_karma` and runs it as pomodoros,
+    /// beginning with a work phase. Only work and learning can be focused on.
+    pub async fn start_focus(
+        &self,
+        name: &str,
+        purpose: Option<KarmaType>,
+    ) -> Result<FocusState, KarmaServiceError> {
+        let _focus = self.focus_lock.lock().await;
+        if self.catch_up_focus().await?.is_some() {
+            return Err(KarmaServiceError::FocusRunning);
+        }
+        let focused = match &purpose {
+            Some(purpose) => purpose.clone(),
+            None => {
+                karma_resolver::resolve(name, self.karma_repository.get_karma_overview().await?)?
+                    .karma
+                    .get_purpose()
+            }
+        };
+        if !matches!(focused, KarmaType::Work | KarmaType::Learning) {
+            return Err(FocusError::NotFocusable(focused).into());
+        }
+
+        let rules = self.focus_rules().await?;
+        let started = self.start_karma(name, purpose, None).await?;
+        let session_id = started.session.get_id().unwrap_or_default();
+        let focus = FocusSession {
+            id: None,
+            session_id,
+            started_at: started.status.timestamp,
+            rules,
+            ended_at: None,
+            rating: None,
+        };
+        let Some(focus) = self.karma_repository.insert_focus(focus).await? else {
+            // Another one started in between, don't leave this session running
+            self.close_session(session_id, None).await?;
+            return Err(KarmaServiceError::FocusRunning);
+        };
+
+        self.focus_state(
+            focus,
+            started.template.get_name(),
+            started.session.get_purpose(),
+        )
+        .await
+    }
+
+    /// Catches the running focus session up with now. Every phase that started since
+    /// the session last changed state is recorded as an active or paused status at
+    /// the time it started, so nothing is lost while the timer wasn't running. Once
+    /// its last pomodoro is over the session is closed as of then, and a session
+    /// closed by other means ends its focus session too.
+    pub async fn advance_focus(&self) -> Result<Option<FocusTick>, KarmaServiceError> {
+        let _focus = self.focus_lock.lock().await;
+        self.catch_up_focus().await
+    }
+
+    /// `advance_focus` for callers already holding the focus lock
+    async fn catch_up_focus(&self) -> Result<Option<FocusTick>, KarmaServiceError> {
+        let Some(focus) = self.karma_repository.get_running_focus().await? else {
+            return Ok(None);
+        };
+        let tracked = self.tracked_session(focus.session_id).await?;
+        let last = tracked.statuses.last();
+        let mut state = last.map(|status| status.state.clone());
+        if let (Some(State::Closed), Some(last)) = (&state, last) {
+            self.karma_repository
+                .end_focus(focus.id.unwrap_or_default(), last.timestamp)
+                .await?;
+            return Ok(None);
+        }
+
+        // Never replay more than the rules allow, however long nothing was watching
+        let now = self.clock.now();
+        let ends_at = focus.ends_at();
+        let after = last
+            .map(|status| status.timestamp)
+            .unwrap_or_default()
+            .max(focus.started_at);
+        let entered: Vec<PhaseSpan> =
+            phases_between(focus.started_at, &focus.rules, after, now.min(ends_at))
+                .into_iter()
+                .filter(|phase| phase.starts_at < ends_at)
+                .collect();
+        for phase in &entered {
+            let to = phase.phase.state();
+            // Paused by hand during work stays paused until the next work phase
+            if state.as_ref() != Some(&to) {
+                self.transition_at(focus.session_id, to.clone(), None, phase.starts_at)
+                    .await?;
+                state = Some(to);
+            }
+        }
+
+        if now >= ends_at {
+            let closed = self
+                .transition_at(focus.session_id, State::Closed, None, ends_at)
+                .await?;
+            self.karma_repository
+                .end_focus(focus.id.unwrap_or_default(), closed.timestamp)
+                .await?;
+            return Ok(None);
+        }
+
+        let state = self
+            .focus_state(focus, tracked.name, tracked.session.get_purpose())
+            .await?;
+        Ok(Some(FocusTick { state, entered }))
+    }
+
+    /// Notes that the running focus session was interrupted now
+    pub async fn log_interruption(
+        &self,
+        note: &str,
+        external: bool,
+    ) -> Result<Interruption, KarmaServiceError> {
+        let focus = self
+            .karma_repository
+            .get_running_focus()
+            .await?
+            .ok_or(KarmaServiceError::NoFocusRunning)?;
+
+        Ok(self
+            .karma_repository
+            .insert_interruption(Interruption {
+                id: None,
+                focus_id: focus.id.unwrap_or_default(),
+                at: self.clock.now(),
+                note: note.trim().to_string(),
+                external,
+            })
+            .await?)
+    }
+
+    /// Ends the running focus session and closes its session, `rating` is how well
+    /// it went from 1 to 5
+    pub async fn stop_focus(&self, rating: Option<u8>) -> Result<FocusSummary, KarmaServiceError> {
+        let rating = rating.map(validate_rating).transpose()?;
+        let _focus = self.focus_lock.lock().await;
+        let tick = self
+            .catch_up_focus()
+            .await?
+            .ok_or(KarmaServiceError::NoFocusRunning)?;
+        let focus = tick.state.focus;
+        let id = focus.id.unwrap_or_default();
+
+        let closed = self.close_session(focus.session_id, None).await?;
+        if !self
+            .karma_repository
+            .end_focus(id, closed.timestamp)
+            .await?
+        {
+            return Err(KarmaServiceError::NoFocusRunning);
+        }
+        if let Some(rating) = rating {
+            self.karma_repository.rate_focus(id, rating).await?;
+        }
+
+        let focus = self
+            .karma_repository
+            .get_focus(id)
+            .await?
+            .ok_or(KarmaServiceError::FocusNotFound(id))?;
+        self.focus_summary(focus).await
+    }
+
+    /// Rates a focus session from 1 to 5 after it ended
+    pub async fn rate_focus(&self, id: i32, rating: u8) -> Result<(), KarmaServiceError> {
+        let rating = validate_rating(rating)?;
+        if !self.karma_repository.rate_focus(id, rating).await? {
+            return Err(KarmaServiceError::FocusNotFound(id));
+        }
+        Ok(())
+    }
+
+    /// The last `limit` focus sessions, newest first
+    pub async fn focus_history(&self, limit: i64) -> Result<Vec<FocusSummary>, KarmaServiceError> {
+        let mut summaries = Vec::new();
+        for focus in self.karma_repository.get_focus_sessions(limit).await? {
+            summaries.push(self.focus_summary(focus).await?);
+        }
+        Ok(summaries)
+    }
+
+    async fn focus_state(
+        &self,
+        focus: FocusSession,
+        name: String,
+        purpose: KarmaType,
+    ) -> Result<FocusState, KarmaServiceError> {
+        let now = self.clock.now();
+        let interruptions = self
+            .karma_repository
+            .get_interruptions(focus.id.unwrap_or_default())
+            .await?;
+        let phase = focus.phase(now);
+
+        Ok(FocusState {
+            remaining_seconds: (phase.ends_at - now).max(0),
+            completed: focus.completed(now),
+            phase,
+            focus,
+            name,
+            purpose,
+            interruptions,
+        })
+    }
+
+    async fn focus_summary(&self, focus: FocusSession) -> Result<FocusSummary, KarmaServiceError> {
+        let tracked = self.tracked_session(focus.session_id).await?;
+        let interruptions = self
+            .karma_repository
+            .get_interruptions(focus.id.unwrap_or_default())
+            .await?;
+
+        Ok(FocusSummary {
+            completed: focus.completed(self.clock.now()),
+            focus,
+            name: tracked.name,
+            purpose: tracked.session.get_purpose(),
+            interruptions: interruptions.len(),
+        })
+    }
+}
+
+#[cfg(test)]
+mod focus_tests {
+    use super::*;
+    use crate::model::focus::FocusPhase;
+    use crate::storage::common_utilities_tests::karma_service_at;
+
+    const MINUTE: i64 = 60;
+
+    #[tokio::test]
+    async fn test_focus_phases_become_statuses() {
+        let (service, clock) = karma_service_at("focus", "2024-01-01 09:00").await;
+        service
+            .set_focus_rules("work=25,short=5,long=15,every=2")
+            .await
+            .unwrap();
+
+        assert!(matches!(
+            service.start_focus("Running", Some(KarmaType::Sport)).await,
+            Err(KarmaServiceError::Focus(FocusError::NotFocusable(
+                KarmaType::Sport
+            )))
+        ));
+        let started = service
+            .start_focus("Thesis", Some(KarmaType::Learning))
+            .await
+            .unwrap();
+        assert_eq!(started.phase.phase, FocusPhase::Work);
+        assert_eq!(started.remaining_seconds, 25 * MINUTE);
+        assert!(matches!(
+            service.start_focus("Thesis", None).await,
+            Err(KarmaServiceError::FocusRunning)
+        ));
+        let session_id = started.focus.session_id;
+
+        clock.advance(26 * MINUTE);
+        let tick = service.advance_focus().await.unwrap().unwrap();
+        assert_eq!(tick.entered.len(), 1);
+        assert_eq!(tick.state.phase.phase, FocusPhase::ShortBreak);
+        assert_eq!(tick.state.completed, 1);
+        service.log_interruption("Phone call", true).await.unwrap();
+
+        // Nobody watched the timer for a while, the phases are recorded as they were
+        clock.advance(40 * MINUTE);
+        let tick = service.advance_focus().await.unwrap().unwrap();
+        let entered: Vec<FocusPhase> = tick.entered.into_iter().map(|span| span.phase).collect();
+        assert_eq!(entered, vec![FocusPhase::Work, FocusPhase::LongBreak]);
+        assert_eq!(
+            service
+                .session_duration(session_id)
+                .await
+                .unwrap()
+                .active_seconds,
+            50 * MINUTE
+        );
+        assert!(service
+            .advance_focus()
+            .await
+            .unwrap()
+            .unwrap()
+            .entered
+            .is_empty());
+
+        assert!(matches!(
+            service.stop_focus(Some(6)).await,
+            Err(KarmaServiceError::Focus(FocusError::InvalidRating(6)))
+        ));
+        let summary = service.stop_focus(Some(4)).await.unwrap();
+        assert_eq!(summary.completed, 2);
+        assert_eq!(summary.interruptions, 1);
+        assert_eq!(summary.focus.rating, Some(4));
+        assert!(service.advance_focus().await.unwrap().is_none());
+        assert!(matches!(
+            service.log_interruption("", false).await,
+            Err(KarmaServiceError::NoFocusRunning)
+        ));
+
+        // Closing the session by hand ends its focus session too
+        let started = service.start_focus("Thesis", None).await.unwrap();
+        service
+            .close_session(started.focus.session_id, None)
+            .await
+            .unwrap();
+        assert!(service.advance_focus().await.unwrap().is_none());
+        assert_eq!(service.focus_history(10).await.unwrap().len(), 2);
+
+        // A forgotten session ends by itself once its last pomodoro is over
+        service.set_focus_rules("max=2").await.unwrap();
+        let started = service.start_focus("Thesis", None).await.unwrap();
+        clock.advance(3 * 24 * 60 * MINUTE);
+        assert!(service.advance_focus().await.unwrap().is_none());
+        let forgotten = &service.focus_history(1).await.unwrap()[0];
+        assert_eq!(forgotten.completed, 2);
+        assert_eq!(
+            forgotten.focus.ended_at,
+            Some(started.focus.started_at + 55 * MINUTE)
+        );
+        assert_eq!(
+            service
+                .session_duration(started.focus.session_id)
+                .await
+                .unwrap()
+                .active_seconds,
+            50 * MINUTE
+        );
+
+        // The timer catching up while the user stops doesn't get in the way
+        service.start_focus("Thesis", None).await.unwrap();
+        clock.advance(30 * MINUTE);
+        let (tick, summary) = tokio::join!(service.advance_focus(), service.stop_focus(None));
+        tick.unwrap();
+        assert_eq!(summary.unwrap().completed, 1);
+    }
+}
@@ -0,0 +1,183 @@
+use crate::model::goal::{
+    goal_status, Goal, GoalComparison, GoalMetric, GoalStatus, GOAL_HISTORY_PERIODS,
+};
+use crate::model::karma::KarmaType;
+use crate::model::report::ReportPeriod;
+use crate::model::subject::KarmaSubject;
+use crate::storage::KarmaStorage;
+
+use super::karma_service::{KarmaService, KarmaServiceError};
+
+impl<R: KarmaStorage> KarmaService<R> {
+    /// `subject` is a type such as Learning or the name of a karma point
+    pub async fn add_goal(
+        &self,
+        subject: &str,
+        metric: GoalMetric,
+        comparison: GoalComparison,
+        target: f64,
+        period: ReportPeriod,
+    ) -> Result<Goal, KarmaServiceError> {
+        let (subject, name) = match KarmaType::from_name(subject) {
+            Some(purpose) => (
+                KarmaSubject::Category(purpose.clone()),
+                format!("{purpose:?}"),
+            ),
+            None => {
+                let template = self.resolve_karma(subject).await?.karma;
+                (
+                    KarmaSubject::Template(template.get_id().unwrap_or_default()),
+                    template.get_name(),
+                )
+            }
+        };
+        let goal = Goal {
+            id: None,
+            subject,
+            name,
+            metric,
+            comparison,
+            target,
+            period,
+            created_at: self.clock.now(),
+        };
+        goal.validate()?;
+
+        Ok(self.karma_repository.insert_goal(goal).await?)
+    }
+
+    pub async fn delete_goal(&self, id: i32) -> Result<(), KarmaServiceError> {
+        if !self.karma_repository.delete_goal(id).await? {
+            return Err(KarmaServiceError::GoalNotFound(id));
+        }
+        Ok(())
+    }
+
+    /// Every goal with its live progress and how the previous periods went
+    pub async fn goals(&self) -> Result<Vec<GoalStatus>, KarmaServiceError> {
+        let settings = self.time_settings().await?;
+        let now = self.clock.now();
+        let items = self.karma_repository.get_karma_overview().await?;
+        let goals = self.karma_repository.get_goals().await?;
+
+        // One read covers the history of every goal
+        let today = settings.local_date(now);
+        let Some(from) = goals
+            .iter()
+            .map(|goal| {
+                let mut first_day = goal.period.bounds(today, &settings).0;
+                for _ in 0..GOAL_HISTORY_PERIODS {
+                    first_day = goal.period.previous(first_day);
+                }
+                first_day.max(
+                    goal.period
+                        .bounds(settings.local_date(goal.created_at), &settings)
+                        .0,
+                )
+            })
+            .min()
+        else {
+            return Ok(Vec::new());
+        };
+        let sessions = self
+            .karma_repository
+            .get_tracked_sessions(settings.day_start(from), now + 1)
+            .await?;
+
+        Ok(goals
+            .into_iter()
+            .map(|goal| {
+                let name = match &goal.subject {
+                    KarmaSubject::Template(template_id) => items
+                        .iter()
+                        .find(|item| item.karma.get_id() == Some(*template_id))
+                        .map(|item| item.karma.get_name())
+                        .unwrap_or_default(),
+                    KarmaSubject::Category(purpose) => format!("{purpose:?}"),
+                };
+                goal_status(Goal { name, ..goal }, &sessions, now, &settings)
+            })
+            .collect())
+    }
+}
+
+#[cfg(test)]
+mod goal_tests {
+    use super::*;
+    use crate::model::goal::GoalState;
+    use crate::storage::common_utilities_tests::karma_service_at;
+
+    const HOUR: i64 = 60 * 60;
+    const DAY: i64 = 24 * HOUR;
+
+    #[tokio::test]
+    async fn test_goal_progress_and_history() {
+        let (service, clock) = karma_service_at("goal", "2024-01-01 09:00").await;
+
+        let learning = service
+            .add_goal(
+                "learning",
+                GoalMetric::Hours,
+                GoalComparison::AtLeast,
+                5.0,
+                ReportPeriod::Week,
+            )
+            .await
+            .unwrap();
+        service
+            .start_karma("Friends", Some(KarmaType::Social), None)
+            .await
+            .unwrap();
+        let social = service
+            .add_goal(
+                "friends",
+                GoalMetric::Sessions,
+                GoalComparison::AtLeast,
+                2.0,
+                ReportPeriod::Week,
+            )
+            .await
+            .unwrap();
+        assert_eq!(social.subject, KarmaSubject::Template(1));
+        assert!(matches!(
+            service
+                .add_goal(
+                    "learning",
+                    GoalMetric::Hours,
+                    GoalComparison::AtLeast,
+                    0.0,
+                    ReportPeriod::Week
+                )
+                .await,
+            Err(KarmaServiceError::Goal(_))
+        ));
+
+        // Six hours of a course during the first week
+        let course = service
+            .start_karma("Course", Some(KarmaType::Learning), None)
+            .await
+            .unwrap();
+        clock.advance(6 * HOUR);
+        service
+            .close_session(course.session.get_id().unwrap(), None)
+            .await
+            .unwrap();
+
+        clock.advance(7 * DAY);
+        let goals = service.goals().await.unwrap();
+        assert_eq!(goals[0].description, "Learning >= 5h/week");
+        assert_eq!(goals[0].current.state, GoalState::AtRisk);
+        assert_eq!(goals[0].history.len(), 1);
+        assert_eq!(goals[0].history[0].value, 6.0);
+        assert_eq!(goals[0].history[0].state, GoalState::Achieved);
+        assert_eq!(goals[1].goal.name, "Friends");
+        assert_eq!(goals[1].history[0].state, GoalState::Missed);
+
+        service.delete_goal(learning.id.unwrap()).await.unwrap();
+        assert_eq!(service.goals().await.unwrap().len(), 1);
+        assert!(matches!(
+            service.delete_goal(learning.id.unwrap()).await,
+            Err(KarmaServiceError::GoalNotFound(_))
+        ));
+    }
+}
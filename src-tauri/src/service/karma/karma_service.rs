@@ -1,24 +1,172 @@
-use crate::model::karma::KarmaPoint;
+use std::sync::Arc;
+
+use crate::model::achievement::AchievementBook;
+use crate::model::balance::BalanceError;
+use crate::model::focus::FocusError;
+use crate::model::goal::GoalError;
+use crate::model::karma::{KarmaPoint, KarmaStatus, KarmaType, State};
+use crate::model::karma_query::{KarmaListItem, KarmaPage, KarmaQuery};
+use crate::model::karma_resolver::{self, KarmaResolveError};
+use crate::model::karma_search::KarmaSearchResult;
+use crate::model::karma_session::{KarmaSession, StartedSession};
+use crate::model::local_time::TimeSettingsError;
+use crate::model::plan::PlanError;
+use crate::model::recurrence::RecurrenceError;
+use crate::model::reward::RewardError;
+use crate::model::scoring::ScoringError;
+use crate::model::sleep::SleepError;
+use crate::model::suggestion::SuggestionError;
+use crate::service::clock::{Clock, SystemClock};
+use crate::service::notifier::{Notifier, SilentNotifier};
 use crate::storage::db::DbManagerError;
-use crate::storage::karma_repository::KarmaRepository;
+use crate::storage::KarmaStorage;
 
+use chrono::NaiveDate;
 use serde::Serialize;
 use thiserror::Error;
+use tokio::sync::Mutex;
 
 #[derive(Debug, Error, Serialize)]
 pub enum KarmaServiceError {
     #[error("Storage failed with: {0}")]
     Storage(#[from] DbManagerError),
+
+    #[error("{0}")]
+    Resolve(#[from] KarmaResolveError),
+
+    #[error("{0}")]
+    Recurrence(#[from] RecurrenceError),
+
+    #[error("{0}")]
+    TimeSettings(#[from] TimeSettingsError),
+
+    #[error("{0}")]
+    Balance(#[from] BalanceError),
+
+    #[error("{0}")]
+    Goal(#[from] GoalError),
+
+    #[error("No goal with id {0}")]
+    GoalNotFound(i32),
+
+    #[error("{0}")]
+    Scoring(#[from] ScoringError),
+
+    #[error("{0}")]
+    Reward(#[from] RewardError),
+
+    #[error("{0}")]
+    Plan(#[from] PlanError),
+
+    #[error("No plan entry with id {0}")]
+    PlanEntryNotFound(i32),
+
+    #[error("{0}")]
+    Suggestion(#[from] SuggestionError),
+
+    #[error("No blocked time with id {0}")]
+    BlockedTimeNotFound(i32),
+
+    #[error("{0}")]
+    Focus(#[from] FocusError),
+
+    #[error("A focus session is already running")]
+    FocusRunning,
+
+    #[error("No focus session is running")]
+    NoFocusRunning,
+
+    #[error("No focus session with id {0}")]
+    FocusNotFound(i32),
+
+    #[error("{0}")]
+    Sleep(#[from] SleepError),
+
+    #[error("Sleep was already tracked then, on the night of {0}")]
+    SleepOverlaps(NaiveDate),
+
+    #[error("No reward called {0}")]
+    RewardNotFound(String),
+
+    #[error("Other redemptions kept coming in, try again")]
+    RedemptionConflict,
+
+    #[error("Karma point {0} has no schedule")]
+    NotScheduled(String),
+
+    #[error("Karma point {0} isn't a habit to avoid")]
+    NotAvoided(String),
+
+    #[error("No occurrence with id {0}")]
+    OccurrenceNotFound(i32),
+
+    #[error("Occurrence {0} is already resolved")]
+    OccurrenceAlreadyResolved(i32),
+
+    #[error("No session with id {0}")]
+    SessionNotFound(i32),
+
+    #[error("A {} session can't become {}", .from.as_ref().map(|state| state.to_string()).unwrap_or_else(|| "new".to_string()), .to.to_string())]
+    InvalidTransition { from: Option<State>, to: State },
 }
 
 #[derive(Debug)]
-pub struct KarmaService<R: KarmaRepository> {
-    karma_repository: R,
+pub struct KarmaService<R: KarmaStorage> {
+    pub(super) karma_repository: R,
+    pub(super) clock: Arc<dyn Clock>,
+    pub(super) achievements: AchievementBook,
+    pub(super) notifier: Arc<dyn Notifier>,
+    /// Held while the running focus session is caught up or stopped, so the timer
+    /// and the user don't record the same phase twice
+    pub(super) focus_lock: Mutex<()>,
+    /// Held while a session is started, sqlite would rather fail than wait when
+    /// two transactions both want to write
+    pub(super) start_lock: Mutex<()>,
 }
 
-impl<R: KarmaRepository> KarmaService<R> {
+impl<R: KarmaStorage> KarmaService<R> {
     pub fn new(karma_repository: R) -> Self {
-        KarmaService { karma_repository }
+        KarmaService {
+            karma_repository,
+            clock: Arc::new(SystemClock),
+            achievements: AchievementBook::bundled(),
+            notifier: Arc::new(SilentNotifier),
+            focus_lock: Mutex::new(()),
+            start_lock: Mutex::new(()),
+        }
+    }
+
+    #[cfg(test)]
+    pub fn with_clock(mut self, clock: Arc<dyn Clock>) -> Self {
+        self.clock = clock;
+        self
+    }
+
+    pub fn with_achievements(mut self, achievements: AchievementBook) -> Self {
+        self.achievements = achievements;
+        self
+    }
+
+    pub fn with_notifier(mut self, notifier: Arc<dyn Notifier>) -> Self {
+        self.notifier = notifier;
+        self
+    }
+
+    /// Brings what is derived from statuses up to date after some were written,
+    /// `closed_with` being what the session turned out to be if it ended as another type
+    pub(super) async fn statuses_changed(
+        &self,
+        template_id: i32,
+        purpose: KarmaType,
+        closed_with: Option<KarmaType>,
+    ) -> Result<(), KarmaServiceError> {
+        let touched: Vec<KarmaType> = [Some(purpose.clone()), closed_with]
+            .into_iter()
+            .flatten()
+            .collect();
+        self.update_streaks(template_id, purpose).await?;
+        self.unlock_achievements(Some(&touched)).await?;
+        Ok(())
     }
 
     pub async fn create_karma(&self, karma: KarmaPoint) -> Result<KarmaPoint, KarmaServiceError> {
@@ -27,4 +175,169 @@ impl<R: KarmaRepository> KarmaService<R> {
             .await
             .map_err(|e| e.into())
     }
+
+    pub async fn list_karma(&self, query: &KarmaQuery) -> Result<KarmaPage, KarmaServiceError> {
+        self.karma_repository
+            .list_karma(query)
+            .await
+            .map_err(|e| e.into())
+    }
+
+    pub async fn search_karma(
+        &self,
+        text: &str,
+        limit: u32,
+    ) -> Result<Vec<KarmaSearchResult>, KarmaServiceError> {
+        self.karma_repository
+            .search_karma(text, limit)
+            .await
+            .map_err(|e| e.into())
+    }
+
+    pub async fn rebuild_search_index(&self) -> Result<(), KarmaServiceError> {
+        self.karma_repository
+            .rebuild_search_index()
+            .await
+            .map_err(|e| e.into())
+    }
+
+    /// Finds the karma point meant by a name typed by the user, tolerating
+    /// different case, a prefix or a typo
+    pub async fn resolve_karma(&self, name: &str) -> Result<KarmaListItem, KarmaServiceError> {
+        let items = self.karma_repository.get_karma_overview().await?;
+        Ok(karma_resolver::resolve(name, items)?)
+    }
+
+    /// Starts a new session of the activity called `name`. With a type the activity is
+    /// created when no template has that name yet, the type then only applies to this
+    /// session. Without one the name must resolve to an existing template.
+    pub async fn start_karma(
+        &self,
+        name: &str,
+        purpose: Option<KarmaType>,
+        planned_duration: Option<i64>,
+    ) -> Result<StartedSession, KarmaServiceError> {
+        let _start = self.start_lock.lock().await;
+        let items = self.karma_repository.get_karma_overview().await?;
+
+        let template = match &purpose {
+            Some(purpose) => {
+                let existing = items.into_iter().find(|item| {
+                    item.karma.get_name().to_lowercase() == name.trim().to_lowercase()
+                });
+                match existing {
+                    Some(item) => item.karma,
+                    // Created together with the session
+                    None => KarmaPoint::new(purpose.clone(), name.trim().to_string())
+                        .with_default_duration(planned_duration),
+                }
+            }
+            None => karma_resolver::resolve(name, items)?.karma,
+        };
+
+        let now = self.clock.now();
+        let settings = self.time_settings().await?;
+        let mut session = KarmaSession::from_template(&template, now);
+        if let Some(purpose) = purpose {
+            session = session.with_purpose(purpose);
+        }
+        if planned_duration.is_some() {
+            session = session.with_planned_duration(planned_duration);
+        }
+
+        let started = self
+            .karma_repository
+            .start_session(
+                template,
+                session,
+                KarmaStatus::new(0, State::Active, now).in_timezone(&settings.timezone),
+                settings.local_date(now),
+            )
+            .await?;
+
+        self.statuses_changed(
+            started.template.get_id().unwrap_or_default(),
+            started.session.get_purpose(),
+            None,
+        )
+        .await?;
+
+        Ok(started)
+    }
+}
+
+#[cfg(test)]
+mod karma_service_tests {
+    use chrono::NaiveDate;
+
+    use super::*;
+    use crate::storage::common_utilities_tests::karma_service_at;
+    use crate::storage::karma_repository::KarmaRepository;
+
+    #[tokio::test]
+    async fn test_start_karma_reuses_templates() {
+        let (service, clock) = karma_service_at("sessions", "2024-01-01 09:00").await;
+
+        let first = service
+            .start_karma("Morning run", Some(KarmaType::Sport), Some(1800))
+            .await
+            .unwrap();
+        let second = service.start_karma("morning", None, None).await.unwrap();
+        let third = service
+            .start_karma("MORNING RUN", Some(KarmaType::Social), None)
+            .await
+            .unwrap();
+
+        assert_eq!(first.template.get_id(), second.template.get_id());
+        assert_eq!(first.template.get_id(), third.template.get_id());
+        assert_ne!(first.session.get_id(), second.session.get_id());
+        assert_eq!(second.session.get_planned_duration(), Some(1800));
+        assert_eq!(third.session.get_purpose(), KarmaType::Social);
+        assert_eq!(third.status.state, State::Active);
+
+        let template_id = first.template.get_id().unwrap();
+        assert_eq!(
+            service
+                .karma_repository
+                .get_sessions(template_id)
+                .await
+                .unwrap()
+                .len(),
+            3
+        );
+
+        assert!(matches!(
+            service.start_karma("Swimming", None, None).await,
+            Err(KarmaServiceError::Resolve(_))
+        ));
+
+        // Two starts of the same new activity share the template either one creates
+        let (first, second) = tokio::join!(
+            service.start_karma("Swimming", Some(KarmaType::Sport), None),
+            service.start_karma("Swimming", Some(KarmaType::Sport), None)
+        );
+        let (first, second) = (first.unwrap(), second.unwrap());
+        assert_eq!(first.template.get_id(), second.template.get_id());
+        assert_ne!(first.session.get_id(), second.session.get_id());
+        assert_eq!(second.status.session_id, second.session.get_id().unwrap());
+
+        // A template another writer created under that name meanwhile is reused
+        let template = KarmaPoint::new(KarmaType::Sport, "Swimming".to_string());
+        let now = clock.now();
+        let third = service
+            .karma_repository
+            .start_session(
+                template.clone(),
+                KarmaSession::from_template(&template, now),
+                KarmaStatus::new(0, State::Active, now),
+                NaiveDate::from_ymd_opt(2024, 1, 1).unwrap(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(third.template.get_id(), first.template.get_id());
+        assert_eq!(
+            third.session.get_template_id(),
+            first.template.get_id().unwrap()
+        );
+    }
 }
@@ -1 +1,17 @@
+pub mod achievement;
+pub mod balance;
+pub mod deviation;
+pub mod focus;
+pub mod goal;
 pub mod karma_service;
+pub mod plan;
+pub mod relapse;
+pub mod report;
+pub mod reward;
+pub mod schedule;
+pub mod scoring;
+pub mod settings;
+pub mod sleep;
+pub mod streak;
+pub mod suggestion;
+pub mod tracking;
@@ -0,0 +1,185 @@
+use chrono::NaiveDate;
+use tracing::info;
+
+use crate::model::karma::KarmaType;
+use crate::model::local_time::TimeSettings;
+use crate::model::plan::{carry_over, review_plan, PlanEntry, PlanError, PlanReview};
+use crate::storage::KarmaStorage;
+
+use super::karma_service::{KarmaService, KarmaServiceError};
+
+impl<R: KarmaStorage> KarmaService<R> {
+    /// Adds the karma point `name` resolves to at the end of the plan of `day`. The
+    /// type and duration default to the point's, `starts_at` is a local time of day
+    /// in seconds.
+    pub async fn add_plan_entry(
+        &self,
+        day: NaiveDate,
+        name: &str,
+        purpose: Option<KarmaType>,
+        duration: Option<i64>,
+        starts_at: Option<u32>,
+    ) -> Result<PlanEntry, KarmaServiceError> {
+        let template = self.resolve_karma(name).await?.karma;
+        let duration = duration
+            .or(template.get_default_duration())
+            .ok_or_else(|| PlanError::MissingDuration(template.get_name()))?;
+        let entry = PlanEntry {
+            id: None,
+            day,
+            position: 0,
+            template_id: template.get_id().unwrap_or_default(),
+            name: template.get_name(),
+            purpose: purpose.unwrap_or(template.get_purpose()),
+            duration,
+            starts_at,
+            session_id: None,
+            carried_from: None,
+            settled: false,
+        };
+        let settings = self.time_settings().await?;
+        entry.validate(settings.local_date(self.clock.now()))?;
+
+        Ok(self.karma_repository.insert_plan_entry(entry).await?)
+    }
+
+    pub async fn remove_plan_entry(&self, id: i32) -> Result<(), KarmaServiceError> {
+        if !self.karma_repository.delete_plan_entry(id).await? {
+            return Err(KarmaServiceError::PlanEntryNotFound(id));
+        }
+        Ok(())
+    }
+
+    /// The plan of `day` next to what was actually done, live for today
+    pub async fn review_plan(&self, day: NaiveDate) -> Result<PlanReview, KarmaServiceError> {
+        let settings = self.time_settings().await?;
+        self.review_day(day, &settings).await
+    }
+
+    /// Settles the plans of the days before `today`, carrying their unfinished entries
+    /// over to today. Returns how many were carried over.
+    pub(super) async fn carry_over_plans(
+        &self,
+        today: NaiveDate,
+        settings: &TimeSettings,
+    ) -> Result<u64, KarmaServiceError> {
+        let mut carried_over = 0;
+        for day in self.karma_repository.get_unsettled_plan_days(today).await? {
+            let review = self.review_day(day, settings).await?;
+            let links: Vec<(i32, i32)> = review
+                .entries
+                .iter()
+                .filter(|review| review.entry.session_id.is_none())
+                .filter_map(|review| Some((review.entry.id?, review.session.as_ref()?.session_id)))
+                .collect();
+            let carried = carry_over(&review, today);
+
+            if self
+                .karma_repository
+                .settle_plan(day, &links, &carried)
+                .await?
+            {
+                info!("Carried {} plan entries of {day} over", carried.len());
+                carried_over += carried.len() as u64;
+            }
+        }
+        Ok(carried_over)
+    }
+
+    async fn review_day(
+        &self,
+        day: NaiveDate,
+        settings: &TimeSettings,
+    ) -> Result<PlanReview, KarmaServiceError> {
+        let entries = self.karma_repository.get_plan(day).await?;
+        let sessions = self
+            .karma_repository
+            .get_tracked_sessions(settings.day_start(day), settings.next_day_start(day))
+            .await?;
+
+        Ok(review_plan(
+            day,
+            &entries,
+            &sessions,
+            self.clock.now(),
+            settings,
+        ))
+    }
+}
+
+#[cfg(test)]
+mod plan_tests {
+    use super::*;
+    use crate::model::plan::PlanOutcome;
+    use crate::model::recurrence::parse_date;
+    use crate::storage::common_utilities_tests::karma_service_at;
+
+    const HOUR: i64 = 60 * 60;
+
+    #[tokio::test]
+    async fn test_plan_review_and_carry_over() {
+        let (service, clock) = karma_service_at("plan", "2024-01-01 09:00").await;
+        let monday = parse_date("2024-01-01").unwrap();
+        let tuesday = parse_date("2024-01-02").unwrap();
+
+        let started = service
+            .start_karma("Thesis", Some(KarmaType::Learning), Some(2 * HOUR))
+            .await
+            .unwrap();
+        clock.advance(HOUR);
+        service
+            .close_session(started.session.get_id().unwrap(), None)
+            .await
+            .unwrap();
+        service
+            .start_karma("Groceries", Some(KarmaType::Work), Some(HOUR))
+            .await
+            .unwrap();
+
+        service
+            .add_plan_entry(monday, "Thesis", None, None, None)
+            .await
+            .unwrap();
+        service
+            .add_plan_entry(monday, "Groceries", None, Some(HOUR / 2), Some(18 * 3600))
+            .await
+            .unwrap();
+        assert!(matches!(
+            service
+                .add_plan_entry(
+                    parse_date("2023-12-31").unwrap(),
+                    "Thesis",
+                    None,
+                    None,
+                    None
+                )
+                .await,
+            Err(KarmaServiceError::Plan(PlanError::PastDay(_)))
+        ));
+
+        let review = service.review_plan(monday).await.unwrap();
+        assert_eq!(review.entries[0].outcome, PlanOutcome::Partial);
+        assert_eq!(review.entries[1].outcome, PlanOutcome::Pending);
+
+        // The groceries session is left running past midnight
+        clock.advance(24 * HOUR);
+        let report = service.run_daily_job().await.unwrap();
+        assert_eq!(report.carried_over, 1);
+        assert_eq!(service.run_daily_job().await.unwrap().carried_over, 0);
+
+        let monday = service.review_plan(monday).await.unwrap();
+        assert!(monday.over);
+        assert!(monday.entries.iter().all(|review| review.entry.settled));
+        assert_eq!(monday.entries[0].entry.session_id, Some(1));
+
+        let tuesday = service.review_plan(tuesday).await.unwrap();
+        assert_eq!(tuesday.entries.len(), 1);
+        assert_eq!(tuesday.entries[0].entry.name, "Thesis");
+        assert_eq!(tuesday.entries[0].entry.duration, HOUR);
+        assert_eq!(
+            tuesday.entries[0].entry.carried_from,
+            Some(parse_date("2024-01-01").unwrap())
+        );
+        assert_eq!(tuesday.entries[0].outcome, PlanOutcome::Pending);
+    }
+}
@@ -0,0 +1,164 @@
+use crate::model::karma::{KarmaPoint, KarmaStatus, Polarity, State};
+use crate::model::karma_session::{KarmaSession, StartedSession};
+use crate::model::relapse::{relapse_report, RelapseReport};
+use crate::model::report::ReportPeriod;
+use crate::storage::KarmaStorage;
+
+use super::karma_service::{KarmaService, KarmaServiceError};
+
+impl<R: KarmaStorage> KarmaService<R> {
+    /// Tracks the karma point called `name` as a habit to avoid, or as something to do
+    /// again. Its past sessions count as relapses or not from now on.
+    pub async fn set_polarity(
+        &self,
+        name: &str,
+        polarity: Polarity,
+    ) -> Result<KarmaPoint, KarmaServiceError> {
+        let template = self.resolve_karma(name).await?.karma;
+        self.karma_repository
+            .update_polarity(template.get_id().unwrap_or_default(), polarity)
+            .await?;
+        // Its sessions stop or start keeping streaks going
+        self.karma_repository.clear_streak_states(None).await?;
+        Ok(template.with_polarity(polarity))
+    }
+
+    /// Records a relapse into the habit to avoid called `name`, as a session
+    /// closed as soon as it started
+    pub async fn log_relapse(&self, name: &str) -> Result<StartedSession, KarmaServiceError> {
+        let template = self.resolve_karma(name).await?.karma;
+        if template.get_polarity() != Polarity::Negative {
+            return Err(KarmaServiceError::NotAvoided(template.get_name()));
+        }
+
+        let now = self.clock.now();
+        let settings = self.time_settings().await?;
+        let session = self
+            .karma_repository
+            .insert_session(KarmaSession::from_template(&template, now))
+            .await?;
+        let session_id = session.get_id().unwrap_or_default();
+        for state in [State::Active, State::Closed] {
+            self.karma_repository
+                .insert_karma_status(
+                    KarmaStatus::new(session_id, state, now).in_timezone(&settings.timezone),
+                )
+                .await?;
+        }
+        self.statuses_changed(
+            template.get_id().unwrap_or_default(),
+            session.get_purpose(),
+            None,
+        )
+        .await?;
+
+        Ok(StartedSession {
+            template,
+            session,
+            status: KarmaStatus::new(session_id, State::Closed, now)
+                .in_timezone(&settings.timezone),
+        })
+    }
+
+    /// Every habit to avoid with its relapses counted per `period`
+    pub async fn relapses(&self, period: ReportPeriod) -> Result<RelapseReport, KarmaServiceError> {
+        let settings = self.time_settings().await?;
+        let rules = self.scoring_rules().await?;
+        let habits: Vec<KarmaPoint> = self
+            .karma_repository
+            .get_all_karma()
+            .await?
+            .into_iter()
+            .filter(|karma| karma.get_polarity() == Polarity::Negative)
+            .collect();
+        let sessions = self.karma_repository.get_relapse_sessions().await?;
+
+        Ok(relapse_report(
+            &habits,
+            &sessions,
+            period,
+            &rules,
+            self.clock.now(),
+            &settings,
+        ))
+    }
+}
+
+#[cfg(test)]
+mod relapse_tests {
+    use super::*;
+    use crate::model::goal::{GoalComparison, GoalMetric};
+    use crate::model::karma::KarmaType;
+    use crate::model::relapse::RelapseTrend;
+    use crate::storage::common_utilities_tests::karma_service_at;
+
+    const HOUR: i64 = 60 * 60;
+
+    #[tokio::test]
+    async fn test_relapses_of_habits_to_avoid() {
+        let (service, clock) = karma_service_at("relapse", "2024-01-01 09:00").await;
+
+        service
+            .start_karma("Doomscrolling", Some(KarmaType::Social), None)
+            .await
+            .unwrap();
+        assert!(matches!(
+            service.log_relapse("doomscrolling").await,
+            Err(KarmaServiceError::NotAvoided(_))
+        ));
+        // Its first session counts as a relapse once it's a habit to avoid
+        let habit = service
+            .set_polarity("doomscrolling", Polarity::Negative)
+            .await
+            .unwrap();
+        assert_eq!(habit.get_polarity(), Polarity::Negative);
+
+        clock.advance(3 * HOUR);
+        service.log_relapse("doom").await.unwrap();
+        clock.advance(HOUR);
+
+        let report = service.relapses(ReportPeriod::Week).await.unwrap();
+        assert_eq!(report.habits.len(), 1);
+        let habit = &report.habits[0];
+        assert_eq!(habit.relapses, 2);
+        assert_eq!(habit.clean_seconds, HOUR);
+        assert_eq!(habit.longest_clean_seconds, 3 * HOUR);
+        assert_eq!(habit.history.len(), 1);
+        assert_eq!(habit.trend, RelapseTrend::Steady);
+        // The first session is still running, only the logged relapse is scored
+        assert_eq!(habit.points, -2.0);
+        assert_eq!(report.points, -2.0);
+
+        let balance = service.points_balance(Some(1)).await.unwrap();
+        assert_eq!(balance.balance, -2.0);
+
+        // Relapses are no social time: no goal progress, streak, balance or deviation
+        service
+            .add_goal(
+                "Social",
+                GoalMetric::Sessions,
+                GoalComparison::AtLeast,
+                1.0,
+                ReportPeriod::Week,
+            )
+            .await
+            .unwrap();
+        assert_eq!(service.goals().await.unwrap()[0].current.value, 0.0);
+        assert!(service.streaks().await.unwrap().is_empty());
+        let social_seconds = service
+            .balance()
+            .await
+            .unwrap()
+            .categories
+            .iter()
+            .find(|category| category.purpose == KarmaType::Social)
+            .map(|category| category.actual_seconds)
+            .unwrap_or_default();
+        assert_eq!(social_seconds, 0);
+        let deviations = service
+            .deviation_analysis(None, None, ReportPeriod::Week)
+            .await
+            .unwrap();
+        assert_eq!(deviations.closed, 0);
+    }
+}
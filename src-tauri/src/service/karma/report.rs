@@ -0,0 +1,104 @@
+use chrono::{Duration, NaiveDate};
+
+use crate::model::local_time::TimeSettings;
+use crate::model::report::{compare, period_stats, KarmaReport, PeriodStats, ReportPeriod};
+use crate::storage::KarmaStorage;
+
+use super::karma_service::{KarmaService, KarmaServiceError};
+
+impl<R: KarmaStorage> KarmaService<R> {
+    /// Summarises the week or month `date` falls in, today by default, next to the
+    /// one before it. Days and weeks follow the time settings.
+    pub async fn report(
+        &self,
+        period: ReportPeriod,
+        date: Option<NaiveDate>,
+    ) -> Result<KarmaReport, KarmaServiceError> {
+        let settings = self.time_settings().await?;
+        let date = date.unwrap_or_else(|| settings.local_date(self.clock.now()));
+
+        let (first_day, last_day) = period.bounds(date, &settings);
+        let previous_first_day = period.previous(first_day);
+        let current = self.period_stats(first_day, last_day, &settings).await?;
+        let previous = self
+            .period_stats(previous_first_day, first_day - Duration::days(1), &settings)
+            .await?;
+
+        Ok(compare(period, current, previous))
+    }
+
+    async fn period_stats(
+        &self,
+        first_day: NaiveDate,
+        last_day: NaiveDate,
+        settings: &TimeSettings,
+    ) -> Result<PeriodStats, KarmaServiceError> {
+        let sessions = self
+            .karma_repository
+            .get_tracked_sessions(
+                settings.day_start(first_day),
+                settings.next_day_start(last_day),
+            )
+            .await?;
+        let occurrences = self
+            .karma_repository
+            .get_occurrences(first_day, last_day, None)
+            .await?;
+
+        Ok(period_stats(
+            first_day,
+            last_day,
+            &sessions,
+            &occurrences,
+            self.clock.now(),
+            settings,
+        ))
+    }
+}
+
+#[cfg(test)]
+mod report_tests {
+    use super::*;
+    use crate::model::karma::KarmaType;
+    use crate::model::report::ReportFormat;
+    use crate::storage::common_utilities_tests::karma_service_at;
+
+    const HOUR: i64 = 60 * 60;
+
+    #[tokio::test]
+    async fn test_monthly_report() {
+        let (service, clock) = karma_service_at("report", "2024-01-31 09:00").await;
+
+        let started = service
+            .start_karma("Reading", Some(KarmaType::Learning), None)
+            .await
+            .unwrap();
+        clock.advance(2 * HOUR);
+        service
+            .close_session(started.session.get_id().unwrap(), None)
+            .await
+            .unwrap();
+        // Runs into February, only the part in January counts
+        clock.advance(12 * HOUR);
+        service.start_karma("reading", None, None).await.unwrap();
+        clock.advance(4 * HOUR);
+
+        let report = service.report(ReportPeriod::Month, None).await.unwrap();
+        assert_eq!(report.current.first_day.to_string(), "2024-02-01");
+        assert_eq!(report.current.total_seconds, 3 * HOUR);
+        assert_eq!(report.current.sessions, 0);
+        assert_eq!(report.changes.percent, Some(0.0));
+
+        let january = service
+            .report(ReportPeriod::Month, NaiveDate::from_ymd_opt(2024, 1, 15))
+            .await
+            .unwrap();
+        assert_eq!(january.current.total_seconds, 3 * HOUR);
+        assert_eq!(january.current.sessions, 2);
+        assert_eq!(january.previous.total_seconds, 0);
+        assert_eq!(january.changes.percent, None);
+        assert!(january
+            .render(ReportFormat::Markdown)
+            .contains("1. Reading, 2 sessions, 3h 00m"));
+    }
+}
@@ -0,0 +1,202 @@
+use crate::model::reward::{
+    check_redemption, reward_status, Redemption, Reward, RewardError, RewardLimit, RewardShop,
+};
+use crate::storage::KarmaStorage;
+
+use super::karma_service::{KarmaService, KarmaServiceError};
+
+/// How often a redemption is checked again when others keep coming in first
+const REDEEM_ATTEMPTS: usize = 3;
+
+impl<R: KarmaStorage> KarmaService<R> {
+    /// `limit` such as `once per week`, unlimited when missing
+    pub async fn add_reward(
+        &self,
+        name: &str,
+        cost: f64,
+        limit: Option<&str>,
+    ) -> Result<Reward, KarmaServiceError> {
+        let reward = Reward {
+            id: None,
+            name: name.trim().to_string(),
+            cost,
+            limit: limit.map(RewardLimit::parse).transpose()?,
+            created_at: self.clock.now(),
+        };
+        reward.validate()?;
+
+        self.karma_repository
+            .insert_reward(reward)
+            .await?
+            .ok_or_else(|| RewardError::DuplicateName(name.trim().to_string()).into())
+    }
+
+    /// Takes the reward off the shop, its redemptions stay in the history
+    pub async fn delete_reward(&self, name: &str) -> Result<(), KarmaServiceError> {
+        let reward = self.find_reward(name).await?;
+        self.karma_repository
+            .delete_reward(reward.id.unwrap_or_default())
+            .await?;
+        Ok(())
+    }
+
+    /// The balance and every reward with whether it can be redeemed today
+    pub async fn reward_shop(&self) -> Result<RewardShop, KarmaServiceError> {
+        let settings = self.time_settings().await?;
+        let today = settings.local_date(self.clock.now());
+        let balance = self.points_balance(Some(1)).await?.balance;
+        let redemptions = self.karma_repository.get_redemptions(0).await?;
+
+        let rewards = self
+            .karma_repository
+            .get_rewards()
+            .await?
+            .iter()
+            .map(|reward| reward_status(reward, &redemptions, balance, today, &settings))
+            .collect();
+
+        Ok(RewardShop { balance, rewards })
+    }
+
+    /// Spends the cost of the reward called `name` when the balance covers it and its
+    /// limit allows it
+    pub async fn redeem_reward(&self, name: &str) -> Result<Redemption, KarmaServiceError> {
+        let reward = self.find_reward(name).await?;
+        let settings = self.time_settings().await?;
+
+        for _ in 0..REDEEM_ATTEMPTS {
+            // Read before the balance, a redemption in between makes the insert fail
+            let redemptions = self.karma_repository.get_redemptions(0).await?;
+            let last_seen = redemptions
+                .iter()
+                .filter_map(|redemption| redemption.id)
+                .max();
+            let balance = self.points_balance(Some(1)).await?.balance;
+
+            let now = self.clock.now();
+            let today = settings.local_date(now);
+            let status = reward_status(&reward, &redemptions, balance, today, &settings);
+            check_redemption(&status, balance)?;
+
+            let redemption = Redemption {
+                id: None,
+                reward_id: reward.id.unwrap_or_default(),
+                name: reward.name.clone(),
+                cost: reward.cost,
+                redeemed_at: now,
+                redeemed_on: today,
+            };
+            if let Some(redemption) = self
+                .karma_repository
+                .insert_redemption(redemption, last_seen)
+                .await?
+            {
+                return Ok(redemption);
+            }
+        }
+
+        Err(KarmaServiceError::RedemptionConflict)
+    }
+
+    /// Every redemption, the latest first
+    pub async fn redemptions(&self) -> Result<Vec<Redemption>, KarmaServiceError> {
+        let mut redemptions = self.karma_repository.get_redemptions(0).await?;
+        redemptions.reverse();
+        Ok(redemptions)
+    }
+
+    async fn find_reward(&self, name: &str) -> Result<Reward, KarmaServiceError> {
+        self.karma_repository
+            .get_rewards()
+            .await?
+            .into_iter()
+            .find(|reward| reward.name.eq_ignore_ascii_case(name.trim()))
+            .ok_or_else(|| KarmaServiceError::RewardNotFound(name.trim().to_string()))
+    }
+}
+
+#[cfg(test)]
+mod reward_tests {
+    use super::*;
+    use crate::model::karma::KarmaType;
+    use crate::storage::common_utilities_tests::karma_service_at;
+
+    const HOUR: i64 = 60 * 60;
+    const DAY: i64 = 24 * HOUR;
+
+    #[tokio::test]
+    async fn test_redeem_within_balance_and_limit() {
+        let (service, clock) = karma_service_at("reward", "2024-01-01 09:00").await;
+        service.set_scoring_rules("sport=2, decay=0").await.unwrap();
+
+        service
+            .add_reward("Cinema", 5.0, Some("once per week"))
+            .await
+            .unwrap();
+        assert!(matches!(
+            service.add_reward("cinema", 1.0, None).await,
+            Err(KarmaServiceError::Reward(RewardError::DuplicateName(_)))
+        ));
+        assert!(service.add_reward("Cake", 0.0, None).await.is_err());
+
+        let started = service
+            .start_karma("Run", Some(KarmaType::Sport), None)
+            .await
+            .unwrap();
+        clock.advance(2 * HOUR);
+        service
+            .close_session(started.session.get_id().unwrap(), None)
+            .await
+            .unwrap();
+        // 4 points earned, not enough yet
+        assert!(matches!(
+            service.redeem_reward("cinema").await,
+            Err(KarmaServiceError::Reward(
+                RewardError::InsufficientBalance { .. }
+            ))
+        ));
+
+        let started = service
+            .start_karma("Run", Some(KarmaType::Sport), None)
+            .await
+            .unwrap();
+        clock.advance(2 * HOUR);
+        service
+            .close_session(started.session.get_id().unwrap(), None)
+            .await
+            .unwrap();
+        let redemption = service.redeem_reward("cinema").await.unwrap();
+        assert_eq!(redemption.cost, 5.0);
+
+        let shop = service.reward_shop().await.unwrap();
+        assert_eq!(shop.balance, 3.0);
+        assert_eq!(shop.rewards[0].redeemed, Some(1));
+        let ledger = service.points_balance(Some(1)).await.unwrap();
+        assert_eq!(ledger.ledger[0].spent, 5.0);
+        assert_eq!(ledger.ledger[0].decayed, 0.0);
+
+        // Affordable again but the limit holds until next Monday
+        service.add_reward("Coffee", 1.0, None).await.unwrap();
+        service.redeem_reward("coffee").await.unwrap();
+        let started = service
+            .start_karma("Run", Some(KarmaType::Sport), None)
+            .await
+            .unwrap();
+        clock.advance(2 * HOUR);
+        service
+            .close_session(started.session.get_id().unwrap(), None)
+            .await
+            .unwrap();
+        assert!(matches!(
+            service.redeem_reward("cinema").await,
+            Err(KarmaServiceError::Reward(RewardError::LimitReached { .. }))
+        ));
+        clock.advance(7 * DAY);
+        service.redeem_reward("cinema").await.unwrap();
+
+        let history = service.redemptions().await.unwrap();
+        let names: Vec<&str> = history.iter().map(|r| r.name.as_str()).collect();
+        assert_eq!(names, vec!["Cinema", "Coffee", "Cinema"]);
+        assert_eq!(service.reward_shop().await.unwrap().balance, 1.0);
+    }
+}
@@ -0,0 +1,287 @@
+use chrono::{Duration, NaiveDate};
+use tracing::info;
+
+use crate::model::local_time::TimeSettings;
+use crate::model::recurrence::Recurrence;
+use crate::model::schedule::{
+    DailyJobReport, KarmaOccurrence, KarmaSchedule, OccurrenceResolution, OccurrenceStatus,
+    PLANNING_HORIZON_DAYS,
+};
+use crate::model::subject::KarmaSubject;
+use crate::storage::KarmaStorage;
+
+use super::karma_service::{KarmaService, KarmaServiceError};
+
+impl<R: KarmaStorage> KarmaService<R> {
+    /// Attaches a recurrence to the template `name` resolves to, replacing any previous
+    /// one, and plans its upcoming occurrences right away
+    pub async fn set_schedule(
+        &self,
+        name: &str,
+        recurrence: Recurrence,
+    ) -> Result<KarmaSchedule, KarmaServiceError> {
+        let template = self.resolve_karma(name).await?.karma;
+        let template_id = template.get_id().unwrap_or_default();
+
+        // Days before today are never planned, so they can't turn into missed ones
+        let settings = self.time_settings().await?;
+        let today = settings.local_date(self.clock.now());
+        let schedule = KarmaSchedule {
+            template_id,
+            recurrence,
+            planned_until: today.pred_opt(),
+        };
+        self.karma_repository.set_schedule(&schedule, today).await?;
+        self.karma_repository
+            .clear_streak_states(Some(&KarmaSubject::Template(template_id)))
+            .await?;
+        info!(
+            "Scheduled {}: {}",
+            template.get_name(),
+            schedule.recurrence.rule
+        );
+
+        self.plan_schedule(&schedule, today, &settings).await?;
+
+        self.karma_repository
+            .get_schedule(template_id)
+            .await?
+            .ok_or_else(|| KarmaServiceError::NotScheduled(template.get_name()))
+    }
+
+    pub async fn clear_schedule(&self, name: &str) -> Result<(), KarmaServiceError> {
+        let template = self.resolve_karma(name).await?.karma;
+        let template_id = template.get_id().unwrap_or_default();
+
+        if !self
+            .karma_repository
+            .delete_schedule(template_id, self.today().await?)
+            .await?
+        {
+            return Err(KarmaServiceError::NotScheduled(template.get_name()));
+        }
+        self.karma_repository
+            .clear_streak_states(Some(&KarmaSubject::Template(template_id)))
+            .await?;
+        Ok(())
+    }
+
+    /// The dates the schedule of `name` falls on, whether planned yet or not
+    pub async fn expand_schedule(
+        &self,
+        name: &str,
+        from: NaiveDate,
+        to: NaiveDate,
+    ) -> Result<Vec<NaiveDate>, KarmaServiceError> {
+        let template = self.resolve_karma(name).await?.karma;
+        let schedule = self
+            .karma_repository
+            .get_schedule(template.get_id().unwrap_or_default())
+            .await?
+            .ok_or_else(|| KarmaServiceError::NotScheduled(template.get_name()))?;
+
+        Ok(schedule.recurrence.occurrences(from, to))
+    }
+
+    pub async fn list_occurrences(
+        &self,
+        from: NaiveDate,
+        to: NaiveDate,
+        status: Option<OccurrenceStatus>,
+    ) -> Result<Vec<KarmaOccurrence>, KarmaServiceError> {
+        Ok(self
+            .karma_repository
+            .get_occurrences(from, to, status)
+            .await?)
+    }
+
+    /// Settles an occurrence the daily job couldn't, typically a missed one
+    pub async fn resolve_occurrence(
+        &self,
+        id: i32,
+        resolution: OccurrenceResolution,
+    ) -> Result<KarmaOccurrence, KarmaServiceError> {
+        let occurrence = self
+            .karma_repository
+            .get_occurrence(id)
+            .await?
+            .ok_or(KarmaServiceError::OccurrenceNotFound(id))?;
+
+        if !matches!(
+            occurrence.status,
+            OccurrenceStatus::Planned | OccurrenceStatus::Missed
+        ) {
+            return Err(KarmaServiceError::OccurrenceAlreadyResolved(id));
+        }
+
+        let (status, session_id) = match resolution {
+            OccurrenceResolution::Done { session_id } => (OccurrenceStatus::Done, session_id),
+            OccurrenceResolution::Skipped => (OccurrenceStatus::Skipped, None),
+        };
+        self.karma_repository
+            .update_occurrence_status(id, status, session_id, self.clock.now())
+            .await?;
+        // The day may already be counted the other way
+        self.karma_repository
+            .clear_streak_states(Some(&KarmaSubject::Template(occurrence.template_id)))
+            .await?;
+
+        Ok(KarmaOccurrence {
+            status,
+            session_id,
+            ..occurrence
+        })
+    }
+
+    /// Plans the occurrences of every schedule for the coming days, links the ones that
+    /// got a session and marks the past ones without any as missed, then carries the
+    /// unfinished plan entries of past days over to today. Safe to run often,
+    /// occurrences are only planned once and days the app wasn't running are caught up.
+    pub async fn run_daily_job(&self) -> Result<DailyJobReport, KarmaServiceError> {
+        let settings = self.time_settings().await?;
+        let today = settings.local_date(self.clock.now());
+        let mut report = DailyJobReport::default();
+
+        for schedule in self.karma_repository.get_schedules().await? {
+            report.planned += self.plan_schedule(&schedule, today, &settings).await?;
+        }
+
+        let (completed, missed) = self
+            .karma_repository
+            .settle_occurrences(today, self.clock.now())
+            .await?;
+        report.completed = completed;
+        report.missed = missed;
+        report.carried_over = self.carry_over_plans(today, &settings).await?;
+
+        Ok(report)
+    }
+
+    /// Due times and days are fixed in the timezone at planning time
+    async fn plan_schedule(
+        &self,
+        schedule: &KarmaSchedule,
+        today: NaiveDate,
+        settings: &TimeSettings,
+    ) -> Result<u64, KarmaServiceError> {
+        let horizon = today + Duration::days(PLANNING_HORIZON_DAYS);
+        let from = match schedule.planned_until.and_then(|until| until.succ_opt()) {
+            Some(next) => next.max(schedule.recurrence.starts_on),
+            None => schedule.recurrence.starts_on,
+        };
+        if from > horizon {
+            return Ok(0);
+        }
+
+        let start_time = schedule.recurrence.start_time.unwrap_or_default();
+        let occurrences: Vec<KarmaOccurrence> = schedule
+            .recurrence
+            .occurrences(from, horizon)
+            .into_iter()
+            .map(|due_on| KarmaOccurrence {
+                id: None,
+                template_id: schedule.template_id,
+                name: String::new(),
+                due_on,
+                due_at: settings.at_time_of_day(due_on, start_time),
+                day_start: settings.day_start(due_on),
+                day_end: settings.next_day_start(due_on),
+                status: OccurrenceStatus::Planned,
+                session_id: None,
+            })
+            .collect();
+
+        Ok(self
+            .karma_repository
+            .insert_planned_occurrences(schedule.template_id, &occurrences, horizon)
+            .await?)
+    }
+
+    async fn today(&self) -> Result<NaiveDate, KarmaServiceError> {
+        Ok(self.time_settings().await?.local_date(self.clock.now()))
+    }
+}
+
+#[cfg(test)]
+mod schedule_tests {
+    use super::*;
+    use crate::model::karma::KarmaType;
+    use crate::model::recurrence::{parse_date, RecurrenceRule};
+    use crate::service::clock::Clock;
+    use crate::storage::common_utilities_tests::karma_service_at;
+    use std::collections::BTreeSet;
+
+    const DAY: i64 = 24 * 60 * 60;
+
+    #[tokio::test]
+    async fn test_daily_job_plans_and_marks_missed() {
+        let (service, clock) = karma_service_at("schedule", "2024-01-01 12:00").await;
+        let start = clock.now();
+
+        service
+            .start_karma("Gym", Some(KarmaType::Sport), None)
+            .await
+            .unwrap();
+        service
+            .set_schedule(
+                "gym",
+                Recurrence {
+                    rule: RecurrenceRule::parse("FREQ=WEEKLY;BYDAY=MO,WE,FR").unwrap(),
+                    starts_on: parse_date("2024-01-01").unwrap(),
+                    start_time: Some(18 * 60 * 60),
+                    exceptions: BTreeSet::from([parse_date("2024-01-05").unwrap()]),
+                },
+            )
+            .await
+            .unwrap();
+
+        let first_week = service
+            .list_occurrences(
+                parse_date("2024-01-01").unwrap(),
+                parse_date("2024-01-07").unwrap(),
+                None,
+            )
+            .await
+            .unwrap();
+        let due: Vec<String> = first_week.iter().map(|o| o.due_on.to_string()).collect();
+        assert_eq!(due, vec!["2024-01-01", "2024-01-03"]);
+        assert_eq!(first_week[0].due_at, start + 6 * 60 * 60);
+
+        // The session started before scheduling counts for Monday, Wednesday passes by
+        clock.advance(3 * DAY);
+        let report = service.run_daily_job().await.unwrap();
+        assert_eq!(report.completed, 1);
+        assert_eq!(report.missed, 1);
+        // Planned a week ahead, only Wednesday the 10th is new
+        assert_eq!(report.planned, 1);
+
+        let missed = service
+            .list_occurrences(
+                parse_date("2024-01-01").unwrap(),
+                parse_date("2024-01-31").unwrap(),
+                Some(OccurrenceStatus::Missed),
+            )
+            .await
+            .unwrap();
+        assert_eq!(missed.len(), 1);
+        assert_eq!(missed[0].name, "Gym");
+
+        let resolved = service
+            .resolve_occurrence(missed[0].id.unwrap(), OccurrenceResolution::Skipped)
+            .await
+            .unwrap();
+        assert_eq!(resolved.status, OccurrenceStatus::Skipped);
+        assert!(matches!(
+            service
+                .resolve_occurrence(missed[0].id.unwrap(), OccurrenceResolution::Skipped)
+                .await,
+            Err(KarmaServiceError::OccurrenceAlreadyResolved(_))
+        ));
+
+        // Running again changes nothing
+        assert_eq!(
+            service.run_daily_job().await.unwrap(),
+            DailyJobReport::default()
+        );
+    }
+}
@@ -0,0 +1,109 @@
+use chrono::Duration;
+
+use crate::model::scoring::{
+    award, points_balance, PointsBalance, ScoringRules, DEFAULT_LEDGER_DAYS, SCORING_RULES_SETTING,
+};
+use crate::storage::KarmaStorage;
+
+use super::karma_service::{KarmaService, KarmaServiceError};
+
+impl<R: KarmaStorage> KarmaService<R> {
+    /// The rules set by the user, the default weights until then
+    pub async fn scoring_rules(&self) -> Result<ScoringRules, KarmaServiceError> {
+        match self
+            .karma_repository
+            .get_setting(SCORING_RULES_SETTING)
+            .await?
+        {
+            Some(rules) => Ok(ScoringRules::parse(&rules)?),
+            None => Ok(ScoringRules::default()),
+        }
+    }
+
+    /// Changes the given rules and keeps the others, past sessions are scored
+    /// again with the new ones
+    pub async fn set_scoring_rules(&self, rules: &str) -> Result<ScoringRules, KarmaServiceError> {
+        let current = self.scoring_rules().await?.to_string();
+        let rules = ScoringRules::parse(&format!("{current},{rules}"))?;
+        self.karma_repository
+            .set_setting(SCORING_RULES_SETTING, &rules.to_string())
+            .await?;
+        Ok(rules)
+    }
+
+    /// The points balance today with a ledger of the last `days` days, redemptions
+    /// deducted
+    pub async fn points_balance(
+        &self,
+        days: Option<i64>,
+    ) -> Result<PointsBalance, KarmaServiceError> {
+        let settings = self.time_settings().await?;
+        let rules = self.scoring_rules().await?;
+        let now = self.clock.now();
+        let today = settings.local_date(now);
+        let first_day = today - Duration::days(days.unwrap_or(DEFAULT_LEDGER_DAYS).max(1) - 1);
+
+        // Which points were spent depends on every award and redemption before
+        let awards: Vec<_> = self
+            .karma_repository
+            .get_tracked_sessions(0, now + 1)
+            .await?
+            .iter()
+            .filter_map(|tracked| award(tracked, &rules, now, &settings))
+            .collect();
+        let redemptions = self.karma_repository.get_redemptions(0).await?;
+
+        Ok(points_balance(
+            &awards,
+            &redemptions,
+            first_day,
+            today,
+            &rules,
+        ))
+    }
+}
+
+#[cfg(test)]
+mod scoring_tests {
+    use crate::model::karma::KarmaType;
+    use crate::storage::common_utilities_tests::karma_service_at;
+
+    const HOUR: i64 = 60 * 60;
+    const DAY: i64 = 24 * HOUR;
+
+    #[tokio::test]
+    async fn test_balance_decays_day_by_day() {
+        let (service, clock) = karma_service_at("scoring", "2024-01-01 09:00").await;
+        let rules = service
+            .set_scoring_rules("sport=5, decay=10")
+            .await
+            .unwrap();
+        assert_eq!(rules.weight(&KarmaType::Work), 1.0);
+
+        let run = service
+            .start_karma("Run", Some(KarmaType::Sport), None)
+            .await
+            .unwrap();
+        clock.advance(2 * HOUR);
+        service
+            .close_session(run.session.get_id().unwrap(), None)
+            .await
+            .unwrap();
+        // Still running, not scored yet
+        service
+            .start_karma("Report", Some(KarmaType::Work), None)
+            .await
+            .unwrap();
+
+        clock.advance(5 * DAY);
+        let balance = service.points_balance(Some(7)).await.unwrap();
+        assert_eq!(balance.ledger.len(), 7);
+        assert_eq!(balance.balance, 5.0);
+
+        let monday = &balance.ledger[5];
+        assert_eq!(monday.day.to_string(), "2024-01-01");
+        assert_eq!(monday.earned, 10.0);
+        assert_eq!(monday.awards[0].reason, "2.0h of Sport at 5/h");
+        assert_eq!(balance.ledger[0].decayed, 1.0);
+    }
+}
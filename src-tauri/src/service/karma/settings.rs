@@ -0,0 +1,92 @@
+use tracing::info;
+
+use crate::model::local_time::{TimeSettings, UserTimezone, TIMEZONE_SETTING, WEEK_START_SETTING};
+use crate::storage::KarmaStorage;
+
+use super::karma_service::{KarmaService, KarmaServiceError};
+
+impl<R: KarmaStorage> KarmaService<R> {
+    /// The timezone and week start days and weeks are counted in, the system
+    /// timezone until the user picks one
+    pub async fn time_settings(&self) -> Result<TimeSettings, KarmaServiceError> {
+        let mut settings = TimeSettings::system();
+
+        if let Some(timezone) = self.karma_repository.get_setting(TIMEZONE_SETTING).await? {
+            settings.timezone = UserTimezone::parse(&timezone)?;
+        }
+        if let Some(week_start) = self
+            .karma_repository
+            .get_setting(WEEK_START_SETTING)
+            .await?
+        {
+            settings.week_start = TimeSettings::parse_week_start(&week_start)?;
+        }
+
+        Ok(settings)
+    }
+
+    /// Changes the given settings and keeps the others. Existing statuses keep the
+    /// timezone they were written in.
+    pub async fn set_time_settings(
+        &self,
+        timezone: Option<&str>,
+        week_start: Option<&str>,
+    ) -> Result<TimeSettings, KarmaServiceError> {
+        // Validate both before storing either
+        let timezone = timezone.map(UserTimezone::parse).transpose()?;
+        let week_start = week_start.map(TimeSettings::parse_week_start).transpose()?;
+
+        if let Some(timezone) = timezone {
+            self.karma_repository
+                .set_setting(TIMEZONE_SETTING, &timezone.to_string())
+                .await?;
+            // Sessions may now fall on other days, streaks are counted again
+            self.karma_repository.clear_streak_states(None).await?;
+        }
+        if let Some(week_start) = week_start {
+            self.karma_repository
+                .set_setting(WEEK_START_SETTING, &week_start.to_string())
+                .await?;
+        }
+
+        let settings = self.time_settings().await?;
+        info!(
+            "Time settings: {}, weeks start on {}",
+            settings.timezone, settings.week_start
+        );
+        Ok(settings)
+    }
+}
+
+#[cfg(test)]
+mod settings_tests {
+    use chrono::Weekday;
+
+    use super::*;
+    use crate::model::local_time::TimeSettingsError;
+    use crate::storage::common_utilities_tests::karma_service_at;
+
+    #[tokio::test]
+    async fn test_time_settings_round_trip() {
+        let (service, _clock) = karma_service_at("settings", "2024-01-01 09:00").await;
+
+        service
+            .set_time_settings(Some("America/New_York"), None)
+            .await
+            .unwrap();
+        let settings = service.set_time_settings(None, Some("sun")).await.unwrap();
+        assert_eq!(settings.timezone.to_string(), "America/New_York");
+        assert_eq!(settings.week_start, Weekday::Sun);
+
+        // Nothing is stored when one of them is wrong
+        assert!(matches!(
+            service
+                .set_time_settings(Some("+01:00"), Some("someday"))
+                .await,
+            Err(KarmaServiceError::TimeSettings(
+                TimeSettingsError::WeekStart(_)
+            ))
+        ));
+        assert_eq!(service.time_settings().await.unwrap(), settings);
+    }
+}
@@ -0,0 +1,255 @@
+use chrono::{Duration, NaiveDateTime};
+
+use crate::model::karma::{KarmaPoint, KarmaStatus, KarmaType, State};
+use crate::model::karma_session::KarmaSession;
+use crate::model::sleep::{
+    night_of, sleep_nights, sleep_report, validate_sleep, Night, SleepReport, SleepRules,
+    SLEEP_RULES_SETTING,
+};
+use crate::model::subject::KarmaSubject;
+use crate::storage::KarmaStorage;
+
+use super::karma_service::{KarmaService, KarmaServiceError};
+
+impl<R: KarmaStorage> KarmaService<R> {
+    /// The rules set by the user, 8 hours over two weeks until then
+    pub async fn sleep_rules(&self) -> Result<SleepRules, KarmaServiceError> {
+        match self
+            .karma_repository
+            .get_setting(SLEEP_RULES_SETTING)
+            .await?
+        {
+            Some(rules) => Ok(SleepRules::parse(&rules)?),
+            None => Ok(SleepRules::default()),
+        }
+    }
+
+    /// Changes the given rules and keeps the others
+    pub async fn set_sleep_rules(&self, rules: &str) -> Result<SleepRules, KarmaServiceError> {
+        let current = self.sleep_rules().await?.to_string();
+        let rules = SleepRules::parse(&format!("{current},{rules}"))?;
+        self.karma_repository
+            .set_setting(SLEEP_RULES_SETTING, &rules.to_string())
+            .await?;
+        Ok(rules)
+    }
+
+    /// Sleep over the last `nights` nights up to the current one, the window of the
+    /// rules by default
+    pub async fn sleep_report(
+        &self,
+        nights: Option<u32>,
+    ) -> Result<SleepReport, KarmaServiceError> {
+        let settings = self.time_settings().await?;
+        let rules = self.sleep_rules().await?;
+        let now = self.clock.now();
+
+        let to = night_of(now, now, &settings);
+        let from = to - Duration::days(nights.unwrap_or(rules.window_nights).max(1) as i64 - 1);
+        let sessions = self
+            .karma_repository
+            .get_tracked_sessions(
+                settings.day_start(from),
+                settings.day_start(to + Duration::days(2)),
+            )
+            .await?;
+
+        Ok(sleep_report(
+            sleep_nights(&sessions, now, &settings),
+            from,
+            to,
+            &settings,
+            &rules,
+        ))
+    }
+
+    /// Records sleep that wasn't tracked as it happened, between local times. The
+    /// sleeping activity called `name` is created when there is none yet.
+    pub async fn log_sleep(
+        &self,
+        name: &str,
+        bedtime: NaiveDateTime,
+        wake: NaiveDateTime,
+    ) -> Result<Night, KarmaServiceError> {
+        let settings = self.time_settings().await?;
+        let now = self.clock.now();
+        let (bedtime, wake) = (
+            settings.timezone.timestamp_of(bedtime),
+            settings.timezone.timestamp_of(wake),
+        );
+        validate_sleep(bedtime, wake, now)?;
+
+        let existing = self
+            .karma_repository
+            .get_tracked_sessions(bedtime, wake)
+            .await?;
+        if let Some(night) = sleep_nights(&existing, now, &settings)
+            .into_iter()
+            .find(|night| night.bedtime < wake && night.wake > bedtime)
+        {
+            return Err(KarmaServiceError::SleepOverlaps(night.night));
+        }
+
+        let existing = self
+            .karma_repository
+            .get_karma_overview()
+            .await?
+            .into_iter()
+            .find(|item| item.karma.get_name().to_lowercase() == name.trim().to_lowercase());
+        let template = match existing {
+            Some(item) => item.karma,
+            None => {
+                self.karma_repository
+                    .insert_karma(KarmaPoint::new(
+                        KarmaType::Sleeping,
+                        name.trim().to_string(),
+                    ))
+                    .await?
+            }
+        };
+
+        let session = self
+            .karma_repository
+            .insert_session(
+                KarmaSession::from_template(&template, bedtime).with_purpose(KarmaType::Sleeping),
+            )
+            .await?;
+        let session_id = session.get_id().unwrap_or_default();
+        for (state, timestamp) in [(State::Active, bedtime), (State::Closed, wake)] {
+            self.karma_repository
+                .insert_karma_status(
+                    KarmaStatus::new(session_id, state, timestamp).in_timezone(&settings.timezone),
+                )
+                .await?;
+        }
+
+        // The day may already be counted as one without sleep
+        let template_id = template.get_id().unwrap_or_default();
+        let day = settings.local_date(bedtime);
+        for scope in [
+            KarmaSubject::Template(template_id),
+            KarmaSubject::Category(KarmaType::Sleeping),
+        ] {
+            if self
+                .karma_repository
+                .get_streak_state(&scope)
+                .await?
+                .and_then(|state| state.settled_until)
+                .is_some_and(|until| day <= until)
+            {
+                self.karma_repository
+                    .clear_streak_states(Some(&scope))
+                    .await?;
+            }
+        }
+        self.statuses_changed(template_id, KarmaType::Sleeping, None)
+            .await?;
+
+        let tracked = self.tracked_session(session_id).await?;
+        sleep_nights(&[tracked], now, &settings)
+            .into_iter()
+            .next()
+            .ok_or(KarmaServiceError::SessionNotFound(session_id))
+    }
+}
+
+#[cfg(test)]
+mod sleep_tests {
+    use super::*;
+    use crate::model::sleep::parse_local_datetime;
+    use crate::storage::common_utilities_tests::karma_service_at;
+
+    const HOUR: i64 = 60 * 60;
+
+    #[tokio::test]
+    async fn test_sleep_report_from_tracked_and_logged_nights() {
+        let (service, clock) = karma_service_at("sleep", "2024-01-01 23:00").await;
+        service.set_sleep_rules("target=8,window=7").await.unwrap();
+
+        // Tracked live across midnight, 7 hours
+        let started = service
+            .start_karma("Sleep", Some(KarmaType::Sleeping), None)
+            .await
+            .unwrap();
+        clock.advance(7 * HOUR);
+        service
+            .close_session(started.session.get_id().unwrap(), None)
+            .await
+            .unwrap();
+
+        // Tuesday night logged on Wednesday morning, 6 hours after midnight
+        clock.advance(25 * HOUR);
+        let night = service
+            .log_sleep(
+                "sleep",
+                parse_local_datetime("2024-01-03 01:00").unwrap(),
+                parse_local_datetime("2024-01-03 07:00").unwrap(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(night.night.to_string(), "2024-01-02");
+        assert_eq!(night.slept_seconds, 6 * HOUR);
+        assert!(matches!(
+            service
+                .log_sleep(
+                    "Sleep",
+                    parse_local_datetime("2024-01-03 05:00").unwrap(),
+                    parse_local_datetime("2024-01-03 06:30").unwrap(),
+                )
+                .await,
+            Err(KarmaServiceError::SleepOverlaps(_))
+        ));
+        assert!(matches!(
+            service
+                .log_sleep(
+                    "Sleep",
+                    parse_local_datetime("2024-01-03 23:00").unwrap(),
+                    parse_local_datetime("2024-01-04 07:00").unwrap(),
+                )
+                .await,
+            Err(KarmaServiceError::Sleep(_))
+        ));
+
+        let report = service.sleep_report(None).await.unwrap();
+        assert_eq!(report.to.to_string(), "2024-01-02");
+        assert_eq!(report.from.to_string(), "2023-12-27");
+        assert_eq!(report.nights.len(), 2);
+        assert_eq!(report.debt_seconds, 3 * HOUR);
+        assert_eq!(report.average_bedtime, Some(0));
+        assert_eq!(report.average_wake, Some(6 * 3600 + 1800));
+        assert_eq!(report.consistency, Some(50));
+        // The current night alone
+        assert_eq!(
+            service.sleep_report(Some(1)).await.unwrap().nights[0]
+                .sessions
+                .len(),
+            1
+        );
+
+        // Tuesday was counted as a day without sleep, until a nap is logged for it
+        let sleeping_streak = || async {
+            service
+                .streaks()
+                .await
+                .unwrap()
+                .into_iter()
+                .find(|streak| streak.scope == KarmaSubject::Category(KarmaType::Sleeping))
+                .unwrap()
+        };
+        assert_eq!(sleeping_streak().await.current, 1);
+        service
+            .log_sleep(
+                "Sleep",
+                parse_local_datetime("2024-01-02 14:00").unwrap(),
+                parse_local_datetime("2024-01-02 14:30").unwrap(),
+            )
+            .await
+            .unwrap();
+        let after = sleeping_streak().await;
+        assert_eq!(after.current, 3);
+        assert_eq!(
+            after.current_since.map(|day| day.to_string()),
+            Some("2024-01-01".to_string())
+        );
+    }
+}
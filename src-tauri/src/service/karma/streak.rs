@@ -0,0 +1,310 @@
+use std::collections::BTreeSet;
+
+use chrono::{Duration, NaiveDate};
+
+use crate::model::karma::KarmaType;
+use crate::model::local_time::TimeSettings;
+use crate::model::recurrence::Recurrence;
+use crate::model::schedule::OccurrenceStatus;
+use crate::model::streak::{day_outcome, due_days, Streak, StreakState};
+use crate::model::subject::KarmaSubject;
+use crate::storage::KarmaStorage;
+
+use super::karma_service::{KarmaService, KarmaServiceError};
+
+/// Days a scope expected something on, what was done and what is excused
+struct StreakDays {
+    due: Vec<NaiveDate>,
+    done: BTreeSet<NaiveDate>,
+    excused: BTreeSet<NaiveDate>,
+}
+
+impl<R: KarmaStorage> KarmaService<R> {
+    /// The streak of every scheduled template and of every type tracked so far,
+    /// templates first
+    pub async fn streaks(&self) -> Result<Vec<Streak>, KarmaServiceError> {
+        let settings = self.time_settings().await?;
+        let today = settings.local_date(self.clock.now());
+        let items = self.karma_repository.get_karma_overview().await?;
+        let mut streaks = Vec::new();
+
+        for schedule in self.karma_repository.get_schedules().await? {
+            let name = items
+                .iter()
+                .find(|item| item.karma.get_id() == Some(schedule.template_id))
+                .map(|item| item.karma.get_name())
+                .unwrap_or_default();
+            let scope = KarmaSubject::Template(schedule.template_id);
+            streaks.push(
+                self.streak(scope, name, Some(&schedule.recurrence), today, &settings)
+                    .await?,
+            );
+        }
+
+        for purpose in KarmaType::ALL {
+            let scope = KarmaSubject::Category(purpose.clone());
+            if self
+                .first_day(&scope, None, today, &settings)
+                .await?
+                .is_some()
+            {
+                streaks.push(
+                    self.streak(scope, format!("{purpose:?}"), None, today, &settings)
+                        .await?,
+                );
+            }
+        }
+
+        Ok(streaks)
+    }
+
+    /// Freezes a day for every streak, it neither extends nor breaks them
+    pub async fn freeze_day(&self, day: NaiveDate) -> Result<bool, KarmaServiceError> {
+        let frozen = self.karma_repository.insert_freeze(day).await?;
+        if frozen {
+            self.karma_repository.clear_streak_states(None).await?;
+        }
+        Ok(frozen)
+    }
+
+    pub async fn unfreeze_day(&self, day: NaiveDate) -> Result<bool, KarmaServiceError> {
+        let unfrozen = self.karma_repository.delete_freeze(day).await?;
+        if unfrozen {
+            self.karma_repository.clear_streak_states(None).await?;
+        }
+        Ok(unfrozen)
+    }
+
+    /// Carries the streaks a new status of a session touches up to yesterday,
+    /// only the days since they were last counted are looked at
+    pub(super) async fn update_streaks(
+        &self,
+        template_id: i32,
+        purpose: KarmaType,
+    ) -> Result<(), KarmaServiceError> {
+        let settings = self.time_settings().await?;
+        let today = settings.local_date(self.clock.now());
+
+        if let Some(schedule) = self.karma_repository.get_schedule(template_id).await? {
+            self.settle_streak(
+                KarmaSubject::Template(template_id),
+                Some(&schedule.recurrence),
+                today,
+                &settings,
+            )
+            .await?;
+        }
+        self.settle_streak(KarmaSubject::Category(purpose), None, today, &settings)
+            .await?;
+
+        Ok(())
+    }
+
+    pub(super) async fn streak(
+        &self,
+        scope: KarmaSubject,
+        name: String,
+        recurrence: Option<&Recurrence>,
+        today: NaiveDate,
+        settings: &TimeSettings,
+    ) -> Result<Streak, KarmaServiceError> {
+        let state = self
+            .settle_streak(scope.clone(), recurrence, today, settings)
+            .await?;
+        let days = self
+            .streak_days(&scope, recurrence, today, today, settings)
+            .await?;
+
+        Ok(Streak::of(
+            &state,
+            name,
+            !days.due.is_empty(),
+            days.done.contains(&today),
+        ))
+    }
+
+    /// The stored state counted on up to yesterday, from the beginning when there is none
+    async fn settle_streak(
+        &self,
+        scope: KarmaSubject,
+        recurrence: Option<&Recurrence>,
+        today: NaiveDate,
+        settings: &TimeSettings,
+    ) -> Result<StreakState, KarmaServiceError> {
+        let Some(yesterday) = today.pred_opt() else {
+            return Ok(StreakState::new(scope));
+        };
+        let mut state = self
+            .karma_repository
+            .get_streak_state(&scope)
+            .await?
+            .unwrap_or_else(|| StreakState::new(scope.clone()));
+
+        let from = match state.settled_until {
+            Some(until) => until + Duration::days(1),
+            None => match self.first_day(&scope, recurrence, today, settings).await? {
+                Some(first) => first,
+                None => return Ok(state),
+            },
+        };
+        if from > yesterday {
+            return Ok(state);
+        }
+
+        let days = self
+            .streak_days(&scope, recurrence, from, yesterday, settings)
+            .await?;
+        state.settle(&days.due, yesterday, |day| {
+            day_outcome(day, &days.done, &days.excused)
+        });
+        self.karma_repository.save_streak_state(&state).await?;
+
+        Ok(state)
+    }
+
+    /// Where counting starts, the schedule start or the day of the first session
+    async fn first_day(
+        &self,
+        scope: &KarmaSubject,
+        recurrence: Option<&Recurrence>,
+        today: NaiveDate,
+        settings: &TimeSettings,
+    ) -> Result<Option<NaiveDate>, KarmaServiceError> {
+        if let Some(recurrence) = recurrence {
+            return Ok(Some(recurrence.starts_on));
+        }
+
+        let starts = self
+            .karma_repository
+            .get_session_starts(scope, 0, settings.next_day_start(today))
+            .await?;
+        Ok(starts.first().map(|start| settings.local_date(*start)))
+    }
+
+    async fn streak_days(
+        &self,
+        scope: &KarmaSubject,
+        recurrence: Option<&Recurrence>,
+        from: NaiveDate,
+        to: NaiveDate,
+        settings: &TimeSettings,
+    ) -> Result<StreakDays, KarmaServiceError> {
+        let mut done: BTreeSet<NaiveDate> = self
+            .karma_repository
+            .get_session_starts(scope, settings.day_start(from), settings.next_day_start(to))
+            .await?
+            .into_iter()
+            .map(|start| settings.local_date(start))
+            .collect();
+        let mut excused: BTreeSet<NaiveDate> = self
+            .karma_repository
+            .get_freezes(from, to)
+            .await?
+            .into_iter()
+            .collect();
+
+        // Occurrences settled by hand count as they were resolved
+        if let KarmaSubject::Template(template_id) = scope {
+            for occurrence in self
+                .karma_repository
+                .get_occurrences(from, to, None)
+                .await?
+                .into_iter()
+                .filter(|occurrence| occurrence.template_id == *template_id)
+            {
+                match occurrence.status {
+                    OccurrenceStatus::Done => {
+                        done.insert(occurrence.due_on);
+                    }
+                    OccurrenceStatus::Skipped => {
+                        excused.insert(occurrence.due_on);
+                    }
+                    OccurrenceStatus::Planned | OccurrenceStatus::Missed => {}
+                }
+            }
+        }
+
+        Ok(StreakDays {
+            due: due_days(recurrence, from, to),
+            done,
+            excused,
+        })
+    }
+}
+
+#[cfg(test)]
+mod streak_tests {
+    use super::*;
+    use crate::model::recurrence::{parse_date, RecurrenceRule};
+    use crate::storage::common_utilities_tests::karma_service_at;
+    use crate::storage::streak_repository::StreakRepository;
+
+    const DAY: i64 = 24 * 60 * 60;
+
+    #[tokio::test]
+    async fn test_streaks_follow_the_schedule() {
+        let (service, clock) = karma_service_at("streak", "2024-01-01 18:00").await;
+
+        service
+            .start_karma("Gym", Some(KarmaType::Sport), None)
+            .await
+            .unwrap();
+        service
+            .set_schedule(
+                "gym",
+                Recurrence {
+                    rule: RecurrenceRule::parse("FREQ=WEEKLY;BYDAY=MO,WE,FR").unwrap(),
+                    starts_on: parse_date("2024-01-01").unwrap(),
+                    start_time: None,
+                    exceptions: BTreeSet::new(),
+                },
+            )
+            .await
+            .unwrap();
+
+        // Wednesday done, Friday frozen, then Monday done again
+        clock.advance(2 * DAY);
+        service.start_karma("gym", None, None).await.unwrap();
+        service
+            .freeze_day(parse_date("2024-01-05").unwrap())
+            .await
+            .unwrap();
+        clock.advance(5 * DAY);
+        service.start_karma("gym", None, None).await.unwrap();
+
+        let streaks = service.streaks().await.unwrap();
+        let gym = &streaks[0];
+        assert_eq!(gym.name, "Gym");
+        assert_eq!(gym.current, 3);
+        assert!(gym.done_today);
+
+        // Every day counts for the type, the rest days between sessions break it
+        let sport = &streaks[1];
+        assert_eq!(sport.scope, KarmaSubject::Category(KarmaType::Sport));
+        assert_eq!(sport.current, 1);
+        assert_eq!(sport.longest, 1);
+
+        // Counted up to Sunday, the next status only looks at the days since
+        let state = service
+            .karma_repository
+            .get_streak_state(&gym.scope)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(state.settled_until, parse_date("2024-01-07").ok());
+        assert_eq!(state.current, 2);
+
+        // Wednesday passes without a session
+        clock.advance(3 * DAY);
+        let streaks = service.streaks().await.unwrap();
+        assert_eq!(streaks[0].current, 0);
+        assert_eq!(streaks[0].longest, 3);
+
+        // Unfreezing Friday breaks the old streak in two
+        service
+            .unfreeze_day(parse_date("2024-01-05").unwrap())
+            .await
+            .unwrap();
+        assert_eq!(service.streaks().await.unwrap()[0].longest, 2);
+    }
+}
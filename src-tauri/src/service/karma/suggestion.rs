@@ -0,0 +1,303 @@
+use std::collections::BTreeMap;
+
+use chrono::{Duration, NaiveDate};
+
+use crate::model::balance::HABIT_HISTORY_WEEKS;
+use crate::model::duration::duration_totals;
+use crate::model::plan::PlanEntry;
+use crate::model::schedule::OccurrenceStatus;
+use crate::model::suggestion::{
+    suggest_schedule, suggestion_targets, BlockedTime, Commitment, ScheduleProposal,
+    SuggestionHistory, SuggestionRules, SUGGESTION_RULES_SETTING,
+};
+use crate::storage::KarmaStorage;
+
+use super::karma_service::{KarmaService, KarmaServiceError};
+
+impl<R: KarmaStorage> KarmaService<R> {
+    /// The rules set by the user, the defaults until then
+    pub async fn suggestion_rules(&self) -> Result<SuggestionRules, KarmaServiceError> {
+        match self
+            .karma_repository
+            .get_setting(SUGGESTION_RULES_SETTING)
+            .await?
+        {
+            Some(rules) => Ok(SuggestionRules::parse(&rules)?),
+            None => Ok(SuggestionRules::default()),
+        }
+    }
+
+    /// Changes the given rules and keeps the others
+    pub async fn set_suggestion_rules(
+        &self,
+        rules: &str,
+    ) -> Result<SuggestionRules, KarmaServiceError> {
+        let current = self.suggestion_rules().await?.to_string();
+        let rules = SuggestionRules::parse(&format!("{current},{rules}"))?;
+        self.karma_repository
+            .set_setting(SUGGESTION_RULES_SETTING, &rules.to_string())
+            .await?;
+        Ok(rules)
+    }
+
+    /// `time` is like `MO,TU 09:00-17:00`, or only the times for every day
+    pub async fn add_blocked_time(
+        &self,
+        label: &str,
+        time: &str,
+    ) -> Result<BlockedTime, KarmaServiceError> {
+        let blocked = BlockedTime::parse(label, time)?;
+        Ok(self.karma_repository.insert_blocked_time(blocked).await?)
+    }
+
+    pub async fn blocked_times(&self) -> Result<Vec<BlockedTime>, KarmaServiceError> {
+        Ok(self.karma_repository.get_blocked_times().await?)
+    }
+
+    pub async fn delete_blocked_time(&self, id: i32) -> Result<(), KarmaServiceError> {
+        if !self.karma_repository.delete_blocked_time(id).await? {
+            return Err(KarmaServiceError::BlockedTimeNotFound(id));
+        }
+        Ok(())
+    }
+
+    /// A schedule for the days left of the week `day` falls in, the next week by
+    /// default, meeting the weekly goals on types around what is already committed
+    pub async fn suggest_schedule(
+        &self,
+        day: Option<NaiveDate>,
+    ) -> Result<ScheduleProposal, KarmaServiceError> {
+        let settings = self.time_settings().await?;
+        let now = self.clock.now();
+        let today = settings.local_date(now);
+        let week_start = settings.week_of(day.unwrap_or(today + Duration::days(7)));
+        let days: Vec<NaiveDate> = (0..7)
+            .map(|offset| week_start + Duration::days(offset))
+            .filter(|day| *day > today)
+            .collect();
+        let from = settings.day_start(week_start);
+        let to = settings.day_start(week_start + Duration::days(7));
+
+        let done: BTreeMap<_, _> = duration_totals(
+            &self.karma_repository.get_tracked_sessions(from, to).await?,
+            from,
+            to,
+            now,
+            &settings,
+        )
+        .per_category
+        .into_iter()
+        .map(|category| (category.purpose, category.seconds))
+        .collect();
+
+        let rules = self.suggestion_rules().await?;
+        let commitments = match (days.first(), days.last()) {
+            (Some(first), Some(last)) => self.commitments(*first, *last, &rules).await?,
+            _ => Vec::new(),
+        };
+
+        let history_from = settings.day_start(week_start - Duration::weeks(HABIT_HISTORY_WEEKS));
+        let history_to = from.min(now);
+        let history = SuggestionHistory::collect(
+            &self
+                .karma_repository
+                .get_tracked_sessions(history_from, history_to)
+                .await?,
+            history_from,
+            history_to,
+            now,
+            &settings,
+        );
+
+        Ok(suggest_schedule(
+            week_start,
+            &days,
+            &suggestion_targets(&self.karma_repository.get_goals().await?),
+            &done,
+            &commitments,
+            &history,
+            &rules,
+        ))
+    }
+
+    /// Adds the suggestions of the week to the plans of their days. Suggestions for a
+    /// type never done before have no activity and are left out.
+    pub async fn accept_schedule_suggestions(
+        &self,
+        day: Option<NaiveDate>,
+    ) -> Result<Vec<PlanEntry>, KarmaServiceError> {
+        let proposal = self.suggest_schedule(day).await?;
+        let settings = self.time_settings().await?;
+        let today = settings.local_date(self.clock.now());
+
+        let mut entries = Vec::new();
+        for suggestion in proposal.suggestions {
+            let Some(activity) = suggestion.activity else {
+                continue;
+            };
+            let entry = PlanEntry {
+                id: None,
+                day: suggestion.day,
+                position: 0,
+                template_id: activity.template_id,
+                name: activity.name,
+                purpose: suggestion.purpose,
+                duration: suggestion.duration,
+                starts_at: Some(suggestion.starts_at),
+                session_id: None,
+                carried_from: None,
+                settled: false,
+            };
+            entry.validate(today)?;
+            entries.push(self.karma_repository.insert_plan_entry(entry).await?);
+        }
+        Ok(entries)
+    }
+
+    /// What already takes time from `first` to `last`: planned occurrences of
+    /// recurring activities, plan entries and blocked time
+    async fn commitments(
+        &self,
+        first: NaiveDate,
+        last: NaiveDate,
+        rules: &SuggestionRules,
+    ) -> Result<Vec<Commitment>, KarmaServiceError> {
+        let items = self.karma_repository.get_karma_overview().await?;
+        let schedules = self.karma_repository.get_schedules().await?;
+        let mut commitments = Vec::new();
+
+        for occurrence in self
+            .karma_repository
+            .get_occurrences(first, last, Some(OccurrenceStatus::Planned))
+            .await?
+        {
+            let Some(template) = items
+                .iter()
+                .find(|item| item.karma.get_id() == Some(occurrence.template_id))
+            else {
+                continue;
+            };
+            commitments.push(Commitment {
+                day: occurrence.due_on,
+                name: occurrence.name,
+                purpose: Some(template.karma.get_purpose()),
+                starts_at: schedules
+                    .iter()
+                    .find(|schedule| schedule.template_id == occurrence.template_id)
+                    .and_then(|schedule| schedule.recurrence.start_time),
+                duration: template
+                    .karma
+                    .get_default_duration()
+                    .unwrap_or(rules.block_minutes as i64 * 60),
+            });
+        }
+
+        let blocked_times = self.karma_repository.get_blocked_times().await?;
+        let mut day = first;
+        while day <= last {
+            for entry in self.karma_repository.get_plan(day).await? {
+                commitments.push(Commitment {
+                    day,
+                    name: entry.name,
+                    purpose: Some(entry.purpose),
+                    starts_at: entry.starts_at,
+                    duration: entry.duration,
+                });
+            }
+            commitments.extend(
+                blocked_times
+                    .iter()
+                    .filter(|blocked| blocked.applies_on(day))
+                    .map(|blocked| Commitment::blocked(day, blocked)),
+            );
+            day += Duration::days(1);
+        }
+
+        Ok(commitments)
+    }
+}
+
+#[cfg(test)]
+mod suggestion_tests {
+    use chrono::Datelike;
+
+    use super::*;
+    use crate::model::goal::{GoalComparison, GoalMetric};
+    use crate::model::karma::KarmaType;
+    use crate::model::report::ReportPeriod;
+    use crate::storage::common_utilities_tests::karma_service_at;
+
+    const HOUR: i64 = 60 * 60;
+
+    #[tokio::test]
+    async fn test_suggest_and_accept_schedule() {
+        let (service, clock) = karma_service_at("suggestion", "2024-01-01 09:00").await;
+
+        let started = service
+            .start_karma("Thesis", Some(KarmaType::Learning), Some(HOUR))
+            .await
+            .unwrap();
+        clock.advance(HOUR);
+        service
+            .close_session(started.session.get_id().unwrap(), None)
+            .await
+            .unwrap();
+        service
+            .add_goal(
+                "learning",
+                GoalMetric::Hours,
+                GoalComparison::AtLeast,
+                3.0,
+                ReportPeriod::Week,
+            )
+            .await
+            .unwrap();
+        service
+            .add_blocked_time("Office", "MO,TU,WE,TH,FR 09:00-17:00")
+            .await
+            .unwrap();
+        assert!(matches!(
+            service.add_blocked_time("Office", "MO 17:00-09:00").await,
+            Err(KarmaServiceError::Suggestion(_))
+        ));
+
+        let proposal = service.suggest_schedule(None).await.unwrap();
+        assert_eq!(proposal.week_start.to_string(), "2024-01-08");
+        assert_eq!(proposal.days.len(), 7);
+        let suggested: i64 = proposal
+            .suggestions
+            .iter()
+            .map(|suggestion| suggestion.duration)
+            .sum();
+        assert_eq!(suggested, 3 * HOUR);
+        for suggestion in &proposal.suggestions {
+            assert_eq!(suggestion.purpose, KarmaType::Learning);
+            assert_eq!(
+                suggestion
+                    .activity
+                    .as_ref()
+                    .map(|activity| activity.name.as_str()),
+                Some("Thesis")
+            );
+            let weekday = suggestion.day.weekday().num_days_from_monday() < 5;
+            let ends_at = suggestion.starts_at as i64 + suggestion.duration;
+            assert!(!weekday || ends_at <= 9 * HOUR || suggestion.starts_at as i64 >= 17 * HOUR);
+        }
+        assert_eq!(
+            service.suggest_schedule(None).await.unwrap().suggestions,
+            proposal.suggestions
+        );
+
+        let entries = service.accept_schedule_suggestions(None).await.unwrap();
+        assert_eq!(entries.len(), proposal.suggestions.len());
+        let proposal = service.suggest_schedule(None).await.unwrap();
+        assert!(proposal.suggestions.is_empty());
+        assert_eq!(proposal.categories[0].committed_seconds, 3 * HOUR);
+
+        service.delete_blocked_time(1).await.unwrap();
+        assert!(matches!(
+            service.delete_blocked_time(1).await,
+            Err(KarmaServiceError::BlockedTimeNotFound(1))
+        ));
+    }
+}
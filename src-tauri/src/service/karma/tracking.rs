@@ -0,0 +1,202 @@
+use crate::model::duration::{self, DurationTotals, SessionDuration, TrackedSession};
+use crate::model::karma::{KarmaStatus, KarmaType, State};
+use crate::storage::KarmaStorage;
+
+use super::karma_service::{KarmaService, KarmaServiceError};
+
+impl<R: KarmaStorage> KarmaService<R> {
+    pub async fn pause_session(&self, session_id: i32) -> Result<KarmaStatus, KarmaServiceError> {
+        self.transition(session_id, State::Paused, None).await
+    }
+
+    pub async fn resume_session(&self, session_id: i32) -> Result<KarmaStatus, KarmaServiceError> {
+        self.transition(session_id, State::Active, None).await
+    }
+
+    /// Ends a session, `closed_with` records what it turned out to be when that
+    /// differs from its planned type
+    pub async fn close_session(
+        &self,
+        session_id: i32,
+        closed_with: Option<KarmaType>,
+    ) -> Result<KarmaStatus, KarmaServiceError> {
+        self.transition(session_id, State::Closed, closed_with)
+            .await
+    }
+
+    pub async fn session_duration(
+        &self,
+        session_id: i32,
+    ) -> Result<SessionDuration, KarmaServiceError> {
+        let tracked = self.tracked_session(session_id).await?;
+        Ok(duration::session_duration(&tracked, self.clock.now()))
+    }
+
+    /// Active time between `from` and `to` in unix seconds, sessions still
+    /// running count up to now
+    pub async fn duration_totals(
+        &self,
+        from: i64,
+        to: i64,
+    ) -> Result<DurationTotals, KarmaServiceError> {
+        let settings = self.time_settings().await?;
+        let sessions = self.karma_repository.get_tracked_sessions(from, to).await?;
+        Ok(duration::duration_totals(
+            &sessions,
+            from,
+            to,
+            self.clock.now(),
+            &settings,
+        ))
+    }
+
+    pub(super) async fn tracked_session(
+        &self,
+        session_id: i32,
+    ) -> Result<TrackedSession, KarmaServiceError> {
+        self.karma_repository
+            .get_tracked_session(session_id)
+            .await?
+            .ok_or(KarmaServiceError::SessionNotFound(session_id))
+    }
+
+    async fn transition(
+        &self,
+        session_id: i32,
+        to: State,
+        closed_with: Option<KarmaType>,
+    ) -> Result<KarmaStatus, KarmaServiceError> {
+        self.transition_at(session_id, to, closed_with, self.clock.now())
+            .await
+    }
+
+    /// Records the transition as of `at` rather than now, such as a focus phase
+    /// that ended while nothing was watching
+    pub(super) async fn transition_at(
+        &self,
+        session_id: i32,
+        to: State,
+        closed_with: Option<KarmaType>,
+        at: i64,
+    ) -> Result<KarmaStatus, KarmaServiceError> {
+        let tracked = self.tracked_session(session_id).await?;
+        let last = tracked.statuses.last();
+        let from = last.map(|status| status.state.clone());
+
+        // Closed sessions stay closed, doing it again is a new session
+        let allowed = matches!(
+            (&from, &to),
+            (None | Some(State::Paused), State::Active)
+                | (Some(State::Active), State::Paused)
+                | (Some(State::Active | State::Paused), State::Closed)
+        );
+        if !allowed {
+            return Err(KarmaServiceError::InvalidTransition { from, to });
+        }
+
+        // Never go back in time, that would make the previous interval negative
+        let timestamp = at.max(last.map(|status| status.timestamp).unwrap_or_default());
+        let status = match closed_with {
+            Some(closed_with) => {
+                KarmaStatus::with_closed_reason(session_id, to.clone(), timestamp, closed_with)
+            }
+            None => KarmaStatus::new(session_id, to.clone(), timestamp),
+        }
+        .in_timezone(&self.time_settings().await?.timezone);
+
+        let Some(status) = self
+            .karma_repository
+            .insert_karma_status_after(status, from)
+            .await?
+        else {
+            // Another transition came first, report the state it left the session in
+            let from = self
+                .tracked_session(session_id)
+                .await?
+                .statuses
+                .last()
+                .map(|status| status.state.clone());
+            return Err(KarmaServiceError::InvalidTransition { from, to });
+        };
+        self.statuses_changed(
+            tracked.session.get_template_id(),
+            tracked.session.get_purpose(),
+            status.closed_with.clone(),
+        )
+        .await?;
+
+        Ok(status)
+    }
+}
+
+#[cfg(test)]
+mod tracking_tests {
+    use super::*;
+    use crate::service::clock::Clock;
+    use crate::storage::common_utilities_tests::karma_service_at;
+
+    #[tokio::test]
+    async fn test_paused_time_is_not_tracked() {
+        let (service, clock) = karma_service_at("tracking", "2024-01-01 00:00").await;
+        let start = clock.now();
+
+        let started = service
+            .start_karma("Deep work", Some(KarmaType::Work), None)
+            .await
+            .unwrap();
+        let session_id = started.session.get_id().unwrap();
+
+        clock.advance(600);
+        service.pause_session(session_id).await.unwrap();
+        assert!(matches!(
+            service.pause_session(session_id).await,
+            Err(KarmaServiceError::InvalidTransition { .. })
+        ));
+
+        // Both see a paused session, only one of them gets to resume it
+        clock.advance(300);
+        let (first, second) = tokio::join!(
+            service.resume_session(session_id),
+            service.resume_session(session_id)
+        );
+        let failed = if first.is_ok() { second } else { first };
+        assert!(matches!(
+            failed,
+            Err(KarmaServiceError::InvalidTransition {
+                from: Some(State::Active),
+                ..
+            })
+        ));
+        clock.advance(120);
+
+        let running = service.session_duration(session_id).await.unwrap();
+        assert_eq!(running.active_seconds, 720);
+        assert!(running.open);
+
+        clock.advance(60);
+        service
+            .close_session(session_id, Some(KarmaType::Social))
+            .await
+            .unwrap();
+        assert!(matches!(
+            service.resume_session(session_id).await,
+            Err(KarmaServiceError::InvalidTransition { .. })
+        ));
+        clock.advance(1000);
+
+        let totals = service
+            .duration_totals(start, start + 24 * 60 * 60)
+            .await
+            .unwrap();
+        assert_eq!(totals.total_seconds, 780);
+        assert_eq!(totals.per_point[0].name, "Deep work");
+        assert_eq!(totals.per_category[0].purpose, KarmaType::Work);
+
+        // Closed before the range, nothing to count
+        let later = service
+            .duration_totals(start + 2000, start + 3000)
+            .await
+            .unwrap();
+        assert_eq!(later.total_seconds, 0);
+    }
+}
@@ -1,2 +1,4 @@
 pub mod accounts;
+pub mod clock;
 pub mod karma;
+pub mod notifier;
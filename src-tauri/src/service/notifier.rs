@@ -0,0 +1,15 @@
+use crate::model::achievement::AchievementStatus;
+
+/// Where services announce what happened along the way of a command, such as an
+/// achievement a new status unlocked. The desktop app forwards it to the frontend.
+pub trait Notifier: std::fmt::Debug + Send + Sync {
+    fn achievements_unlocked(&self, unlocked: &[AchievementStatus]);
+}
+
+/// Announces nothing, for the command line and tests
+#[derive(Debug, Default, Clone, Copy)]
+pub struct SilentNotifier;
+
+impl Notifier for SilentNotifier {
+    fn achievements_unlocked(&self, _unlocked: &[AchievementStatus]) {}
+}
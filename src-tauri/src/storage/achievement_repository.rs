@@ -0,0 +1,41 @@
+use async_trait::async_trait;
+
+use crate::model::achievement::AchievementUnlock;
+use crate::storage::db::{DbManager, DbManagerError};
+
+#[async_trait]
+pub trait AchievementRepository {
+    /// Oldest first
+    async fn get_unlocks(&self) -> Result<Vec<AchievementUnlock>, DbManagerError>;
+    /// Returns whether it wasn't unlocked yet, so an unlock is only announced once
+    async fn insert_unlock(&self, id: &str, unlocked_at: i64) -> Result<bool, DbManagerError>;
+}
+
+#[async_trait]
+impl AchievementRepository for DbManager {
+    async fn get_unlocks(&self) -> Result<Vec<AchievementUnlock>, DbManagerError> {
+        let unlocks: Vec<(String, i64)> = sqlx::query_as(
+            "SELECT id, unlocked_at FROM achievement_unlock ORDER BY unlocked_at, id;",
+        )
+        .fetch_all(&self.connection_pool)
+        .await?;
+
+        Ok(unlocks
+            .into_iter()
+            .map(|(id, unlocked_at)| AchievementUnlock { id, unlocked_at })
+            .collect())
+    }
+
+    async fn insert_unlock(&self, id: &str, unlocked_at: i64) -> Result<bool, DbManagerError> {
+        let inserted = sqlx::query(
+            "INSERT OR IGNORE INTO achievement_unlock (id, unlocked_at) VALUES (?, ?);",
+        )
+        .bind(id)
+        .bind(unlocked_at)
+        .execute(&self.connection_pool)
+        .await?
+        .rows_affected();
+
+        Ok(inserted > 0)
+    }
+}
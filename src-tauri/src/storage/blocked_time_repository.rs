@@ -0,0 +1,89 @@
+use async_trait::async_trait;
+use sqlx::{sqlite::SqliteRow, Error as SqlxError, FromRow, Row};
+
+use crate::model::recurrence::{parse_weekday, weekday_code};
+use crate::model::suggestion::BlockedTime;
+use crate::storage::db::{DbManager, DbManagerError};
+
+impl<'r> FromRow<'r, SqliteRow> for BlockedTime {
+    fn from_row(row: &'r SqliteRow) -> Result<Self, SqlxError> {
+        let weekdays: String = row.try_get("weekdays")?;
+
+        Ok(BlockedTime {
+            id: row.try_get("id")?,
+            label: row.try_get("label")?,
+            weekdays: weekdays
+                .split(',')
+                .filter(|day| !day.is_empty())
+                .map(|day| {
+                    parse_weekday(day)
+                        .ok_or_else(|| SqlxError::Decode(format!("unknown weekday {day}").into()))
+                })
+                .collect::<Result<_, _>>()?,
+            starts_at: row.try_get("starts_at")?,
+            ends_at: row.try_get("ends_at")?,
+        })
+    }
+}
+
+/// Weekly time kept free of schedule suggestions
+#[async_trait]
+pub trait BlockedTimeRepository {
+    async fn insert_blocked_time(
+        &self,
+        blocked: BlockedTime,
+    ) -> Result<BlockedTime, DbManagerError>;
+    /// By time of day
+    async fn get_blocked_times(&self) -> Result<Vec<BlockedTime>, DbManagerError>;
+    /// Returns whether there was such a blocked time
+    async fn delete_blocked_time(&self, id: i32) -> Result<bool, DbManagerError>;
+}
+
+#[async_trait]
+impl BlockedTimeRepository for DbManager {
+    async fn insert_blocked_time(
+        &self,
+        blocked: BlockedTime,
+    ) -> Result<BlockedTime, DbManagerError> {
+        let weekdays: Vec<&str> = blocked
+            .weekdays
+            .iter()
+            .map(|day| weekday_code(*day))
+            .collect();
+        let id = sqlx::query(
+            "INSERT INTO blocked_time (label, weekdays, starts_at, ends_at) VALUES (?, ?, ?, ?);",
+        )
+        .bind(&blocked.label)
+        .bind(weekdays.join(","))
+        .bind(blocked.starts_at)
+        .bind(blocked.ends_at)
+        .execute(&self.connection_pool)
+        .await?
+        .last_insert_rowid() as i32;
+
+        Ok(BlockedTime {
+            id: Some(id),
+            ..blocked
+        })
+    }
+
+    async fn get_blocked_times(&self) -> Result<Vec<BlockedTime>, DbManagerError> {
+        let blocked = sqlx::query_as::<_, BlockedTime>(
+            "SELECT * FROM blocked_time ORDER BY starts_at, ends_at, id;",
+        )
+        .fetch_all(&self.connection_pool)
+        .await?;
+
+        Ok(blocked)
+    }
+
+    async fn delete_blocked_time(&self, id: i32) -> Result<bool, DbManagerError> {
+        let deleted = sqlx::query("DELETE FROM blocked_time WHERE id = ?;")
+            .bind(id)
+            .execute(&self.connection_pool)
+            .await?
+            .rows_affected();
+
+        Ok(deleted > 0)
+    }
+}
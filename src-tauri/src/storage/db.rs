@@ -4,6 +4,7 @@ use thiserror::Error;
 use tracing::info;
 
 use super::karma_repository::KarmaRepositoryError;
+use super::migrations::run_migrations;
 
 #[derive(Debug, Error)]
 pub enum DbManagerError {
@@ -12,6 +13,9 @@ pub enum DbManagerError {
 
     #[error("Karma repository failure: {0}")]
     KarmaRepositoryFailure(#[from] KarmaRepositoryError),
+
+    #[error("Failed to migrate the database schema: {0}")]
+    Migration(SqlxError),
 }
 
 // Serialize is needed by tauri when returning results from handlers
@@ -35,6 +39,10 @@ impl serde::Serialize for DbManagerError {
                 state.serialize_field("kind", "external")?;
                 state.end()
             }
+            DbManagerError::Migration(_) => {
+                state.serialize_field("kind", "migration")?;
+                state.end()
+            }
         }
     }
 }
@@ -100,10 +108,32 @@ impl DbManager {
 
         // create the db connection pool
 
+        run_migrations(&db)
+            .await
+            .map_err(DbManagerError::Migration)?;
+
         Ok(db)
     }
 }
 
+impl DbManager {
+    /// Rebuilds the database file so deleted rows can't be recovered from free pages,
+    /// then empties the write ahead log which may still hold copies of them
+    pub async fn vacuum(&self) -> Result<(), DbManagerError> {
+        // secure_delete is per connection, so all three have to run on the same one
+        let mut connection = self.connection_pool.acquire().await?;
+        sqlx::query("PRAGMA secure_delete = ON;")
+            .execute(&mut *connection)
+            .await?;
+        sqlx::query("VACUUM;").execute(&mut *connection).await?;
+        sqlx::query("PRAGMA wal_checkpoint(TRUNCATE);")
+            .execute(&mut *connection)
+            .await?;
+
+        Ok(())
+    }
+}
+
 impl AsRef<DbManager> for DbManager {
     fn as_ref(&self) -> &DbManager {
         self
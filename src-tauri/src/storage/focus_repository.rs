@@ -0,0 +1,191 @@
+use async_trait::async_trait;
+use sqlx::{sqlite::SqliteRow, Error as SqlxError, FromRow, Row};
+
+use crate::model::focus::{FocusRules, FocusSession, Interruption};
+use crate::storage::db::{DbManager, DbManagerError};
+
+impl<'r> FromRow<'r, SqliteRow> for FocusSession {
+    fn from_row(row: &'r SqliteRow) -> Result<Self, SqlxError> {
+        Ok(FocusSession {
+            id: row.try_get("id")?,
+            session_id: row.try_get("session_id")?,
+            started_at: row.try_get("started_at")?,
+            rules: FocusRules {
+                work_minutes: row.try_get("work_minutes")?,
+                short_break_minutes: row.try_get("short_break_minutes")?,
+                long_break_minutes: row.try_get("long_break_minutes")?,
+                long_break_every: row.try_get("long_break_every")?,
+                max_pomodoros: row.try_get("max_pomodoros")?,
+            },
+            ended_at: row.try_get("ended_at")?,
+            rating: row.try_get("rating")?,
+        })
+    }
+}
+
+impl<'r> FromRow<'r, SqliteRow> for Interruption {
+    fn from_row(row: &'r SqliteRow) -> Result<Self, SqlxError> {
+        Ok(Interruption {
+            id: row.try_get("id")?,
+            focus_id: row.try_get("focus_id")?,
+            at: row.try_get("at")?,
+            note: row.try_get("note")?,
+            external: row.try_get("external")?,
+        })
+    }
+}
+
+/// Pomodoro sessions on top of karma sessions, at most one runs at a time
+#[async_trait]
+pub trait FocusRepository {
+    /// Returns None when another focus session is still running
+    async fn insert_focus(
+        &self,
+        focus: FocusSession,
+    ) -> Result<Option<FocusSession>, DbManagerError>;
+    async fn get_running_focus(&self) -> Result<Option<FocusSession>, DbManagerError>;
+    async fn get_focus(&self, id: i32) -> Result<Option<FocusSession>, DbManagerError>;
+    /// The last `limit` ones, newest first
+    async fn get_focus_sessions(&self, limit: i64) -> Result<Vec<FocusSession>, DbManagerError>;
+    /// Returns whether it was still running
+    async fn end_focus(&self, id: i32, ended_at: i64) -> Result<bool, DbManagerError>;
+    /// Returns whether there was such a focus session
+    async fn rate_focus(&self, id: i32, rating: u8) -> Result<bool, DbManagerError>;
+    async fn insert_interruption(
+        &self,
+        interruption: Interruption,
+    ) -> Result<Interruption, DbManagerError>;
+    /// In the order they happened
+    async fn get_interruptions(&self, focus_id: i32) -> Result<Vec<Interruption>, DbManagerError>;
+    /// Of every focus session, in the order they happened
+    async fn get_all_interruptions(&self) -> Result<Vec<Interruption>, DbManagerError>;
+}
+
+#[async_trait]
+impl FocusRepository for DbManager {
+    async fn insert_focus(
+        &self,
+        focus: FocusSession,
+    ) -> Result<Option<FocusSession>, DbManagerError> {
+        // Checked in the same statement so two starts can't both run
+        let inserted = sqlx::query(
+            "INSERT INTO focus_session (session_id, started_at, work_minutes, \
+            short_break_minutes, long_break_minutes, long_break_every, max_pomodoros) \
+            SELECT ?, ?, ?, ?, ?, ?, ? \
+            WHERE NOT EXISTS (SELECT 1 FROM focus_session WHERE ended_at IS NULL);",
+        )
+        .bind(focus.session_id)
+        .bind(focus.started_at)
+        .bind(focus.rules.work_minutes)
+        .bind(focus.rules.short_break_minutes)
+        .bind(focus.rules.long_break_minutes)
+        .bind(focus.rules.long_break_every)
+        .bind(focus.rules.max_pomodoros)
+        .execute(&self.connection_pool)
+        .await?;
+
+        if inserted.rows_affected() == 0 {
+            return Ok(None);
+        }
+        Ok(Some(FocusSession {
+            id: Some(inserted.last_insert_rowid() as i32),
+            ended_at: None,
+            rating: None,
+            ..focus
+        }))
+    }
+
+    async fn get_running_focus(&self) -> Result<Option<FocusSession>, DbManagerError> {
+        let focus = sqlx::query_as::<_, FocusSession>(
+            "SELECT * FROM focus_session WHERE ended_at IS NULL ORDER BY id DESC LIMIT 1;",
+        )
+        .fetch_optional(&self.connection_pool)
+        .await?;
+
+        Ok(focus)
+    }
+
+    async fn get_focus(&self, id: i32) -> Result<Option<FocusSession>, DbManagerError> {
+        let focus = sqlx::query_as::<_, FocusSession>("SELECT * FROM focus_session WHERE id = ?;")
+            .bind(id)
+            .fetch_optional(&self.connection_pool)
+            .await?;
+
+        Ok(focus)
+    }
+
+    async fn get_focus_sessions(&self, limit: i64) -> Result<Vec<FocusSession>, DbManagerError> {
+        let sessions = sqlx::query_as::<_, FocusSession>(
+            "SELECT * FROM focus_session ORDER BY started_at DESC, id DESC LIMIT ?;",
+        )
+        .bind(limit)
+        .fetch_all(&self.connection_pool)
+        .await?;
+
+        Ok(sessions)
+    }
+
+    async fn end_focus(&self, id: i32, ended_at: i64) -> Result<bool, DbManagerError> {
+        let ended =
+            sqlx::query("UPDATE focus_session SET ended_at = ? WHERE id = ? AND ended_at IS NULL;")
+                .bind(ended_at)
+                .bind(id)
+                .execute(&self.connection_pool)
+                .await?
+                .rows_affected();
+
+        Ok(ended > 0)
+    }
+
+    async fn rate_focus(&self, id: i32, rating: u8) -> Result<bool, DbManagerError> {
+        let rated = sqlx::query("UPDATE focus_session SET rating = ? WHERE id = ?;")
+            .bind(rating)
+            .bind(id)
+            .execute(&self.connection_pool)
+            .await?
+            .rows_affected();
+
+        Ok(rated > 0)
+    }
+
+    async fn insert_interruption(
+        &self,
+        interruption: Interruption,
+    ) -> Result<Interruption, DbManagerError> {
+        let id = sqlx::query(
+            "INSERT INTO focus_interruption (focus_id, at, note, external) VALUES (?, ?, ?, ?);",
+        )
+        .bind(interruption.focus_id)
+        .bind(interruption.at)
+        .bind(&interruption.note)
+        .bind(interruption.external)
+        .execute(&self.connection_pool)
+        .await?
+        .last_insert_rowid() as i32;
+
+        Ok(Interruption {
+            id: Some(id),
+            ..interruption
+        })
+    }
+
+    async fn get_interruptions(&self, focus_id: i32) -> Result<Vec<Interruption>, DbManagerError> {
+        let interruptions = sqlx::query_as::<_, Interruption>(
+            "SELECT * FROM focus_interruption WHERE focus_id = ? ORDER BY at, id;",
+        )
+        .bind(focus_id)
+        .fetch_all(&self.connection_pool)
+        .await?;
+
+        Ok(interruptions)
+    }
+
+    async fn get_all_interruptions(&self) -> Result<Vec<Interruption>, DbManagerError> {
+        let interruptions =
+            sqlx::query_as::<_, Interruption>("SELECT * FROM focus_interruption ORDER BY at, id;")
+                .fetch_all(&self.connection_pool)
+                .await?;
+
+        Ok(interruptions)
+    }
+}
@@ -0,0 +1,84 @@
+use async_trait::async_trait;
+use sqlx::{sqlite::SqliteRow, Error as SqlxError, FromRow, Row};
+
+use crate::model::goal::{Goal, GoalComparison, GoalMetric};
+use crate::model::report::ReportPeriod;
+use crate::model::subject::KarmaSubject;
+use crate::storage::db::{DbManager, DbManagerError};
+
+impl<'r> FromRow<'r, SqliteRow> for Goal {
+    fn from_row(row: &'r SqliteRow) -> Result<Self, SqlxError> {
+        let kind: String = row.try_get("subject_kind")?;
+        let subject_id: i32 = row.try_get("subject_id")?;
+        let metric: String = row.try_get("metric")?;
+        let comparison: String = row.try_get("comparison")?;
+        let period: String = row.try_get("period")?;
+        let decode = |e: String| SqlxError::Decode(e.into());
+
+        Ok(Goal {
+            id: row.try_get("id")?,
+            subject: KarmaSubject::from_parts(&kind, subject_id)
+                .ok_or_else(|| decode(format!("unknown subject {kind}:{subject_id}")))?,
+            name: String::new(),
+            metric: GoalMetric::try_from(metric.as_str()).map_err(|e| decode(e.to_string()))?,
+            comparison: GoalComparison::try_from(comparison.as_str())
+                .map_err(|e| decode(e.to_string()))?,
+            target: row.try_get("target")?,
+            period: ReportPeriod::try_from(period.as_str()).map_err(|e| decode(e.to_string()))?,
+            created_at: row.try_get("created_at")?,
+        })
+    }
+}
+
+#[async_trait]
+pub trait GoalRepository {
+    async fn insert_goal(&self, goal: Goal) -> Result<Goal, DbManagerError>;
+    /// Oldest first, names are left for the caller to fill in
+    async fn get_goals(&self) -> Result<Vec<Goal>, DbManagerError>;
+    /// Returns whether there was such a goal
+    async fn delete_goal(&self, id: i32) -> Result<bool, DbManagerError>;
+}
+
+#[async_trait]
+impl GoalRepository for DbManager {
+    async fn insert_goal(&self, goal: Goal) -> Result<Goal, DbManagerError> {
+        let id = sqlx::query(
+            "INSERT INTO karma_goal \
+            (subject_kind, subject_id, metric, comparison, target, period, created_at) \
+            VALUES (?, ?, ?, ?, ?, ?, ?);",
+        )
+        .bind(goal.subject.kind())
+        .bind(goal.subject.id())
+        .bind(goal.metric.as_str())
+        .bind(goal.comparison.as_str())
+        .bind(goal.target)
+        .bind(goal.period.noun())
+        .bind(goal.created_at)
+        .execute(&self.connection_pool)
+        .await?
+        .last_insert_rowid() as i32;
+
+        Ok(Goal {
+            id: Some(id),
+            ..goal
+        })
+    }
+
+    async fn get_goals(&self) -> Result<Vec<Goal>, DbManagerError> {
+        let goals = sqlx::query_as::<_, Goal>("SELECT * FROM karma_goal ORDER BY id;")
+            .fetch_all(&self.connection_pool)
+            .await?;
+
+        Ok(goals)
+    }
+
+    async fn delete_goal(&self, id: i32) -> Result<bool, DbManagerError> {
+        let deleted = sqlx::query("DELETE FROM karma_goal WHERE id = ?;")
+            .bind(id)
+            .execute(&self.connection_pool)
+            .await?
+            .rows_affected();
+
+        Ok(deleted > 0)
+    }
+}
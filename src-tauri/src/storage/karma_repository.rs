@@ -1,9 +1,20 @@
 use async_trait::async_trait;
-use sqlx::Error as SqlxError;
+use chrono::NaiveDate;
+use sqlx::sqlite::SqliteRow;
+use sqlx::{Error as SqlxError, FromRow, QueryBuilder, Row, Sqlite};
 use thiserror::Error;
 
-use crate::model::karma::{KarmaPoint, KarmaStatus};
+use std::collections::HashMap;
+
+use crate::model::duration::TrackedSession;
+use crate::model::karma::{KarmaPoint, KarmaStatus, Polarity, State};
+use crate::model::karma_query::{KarmaCursor, KarmaListItem, KarmaPage, KarmaQuery, KarmaSort};
+use crate::model::karma_search::{
+    split_highlights, to_fts_query, KarmaSearchResult, HIGHLIGHT_END, HIGHLIGHT_START,
+};
+use crate::model::karma_session::{KarmaSession, StartedSession};
 use crate::storage::db::{DbManager, DbManagerError};
+use crate::storage::schedule_repository::complete_occurrence_on;
 
 #[derive(Debug, Error)]
 pub enum KarmaRepositoryError {
@@ -13,23 +24,116 @@ pub enum KarmaRepositoryError {
     #[error("Failed to fetch karma point {0} because {1}")]
     KarmaPointFetchingFailed(String, SqlxError),
 
+    #[error("Failed to update karma point {0} because {1}")]
+    KarmaPointUpdateFailed(i32, SqlxError),
+
     #[error("Insertion of karma status failed with: {0}")]
     KarmaStatusInsertionFailed(SqlxError),
 
     #[error("Failed to fetch karma status because: {0}")]
     KarmaStatusFetchingFailed(SqlxError),
+
+    #[error("Insertion of karma session failed with: {0}")]
+    KarmaSessionInsertionFailed(SqlxError),
+
+    #[error("Failed to fetch karma sessions because: {0}")]
+    KarmaSessionFetchingFailed(SqlxError),
+
+    #[error("Failed to list karma points because: {0}")]
+    KarmaPointListingFailed(SqlxError),
+
+    #[error("Failed to search karma points because: {0}")]
+    KarmaSearchFailed(SqlxError),
+
+    #[error("Failed to rebuild the search index because: {0}")]
+    SearchIndexRebuildFailed(SqlxError),
+}
+
+/// Every karma point with the latest state of any of its sessions and its last activity
+const KARMA_OVERVIEW: &str = "SELECT k.id, k.purpose, k.name, k.created_at, k.default_duration, \
+    k.polarity, \
+    (SELECT s.current_state FROM karma_status s \
+    JOIN karma_session ks ON ks.id = s.session_id WHERE ks.template_id = k.id \
+    ORDER BY s.timestamp DESC, s.id DESC LIMIT 1) AS current_state, \
+    COALESCE((SELECT MAX(s.timestamp) FROM karma_status s \
+    JOIN karma_session ks ON ks.id = s.session_id WHERE ks.template_id = k.id), \
+    k.created_at) AS last_activity \
+    FROM karma k";
+
+fn list_item_from_row(row: &SqliteRow) -> Result<KarmaListItem, SqlxError> {
+    let state: Option<String> = row.try_get("current_state")?;
+
+    Ok(KarmaListItem {
+        karma: KarmaPoint::from_row(row)?,
+        state: state.map(State::from),
+        last_activity: row.try_get("last_activity")?,
+    })
 }
 
 #[async_trait]
 pub trait KarmaRepository {
     async fn insert_karma(&self, karma: KarmaPoint) -> Result<KarmaPoint, DbManagerError>;
     async fn get_karma_by_name(&self, name: String) -> Result<KarmaPoint, DbManagerError>;
+    async fn update_polarity(
+        &self,
+        template_id: i32,
+        polarity: Polarity,
+    ) -> Result<(), DbManagerError>;
     async fn insert_karma_status(&self, status: KarmaStatus)
         -> Result<KarmaStatus, DbManagerError>;
+    /// Inserts the status only if the latest one of its session is still in the
+    /// `last_seen` state, so two transitions checked against the same state can't
+    /// both happen. Returns none when another status came first.
+    async fn insert_karma_status_after(
+        &self,
+        status: KarmaStatus,
+        last_seen: Option<State>,
+    ) -> Result<Option<KarmaStatus>, DbManagerError>;
+    /// The latest status of the latest session started from `karma_point`
     async fn get_karma_status(
         &self,
         karma_point: KarmaPoint,
     ) -> Result<KarmaStatus, DbManagerError>;
+    async fn insert_session(&self, session: KarmaSession) -> Result<KarmaSession, DbManagerError>;
+    /// Creates the template unless it has an id or its name is taken, in which case the
+    /// existing one is used, then the session with its first status, and completes the
+    /// occurrence due on `due_on`. All in one transaction.
+    async fn start_session(
+        &self,
+        template: KarmaPoint,
+        session: KarmaSession,
+        status: KarmaStatus,
+        due_on: NaiveDate,
+    ) -> Result<StartedSession, DbManagerError>;
+    /// The sessions started from a template, latest first
+    async fn get_sessions(&self, template_id: i32) -> Result<Vec<KarmaSession>, DbManagerError>;
+    async fn get_tracked_session(
+        &self,
+        session_id: i32,
+    ) -> Result<Option<TrackedSession>, DbManagerError>;
+    /// The sessions that may have been active between `from` and `to`: started before
+    /// `to` and not closed before `from`
+    async fn get_tracked_sessions(
+        &self,
+        from: i64,
+        to: i64,
+    ) -> Result<Vec<TrackedSession>, DbManagerError>;
+    /// Every session of the habits to avoid, oldest first
+    async fn get_relapse_sessions(&self) -> Result<Vec<TrackedSession>, DbManagerError>;
+    async fn get_all_karma(&self) -> Result<Vec<KarmaPoint>, DbManagerError>;
+    async fn get_all_sessions(&self) -> Result<Vec<KarmaSession>, DbManagerError>;
+    async fn get_all_karma_statuses(&self) -> Result<Vec<KarmaStatus>, DbManagerError>;
+    async fn list_karma(&self, query: &KarmaQuery) -> Result<KarmaPage, DbManagerError>;
+    /// Every karma point with its current state, unpaginated, for name resolution
+    async fn get_karma_overview(&self) -> Result<Vec<KarmaListItem>, DbManagerError>;
+    /// Best matches first, every word of `text` is matched as a prefix
+    async fn search_karma(
+        &self,
+        text: &str,
+        limit: u32,
+    ) -> Result<Vec<KarmaSearchResult>, DbManagerError>;
+    /// Repopulates the search index from the karma table
+    async fn rebuild_search_index(&self) -> Result<(), DbManagerError>;
 }
 
 #[async_trait]
@@ -37,14 +141,29 @@ impl KarmaRepository for DbManager {
     async fn insert_karma(&self, karma: KarmaPoint) -> Result<KarmaPoint, DbManagerError> {
         let karma_point_name = karma.get_name();
 
-        let _ = sqlx::query("INSERT INTO karma(purpose, name) VALUES(?, ?);")
-            .bind(karma.get_purpose() as i32)
-            .bind(&karma_point_name)
-            .execute(&self.connection_pool)
-            .await
-            .map_err(|e| KarmaRepositoryError::KarmaPointInsertionFailed(karma_point_name, e))?;
+        let query_result = sqlx::query(
+            "INSERT INTO karma(purpose, name, created_at, default_duration, polarity) \
+            VALUES(?, ?, ?, ?, ?);",
+        )
+        .bind(karma.get_purpose() as i32)
+        .bind(&karma_point_name)
+        .bind(karma.get_created_at())
+        .bind(karma.get_default_duration())
+        .bind(karma.get_polarity() as i32)
+        .execute(&self.connection_pool)
+        .await
+        .map_err(|e| {
+            KarmaRepositoryError::KarmaPointInsertionFailed(karma_point_name.clone(), e)
+        })?;
 
-        Ok(karma)
+        Ok(KarmaPoint::with_id(
+            query_result.last_insert_rowid() as i32,
+            karma.get_purpose(),
+            karma_point_name,
+            karma.get_created_at(),
+        )
+        .with_default_duration(karma.get_default_duration())
+        .with_polarity(karma.get_polarity()))
     }
 
     async fn get_karma_by_name(&self, name: String) -> Result<KarmaPoint, DbManagerError> {
@@ -58,16 +177,40 @@ impl KarmaRepository for DbManager {
         Ok(karma_point_result)
     }
 
+    async fn update_polarity(
+        &self,
+        template_id: i32,
+        polarity: Polarity,
+    ) -> Result<(), DbManagerError> {
+        sqlx::query("UPDATE karma SET polarity = ? WHERE id = ?;")
+            .bind(polarity as i32)
+            .bind(template_id)
+            .execute(&self.connection_pool)
+            .await
+            .map_err(|e| KarmaRepositoryError::KarmaPointUpdateFailed(template_id, e))?;
+
+        Ok(())
+    }
+
     async fn insert_karma_status(
         &self,
         status: KarmaStatus,
     ) -> Result<KarmaStatus, DbManagerError> {
         sqlx::query::<sqlx::Sqlite>(
-            "INSERT INTO karma_status(karma_id, current_state, timestamp) VALUES(?, ?, ?);",
+            "INSERT INTO karma_status(session_id, closed_with, current_state, timestamp, \
+            timezone, utc_offset) VALUES(?, ?, ?, ?, ?, ?);",
+        )
+        .bind(status.session_id)
+        .bind(
+            status
+                .closed_with
+                .clone()
+                .map(|closed_with| closed_with as i32),
         )
-        .bind(status.karma_id)
         .bind(status.state.to_string())
         .bind(status.timestamp)
+        .bind(status.timezone.clone())
+        .bind(status.utc_offset)
         .execute(&self.connection_pool)
         .await
         .map_err(|e| KarmaRepositoryError::KarmaStatusInsertionFailed(e))?;
@@ -75,6 +218,41 @@ impl KarmaRepository for DbManager {
         Ok(status)
     }
 
+    async fn insert_karma_status_after(
+        &self,
+        status: KarmaStatus,
+        last_seen: Option<State>,
+    ) -> Result<Option<KarmaStatus>, DbManagerError> {
+        // A single statement, the check and the insert can't be interleaved
+        let inserted = sqlx::query(
+            "INSERT INTO karma_status(session_id, closed_with, current_state, timestamp, \
+            timezone, utc_offset) SELECT ?, ?, ?, ?, ?, ? \
+            WHERE (SELECT current_state FROM karma_status WHERE session_id = ? \
+            ORDER BY timestamp DESC, id DESC LIMIT 1) IS ?;",
+        )
+        .bind(status.session_id)
+        .bind(
+            status
+                .closed_with
+                .clone()
+                .map(|closed_with| closed_with as i32),
+        )
+        .bind(status.state.to_string())
+        .bind(status.timestamp)
+        .bind(status.timezone.clone())
+        .bind(status.utc_offset)
+        .bind(status.session_id)
+        .bind(last_seen.map(|state| state.to_string()))
+        .execute(&self.connection_pool)
+        .await
+        .map_err(KarmaRepositoryError::KarmaStatusInsertionFailed)?;
+
+        if inserted.rows_affected() == 0 {
+            return Ok(None);
+        }
+        Ok(Some(status))
+    }
+
     async fn get_karma_status(
         &self,
         karma_point: KarmaPoint,
@@ -84,15 +262,429 @@ impl KarmaRepository for DbManager {
         // shouldn't fail
         let karma_id = karma_point.get_id().unwrap();
 
-        let karma_status_result =
-            sqlx::query_as::<_, KarmaStatus>("SELECT * FROM karma_status WHERE karma_id = ?;")
-                .bind(karma_id)
-                .fetch_one(&self.connection_pool)
-                .await
-                .map_err(|e| KarmaRepositoryError::KarmaStatusFetchingFailed(e))?;
+        let karma_status_result = sqlx::query_as::<_, KarmaStatus>(
+            "SELECT s.* FROM karma_status s JOIN karma_session ks ON ks.id = s.session_id \
+            WHERE ks.template_id = ? ORDER BY s.timestamp DESC, s.id DESC LIMIT 1;",
+        )
+        .bind(karma_id)
+        .fetch_one(&self.connection_pool)
+        .await
+        .map_err(|e| KarmaRepositoryError::KarmaStatusFetchingFailed(e))?;
 
         Ok(karma_status_result)
     }
+
+    async fn insert_session(&self, session: KarmaSession) -> Result<KarmaSession, DbManagerError> {
+        let query_result = sqlx::query(
+            "INSERT INTO karma_session(template_id, purpose, planned_duration, started_at) \
+            VALUES(?, ?, ?, ?);",
+        )
+        .bind(session.get_template_id())
+        .bind(session.get_purpose() as i32)
+        .bind(session.get_planned_duration())
+        .bind(session.get_started_at())
+        .execute(&self.connection_pool)
+        .await
+        .map_err(KarmaRepositoryError::KarmaSessionInsertionFailed)?;
+
+        Ok(KarmaSession::with_id(
+            query_result.last_insert_rowid() as i32,
+            session.get_template_id(),
+            session.get_purpose(),
+            session.get_planned_duration(),
+            session.get_started_at(),
+        ))
+    }
+
+    async fn start_session(
+        &self,
+        template: KarmaPoint,
+        session: KarmaSession,
+        status: KarmaStatus,
+        due_on: NaiveDate,
+    ) -> Result<StartedSession, DbManagerError> {
+        let mut transaction = self.connection_pool.begin().await?;
+
+        let template = match template.get_id() {
+            Some(_) => template,
+            None => {
+                // Another start may have created it since the name was looked up
+                sqlx::query(
+                    "INSERT INTO karma(purpose, name, created_at, default_duration, polarity) \
+                    VALUES(?, ?, ?, ?, ?) ON CONFLICT(name) DO NOTHING;",
+                )
+                .bind(template.get_purpose() as i32)
+                .bind(template.get_name())
+                .bind(template.get_created_at())
+                .bind(template.get_default_duration())
+                .bind(template.get_polarity() as i32)
+                .execute(&mut *transaction)
+                .await?;
+                sqlx::query_as::<_, KarmaPoint>("SELECT * FROM karma WHERE name = ?;")
+                    .bind(template.get_name())
+                    .fetch_one(&mut *transaction)
+                    .await?
+            }
+        };
+        let template_id = template.get_id().unwrap_or_default();
+
+        let session_id = sqlx::query(
+            "INSERT INTO karma_session(template_id, purpose, planned_duration, started_at) \
+            VALUES(?, ?, ?, ?);",
+        )
+        .bind(template_id)
+        .bind(session.get_purpose() as i32)
+        .bind(session.get_planned_duration())
+        .bind(session.get_started_at())
+        .execute(&mut *transaction)
+        .await?
+        .last_insert_rowid() as i32;
+
+        let status = KarmaStatus {
+            session_id,
+            ..status
+        };
+        sqlx::query(
+            "INSERT INTO karma_status(session_id, closed_with, current_state, timestamp, \
+            timezone, utc_offset) VALUES(?, ?, ?, ?, ?, ?);",
+        )
+        .bind(status.session_id)
+        .bind(
+            status
+                .closed_with
+                .clone()
+                .map(|closed_with| closed_with as i32),
+        )
+        .bind(status.state.to_string())
+        .bind(status.timestamp)
+        .bind(status.timezone.clone())
+        .bind(status.utc_offset)
+        .execute(&mut *transaction)
+        .await?;
+
+        complete_occurrence_on(
+            &mut transaction,
+            template_id,
+            due_on,
+            session_id,
+            session.get_started_at(),
+        )
+        .await?;
+
+        transaction.commit().await?;
+        Ok(StartedSession {
+            session: KarmaSession::with_id(
+                session_id,
+                template_id,
+                session.get_purpose(),
+                session.get_planned_duration(),
+                session.get_started_at(),
+            ),
+            template,
+            status,
+        })
+    }
+
+    async fn get_sessions(&self, template_id: i32) -> Result<Vec<KarmaSession>, DbManagerError> {
+        let sessions = sqlx::query_as::<_, KarmaSession>(
+            "SELECT * FROM karma_session WHERE template_id = ? ORDER BY started_at DESC, id DESC;",
+        )
+        .bind(template_id)
+        .fetch_all(&self.connection_pool)
+        .await
+        .map_err(KarmaRepositoryError::KarmaSessionFetchingFailed)?;
+
+        Ok(sessions)
+    }
+
+    async fn get_tracked_session(
+        &self,
+        session_id: i32,
+    ) -> Result<Option<TrackedSession>, DbManagerError> {
+        let mut sessions = self
+            .fetch_tracked_sessions("ks.id = ?", |query| query.bind(session_id))
+            .await?;
+
+        Ok(sessions.pop())
+    }
+
+    async fn get_tracked_sessions(
+        &self,
+        from: i64,
+        to: i64,
+    ) -> Result<Vec<TrackedSession>, DbManagerError> {
+        self.fetch_tracked_sessions(
+            "ks.started_at < ? AND COALESCE((SELECT last.current_state = 'closed' \
+            AND last.timestamp < ? FROM karma_status last WHERE last.session_id = ks.id \
+            ORDER BY last.timestamp DESC, last.id DESC LIMIT 1), 0) = 0",
+            |query| query.bind(to).bind(from),
+        )
+        .await
+    }
+
+    async fn get_relapse_sessions(&self) -> Result<Vec<TrackedSession>, DbManagerError> {
+        self.fetch_tracked_sessions(
+            "ks.template_id IN (SELECT id FROM karma WHERE polarity < 0)",
+            |query| query,
+        )
+        .await
+    }
+
+    async fn get_all_karma(&self) -> Result<Vec<KarmaPoint>, DbManagerError> {
+        let karma_points = sqlx::query_as::<_, KarmaPoint>("SELECT * FROM karma ORDER BY id;")
+            .fetch_all(&self.connection_pool)
+            .await
+            .map_err(KarmaRepositoryError::KarmaPointListingFailed)?;
+
+        Ok(karma_points)
+    }
+
+    async fn get_all_sessions(&self) -> Result<Vec<KarmaSession>, DbManagerError> {
+        let sessions =
+            sqlx::query_as::<_, KarmaSession>("SELECT * FROM karma_session ORDER BY id;")
+                .fetch_all(&self.connection_pool)
+                .await
+                .map_err(KarmaRepositoryError::KarmaSessionFetchingFailed)?;
+
+        Ok(sessions)
+    }
+
+    async fn get_all_karma_statuses(&self) -> Result<Vec<KarmaStatus>, DbManagerError> {
+        let karma_statuses =
+            sqlx::query_as::<_, KarmaStatus>("SELECT * FROM karma_status ORDER BY id;")
+                .fetch_all(&self.connection_pool)
+                .await
+                .map_err(KarmaRepositoryError::KarmaStatusFetchingFailed)?;
+
+        Ok(karma_statuses)
+    }
+
+    async fn list_karma(&self, query: &KarmaQuery) -> Result<KarmaPage, DbManagerError> {
+        let sort_column = match query.sort {
+            KarmaSort::CreatedAt => "created_at",
+            KarmaSort::LastActivity => "last_activity",
+        };
+
+        let mut builder =
+            QueryBuilder::<Sqlite>::new(format!("SELECT * FROM ({KARMA_OVERVIEW}) WHERE 1 = 1"));
+
+        if let Some(purpose) = &query.purpose {
+            builder
+                .push(" AND purpose = ")
+                .push_bind(purpose.clone() as i32);
+        }
+
+        if let Some(state) = &query.state {
+            builder
+                .push(" AND current_state = ")
+                .push_bind(state.to_string());
+        }
+
+        if let Some(from) = query.from {
+            builder
+                .push(format!(" AND {sort_column} >= "))
+                .push_bind(from);
+        }
+
+        if let Some(to) = query.to {
+            builder
+                .push(format!(" AND {sort_column} <= "))
+                .push_bind(to);
+        }
+
+        if let Some(prefix) = &query.name_prefix {
+            let escaped = prefix
+                .replace('\\', "\\\\")
+                .replace('%', "\\%")
+                .replace('_', "\\_");
+            builder
+                .push(" AND name LIKE ")
+                .push_bind(format!("{escaped}%"))
+                .push(" ESCAPE '\\'");
+        }
+
+        if let Some(after) = query.after {
+            builder
+                .push(format!(" AND ({sort_column} < "))
+                .push_bind(after.sort_key)
+                .push(format!(" OR ({sort_column} = "))
+                .push_bind(after.sort_key)
+                .push(" AND id < ")
+                .push_bind(after.id)
+                .push("))");
+        }
+
+        // One more than the page to know whether there is a next one
+        let page_size = query.page_size();
+        builder
+            .push(format!(" ORDER BY {sort_column} DESC, id DESC LIMIT "))
+            .push_bind(page_size as i64 + 1);
+
+        let rows = builder
+            .build()
+            .fetch_all(&self.connection_pool)
+            .await
+            .map_err(KarmaRepositoryError::KarmaPointListingFailed)?;
+
+        let mut items = rows
+            .iter()
+            .map(list_item_from_row)
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(KarmaRepositoryError::KarmaPointListingFailed)?;
+
+        let mut next_cursor = None;
+        if items.len() > page_size as usize {
+            items.truncate(page_size as usize);
+            next_cursor = items.last().map(|last| {
+                let sort_key = match query.sort {
+                    KarmaSort::CreatedAt => last.karma.get_created_at(),
+                    KarmaSort::LastActivity => last.last_activity,
+                };
+
+                // Items read back from the database always have an id
+                KarmaCursor {
+                    sort_key,
+                    id: last.karma.get_id().unwrap_or_default(),
+                }
+                .encode()
+            });
+        }
+
+        Ok(KarmaPage { items, next_cursor })
+    }
+
+    async fn get_karma_overview(&self) -> Result<Vec<KarmaListItem>, DbManagerError> {
+        let rows = sqlx::query(KARMA_OVERVIEW)
+            .fetch_all(&self.connection_pool)
+            .await
+            .map_err(KarmaRepositoryError::KarmaPointListingFailed)?;
+
+        let items = rows
+            .iter()
+            .map(list_item_from_row)
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(KarmaRepositoryError::KarmaPointListingFailed)?;
+
+        Ok(items)
+    }
+
+    async fn search_karma(
+        &self,
+        text: &str,
+        limit: u32,
+    ) -> Result<Vec<KarmaSearchResult>, DbManagerError> {
+        let Some(fts_query) = to_fts_query(text) else {
+            return Ok(Vec::new());
+        };
+
+        let rows = sqlx::query(
+            "SELECT k.id, k.purpose, k.name, k.created_at, k.default_duration, k.polarity, \
+            bm25(karma_search) AS rank, \
+            snippet(karma_search, 0, ?, ?, '…', 12) AS snippet \
+            FROM karma_search JOIN karma k ON k.id = karma_search.rowid \
+            WHERE karma_search MATCH ? \
+            ORDER BY rank, k.id DESC LIMIT ?;",
+        )
+        .bind(HIGHLIGHT_START.to_string())
+        .bind(HIGHLIGHT_END.to_string())
+        .bind(fts_query)
+        .bind(limit as i64)
+        .fetch_all(&self.connection_pool)
+        .await
+        .map_err(KarmaRepositoryError::KarmaSearchFailed)?;
+
+        let mut results = Vec::with_capacity(rows.len());
+        for row in &rows {
+            let karma =
+                KarmaPoint::from_row(row).map_err(KarmaRepositoryError::KarmaSearchFailed)?;
+            let rank: f64 = row
+                .try_get("rank")
+                .map_err(KarmaRepositoryError::KarmaSearchFailed)?;
+            let snippet: String = row
+                .try_get("snippet")
+                .map_err(KarmaRepositoryError::KarmaSearchFailed)?;
+
+            results.push(KarmaSearchResult {
+                karma,
+                rank,
+                snippet: split_highlights(&snippet),
+            });
+        }
+
+        Ok(results)
+    }
+
+    async fn rebuild_search_index(&self) -> Result<(), DbManagerError> {
+        sqlx::query("INSERT INTO karma_search(karma_search) VALUES ('rebuild');")
+            .execute(&self.connection_pool)
+            .await
+            .map_err(KarmaRepositoryError::SearchIndexRebuildFailed)?;
+
+        Ok(())
+    }
+}
+
+type SqliteQuery<'q> = sqlx::query::Query<'q, Sqlite, sqlx::sqlite::SqliteArguments<'q>>;
+
+impl DbManager {
+    /// Sessions matching `condition` on `karma_session ks`, with their statuses. The
+    /// condition is applied twice, `bind` has to bind its parameters to either query.
+    async fn fetch_tracked_sessions(
+        &self,
+        condition: &str,
+        bind: impl for<'q> Fn(SqliteQuery<'q>) -> SqliteQuery<'q>,
+    ) -> Result<Vec<TrackedSession>, DbManagerError> {
+        let session_query = format!(
+            "SELECT ks.*, k.name, k.polarity FROM karma_session ks JOIN karma k ON k.id = ks.template_id \
+            WHERE {condition} ORDER BY ks.started_at, ks.id;"
+        );
+        let session_rows = bind(sqlx::query(&session_query))
+            .fetch_all(&self.connection_pool)
+            .await
+            .map_err(KarmaRepositoryError::KarmaSessionFetchingFailed)?;
+
+        let status_query = format!(
+            "SELECT st.* FROM karma_status st JOIN karma_session ks ON ks.id = st.session_id \
+            WHERE {condition} ORDER BY st.timestamp, st.id;"
+        );
+        let statuses = bind(sqlx::query(&status_query))
+            .fetch_all(&self.connection_pool)
+            .await
+            .map_err(KarmaRepositoryError::KarmaStatusFetchingFailed)?;
+
+        let mut statuses_by_session: HashMap<i32, Vec<KarmaStatus>> = HashMap::new();
+        for row in &statuses {
+            let status = KarmaStatus::from_row(row)
+                .map_err(KarmaRepositoryError::KarmaStatusFetchingFailed)?;
+            statuses_by_session
+                .entry(status.session_id)
+                .or_default()
+                .push(status);
+        }
+
+        let mut sessions = Vec::with_capacity(session_rows.len());
+        for row in &session_rows {
+            let session = KarmaSession::from_row(row)
+                .map_err(KarmaRepositoryError::KarmaSessionFetchingFailed)?;
+            let name: String = row
+                .try_get("name")
+                .map_err(KarmaRepositoryError::KarmaSessionFetchingFailed)?;
+            let polarity: i32 = row
+                .try_get("polarity")
+                .map_err(KarmaRepositoryError::KarmaSessionFetchingFailed)?;
+
+            sessions.push(TrackedSession {
+                statuses: statuses_by_session
+                    .remove(&session.get_id().unwrap_or_default())
+                    .unwrap_or_default(),
+                session,
+                name,
+                polarity: polarity.into(),
+            });
+        }
+
+        Ok(sessions)
+    }
 }
 
 #[cfg(test)]
@@ -169,9 +761,17 @@ pub mod karma_repository_tests {
     async fn test_karma_status_operations() {
         let karma = KarmaPoint::new(KarmaType::Learning, "Learning K".to_string());
         let karma = get_or_insert(karma).await;
+        let timestamp = chrono::Utc::now().timestamp();
+        let session = DB
+            .lock()
+            .await
+            .as_ref()
+            .unwrap()
+            .insert_session(KarmaSession::from_template(&karma, timestamp))
+            .await
+            .expect("Failed to insert the karma session");
 
-        if let Some(id) = karma.get_id() {
-            let timestamp = chrono::Utc::now().timestamp();
+        if let Some(id) = session.get_id() {
             let karma_status = KarmaStatus::new(id, State::Active, timestamp);
 
             let _inserted_karma_status = DB
@@ -195,6 +795,166 @@ pub mod karma_repository_tests {
             .await
             .expect("Failed to retrieve the karma status");
 
-        assert_eq!(karma_status.karma_id, karma.get_id().unwrap());
+        assert_eq!(karma_status.session_id, session.get_id().unwrap());
+    }
+
+    #[tokio::test]
+    async fn test_list_karma_pagination() {
+        setup_once().await;
+        for index in 1..=5 {
+            get_or_insert(KarmaPoint::new(KarmaType::Work, format!("Paging {index}"))).await;
+        }
+
+        let mut query = KarmaQuery {
+            name_prefix: Some("Paging".to_string()),
+            limit: 2,
+            ..KarmaQuery::default()
+        };
+
+        let db = DB.lock().await;
+        let db = db.as_ref().unwrap();
+        let first_page = db.list_karma(&query).await.unwrap();
+        let names: Vec<String> = first_page
+            .items
+            .iter()
+            .map(|item| item.karma.get_name())
+            .collect();
+        assert_eq!(names, vec!["Paging 5", "Paging 4"]);
+
+        // A point inserted between two pages shows up on the first page, not the next ones
+        db.insert_karma(KarmaPoint::new(KarmaType::Work, "Paging 6".to_string()))
+            .await
+            .unwrap();
+
+        query.after = Some(KarmaCursor::decode(&first_page.next_cursor.unwrap()).unwrap());
+        let second_page = db.list_karma(&query).await.unwrap();
+        let names: Vec<String> = second_page
+            .items
+            .iter()
+            .map(|item| item.karma.get_name())
+            .collect();
+        assert_eq!(names, vec!["Paging 3", "Paging 2"]);
+
+        query.after = Some(KarmaCursor::decode(&second_page.next_cursor.unwrap()).unwrap());
+        let last_page = db.list_karma(&query).await.unwrap();
+        assert_eq!(last_page.items.len(), 1);
+        assert!(last_page.next_cursor.is_none());
+    }
+
+    #[tokio::test]
+    async fn test_list_karma_filters() {
+        let active =
+            get_or_insert(KarmaPoint::new(KarmaType::Sport, "Filter run".to_string())).await;
+        get_or_insert(KarmaPoint::new(
+            KarmaType::Learning,
+            "Filter read".to_string(),
+        ))
+        .await;
+
+        let db = DB.lock().await;
+        let db = db.as_ref().unwrap();
+        let session = db
+            .insert_session(KarmaSession::from_template(
+                &active,
+                active.get_created_at() + 60,
+            ))
+            .await
+            .unwrap();
+        db.insert_karma_status(KarmaStatus::new(
+            session.get_id().unwrap(),
+            State::Active,
+            active.get_created_at() + 60,
+        ))
+        .await
+        .unwrap();
+
+        let page = db
+            .list_karma(&KarmaQuery {
+                name_prefix: Some("filter".to_string()),
+                state: Some(State::Active),
+                sort: KarmaSort::LastActivity,
+                ..KarmaQuery::default()
+            })
+            .await
+            .unwrap();
+        assert_eq!(page.items.len(), 1);
+        assert_eq!(page.items[0].karma.get_name(), "Filter run");
+        assert_eq!(page.items[0].last_activity, active.get_created_at() + 60);
+
+        let page = db
+            .list_karma(&KarmaQuery {
+                name_prefix: Some("Filter".to_string()),
+                purpose: Some(KarmaType::Learning),
+                ..KarmaQuery::default()
+            })
+            .await
+            .unwrap();
+        assert_eq!(page.items.len(), 1);
+        assert_eq!(page.items[0].state, None);
+
+        // LIKE wildcards in the prefix are taken literally
+        let page = db
+            .list_karma(&KarmaQuery {
+                name_prefix: Some("Filter%".to_string()),
+                ..KarmaQuery::default()
+            })
+            .await
+            .unwrap();
+        assert!(page.items.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_search_karma() {
+        get_or_insert(KarmaPoint::new(
+            KarmaType::Sport,
+            "Searchable morning run".to_string(),
+        ))
+        .await;
+        get_or_insert(KarmaPoint::new(
+            KarmaType::Social,
+            "Searchable café run".to_string(),
+        ))
+        .await;
+
+        let db = DB.lock().await;
+        let db = db.as_ref().unwrap();
+
+        let results = db.search_karma("searchab cafe", 10).await.unwrap();
+        assert_eq!(results.len(), 1);
+        assert_eq!(results[0].karma.get_name(), "Searchable café run");
+        assert!(results[0]
+            .snippet
+            .iter()
+            .any(|part| part.highlighted && part.text == "café"));
+
+        assert_eq!(
+            db.search_karma("searchable run", 10).await.unwrap().len(),
+            2
+        );
+        assert!(db.search_karma("\"*", 10).await.unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_rebuild_search_index() {
+        let karma = get_or_insert(KarmaPoint::new(
+            KarmaType::Work,
+            "Reindexed karma".to_string(),
+        ))
+        .await;
+
+        let db = DB.lock().await;
+        let db = db.as_ref().unwrap();
+
+        // Simulate an index that went out of sync
+        sqlx::query("INSERT INTO karma_search(karma_search, rowid, name) VALUES ('delete', ?, ?);")
+            .bind(karma.get_id())
+            .bind(karma.get_name())
+            .execute(&db.connection_pool)
+            .await
+            .unwrap();
+        assert!(db.search_karma("reindexed", 10).await.unwrap().is_empty());
+
+        db.rebuild_search_index().await.unwrap();
+        assert_eq!(db.search_karma("reindexed", 10).await.unwrap().len(), 1);
     }
 }
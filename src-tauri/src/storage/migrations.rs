@@ -0,0 +1,365 @@
+use sqlx::{Error as SqlxError, SqliteConnection, SqlitePool};
+use tracing::info;
+
+/// A schema change applied on top of the tables created by `DbManager::db_setup`
+pub struct Migration {
+    pub description: &'static str,
+    pub statements: &'static [&'static str],
+}
+
+/// Applied in order, the schema version stored in `PRAGMA user_version`
+/// is the number of migrations already applied. Never edit or reorder
+/// a migration once released, append a new one instead.
+pub const MIGRATIONS: &[Migration] = &[
+    Migration {
+        description: "TOTP second factor and backup codes",
+        statements: &[
+            "CREATE TABLE IF NOT EXISTS user_totp \
+            (username VARCHAR(250) PRIMARY KEY NOT NULL, \
+            secret VARCHAR(128) NOT NULL, \
+            confirmed INTEGER NOT NULL DEFAULT 0, \
+            last_used_step INTEGER, \
+            FOREIGN KEY(username) REFERENCES users(username));",
+            "CREATE TABLE IF NOT EXISTS user_backup_codes \
+            (id INTEGER PRIMARY KEY NOT NULL UNIQUE, \
+            username VARCHAR(250) NOT NULL, \
+            code_hash VARCHAR(250) NOT NULL, \
+            used_at INTEGER, \
+            FOREIGN KEY(username) REFERENCES users(username));",
+        ],
+    },
+    Migration {
+        description: "Karma creation time and listing indexes",
+        statements: &[
+            "ALTER TABLE karma ADD COLUMN created_at INTEGER NOT NULL DEFAULT 0;",
+            // Existing points get the time of their first status, the best guess we have
+            "UPDATE karma SET created_at = COALESCE(\
+            (SELECT MIN(timestamp) FROM karma_status WHERE karma_status.karma_id = karma.id), 0);",
+            "CREATE INDEX IF NOT EXISTS karma_created_at_idx ON karma(created_at, id);",
+            "CREATE INDEX IF NOT EXISTS karma_status_karma_id_idx \
+            ON karma_status(karma_id, timestamp);",
+        ],
+    },
+    Migration {
+        description: "Full text search index over karma names",
+        statements: &[
+            // External content table, the text lives in karma and the triggers keep
+            // the index in sync. Notes or tags become extra columns here.
+            "CREATE VIRTUAL TABLE IF NOT EXISTS karma_search USING fts5(\
+            name, content='karma', content_rowid='id', \
+            tokenize='unicode61 remove_diacritics 2', prefix='2 3');",
+            "CREATE TRIGGER IF NOT EXISTS karma_search_insert AFTER INSERT ON karma BEGIN \
+            INSERT INTO karma_search(rowid, name) VALUES (new.id, new.name); \
+            END;",
+            "CREATE TRIGGER IF NOT EXISTS karma_search_delete AFTER DELETE ON karma BEGIN \
+            INSERT INTO karma_search(karma_search, rowid, name) VALUES ('delete', old.id, old.name); \
+            END;",
+            "CREATE TRIGGER IF NOT EXISTS karma_search_update AFTER UPDATE OF name ON karma BEGIN \
+            INSERT INTO karma_search(karma_search, rowid, name) VALUES ('delete', old.id, old.name); \
+            INSERT INTO karma_search(rowid, name) VALUES (new.id, new.name); \
+            END;",
+            "INSERT INTO karma_search(karma_search) VALUES ('rebuild');",
+        ],
+    },
+    Migration {
+        description: "Karma points become templates, statuses belong to sessions",
+        statements: &[
+            "ALTER TABLE karma ADD COLUMN default_duration INTEGER;",
+            "CREATE TABLE IF NOT EXISTS karma_session \
+            (id INTEGER PRIMARY KEY NOT NULL UNIQUE, \
+            template_id INTEGER NOT NULL, \
+            purpose INTEGER NOT NULL, \
+            planned_duration INTEGER, \
+            started_at INTEGER NOT NULL, \
+            FOREIGN KEY(template_id) REFERENCES karma(id));",
+            // Every point already started becomes a single session with the same id,
+            // so the existing statuses keep pointing at the right row
+            "INSERT INTO karma_session(id, template_id, purpose, started_at) \
+            SELECT k.id, k.id, k.purpose, \
+            COALESCE((SELECT MIN(s.timestamp) FROM karma_status s WHERE s.karma_id = k.id), \
+            k.created_at) \
+            FROM karma k WHERE EXISTS (SELECT 1 FROM karma_status s WHERE s.karma_id = k.id);",
+            "CREATE TABLE karma_status_sessions \
+            (id INTEGER PRIMARY KEY NOT NULL UNIQUE, \
+            session_id INTEGER NOT NULL, \
+            closed_with INTEGER, \
+            current_state VARCHAR(50) NOT NULL, \
+            timestamp INTEGER NOT NULL, \
+            FOREIGN KEY(session_id) REFERENCES karma_session(id));",
+            "INSERT INTO karma_status_sessions(id, session_id, closed_with, current_state, timestamp) \
+            SELECT id, karma_id, NULLIF(closed_with, 0), current_state, timestamp FROM karma_status \
+            WHERE karma_id IN (SELECT id FROM karma_session);",
+            "DROP TABLE karma_status;",
+            "ALTER TABLE karma_status_sessions RENAME TO karma_status;",
+            "CREATE INDEX IF NOT EXISTS karma_status_session_id_idx \
+            ON karma_status(session_id, timestamp);",
+            "CREATE INDEX IF NOT EXISTS karma_session_template_id_idx \
+            ON karma_session(template_id, started_at);",
+        ],
+    },
+    Migration {
+        description: "Recurring schedules and their planned occurrences",
+        statements: &[
+            "CREATE TABLE IF NOT EXISTS karma_schedule \
+            (template_id INTEGER PRIMARY KEY NOT NULL, \
+            rrule TEXT NOT NULL, \
+            starts_on TEXT NOT NULL, \
+            start_time INTEGER, \
+            exceptions TEXT NOT NULL DEFAULT '', \
+            planned_until TEXT, \
+            FOREIGN KEY(template_id) REFERENCES karma(id));",
+            "CREATE TABLE IF NOT EXISTS karma_occurrence \
+            (id INTEGER PRIMARY KEY NOT NULL UNIQUE, \
+            template_id INTEGER NOT NULL, \
+            due_on TEXT NOT NULL, \
+            due_at INTEGER NOT NULL, \
+            status VARCHAR(20) NOT NULL, \
+            session_id INTEGER, \
+            resolved_at INTEGER, \
+            UNIQUE(template_id, due_on), \
+            FOREIGN KEY(template_id) REFERENCES karma(id), \
+            FOREIGN KEY(session_id) REFERENCES karma_session(id));",
+            "CREATE INDEX IF NOT EXISTS karma_occurrence_due_on_idx \
+            ON karma_occurrence(due_on, status);",
+        ],
+    },
+    Migration {
+        description: "Timezone of each status, user settings and local occurrence days",
+        statements: &[
+            // Statuses written before were always treated as UTC
+            "ALTER TABLE karma_status ADD COLUMN timezone TEXT;",
+            "ALTER TABLE karma_status ADD COLUMN utc_offset INTEGER NOT NULL DEFAULT 0;",
+            "CREATE TABLE IF NOT EXISTS settings \
+            (key VARCHAR(50) PRIMARY KEY NOT NULL, \
+            value TEXT NOT NULL);",
+            "ALTER TABLE karma_occurrence ADD COLUMN day_start INTEGER NOT NULL DEFAULT 0;",
+            "ALTER TABLE karma_occurrence ADD COLUMN day_end INTEGER NOT NULL DEFAULT 0;",
+            "UPDATE karma_occurrence SET \
+            day_start = CAST(strftime('%s', due_on) AS INTEGER), \
+            day_end = CAST(strftime('%s', due_on) AS INTEGER) + 86400;",
+        ],
+    },
+    Migration {
+        description: "Streaks counted so far and frozen days",
+        statements: &[
+            "CREATE TABLE IF NOT EXISTS karma_streak \
+            (scope_kind VARCHAR(20) NOT NULL, \
+            scope_id INTEGER NOT NULL, \
+            current INTEGER NOT NULL, \
+            longest INTEGER NOT NULL, \
+            current_since TEXT, \
+            settled_until TEXT, \
+            PRIMARY KEY(scope_kind, scope_id));",
+            "CREATE TABLE IF NOT EXISTS streak_freeze (day TEXT PRIMARY KEY NOT NULL);",
+        ],
+    },
+    Migration {
+        description: "Goals per type or karma point",
+        statements: &[
+            "CREATE TABLE IF NOT EXISTS karma_goal \
+            (id INTEGER PRIMARY KEY NOT NULL UNIQUE, \
+            subject_kind VARCHAR(20) NOT NULL, \
+            subject_id INTEGER NOT NULL, \
+            metric VARCHAR(20) NOT NULL, \
+            comparison VARCHAR(20) NOT NULL, \
+            target REAL NOT NULL, \
+            period VARCHAR(20) NOT NULL, \
+            created_at INTEGER NOT NULL);",
+        ],
+    },
+    Migration {
+        description: "Karma points can be habits to avoid",
+        statements: &["ALTER TABLE karma ADD COLUMN polarity INTEGER NOT NULL DEFAULT 1;"],
+    },
+    Migration {
+        description: "Rewards to spend points on and their redemptions",
+        statements: &[
+            "CREATE TABLE IF NOT EXISTS karma_reward \
+            (id INTEGER PRIMARY KEY NOT NULL UNIQUE, \
+            name TEXT NOT NULL UNIQUE COLLATE NOCASE, \
+            cost REAL NOT NULL, \
+            limit_times INTEGER, \
+            limit_period VARCHAR(20), \
+            created_at INTEGER NOT NULL);",
+            "CREATE TABLE IF NOT EXISTS reward_redemption \
+            (id INTEGER PRIMARY KEY NOT NULL UNIQUE, \
+            reward_id INTEGER NOT NULL, \
+            name TEXT NOT NULL, \
+            cost REAL NOT NULL, \
+            redeemed_at INTEGER NOT NULL, \
+            redeemed_on TEXT NOT NULL);",
+        ],
+    },
+    Migration {
+        description: "Unlocked achievements",
+        statements: &["CREATE TABLE IF NOT EXISTS achievement_unlock \
+            (id TEXT PRIMARY KEY NOT NULL, \
+            unlocked_at INTEGER NOT NULL);"],
+    },
+    Migration {
+        description: "Daily plans of karma points",
+        statements: &[
+            "CREATE TABLE IF NOT EXISTS plan_entry \
+            (id INTEGER PRIMARY KEY NOT NULL UNIQUE, \
+            day TEXT NOT NULL, \
+            position INTEGER NOT NULL, \
+            template_id INTEGER NOT NULL, \
+            purpose INTEGER NOT NULL, \
+            duration INTEGER NOT NULL, \
+            starts_at INTEGER, \
+            session_id INTEGER, \
+            carried_from TEXT, \
+            settled INTEGER NOT NULL DEFAULT 0, \
+            FOREIGN KEY(template_id) REFERENCES karma(id), \
+            FOREIGN KEY(session_id) REFERENCES karma_session(id));",
+            "CREATE INDEX IF NOT EXISTS plan_entry_day_idx ON plan_entry(day, position);",
+        ],
+    },
+    Migration {
+        description: "Weekly time blocked from schedule suggestions",
+        statements: &["CREATE TABLE IF NOT EXISTS blocked_time \
+            (id INTEGER PRIMARY KEY NOT NULL UNIQUE, \
+            label TEXT NOT NULL, \
+            weekdays TEXT NOT NULL DEFAULT '', \
+            starts_at INTEGER NOT NULL, \
+            ends_at INTEGER NOT NULL);"],
+    },
+    Migration {
+        description: "Pomodoro focus sessions and their interruptions",
+        statements: &[
+            "CREATE TABLE IF NOT EXISTS focus_session \
+            (id INTEGER PRIMARY KEY NOT NULL UNIQUE, \
+            session_id INTEGER NOT NULL UNIQUE, \
+            started_at INTEGER NOT NULL, \
+            work_minutes INTEGER NOT NULL, \
+            short_break_minutes INTEGER NOT NULL, \
+            long_break_minutes INTEGER NOT NULL, \
+            long_break_every INTEGER NOT NULL, \
+            ended_at INTEGER, \
+            rating INTEGER, \
+            FOREIGN KEY(session_id) REFERENCES karma_session(id));",
+            "CREATE TABLE IF NOT EXISTS focus_interruption \
+            (id INTEGER PRIMARY KEY NOT NULL UNIQUE, \
+            focus_id INTEGER NOT NULL, \
+            at INTEGER NOT NULL, \
+            note TEXT NOT NULL DEFAULT '', \
+            external INTEGER NOT NULL DEFAULT 0, \
+            FOREIGN KEY(focus_id) REFERENCES focus_session(id));",
+        ],
+    },
+    Migration {
+        description: "Pomodoros after which a focus session ends by itself",
+        statements: &["ALTER TABLE focus_session \
+            ADD COLUMN max_pomodoros INTEGER NOT NULL DEFAULT 8;"],
+    },
+];
+
+pub async fn run_migrations(pool: &SqlitePool) -> Result<(), SqlxError> {
+    let mut connection = pool.acquire().await?;
+
+    // Take the write lock before reading the version, so two pools opened on
+    // the same file can't both apply the same migration
+    sqlx::query("BEGIN IMMEDIATE;")
+        .execute(&mut *connection)
+        .await?;
+
+    let result = apply_pending(&mut connection).await;
+    let end = if result.is_ok() {
+        "COMMIT;"
+    } else {
+        "ROLLBACK;"
+    };
+    sqlx::query(end).execute(&mut *connection).await?;
+
+    result
+}
+
+async fn apply_pending(connection: &mut SqliteConnection) -> Result<(), SqlxError> {
+    let (current_version,): (i64,) = sqlx::query_as("PRAGMA user_version;")
+        .fetch_one(&mut *connection)
+        .await?;
+
+    for (index, migration) in MIGRATIONS
+        .iter()
+        .enumerate()
+        .skip(current_version.max(0) as usize)
+    {
+        let version = index + 1;
+        info!("Applying migration {version}: {}", migration.description);
+
+        for statement in migration.statements {
+            sqlx::query(statement).execute(&mut *connection).await?;
+        }
+
+        // PRAGMA doesn't support bound parameters
+        sqlx::query(&format!("PRAGMA user_version = {version};"))
+            .execute(&mut *connection)
+            .await?;
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod migrations_tests {
+    use sqlx::{migrate::MigrateDatabase, Sqlite};
+
+    use super::*;
+
+    #[tokio::test]
+    async fn test_points_with_statuses_become_sessions() {
+        let db_url = "test_migrations.sqlite";
+        let _ = std::fs::remove_file(db_url);
+        Sqlite::create_database(db_url).await.unwrap();
+        let pool = SqlitePool::connect(db_url).await.unwrap();
+
+        // The schema as left by the third migration, without the search index
+        for statement in [
+            "CREATE TABLE karma (id INTEGER PRIMARY KEY NOT NULL UNIQUE, \
+            purpose INTEGER NOT NULL, name VARCHAR(50) NOT NULL UNIQUE, \
+            created_at INTEGER NOT NULL DEFAULT 0);",
+            "CREATE TABLE karma_status (id INTEGER PRIMARY KEY NOT NULL UNIQUE, \
+            karma_id INTEGER NOT NULL, closed_with INTEGER, \
+            current_state VARCHAR(50) NOT NULL, timestamp INTEGER NOT NULL, \
+            FOREIGN KEY(karma_id) REFERENCES karma(id));",
+            "INSERT INTO karma(id, purpose, name, created_at) VALUES \
+            (1, 3, 'Morning run', 100), (2, 4, 'Never started', 200);",
+            "INSERT INTO karma_status(karma_id, closed_with, current_state, timestamp) VALUES \
+            (1, NULL, 'active', 150), (1, 2, 'closed', 250);",
+            "PRAGMA user_version = 3;",
+        ] {
+            sqlx::query(statement).execute(&pool).await.unwrap();
+        }
+
+        run_migrations(&pool).await.unwrap();
+
+        let sessions: Vec<(i32, i32, i32, i64)> =
+            sqlx::query_as("SELECT id, template_id, purpose, started_at FROM karma_session;")
+                .fetch_all(&pool)
+                .await
+                .unwrap();
+        assert_eq!(sessions, vec![(1, 1, 3, 150)]);
+
+        let statuses: Vec<(i32, Option<i32>, String)> = sqlx::query_as(
+            "SELECT session_id, closed_with, current_state FROM karma_status ORDER BY id;",
+        )
+        .fetch_all(&pool)
+        .await
+        .unwrap();
+        assert_eq!(
+            statuses,
+            vec![
+                (1, None, "active".to_string()),
+                (1, Some(2), "closed".to_string())
+            ]
+        );
+
+        // Names are still unique per template, a second run only creates a session
+        sqlx::query(
+            "INSERT INTO karma_session(template_id, purpose, started_at) VALUES (1, 3, 300);",
+        )
+        .execute(&pool)
+        .await
+        .unwrap();
+    }
+}
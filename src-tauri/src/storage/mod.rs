@@ -1,12 +1,69 @@
+pub mod achievement_repository;
+pub mod blocked_time_repository;
 pub mod db;
+pub mod focus_repository;
+pub mod goal_repository;
 pub mod karma_repository;
+pub mod migrations;
+pub mod plan_repository;
+pub mod profile_registry;
+pub mod reward_repository;
+pub mod schedule_repository;
+pub mod settings_repository;
+pub mod streak_repository;
+pub mod two_factor_repository;
 pub mod user_repository;
 
+use achievement_repository::AchievementRepository;
+use blocked_time_repository::BlockedTimeRepository;
+use focus_repository::FocusRepository;
+use goal_repository::GoalRepository;
+use karma_repository::KarmaRepository;
+use plan_repository::PlanRepository;
+use reward_repository::RewardRepository;
+use schedule_repository::ScheduleRepository;
+use settings_repository::SettingsRepository;
+use streak_repository::StreakRepository;
+
+/// Every repository the karma service works with, implemented for whatever
+/// provides all of them
+pub trait KarmaStorage:
+    KarmaRepository
+    + ScheduleRepository
+    + SettingsRepository
+    + StreakRepository
+    + GoalRepository
+    + RewardRepository
+    + AchievementRepository
+    + PlanRepository
+    + BlockedTimeRepository
+    + FocusRepository
+{
+}
+
+impl<T> KarmaStorage for T where
+    T: KarmaRepository
+        + ScheduleRepository
+        + SettingsRepository
+        + StreakRepository
+        + GoalRepository
+        + RewardRepository
+        + AchievementRepository
+        + PlanRepository
+        + BlockedTimeRepository
+        + FocusRepository
+{
+}
+
 #[cfg(test)]
 pub mod common_utilities_tests {
+    use std::ops::Deref;
     use std::sync::Arc;
 
+    use crate::service::clock::FixedClock;
+    use crate::service::karma::karma_service::KarmaService;
     use crate::storage::db::DbManager;
+    use chrono::NaiveDateTime;
     use lazy_static::lazy_static;
     use std::path::Path;
     use tokio::sync::Mutex;
@@ -56,4 +113,72 @@ pub mod common_utilities_tests {
             println!("The db was not none");
         }
     }
+
+    /// A karma service on a database file of its own, the file is removed
+    /// again when the service is dropped
+    pub struct TestKarmaService {
+        service: Option<KarmaService<DbManager>>,
+        db_url: String,
+    }
+
+    impl TestKarmaService {
+        /// For the builder methods, they take the service by value
+        pub fn map(
+            mut self,
+            configure: impl FnOnce(KarmaService<DbManager>) -> KarmaService<DbManager>,
+        ) -> TestKarmaService {
+            self.service = self.service.take().map(configure);
+            self
+        }
+    }
+
+    impl Deref for TestKarmaService {
+        type Target = KarmaService<DbManager>;
+
+        fn deref(&self) -> &Self::Target {
+            self.service.as_ref().expect("the test service is set")
+        }
+    }
+
+    impl Drop for TestKarmaService {
+        fn drop(&mut self) {
+            self.service = None;
+            remove_db_files(&self.db_url);
+        }
+    }
+
+    /// Starts `test_karma_{name}.sqlite` afresh, with the clock fixed at `now`,
+    /// a `YYYY-MM-DD HH:MM` time in UTC, and days counted in UTC
+    pub async fn karma_service_at(name: &str, now: &str) -> (TestKarmaService, Arc<FixedClock>) {
+        let db_url = format!("test_karma_{name}.sqlite");
+        remove_db_files(&db_url);
+
+        let now = NaiveDateTime::parse_from_str(now, "%Y-%m-%d %H:%M")
+            .expect("Invalid test time")
+            .and_utc()
+            .timestamp();
+        let clock = Arc::new(FixedClock::new(now));
+        let service = KarmaService::new(
+            DbManager::new(&db_url)
+                .await
+                .expect("Failed to create the test db manager"),
+        )
+        .with_clock(clock.clone());
+        service
+            .set_time_settings(Some("UTC"), None)
+            .await
+            .expect("Failed to set the test timezone");
+
+        let service = TestKarmaService {
+            service: Some(service),
+            db_url,
+        };
+        (service, clock)
+    }
+
+    fn remove_db_files(db_url: &str) {
+        for suffix in ["", "-shm", "-wal"] {
+            let _ = std::fs::remove_file(format!("{db_url}{suffix}"));
+        }
+    }
 }
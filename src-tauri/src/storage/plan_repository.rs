@@ -0,0 +1,186 @@
+use async_trait::async_trait;
+use chrono::NaiveDate;
+use sqlx::{sqlite::SqliteRow, Error as SqlxError, FromRow, Row, Sqlite, Transaction};
+
+use crate::model::karma::KarmaType;
+use crate::model::plan::PlanEntry;
+use crate::storage::db::{DbManager, DbManagerError};
+use crate::storage::schedule_repository::{decode_date, encode_date};
+
+impl<'r> FromRow<'r, SqliteRow> for PlanEntry {
+    fn from_row(row: &'r SqliteRow) -> Result<Self, SqlxError> {
+        let day: String = row.try_get("day")?;
+        let purpose: i32 = row.try_get("purpose")?;
+        let carried_from: Option<String> = row.try_get("carried_from")?;
+
+        Ok(PlanEntry {
+            id: row.try_get("id")?,
+            day: decode_date(&day)?,
+            position: row.try_get("position")?,
+            template_id: row.try_get("template_id")?,
+            name: row.try_get("name")?,
+            purpose: KarmaType::try_from(purpose)
+                .map_err(|e| SqlxError::Decode(e.to_string().into()))?,
+            duration: row.try_get("duration")?,
+            starts_at: row.try_get("starts_at")?,
+            session_id: row.try_get("session_id")?,
+            carried_from: carried_from.as_deref().map(decode_date).transpose()?,
+            settled: row.try_get("settled")?,
+        })
+    }
+}
+
+/// The karma points planned per day
+#[async_trait]
+pub trait PlanRepository {
+    /// Appends the entry to the plan of its day
+    async fn insert_plan_entry(&self, entry: PlanEntry) -> Result<PlanEntry, DbManagerError>;
+    /// In plan order
+    async fn get_plan(&self, day: NaiveDate) -> Result<Vec<PlanEntry>, DbManagerError>;
+    /// Every day's plan, by day and plan order
+    async fn get_all_plan_entries(&self) -> Result<Vec<PlanEntry>, DbManagerError>;
+    /// Returns whether there was such an entry
+    async fn delete_plan_entry(&self, id: i32) -> Result<bool, DbManagerError>;
+    /// Days before `before` with entries not settled yet, oldest first
+    async fn get_unsettled_plan_days(
+        &self,
+        before: NaiveDate,
+    ) -> Result<Vec<NaiveDate>, DbManagerError>;
+    /// Settles the plan of `day`: links entries to the sessions that carried them out
+    /// and appends the unfinished ones carried over to their new day. Does nothing
+    /// and returns false when the day was settled meanwhile.
+    async fn settle_plan(
+        &self,
+        day: NaiveDate,
+        links: &[(i32, i32)],
+        carried: &[PlanEntry],
+    ) -> Result<bool, DbManagerError>;
+}
+
+async fn append_plan_entry(
+    transaction: &mut Transaction<'_, Sqlite>,
+    entry: &PlanEntry,
+) -> Result<PlanEntry, DbManagerError> {
+    let (position,): (u32,) =
+        sqlx::query_as("SELECT COALESCE(MAX(position), 0) + 1 FROM plan_entry WHERE day = ?;")
+            .bind(encode_date(entry.day))
+            .fetch_one(&mut **transaction)
+            .await?;
+
+    let id = sqlx::query(
+        "INSERT INTO plan_entry \
+        (day, position, template_id, purpose, duration, starts_at, carried_from) \
+        VALUES (?, ?, ?, ?, ?, ?, ?);",
+    )
+    .bind(encode_date(entry.day))
+    .bind(position)
+    .bind(entry.template_id)
+    .bind(entry.purpose.clone() as i32)
+    .bind(entry.duration)
+    .bind(entry.starts_at)
+    .bind(entry.carried_from.map(encode_date))
+    .execute(&mut **transaction)
+    .await?
+    .last_insert_rowid() as i32;
+
+    Ok(PlanEntry {
+        id: Some(id),
+        position,
+        ..entry.clone()
+    })
+}
+
+#[async_trait]
+impl PlanRepository for DbManager {
+    async fn insert_plan_entry(&self, entry: PlanEntry) -> Result<PlanEntry, DbManagerError> {
+        let mut transaction = self.connection_pool.begin().await?;
+        let entry = append_plan_entry(&mut transaction, &entry).await?;
+        transaction.commit().await?;
+
+        Ok(entry)
+    }
+
+    async fn get_plan(&self, day: NaiveDate) -> Result<Vec<PlanEntry>, DbManagerError> {
+        let entries = sqlx::query_as::<_, PlanEntry>(
+            "SELECT p.*, k.name FROM plan_entry p JOIN karma k ON k.id = p.template_id \
+            WHERE p.day = ? ORDER BY p.position;",
+        )
+        .bind(encode_date(day))
+        .fetch_all(&self.connection_pool)
+        .await?;
+
+        Ok(entries)
+    }
+
+    async fn get_all_plan_entries(&self) -> Result<Vec<PlanEntry>, DbManagerError> {
+        let entries = sqlx::query_as::<_, PlanEntry>(
+            "SELECT p.*, k.name FROM plan_entry p JOIN karma k ON k.id = p.template_id \
+            ORDER BY p.day, p.position;",
+        )
+        .fetch_all(&self.connection_pool)
+        .await?;
+
+        Ok(entries)
+    }
+
+    async fn delete_plan_entry(&self, id: i32) -> Result<bool, DbManagerError> {
+        let deleted = sqlx::query("DELETE FROM plan_entry WHERE id = ?;")
+            .bind(id)
+            .execute(&self.connection_pool)
+            .await?
+            .rows_affected();
+
+        Ok(deleted > 0)
+    }
+
+    async fn get_unsettled_plan_days(
+        &self,
+        before: NaiveDate,
+    ) -> Result<Vec<NaiveDate>, DbManagerError> {
+        let days: Vec<(String,)> = sqlx::query_as(
+            "SELECT DISTINCT day FROM plan_entry WHERE settled = 0 AND day < ? ORDER BY day;",
+        )
+        .bind(encode_date(before))
+        .fetch_all(&self.connection_pool)
+        .await?;
+
+        Ok(days
+            .iter()
+            .map(|(day,)| decode_date(day))
+            .collect::<Result<_, _>>()?)
+    }
+
+    async fn settle_plan(
+        &self,
+        day: NaiveDate,
+        links: &[(i32, i32)],
+        carried: &[PlanEntry],
+    ) -> Result<bool, DbManagerError> {
+        let mut transaction = self.connection_pool.begin().await?;
+
+        // Claims the day first, a concurrent run carries nothing twice
+        let settled =
+            sqlx::query("UPDATE plan_entry SET settled = 1 WHERE day = ? AND settled = 0;")
+                .bind(encode_date(day))
+                .execute(&mut *transaction)
+                .await?
+                .rows_affected();
+        if settled == 0 {
+            return Ok(false);
+        }
+
+        for (entry_id, session_id) in links {
+            sqlx::query("UPDATE plan_entry SET session_id = ? WHERE id = ?;")
+                .bind(session_id)
+                .bind(entry_id)
+                .execute(&mut *transaction)
+                .await?;
+        }
+        for entry in carried {
+            append_plan_entry(&mut transaction, entry).await?;
+        }
+
+        transaction.commit().await?;
+        Ok(true)
+    }
+}
@@ -0,0 +1,150 @@
+use std::path::{Path, PathBuf};
+
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+use crate::model::profile::Profile;
+
+pub const PROFILES_FILE: &str = "profiles.json";
+
+#[derive(Debug, Error)]
+pub enum ProfileRegistryError {
+    #[error("Failed to access the profiles file: {0}")]
+    Io(#[from] std::io::Error),
+
+    #[error("The profiles file is corrupted: {0}")]
+    Format(#[from] serde_json::Error),
+
+    #[error("Profile {0} already exists")]
+    AlreadyExists(String),
+
+    #[error("Profile {0} does not exist")]
+    NotFound(String),
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+struct RegistryContent {
+    profiles: Vec<Profile>,
+    last_used: Option<String>,
+}
+
+/// The known profiles and the one used last, kept as json next to the databases.
+/// The default profile always exists so installs from before profiles keep their data.
+#[derive(Debug)]
+pub struct ProfileRegistry {
+    path: PathBuf,
+    content: RegistryContent,
+}
+
+impl ProfileRegistry {
+    pub async fn load(path: &Path) -> Result<ProfileRegistry, ProfileRegistryError> {
+        let mut content = match tokio::fs::read_to_string(path).await {
+            Ok(json) => serde_json::from_str::<RegistryContent>(&json)?,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => RegistryContent::default(),
+            Err(e) => return Err(e.into()),
+        };
+
+        let default_profile = Profile::default();
+        if !content
+            .profiles
+            .iter()
+            .any(|profile| profile.name == default_profile.name)
+        {
+            content.profiles.insert(0, default_profile);
+        }
+
+        Ok(ProfileRegistry {
+            path: path.to_path_buf(),
+            content,
+        })
+    }
+
+    pub fn profiles(&self) -> &[Profile] {
+        &self.content.profiles
+    }
+
+    pub fn get(&self, name: &str) -> Option<&Profile> {
+        self.content
+            .profiles
+            .iter()
+            .find(|profile| profile.name == name)
+    }
+
+    /// The profile picked last time, falling back to the default one
+    pub fn last_used(&self) -> Profile {
+        self.content
+            .last_used
+            .as_deref()
+            .and_then(|name| self.get(name))
+            .cloned()
+            .unwrap_or_default()
+    }
+
+    pub async fn add(&mut self, profile: Profile) -> Result<(), ProfileRegistryError> {
+        if self
+            .content
+            .profiles
+            .iter()
+            .any(|existing| existing.name == profile.name || existing.db_file == profile.db_file)
+        {
+            return Err(ProfileRegistryError::AlreadyExists(profile.name));
+        }
+
+        self.content.profiles.push(profile);
+        self.save().await
+    }
+
+    pub async fn set_last_used(&mut self, name: &str) -> Result<Profile, ProfileRegistryError> {
+        let profile = self
+            .get(name)
+            .cloned()
+            .ok_or_else(|| ProfileRegistryError::NotFound(name.to_string()))?;
+
+        self.content.last_used = Some(profile.name.clone());
+        self.save().await?;
+        Ok(profile)
+    }
+
+    async fn save(&self) -> Result<(), ProfileRegistryError> {
+        let json = serde_json::to_string_pretty(&self.content)?;
+
+        // Write then rename so a crash never leaves a half written registry behind
+        let temporary_path = self.path.with_extension("json.tmp");
+        tokio::fs::write(&temporary_path, json).await?;
+        tokio::fs::rename(&temporary_path, &self.path).await?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod profile_registry_tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_registry_round_trip() {
+        let path = Path::new("test_profiles.json");
+        let _ = std::fs::remove_file(path);
+
+        let mut registry = ProfileRegistry::load(path).await.unwrap();
+        assert_eq!(registry.profiles(), &[Profile::default()]);
+        assert_eq!(registry.last_used(), Profile::default());
+
+        let work = Profile::new("work").unwrap();
+        registry.add(work.clone()).await.unwrap();
+        assert!(matches!(
+            registry.add(work.clone()).await,
+            Err(ProfileRegistryError::AlreadyExists(_))
+        ));
+        registry.set_last_used("work").await.unwrap();
+        assert!(matches!(
+            registry.set_last_used("personal").await,
+            Err(ProfileRegistryError::NotFound(_))
+        ));
+
+        let reloaded = ProfileRegistry::load(path).await.unwrap();
+        assert_eq!(reloaded.profiles(), &[Profile::default(), work.clone()]);
+        assert_eq!(reloaded.last_used(), work);
+
+        let _ = std::fs::remove_file(path);
+    }
+}
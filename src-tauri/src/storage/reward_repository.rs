@@ -0,0 +1,151 @@
+use async_trait::async_trait;
+use sqlx::{sqlite::SqliteRow, Error as SqlxError, FromRow, Row};
+
+use crate::model::report::ReportPeriod;
+use crate::model::reward::{Redemption, Reward, RewardLimit};
+use crate::storage::db::{DbManager, DbManagerError};
+use crate::storage::schedule_repository::{decode_date, encode_date};
+
+impl<'r> FromRow<'r, SqliteRow> for Reward {
+    fn from_row(row: &'r SqliteRow) -> Result<Self, SqlxError> {
+        let limit_times: Option<u32> = row.try_get("limit_times")?;
+        let limit_period: Option<String> = row.try_get("limit_period")?;
+        let limit = match (limit_times, limit_period) {
+            (Some(times), Some(period)) => Some(RewardLimit {
+                times,
+                period: ReportPeriod::try_from(period.as_str())
+                    .map_err(|e| SqlxError::Decode(e.to_string().into()))?,
+            }),
+            _ => None,
+        };
+
+        Ok(Reward {
+            id: row.try_get("id")?,
+            name: row.try_get("name")?,
+            cost: row.try_get("cost")?,
+            limit,
+            created_at: row.try_get("created_at")?,
+        })
+    }
+}
+
+impl<'r> FromRow<'r, SqliteRow> for Redemption {
+    fn from_row(row: &'r SqliteRow) -> Result<Self, SqlxError> {
+        let redeemed_on: String = row.try_get("redeemed_on")?;
+
+        Ok(Redemption {
+            id: row.try_get("id")?,
+            reward_id: row.try_get("reward_id")?,
+            name: row.try_get("name")?,
+            cost: row.try_get("cost")?,
+            redeemed_at: row.try_get("redeemed_at")?,
+            redeemed_on: decode_date(&redeemed_on)?,
+        })
+    }
+}
+
+/// Rewards on offer and what was spent on them
+#[async_trait]
+pub trait RewardRepository {
+    /// Returns none when a reward already has that name, ignoring case
+    async fn insert_reward(&self, reward: Reward) -> Result<Option<Reward>, DbManagerError>;
+    /// Cheapest first
+    async fn get_rewards(&self) -> Result<Vec<Reward>, DbManagerError>;
+    /// Returns whether there was such a reward, its redemptions are kept
+    async fn delete_reward(&self, id: i32) -> Result<bool, DbManagerError>;
+    /// Redeemed at or after `since`, oldest first
+    async fn get_redemptions(&self, since: i64) -> Result<Vec<Redemption>, DbManagerError>;
+    /// Inserts the redemption only if the latest one is still `last_seen`, so a
+    /// redemption checked against a balance can't race another one. Returns none
+    /// when another redemption came first.
+    async fn insert_redemption(
+        &self,
+        redemption: Redemption,
+        last_seen: Option<i32>,
+    ) -> Result<Option<Redemption>, DbManagerError>;
+}
+
+#[async_trait]
+impl RewardRepository for DbManager {
+    async fn insert_reward(&self, reward: Reward) -> Result<Option<Reward>, DbManagerError> {
+        let inserted = sqlx::query(
+            "INSERT OR IGNORE INTO karma_reward \
+            (name, cost, limit_times, limit_period, created_at) VALUES (?, ?, ?, ?, ?);",
+        )
+        .bind(&reward.name)
+        .bind(reward.cost)
+        .bind(reward.limit.map(|limit| limit.times))
+        .bind(reward.limit.map(|limit| limit.period.noun()))
+        .bind(reward.created_at)
+        .execute(&self.connection_pool)
+        .await?;
+
+        if inserted.rows_affected() == 0 {
+            return Ok(None);
+        }
+        Ok(Some(Reward {
+            id: Some(inserted.last_insert_rowid() as i32),
+            ..reward
+        }))
+    }
+
+    async fn get_rewards(&self) -> Result<Vec<Reward>, DbManagerError> {
+        let rewards =
+            sqlx::query_as::<_, Reward>("SELECT * FROM karma_reward ORDER BY cost, name;")
+                .fetch_all(&self.connection_pool)
+                .await?;
+
+        Ok(rewards)
+    }
+
+    async fn delete_reward(&self, id: i32) -> Result<bool, DbManagerError> {
+        let deleted = sqlx::query("DELETE FROM karma_reward WHERE id = ?;")
+            .bind(id)
+            .execute(&self.connection_pool)
+            .await?
+            .rows_affected();
+
+        Ok(deleted > 0)
+    }
+
+    async fn get_redemptions(&self, since: i64) -> Result<Vec<Redemption>, DbManagerError> {
+        let redemptions = sqlx::query_as::<_, Redemption>(
+            "SELECT * FROM reward_redemption WHERE redeemed_at >= ? ORDER BY redeemed_at, id;",
+        )
+        .bind(since)
+        .fetch_all(&self.connection_pool)
+        .await?;
+
+        Ok(redemptions)
+    }
+
+    async fn insert_redemption(
+        &self,
+        redemption: Redemption,
+        last_seen: Option<i32>,
+    ) -> Result<Option<Redemption>, DbManagerError> {
+        // A single statement, the check and the insert can't be interleaved
+        let inserted = sqlx::query(
+            "INSERT INTO reward_redemption \
+            (reward_id, name, cost, redeemed_at, redeemed_on) \
+            SELECT ?, ?, ?, ?, ? \
+            WHERE (SELECT MAX(id) FROM reward_redemption) IS ?;",
+        )
+        .bind(redemption.reward_id)
+        .bind(&redemption.name)
+        .bind(redemption.cost)
+        .bind(redemption.redeemed_at)
+        .bind(encode_date(redemption.redeemed_on))
+        .bind(last_seen)
+        .execute(&self.connection_pool)
+        .await?;
+
+        if inserted.rows_affected() == 0 {
+            return Ok(None);
+        }
+        Ok(Some(Redemption {
+            id: Some(inserted.last_insert_rowid() as i32),
+            ..redemption
+        }))
+    }
+}
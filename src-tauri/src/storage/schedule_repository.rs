@@ -0,0 +1,383 @@
+use std::collections::BTreeSet;
+
+use async_trait::async_trait;
+use chrono::NaiveDate;
+use sqlx::{
+    sqlite::SqliteRow, Error as SqlxError, FromRow, QueryBuilder, Row, Sqlite, SqliteConnection,
+};
+
+use crate::model::recurrence::{Recurrence, RecurrenceRule};
+use crate::model::schedule::{KarmaOccurrence, KarmaSchedule, OccurrenceStatus};
+use crate::storage::db::{DbManager, DbManagerError};
+
+const DATE_FORMAT: &str = "%Y-%m-%d";
+
+pub(crate) fn decode_date(value: &str) -> Result<NaiveDate, SqlxError> {
+    NaiveDate::parse_from_str(value, DATE_FORMAT).map_err(|e| SqlxError::Decode(Box::new(e)))
+}
+
+pub(crate) fn encode_date(date: NaiveDate) -> String {
+    date.format(DATE_FORMAT).to_string()
+}
+
+impl<'r> FromRow<'r, SqliteRow> for KarmaSchedule {
+    fn from_row(row: &'r SqliteRow) -> Result<Self, SqlxError> {
+        let rrule: String = row.try_get("rrule")?;
+        let rule = RecurrenceRule::parse(&rrule).map_err(|e| SqlxError::Decode(Box::new(e)))?;
+        let starts_on: String = row.try_get("starts_on")?;
+        let start_time: Option<u32> = row.try_get("start_time")?;
+        let exceptions: String = row.try_get("exceptions")?;
+        let planned_until: Option<String> = row.try_get("planned_until")?;
+
+        Ok(KarmaSchedule {
+            template_id: row.try_get("template_id")?,
+            recurrence: Recurrence {
+                rule,
+                starts_on: decode_date(&starts_on)?,
+                start_time,
+                exceptions: exceptions
+                    .split(',')
+                    .filter(|date| !date.is_empty())
+                    .map(decode_date)
+                    .collect::<Result<BTreeSet<_>, _>>()?,
+            },
+            planned_until: planned_until.as_deref().map(decode_date).transpose()?,
+        })
+    }
+}
+
+impl<'r> FromRow<'r, SqliteRow> for KarmaOccurrence {
+    fn from_row(row: &'r SqliteRow) -> Result<Self, SqlxError> {
+        let due_on: String = row.try_get("due_on")?;
+        let status: String = row.try_get("status")?;
+
+        Ok(KarmaOccurrence {
+            id: row.try_get("id")?,
+            template_id: row.try_get("template_id")?,
+            name: row.try_get("name")?,
+            due_on: decode_date(&due_on)?,
+            due_at: row.try_get("due_at")?,
+            day_start: row.try_get("day_start")?,
+            day_end: row.try_get("day_end")?,
+            status: OccurrenceStatus::try_from(status.as_str())
+                .map_err(|status| SqlxError::Decode(format!("unknown status {status}").into()))?,
+            session_id: row.try_get("session_id")?,
+        })
+    }
+}
+
+#[async_trait]
+pub trait ScheduleRepository {
+    /// Creates or replaces the schedule of a template. Planned occurrences from
+    /// `replan_from` on are dropped so they get planned again with the new rule.
+    async fn set_schedule(
+        &self,
+        schedule: &KarmaSchedule,
+        replan_from: NaiveDate,
+    ) -> Result<(), DbManagerError>;
+    async fn get_schedule(&self, template_id: i32)
+        -> Result<Option<KarmaSchedule>, DbManagerError>;
+    async fn get_schedules(&self) -> Result<Vec<KarmaSchedule>, DbManagerError>;
+    /// Removes the schedule and its occurrences still planned from `from` on,
+    /// returns whether there was a schedule
+    async fn delete_schedule(
+        &self,
+        template_id: i32,
+        from: NaiveDate,
+    ) -> Result<bool, DbManagerError>;
+    /// Stores the occurrences not planned yet and moves the planning horizon of the
+    /// schedule, returns how many were new
+    async fn insert_planned_occurrences(
+        &self,
+        template_id: i32,
+        occurrences: &[KarmaOccurrence],
+        planned_until: NaiveDate,
+    ) -> Result<u64, DbManagerError>;
+    /// Marks the occurrences up to `today` that have a session on their day as done and
+    /// the older ones without any as missed. Returns how many were done and missed.
+    async fn settle_occurrences(
+        &self,
+        today: NaiveDate,
+        now: i64,
+    ) -> Result<(u64, u64), DbManagerError>;
+    async fn get_occurrence(&self, id: i32) -> Result<Option<KarmaOccurrence>, DbManagerError>;
+    /// Occurrences due between both dates inclusive, earliest first
+    async fn get_occurrences(
+        &self,
+        from: NaiveDate,
+        to: NaiveDate,
+        status: Option<OccurrenceStatus>,
+    ) -> Result<Vec<KarmaOccurrence>, DbManagerError>;
+    async fn update_occurrence_status(
+        &self,
+        id: i32,
+        status: OccurrenceStatus,
+        session_id: Option<i32>,
+        now: i64,
+    ) -> Result<(), DbManagerError>;
+    async fn get_all_occurrences(&self) -> Result<Vec<KarmaOccurrence>, DbManagerError>;
+}
+
+// Sessions started during the local day an occurrence is due on
+const SESSION_ON_DUE_DAY: &str = "SELECT ks.id FROM karma_session ks \
+    WHERE ks.template_id = karma_occurrence.template_id \
+    AND ks.started_at >= karma_occurrence.day_start \
+    AND ks.started_at < karma_occurrence.day_end \
+    ORDER BY ks.started_at LIMIT 1";
+
+const OCCURRENCE_COLUMNS: &str = "SELECT o.id, o.template_id, k.name, o.due_on, o.due_at, \
+    o.day_start, o.day_end, o.status, o.session_id \
+    FROM karma_occurrence o JOIN karma k ON k.id = o.template_id";
+
+/// Links a session to the occurrence of its template due that day, if any, on a
+/// connection that may be inside a transaction
+pub(super) async fn complete_occurrence_on(
+    connection: &mut SqliteConnection,
+    template_id: i32,
+    due_on: NaiveDate,
+    session_id: i32,
+    now: i64,
+) -> Result<bool, SqlxError> {
+    let updated = sqlx::query(
+        "UPDATE karma_occurrence SET status = 'done', session_id = ?, resolved_at = ? \
+        WHERE template_id = ? AND due_on = ? AND status IN ('planned', 'missed');",
+    )
+    .bind(session_id)
+    .bind(now)
+    .bind(template_id)
+    .bind(encode_date(due_on))
+    .execute(connection)
+    .await?
+    .rows_affected();
+
+    Ok(updated > 0)
+}
+
+#[async_trait]
+impl ScheduleRepository for DbManager {
+    async fn set_schedule(
+        &self,
+        schedule: &KarmaSchedule,
+        replan_from: NaiveDate,
+    ) -> Result<(), DbManagerError> {
+        let recurrence = &schedule.recurrence;
+        let exceptions: Vec<String> = recurrence
+            .exceptions
+            .iter()
+            .map(|date| encode_date(*date))
+            .collect();
+
+        let mut transaction = self.connection_pool.begin().await?;
+
+        sqlx::query(
+            "INSERT OR REPLACE INTO karma_schedule \
+            (template_id, rrule, starts_on, start_time, exceptions, planned_until) \
+            VALUES (?, ?, ?, ?, ?, ?);",
+        )
+        .bind(schedule.template_id)
+        .bind(recurrence.rule.to_string())
+        .bind(encode_date(recurrence.starts_on))
+        .bind(recurrence.start_time)
+        .bind(exceptions.join(","))
+        .bind(replan_from.pred_opt().map(encode_date))
+        .execute(&mut *transaction)
+        .await?;
+
+        sqlx::query(
+            "DELETE FROM karma_occurrence \
+            WHERE template_id = ? AND status = 'planned' AND due_on >= ?;",
+        )
+        .bind(schedule.template_id)
+        .bind(encode_date(replan_from))
+        .execute(&mut *transaction)
+        .await?;
+
+        transaction.commit().await?;
+        Ok(())
+    }
+
+    async fn get_schedule(
+        &self,
+        template_id: i32,
+    ) -> Result<Option<KarmaSchedule>, DbManagerError> {
+        let schedule = sqlx::query_as::<_, KarmaSchedule>(
+            "SELECT * FROM karma_schedule WHERE template_id = ?;",
+        )
+        .bind(template_id)
+        .fetch_optional(&self.connection_pool)
+        .await?;
+
+        Ok(schedule)
+    }
+
+    async fn get_schedules(&self) -> Result<Vec<KarmaSchedule>, DbManagerError> {
+        let schedules = sqlx::query_as::<_, KarmaSchedule>(
+            "SELECT * FROM karma_schedule ORDER BY template_id;",
+        )
+        .fetch_all(&self.connection_pool)
+        .await?;
+
+        Ok(schedules)
+    }
+
+    async fn delete_schedule(
+        &self,
+        template_id: i32,
+        from: NaiveDate,
+    ) -> Result<bool, DbManagerError> {
+        let mut transaction = self.connection_pool.begin().await?;
+
+        sqlx::query(
+            "DELETE FROM karma_occurrence \
+            WHERE template_id = ? AND status = 'planned' AND due_on >= ?;",
+        )
+        .bind(template_id)
+        .bind(encode_date(from))
+        .execute(&mut *transaction)
+        .await?;
+
+        let deleted = sqlx::query("DELETE FROM karma_schedule WHERE template_id = ?;")
+            .bind(template_id)
+            .execute(&mut *transaction)
+            .await?
+            .rows_affected();
+
+        transaction.commit().await?;
+        Ok(deleted > 0)
+    }
+
+    async fn insert_planned_occurrences(
+        &self,
+        template_id: i32,
+        occurrences: &[KarmaOccurrence],
+        planned_until: NaiveDate,
+    ) -> Result<u64, DbManagerError> {
+        let mut transaction = self.connection_pool.begin().await?;
+
+        let mut inserted = 0;
+        if !occurrences.is_empty() {
+            let mut builder = QueryBuilder::<Sqlite>::new(
+                "INSERT OR IGNORE INTO karma_occurrence\
+                (template_id, due_on, due_at, day_start, day_end, status) ",
+            );
+            builder.push_values(occurrences, |mut values, occurrence| {
+                values
+                    .push_bind(template_id)
+                    .push_bind(encode_date(occurrence.due_on))
+                    .push_bind(occurrence.due_at)
+                    .push_bind(occurrence.day_start)
+                    .push_bind(occurrence.day_end)
+                    .push_bind(occurrence.status.as_str());
+            });
+            inserted = builder
+                .build()
+                .execute(&mut *transaction)
+                .await?
+                .rows_affected();
+        }
+
+        sqlx::query("UPDATE karma_schedule SET planned_until = ? WHERE template_id = ?;")
+            .bind(encode_date(planned_until))
+            .bind(template_id)
+            .execute(&mut *transaction)
+            .await?;
+
+        transaction.commit().await?;
+        Ok(inserted)
+    }
+
+    async fn settle_occurrences(
+        &self,
+        today: NaiveDate,
+        now: i64,
+    ) -> Result<(u64, u64), DbManagerError> {
+        let mut transaction = self.connection_pool.begin().await?;
+
+        let done = sqlx::query(&format!(
+            "UPDATE karma_occurrence SET status = 'done', resolved_at = ?, \
+            session_id = ({SESSION_ON_DUE_DAY}) \
+            WHERE status = 'planned' AND due_on <= ? AND EXISTS ({SESSION_ON_DUE_DAY});"
+        ))
+        .bind(now)
+        .bind(encode_date(today))
+        .execute(&mut *transaction)
+        .await?
+        .rows_affected();
+
+        let missed = sqlx::query(
+            "UPDATE karma_occurrence SET status = 'missed' WHERE status = 'planned' AND due_on < ?;",
+        )
+        .bind(encode_date(today))
+        .execute(&mut *transaction)
+        .await?
+        .rows_affected();
+
+        transaction.commit().await?;
+        Ok((done, missed))
+    }
+
+    async fn get_occurrence(&self, id: i32) -> Result<Option<KarmaOccurrence>, DbManagerError> {
+        let occurrence =
+            sqlx::query_as::<_, KarmaOccurrence>(&format!("{OCCURRENCE_COLUMNS} WHERE o.id = ?;"))
+                .bind(id)
+                .fetch_optional(&self.connection_pool)
+                .await?;
+
+        Ok(occurrence)
+    }
+
+    async fn get_occurrences(
+        &self,
+        from: NaiveDate,
+        to: NaiveDate,
+        status: Option<OccurrenceStatus>,
+    ) -> Result<Vec<KarmaOccurrence>, DbManagerError> {
+        let mut builder = QueryBuilder::<Sqlite>::new(OCCURRENCE_COLUMNS);
+        builder
+            .push(" WHERE o.due_on >= ")
+            .push_bind(encode_date(from))
+            .push(" AND o.due_on <= ")
+            .push_bind(encode_date(to));
+        if let Some(status) = status {
+            builder.push(" AND o.status = ").push_bind(status.as_str());
+        }
+        builder.push(" ORDER BY o.due_at, o.id;");
+
+        let occurrences = builder
+            .build_query_as::<KarmaOccurrence>()
+            .fetch_all(&self.connection_pool)
+            .await?;
+
+        Ok(occurrences)
+    }
+
+    async fn get_all_occurrences(&self) -> Result<Vec<KarmaOccurrence>, DbManagerError> {
+        let occurrences = sqlx::query_as::<_, KarmaOccurrence>(&format!(
+            "{OCCURRENCE_COLUMNS} ORDER BY o.due_at, o.id;"
+        ))
+        .fetch_all(&self.connection_pool)
+        .await?;
+
+        Ok(occurrences)
+    }
+
+    async fn update_occurrence_status(
+        &self,
+        id: i32,
+        status: OccurrenceStatus,
+        session_id: Option<i32>,
+        now: i64,
+    ) -> Result<(), DbManagerError> {
+        sqlx::query(
+            "UPDATE karma_occurrence SET status = ?, session_id = ?, resolved_at = ? WHERE id = ?;",
+        )
+        .bind(status.as_str())
+        .bind(session_id)
+        .bind(now)
+        .bind(id)
+        .execute(&self.connection_pool)
+        .await?;
+
+        Ok(())
+    }
+}
@@ -0,0 +1,43 @@
+use async_trait::async_trait;
+
+use crate::storage::db::{DbManager, DbManagerError};
+
+/// Preferences of the profile as plain key/value pairs, parsed by whoever owns them
+#[async_trait]
+pub trait SettingsRepository {
+    async fn get_setting(&self, key: &str) -> Result<Option<String>, DbManagerError>;
+    async fn set_setting(&self, key: &str, value: &str) -> Result<(), DbManagerError>;
+    /// Every key with its value, sorted by key
+    async fn get_all_settings(&self) -> Result<Vec<(String, String)>, DbManagerError>;
+}
+
+#[async_trait]
+impl SettingsRepository for DbManager {
+    async fn get_setting(&self, key: &str) -> Result<Option<String>, DbManagerError> {
+        let value = sqlx::query_scalar::<_, String>("SELECT value FROM settings WHERE key = ?;")
+            .bind(key)
+            .fetch_optional(&self.connection_pool)
+            .await?;
+
+        Ok(value)
+    }
+
+    async fn set_setting(&self, key: &str, value: &str) -> Result<(), DbManagerError> {
+        sqlx::query("INSERT OR REPLACE INTO settings(key, value) VALUES(?, ?);")
+            .bind(key)
+            .bind(value)
+            .execute(&self.connection_pool)
+            .await?;
+
+        Ok(())
+    }
+
+    async fn get_all_settings(&self) -> Result<Vec<(String, String)>, DbManagerError> {
+        let settings =
+            sqlx::query_as::<_, (String, String)>("SELECT key, value FROM settings ORDER BY key;")
+                .fetch_all(&self.connection_pool)
+                .await?;
+
+        Ok(settings)
+    }
+}
@@ -0,0 +1,191 @@
+use async_trait::async_trait;
+use chrono::NaiveDate;
+use sqlx::{sqlite::SqliteRow, Error as SqlxError, FromRow, Row};
+
+use crate::model::streak::StreakState;
+use crate::model::subject::KarmaSubject;
+use crate::storage::db::{DbManager, DbManagerError};
+use crate::storage::schedule_repository::{decode_date, encode_date};
+
+impl<'r> FromRow<'r, SqliteRow> for StreakState {
+    fn from_row(row: &'r SqliteRow) -> Result<Self, SqlxError> {
+        let kind: String = row.try_get("scope_kind")?;
+        let id: i32 = row.try_get("scope_id")?;
+        let current_since: Option<String> = row.try_get("current_since")?;
+        let settled_until: Option<String> = row.try_get("settled_until")?;
+
+        Ok(StreakState {
+            scope: KarmaSubject::from_parts(&kind, id)
+                .ok_or_else(|| SqlxError::Decode(format!("unknown scope {kind}:{id}").into()))?,
+            current: row.try_get("current")?,
+            longest: row.try_get("longest")?,
+            current_since: current_since.as_deref().map(decode_date).transpose()?,
+            settled_until: settled_until.as_deref().map(decode_date).transpose()?,
+        })
+    }
+}
+
+/// Streaks counted so far and the days the user froze
+#[async_trait]
+pub trait StreakRepository {
+    async fn get_streak_state(
+        &self,
+        scope: &KarmaSubject,
+    ) -> Result<Option<StreakState>, DbManagerError>;
+    async fn save_streak_state(&self, state: &StreakState) -> Result<(), DbManagerError>;
+    /// Forgets what was counted so the next read starts over, for every scope when none
+    async fn clear_streak_states(&self, scope: Option<&KarmaSubject>)
+        -> Result<(), DbManagerError>;
+    /// When the sessions of the scope in `[from, to)` started, earliest first
+    async fn get_session_starts(
+        &self,
+        scope: &KarmaSubject,
+        from: i64,
+        to: i64,
+    ) -> Result<Vec<i64>, DbManagerError>;
+    /// Returns whether the day wasn't frozen yet
+    async fn insert_freeze(&self, day: NaiveDate) -> Result<bool, DbManagerError>;
+    /// Returns whether the day was frozen
+    async fn delete_freeze(&self, day: NaiveDate) -> Result<bool, DbManagerError>;
+    /// Frozen days between both dates inclusive
+    async fn get_freezes(
+        &self,
+        from: NaiveDate,
+        to: NaiveDate,
+    ) -> Result<Vec<NaiveDate>, DbManagerError>;
+    async fn get_all_freezes(&self) -> Result<Vec<NaiveDate>, DbManagerError>;
+}
+
+#[async_trait]
+impl StreakRepository for DbManager {
+    async fn get_streak_state(
+        &self,
+        scope: &KarmaSubject,
+    ) -> Result<Option<StreakState>, DbManagerError> {
+        let state = sqlx::query_as::<_, StreakState>(
+            "SELECT * FROM karma_streak WHERE scope_kind = ? AND scope_id = ?;",
+        )
+        .bind(scope.kind())
+        .bind(scope.id())
+        .fetch_optional(&self.connection_pool)
+        .await?;
+
+        Ok(state)
+    }
+
+    async fn save_streak_state(&self, state: &StreakState) -> Result<(), DbManagerError> {
+        sqlx::query(
+            "INSERT OR REPLACE INTO karma_streak \
+            (scope_kind, scope_id, current, longest, current_since, settled_until) \
+            VALUES (?, ?, ?, ?, ?, ?);",
+        )
+        .bind(state.scope.kind())
+        .bind(state.scope.id())
+        .bind(state.current)
+        .bind(state.longest)
+        .bind(state.current_since.map(encode_date))
+        .bind(state.settled_until.map(encode_date))
+        .execute(&self.connection_pool)
+        .await?;
+
+        Ok(())
+    }
+
+    async fn clear_streak_states(
+        &self,
+        scope: Option<&KarmaSubject>,
+    ) -> Result<(), DbManagerError> {
+        match scope {
+            Some(scope) => {
+                sqlx::query("DELETE FROM karma_streak WHERE scope_kind = ? AND scope_id = ?;")
+                    .bind(scope.kind())
+                    .bind(scope.id())
+                    .execute(&self.connection_pool)
+                    .await?
+            }
+            None => {
+                sqlx::query("DELETE FROM karma_streak;")
+                    .execute(&self.connection_pool)
+                    .await?
+            }
+        };
+
+        Ok(())
+    }
+
+    async fn get_session_starts(
+        &self,
+        scope: &KarmaSubject,
+        from: i64,
+        to: i64,
+    ) -> Result<Vec<i64>, DbManagerError> {
+        let column = match scope {
+            KarmaSubject::Template(_) => "template_id",
+            KarmaSubject::Category(_) => "purpose",
+        };
+        // Relapses don't keep a streak going
+        let starts = sqlx::query_scalar::<_, i64>(&format!(
+            "SELECT started_at FROM karma_session \
+            WHERE {column} = ? AND started_at >= ? AND started_at < ? \
+            AND template_id IN (SELECT id FROM karma WHERE polarity > 0) \
+            ORDER BY started_at;"
+        ))
+        .bind(scope.id())
+        .bind(from)
+        .bind(to)
+        .fetch_all(&self.connection_pool)
+        .await?;
+
+        Ok(starts)
+    }
+
+    async fn insert_freeze(&self, day: NaiveDate) -> Result<bool, DbManagerError> {
+        let inserted = sqlx::query("INSERT OR IGNORE INTO streak_freeze(day) VALUES (?);")
+            .bind(encode_date(day))
+            .execute(&self.connection_pool)
+            .await?
+            .rows_affected();
+
+        Ok(inserted > 0)
+    }
+
+    async fn delete_freeze(&self, day: NaiveDate) -> Result<bool, DbManagerError> {
+        let deleted = sqlx::query("DELETE FROM streak_freeze WHERE day = ?;")
+            .bind(encode_date(day))
+            .execute(&self.connection_pool)
+            .await?
+            .rows_affected();
+
+        Ok(deleted > 0)
+    }
+
+    async fn get_freezes(
+        &self,
+        from: NaiveDate,
+        to: NaiveDate,
+    ) -> Result<Vec<NaiveDate>, DbManagerError> {
+        let days = sqlx::query_scalar::<_, String>(
+            "SELECT day FROM streak_freeze WHERE day >= ? AND day <= ? ORDER BY day;",
+        )
+        .bind(encode_date(from))
+        .bind(encode_date(to))
+        .fetch_all(&self.connection_pool)
+        .await?;
+
+        Ok(days
+            .iter()
+            .map(|day| decode_date(day))
+            .collect::<Result<_, _>>()?)
+    }
+
+    async fn get_all_freezes(&self) -> Result<Vec<NaiveDate>, DbManagerError> {
+        let days = sqlx::query_scalar::<_, String>("SELECT day FROM streak_freeze ORDER BY day;")
+            .fetch_all(&self.connection_pool)
+            .await?;
+
+        Ok(days
+            .iter()
+            .map(|day| decode_date(day))
+            .collect::<Result<_, _>>()?)
+    }
+}
@@ -0,0 +1,201 @@
+use async_trait::async_trait;
+use sqlx::{sqlite::SqliteRow, Error as SqlxError, FromRow, Row};
+
+use crate::storage::db::{DbManager, DbManagerError};
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct TotpRecord {
+    pub username: String,
+    pub secret: String,
+    pub confirmed: bool,
+    pub last_used_step: Option<i64>,
+}
+
+impl<'r> FromRow<'r, SqliteRow> for TotpRecord {
+    fn from_row(row: &'r SqliteRow) -> Result<Self, SqlxError> {
+        Ok(TotpRecord {
+            username: row.try_get("username")?,
+            secret: row.try_get("secret")?,
+            confirmed: row.try_get("confirmed")?,
+            last_used_step: row.try_get("last_used_step")?,
+        })
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct BackupCodeRecord {
+    pub id: i64,
+    pub code_hash: String,
+}
+
+impl<'r> FromRow<'r, SqliteRow> for BackupCodeRecord {
+    fn from_row(row: &'r SqliteRow) -> Result<Self, SqlxError> {
+        Ok(BackupCodeRecord {
+            id: row.try_get("id")?,
+            code_hash: row.try_get("code_hash")?,
+        })
+    }
+}
+
+#[async_trait]
+pub trait TwoFactorRepository {
+    /// Stores a new, unconfirmed secret replacing any previous enrolment attempt
+    async fn upsert_totp_secret(&self, username: &str, secret: &str) -> Result<(), DbManagerError>;
+    async fn get_totp(&self, username: &str) -> Result<Option<TotpRecord>, DbManagerError>;
+    async fn confirm_totp(&self, username: &str, step: i64) -> Result<(), DbManagerError>;
+    /// Returns false when the step or a later one was used already, checked in the
+    /// same statement so two logins can't both use the same code
+    async fn set_totp_last_used_step(
+        &self,
+        username: &str,
+        step: i64,
+    ) -> Result<bool, DbManagerError>;
+    /// Removes the secret together with every backup code
+    async fn delete_totp(&self, username: &str) -> Result<(), DbManagerError>;
+    async fn replace_backup_codes(
+        &self,
+        username: &str,
+        code_hashes: Vec<String>,
+    ) -> Result<(), DbManagerError>;
+    async fn get_unused_backup_codes(
+        &self,
+        username: &str,
+    ) -> Result<Vec<BackupCodeRecord>, DbManagerError>;
+    /// Returns false when the user has no such unused code
+    async fn use_backup_code(
+        &self,
+        username: &str,
+        code_hash: &str,
+        used_at: i64,
+    ) -> Result<bool, DbManagerError>;
+}
+
+#[async_trait]
+impl TwoFactorRepository for DbManager {
+    async fn upsert_totp_secret(&self, username: &str, secret: &str) -> Result<(), DbManagerError> {
+        sqlx::query(
+            "INSERT INTO user_totp(username, secret, confirmed, last_used_step) \
+            VALUES(?, ?, 0, NULL) \
+            ON CONFLICT(username) DO UPDATE SET \
+            secret = excluded.secret, confirmed = 0, last_used_step = NULL;",
+        )
+        .bind(username)
+        .bind(secret)
+        .execute(&self.connection_pool)
+        .await?;
+
+        Ok(())
+    }
+
+    async fn get_totp(&self, username: &str) -> Result<Option<TotpRecord>, DbManagerError> {
+        let record = sqlx::query_as::<_, TotpRecord>("SELECT * FROM user_totp WHERE username = ?;")
+            .bind(username)
+            .fetch_optional(&self.connection_pool)
+            .await?;
+
+        Ok(record)
+    }
+
+    async fn confirm_totp(&self, username: &str, step: i64) -> Result<(), DbManagerError> {
+        sqlx::query("UPDATE user_totp SET confirmed = 1, last_used_step = ? WHERE username = ?;")
+            .bind(step)
+            .bind(username)
+            .execute(&self.connection_pool)
+            .await?;
+
+        Ok(())
+    }
+
+    async fn set_totp_last_used_step(
+        &self,
+        username: &str,
+        step: i64,
+    ) -> Result<bool, DbManagerError> {
+        let updated = sqlx::query(
+            "UPDATE user_totp SET last_used_step = ? WHERE username = ? \
+            AND (last_used_step IS NULL OR last_used_step < ?);",
+        )
+        .bind(step)
+        .bind(username)
+        .bind(step)
+        .execute(&self.connection_pool)
+        .await?
+        .rows_affected();
+
+        Ok(updated > 0)
+    }
+
+    async fn delete_totp(&self, username: &str) -> Result<(), DbManagerError> {
+        let mut transaction = self.connection_pool.begin().await?;
+
+        sqlx::query("DELETE FROM user_backup_codes WHERE username = ?;")
+            .bind(username)
+            .execute(&mut *transaction)
+            .await?;
+        sqlx::query("DELETE FROM user_totp WHERE username = ?;")
+            .bind(username)
+            .execute(&mut *transaction)
+            .await?;
+
+        transaction.commit().await?;
+        Ok(())
+    }
+
+    async fn replace_backup_codes(
+        &self,
+        username: &str,
+        code_hashes: Vec<String>,
+    ) -> Result<(), DbManagerError> {
+        let mut transaction = self.connection_pool.begin().await?;
+
+        sqlx::query("DELETE FROM user_backup_codes WHERE username = ?;")
+            .bind(username)
+            .execute(&mut *transaction)
+            .await?;
+
+        for code_hash in code_hashes {
+            sqlx::query("INSERT INTO user_backup_codes(username, code_hash) VALUES(?, ?);")
+                .bind(username)
+                .bind(code_hash)
+                .execute(&mut *transaction)
+                .await?;
+        }
+
+        transaction.commit().await?;
+        Ok(())
+    }
+
+    async fn get_unused_backup_codes(
+        &self,
+        username: &str,
+    ) -> Result<Vec<BackupCodeRecord>, DbManagerError> {
+        let codes = sqlx::query_as::<_, BackupCodeRecord>(
+            "SELECT id, code_hash FROM user_backup_codes WHERE username = ? AND used_at IS NULL;",
+        )
+        .bind(username)
+        .fetch_all(&self.connection_pool)
+        .await?;
+
+        Ok(codes)
+    }
+
+    async fn use_backup_code(
+        &self,
+        username: &str,
+        code_hash: &str,
+        used_at: i64,
+    ) -> Result<bool, DbManagerError> {
+        let updated = sqlx::query(
+            "UPDATE user_backup_codes SET used_at = ? \
+            WHERE username = ? AND code_hash = ? AND used_at IS NULL;",
+        )
+        .bind(used_at)
+        .bind(username)
+        .bind(code_hash)
+        .execute(&self.connection_pool)
+        .await?
+        .rows_affected();
+
+        Ok(updated > 0)
+    }
+}
@@ -1,14 +1,22 @@
-/// Currently everything related to accounts is dropped as login and auth is a bit beyond
-/// the scope of this app
 use async_trait::async_trait;
 
-use crate::model::user::User;
+use crate::model::user::{Password, User};
 use crate::storage::db::{DbManager, DbManagerError};
 
 #[async_trait]
 pub trait UserRepository {
     async fn insert_user(&self, user: User) -> Result<User, DbManagerError>;
     async fn get_user(&self, username: &str) -> Result<User, DbManagerError>;
+    async fn update_password(
+        &self,
+        username: &str,
+        password: &Password,
+    ) -> Result<(), DbManagerError>;
+    /// Removes the user and everything tied to them in one transaction. Karma data
+    /// belongs to the profile rather than to a user, so it is only removed together
+    /// with the last account. Returns whether the karma data was removed.
+    async fn delete_user(&self, username: &str) -> Result<bool, DbManagerError>;
+    async fn count_users(&self) -> Result<i64, DbManagerError>;
 }
 
 #[async_trait]
@@ -31,4 +39,75 @@ impl UserRepository for DbManager {
 
         Ok(query_result)
     }
+
+    async fn update_password(
+        &self,
+        username: &str,
+        password: &Password,
+    ) -> Result<(), DbManagerError> {
+        let _query_result = sqlx::query("UPDATE users SET password=? WHERE username=?;")
+            .bind(password.get_password())
+            .bind(username)
+            .execute(&self.connection_pool)
+            .await?;
+
+        Ok(())
+    }
+
+    async fn delete_user(&self, username: &str) -> Result<bool, DbManagerError> {
+        let mut transaction = self.connection_pool.begin().await?;
+
+        for statement in [
+            "DELETE FROM user_backup_codes WHERE username = ?;",
+            "DELETE FROM user_totp WHERE username = ?;",
+            "DELETE FROM users WHERE username = ?;",
+        ] {
+            sqlx::query(statement)
+                .bind(username)
+                .execute(&mut *transaction)
+                .await?;
+        }
+
+        let (remaining_users,): (i64,) = sqlx::query_as("SELECT COUNT(*) FROM users;")
+            .fetch_one(&mut *transaction)
+            .await?;
+
+        let delete_karma_data = remaining_users == 0;
+        if delete_karma_data {
+            for statement in [
+                "DELETE FROM focus_interruption;",
+                "DELETE FROM focus_session;",
+                "DELETE FROM blocked_time;",
+                "DELETE FROM plan_entry;",
+                "DELETE FROM achievement_unlock;",
+                "DELETE FROM reward_redemption;",
+                "DELETE FROM karma_reward;",
+                "DELETE FROM karma_goal;",
+                "DELETE FROM karma_streak;",
+                "DELETE FROM streak_freeze;",
+                "DELETE FROM karma_occurrence;",
+                "DELETE FROM karma_schedule;",
+                "DELETE FROM karma_status;",
+                "DELETE FROM karma_session;",
+                "DELETE FROM karma;",
+                // The delete trigger only adds tombstones, the names would stay
+                // in the index segments
+                "INSERT INTO karma_search(karma_search) VALUES ('delete-all');",
+                "DELETE FROM settings;",
+            ] {
+                sqlx::query(statement).execute(&mut *transaction).await?;
+            }
+        }
+
+        transaction.commit().await?;
+        Ok(delete_karma_data)
+    }
+
+    async fn count_users(&self) -> Result<i64, DbManagerError> {
+        let (users,): (i64,) = sqlx::query_as("SELECT COUNT(*) FROM users;")
+            .fetch_one(&self.connection_pool)
+            .await?;
+
+        Ok(users)
+    }
 }